@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use futures_core::Stream;
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     env,
     fmt::{self, Display},
     io::{self, ErrorKind},
@@ -22,10 +22,10 @@ use wayland_protocols::{
     wp::{
         keyboard_shortcuts_inhibit::zv1::client::{
             zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1,
-            zwp_keyboard_shortcuts_inhibitor_v1::ZwpKeyboardShortcutsInhibitorV1,
+            zwp_keyboard_shortcuts_inhibitor_v1::{self, ZwpKeyboardShortcutsInhibitorV1},
         },
         pointer_constraints::zv1::client::{
-            zwp_locked_pointer_v1::ZwpLockedPointerV1,
+            zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
             zwp_pointer_constraints_v1::{Lifetime, ZwpPointerConstraintsV1},
         },
         relative_pointer::zv1::client::{
@@ -39,14 +39,20 @@ use wayland_protocols::{
     },
 };
 
-use wayland_protocols_wlr::layer_shell::v1::client::{
-    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
-    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+use wayland_protocols_wlr::{
+    foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+    },
 };
 
 use wayland_client::{
     backend::{ReadEventsGuard, WaylandError},
-    delegate_noop,
+    delegate_noop, event_created_child,
     globals::{registry_queue_init, Global, GlobalList, GlobalListContents},
     protocol::{
         wl_buffer, wl_compositor,
@@ -61,9 +67,9 @@ use wayland_client::{
     Connection, Dispatch, DispatchError, EventQueue, QueueHandle, WEnum,
 };
 
-use input_event::{Event, KeyboardEvent, PointerEvent};
+use input_event::{Event, KeyboardEvent, PointerEvent, ScrollSource};
 
-use crate::{CaptureError, CaptureEvent};
+use crate::{CaptureError, CaptureEvent, GrabMode};
 
 use super::{
     error::{LayerShellCaptureCreationError, WaylandBindError},
@@ -79,6 +85,23 @@ struct Globals {
     shm: wl_shm::WlShm,
     layer_shell: ZwlrLayerShellV1,
     xdg_output_manager: ZxdgOutputManagerV1,
+    /// only bound if capture is restricted to a focused app
+    toplevel_manager: Option<ZwlrForeignToplevelManagerV1>,
+}
+
+/// a window of another application, as reported by the compositor
+#[derive(Debug, Default)]
+struct Toplevel {
+    app_id: String,
+    title: String,
+    activated: bool,
+}
+
+impl Toplevel {
+    /// the app id equals the filter or the title contains it
+    fn matches(&self, filter: &str) -> bool {
+        self.app_id == filter || self.title.contains(filter)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -129,6 +152,14 @@ struct State {
     pending_events: VecDeque<(Position, CaptureEvent)>,
     outputs: Vec<Output>,
     scroll_discrete_pending: bool,
+    scroll_source: ScrollSource,
+    grab_mode: GrabMode,
+    frame_grouping: bool,
+    /// forward motion without the acceleration of the compositor
+    raw_motion: bool,
+    /// only capture while a matching window is focused
+    focus_filter: Option<String>,
+    toplevels: HashMap<ZwlrForeignToplevelHandleV1, Toplevel>,
 }
 
 struct Inner {
@@ -270,7 +301,12 @@ fn draw(f: &mut File, (width, height): (u32, u32)) {
 }
 
 impl LayerShellInputCapture {
-    pub fn new() -> std::result::Result<Self, LayerShellCaptureCreationError> {
+    pub fn new(
+        grab_mode: GrabMode,
+        frame_grouping: bool,
+        raw_motion: bool,
+        focus_filter: Option<String>,
+    ) -> std::result::Result<Self, LayerShellCaptureCreationError> {
         let conn = Connection::connect_to_env()?;
         let (global_list, mut queue) = registry_queue_init::<State>(&conn)?;
 
@@ -305,12 +341,26 @@ impl LayerShellInputCapture {
             .bind(&qh, 1..=1, ())
             .map_err(|e| WaylandBindError::new(e, "zwp_keyboard_shortcuts_inhibit_manager_v1"));
         // layer-shell backend still works without this protocol so we make it an optional dependency
-        if let Err(e) = &shortcut_inhibit_manager {
+        if let (Err(e), GrabMode::Full) = (&shortcut_inhibit_manager, grab_mode) {
             log::warn!("shortcut_inhibit_manager not supported: {e}\nkeybinds handled by the compositor will not be passed
                 to the client");
         }
         let shortcut_inhibit_manager = shortcut_inhibit_manager.ok();
 
+        // the focused window is only needed to restrict capture
+        let toplevel_manager = match focus_filter {
+            Some(_) => match global_list.bind(&qh, 1..=3, ()) {
+                Ok(m) => Some(m),
+                Err(e) => {
+                    log::warn!(
+                        "zwlr_foreign_toplevel_manager_v1 not supported: {e} - the focused window can not be determined, capture is not restricted"
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
         let mut state = State {
             active_positions: Default::default(),
             pointer: None,
@@ -325,6 +375,7 @@ impl LayerShellInputCapture {
                 relative_pointer_manager,
                 shortcut_inhibit_manager,
                 xdg_output_manager,
+                toplevel_manager,
             },
             pointer_lock: None,
             rel_pointer: None,
@@ -337,6 +388,12 @@ impl LayerShellInputCapture {
             pending_events: VecDeque::new(),
             outputs: vec![],
             scroll_discrete_pending: false,
+            scroll_source: ScrollSource::default(),
+            grab_mode,
+            frame_grouping,
+            raw_motion,
+            focus_filter,
+            toplevels: HashMap::new(),
         };
 
         for global in state.global_list.contents().clone_list() {
@@ -378,6 +435,18 @@ impl LayerShellInputCapture {
 }
 
 impl State {
+    /// whether a window matching the focus filter is focused,
+    /// always true if there is no filter or no way to tell
+    fn focus_allowed(&self) -> bool {
+        match (&self.focus_filter, &self.globals.toplevel_manager) {
+            (Some(filter), Some(_)) => self
+                .toplevels
+                .values()
+                .any(|t| t.activated && t.matches(filter)),
+            _ => true,
+        }
+    }
+
     fn update_output_info(&mut self, name: u32) {
         let output = self
             .outputs
@@ -464,7 +533,9 @@ impl State {
         }
 
         // capture modifier keys
-        if let Some(shortcut_inhibit_manager) = &self.globals.shortcut_inhibit_manager {
+        if let (GrabMode::Full, Some(shortcut_inhibit_manager)) =
+            (self.grab_mode, &self.globals.shortcut_inhibit_manager)
+        {
             if self.shortcut_inhibitor.is_none() {
                 self.shortcut_inhibitor = Some(shortcut_inhibit_manager.inhibit_shortcuts(
                     surface,
@@ -636,7 +707,10 @@ impl Capture for LayerShellInputCapture {
     }
 
     async fn terminate(&mut self) -> Result<(), CaptureError> {
-        Ok(())
+        // make sure input is not left grabbed
+        let inner = self.0.get_mut();
+        inner.state.ungrab();
+        Ok(inner.flush_events()?)
     }
 }
 
@@ -738,6 +812,10 @@ impl Dispatch<WlPointer, ()> for State {
                 surface_x: _,
                 surface_y: _,
             } => {
+                if !app.focus_allowed() {
+                    log::debug!("focused window does not match, not capturing");
+                    return;
+                }
                 // get client corresponding to the focused surface
                 {
                     if let Some(window) = app.active_windows.iter().find(|w| w.surface == surface) {
@@ -798,6 +876,7 @@ impl Dispatch<WlPointer, ()> for State {
                             time,
                             axis: u32::from(axis) as u8,
                             value,
+                            source: app.scroll_source,
                         })),
                     ));
                 }
@@ -813,10 +892,36 @@ impl Dispatch<WlPointer, ()> for State {
                     })),
                 ));
             }
-            wl_pointer::Event::Frame => {
-                // TODO properly handle frame events
-                // we simply insert a frame event on the client side
-                // after each event for now
+            wl_pointer::Event::AxisStop { time, axis } => {
+                if let Some(window) = app.focused.as_ref() {
+                    app.pending_events.push_back((
+                        window.pos,
+                        CaptureEvent::Input(Event::Pointer(PointerEvent::ScrollStop {
+                            time,
+                            axis: u32::from(axis) as u8,
+                        })),
+                    ));
+                }
+            }
+            wl_pointer::Event::AxisSource {
+                axis_source: WEnum::Value(source),
+            } => {
+                // sent before the axis events of the same frame
+                app.scroll_source = match source {
+                    wl_pointer::AxisSource::Wheel => ScrollSource::Wheel,
+                    wl_pointer::AxisSource::Finger => ScrollSource::Finger,
+                    wl_pointer::AxisSource::Continuous => ScrollSource::Continuous,
+                    wl_pointer::AxisSource::WheelTilt => ScrollSource::WheelTilt,
+                    _ => ScrollSource::default(),
+                };
+            }
+            // without frame grouping the client side
+            // inserts a frame after each event
+            wl_pointer::Event::Frame if app.frame_grouping => {
+                if let Some(window) = app.focused.as_ref() {
+                    app.pending_events
+                        .push_back((window.pos, CaptureEvent::Frame));
+                }
             }
             _ => {}
         }
@@ -887,11 +992,16 @@ impl Dispatch<ZwpRelativePointerV1, ()> for State {
         if let zwp_relative_pointer_v1::Event::RelativeMotion {
             utime_hi,
             utime_lo,
-            dx_unaccel: dx,
-            dy_unaccel: dy,
-            ..
+            dx,
+            dy,
+            dx_unaccel,
+            dy_unaccel,
         } = event
         {
+            let (dx, dy) = match app.raw_motion {
+                true => (dx_unaccel, dy_unaccel),
+                false => (dx, dy),
+            };
             if let Some(window) = &app.focused {
                 let time = ((((utime_hi as u64) << 32) | utime_lo as u64) / 1000) as u32;
                 app.pending_events.push_back((
@@ -929,6 +1039,64 @@ impl Dispatch<ZwlrLayerSurfaceV1, ()> for State {
     }
 }
 
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        app: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: <ZwlrForeignToplevelManagerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                app.toplevels.insert(toplevel, Toplevel::default());
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                log::warn!("compositor stopped reporting windows, capture is not restricted");
+                app.toplevels.clear();
+                app.globals.toplevel_manager = None;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        app: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: <ZwlrForeignToplevelHandleV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            app.toplevels.remove(handle);
+            handle.destroy();
+            return;
+        }
+        let Some(toplevel) = app.toplevels.get_mut(handle) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                toplevel.activated = state
+                    .chunks_exact(4)
+                    .map(|s| u32::from_ne_bytes(s.try_into().unwrap()))
+                    .any(|s| s == zwlr_foreign_toplevel_handle_v1::State::Activated as u32);
+            }
+            _ => {}
+        }
+    }
+}
+
 // delegate wl_registry events to App itself
 impl Dispatch<WlRegistry, GlobalListContents> for State {
     fn event(
@@ -1017,6 +1185,48 @@ impl Dispatch<WlOutput, u32> for State {
     }
 }
 
+impl Dispatch<ZwpLockedPointerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwpLockedPointerV1,
+        event: <ZwpLockedPointerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_locked_pointer_v1::Event::Locked => log::debug!("pointer locked"),
+            zwp_locked_pointer_v1::Event::Unlocked if state.pointer_lock.is_some() => {
+                log::warn!("pointer lock deactivated by the compositor - pointer input may reach the local system");
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpKeyboardShortcutsInhibitorV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwpKeyboardShortcutsInhibitorV1,
+        event: <ZwpKeyboardShortcutsInhibitorV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_keyboard_shortcuts_inhibitor_v1::Event::Active => {
+                log::debug!("compositor shortcuts inhibited")
+            }
+            zwp_keyboard_shortcuts_inhibitor_v1::Event::Inactive
+                if state.shortcut_inhibitor.is_some() =>
+            {
+                log::warn!("shortcut inhibitor deactivated by the compositor - keybinds will be handled locally");
+            }
+            _ => {}
+        }
+    }
+}
+
 // don't emit any events
 delegate_noop!(State: wl_region::WlRegion);
 delegate_noop!(State: wl_shm_pool::WlShmPool);
@@ -1031,5 +1241,3 @@ delegate_noop!(State: ignore ZxdgOutputManagerV1);
 delegate_noop!(State: ignore wl_shm::WlShm);
 delegate_noop!(State: ignore wl_buffer::WlBuffer);
 delegate_noop!(State: ignore WlSurface);
-delegate_noop!(State: ignore ZwpKeyboardShortcutsInhibitorV1);
-delegate_noop!(State: ignore ZwpLockedPointerV1);
@@ -1,3 +1,30 @@
+//! Cross-platform input capture.
+//!
+//! An [`InputCapture`] captures the cursor when it reaches an edge of the
+//! screen at which a capture was created and yields the input events
+//! that follow, until it is released again.
+//!
+//! # Lifecycle
+//!
+//! 1. Create the capture with [`InputCapture::new`] or
+//!    [`InputCapture::with_options`], selecting a backend automatically
+//!    unless one is given.
+//! 2. Register a [`CaptureHandle`] for each edge with [`InputCapture::create`].
+//!    Several handles may share an edge, each of them receives the events.
+//! 3. Poll the capture as a [`Stream`] of `(handle, event)` pairs.
+//!    Entering an edge yields [`CaptureEvent::Begin`], followed by
+//!    [`CaptureEvent::Input`] events until the capture is released.
+//! 4. Return the cursor to the local device with [`InputCapture::release`].
+//!    Capturing continues once an edge is reached again.
+//! 5. Shut the capture down with [`InputCapture::terminate`] before dropping it.
+//!
+//! # Errors
+//!
+//! Creating a capture fails with a [`CaptureCreationError`] if no backend is
+//! available. Errors yielded by the stream or returned by its methods are
+//! [`CaptureError`]s, after which the capture should be terminated and,
+//! if desired, created again. The stream ends once the backend is closed.
+
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
@@ -9,7 +36,7 @@ use async_trait::async_trait;
 use futures::StreamExt;
 use futures_core::Stream;
 
-use input_event::{scancode, Event, KeyboardEvent};
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
 
 pub use error::{CaptureCreationError, CaptureError, InputCaptureError};
 
@@ -41,6 +68,11 @@ pub enum CaptureEvent {
     Begin,
     /// input event coming from capture handle
     Input(Event),
+    /// end of a group of input events that belong together
+    Frame,
+    /// the following input events originate from the seat with the given id
+    /// (only emitted when capturing from multiple seats)
+    Origin(u32),
 }
 
 impl Display for CaptureEvent {
@@ -48,6 +80,8 @@ impl Display for CaptureEvent {
         match self {
             CaptureEvent::Begin => write!(f, "begin capture"),
             CaptureEvent::Input(e) => write!(f, "{e}"),
+            CaptureEvent::Frame => write!(f, "frame"),
+            CaptureEvent::Origin(id) => write!(f, "origin({id})"),
         }
     }
 }
@@ -83,6 +117,127 @@ impl Display for Position {
     }
 }
 
+/// what to do with an event if the channel
+/// between backend and consumer is full
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BackpressurePolicy {
+    /// wait until the event can be delivered
+    #[default]
+    Block,
+    /// keep only the newest event while the consumer can not keep up,
+    /// discarding older ones that were not delivered yet (non-blocking)
+    Drop,
+}
+
+/// [`BackpressurePolicy`] per type of event
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Backpressure {
+    /// relative and absolute pointer motion
+    pub motion: BackpressurePolicy,
+    /// scroll events (continuous & discrete)
+    pub scroll: BackpressurePolicy,
+    /// pointer buttons
+    pub button: BackpressurePolicy,
+    /// keys & modifiers
+    pub key: BackpressurePolicy,
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Self {
+            motion: BackpressurePolicy::Drop,
+            scroll: BackpressurePolicy::Block,
+            button: BackpressurePolicy::Block,
+            key: BackpressurePolicy::Block,
+        }
+    }
+}
+
+impl Backpressure {
+    /// the policy to apply to the given event
+    pub fn policy(&self, event: &CaptureEvent) -> BackpressurePolicy {
+        match event {
+            CaptureEvent::Begin | CaptureEvent::Frame | CaptureEvent::Origin(_) => {
+                BackpressurePolicy::Block
+            }
+            CaptureEvent::Input(Event::Pointer(p)) => match p {
+                PointerEvent::Motion { .. } | PointerEvent::MotionAbsolute { .. } => self.motion,
+                PointerEvent::Button { .. } => self.button,
+                PointerEvent::Axis { .. }
+                | PointerEvent::AxisDiscrete120 { .. }
+                | PointerEvent::ScrollStop { .. }
+                | PointerEvent::ScrollCancel { .. } => self.scroll,
+            },
+            CaptureEvent::Input(Event::Keyboard(_)) => self.key,
+        }
+    }
+}
+
+/// how local input is grabbed while it is being forwarded
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GrabMode {
+    /// grab pointer and keyboard and inhibit
+    /// keyboard shortcuts of the compositor
+    #[default]
+    Full,
+    /// grab pointer and keyboard but let the
+    /// compositor handle its keyboard shortcuts
+    KeepShortcuts,
+}
+
+impl Display for GrabMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrabMode::Full => write!(f, "full"),
+            GrabMode::KeepShortcuts => write!(f, "keep-shortcuts"),
+        }
+    }
+}
+
+/// options applied to an [`InputCapture`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaptureOptions {
+    /// [`Backpressure`] policy per event type
+    pub backpressure: Backpressure,
+    /// how local input is grabbed during capture
+    pub grab: GrabMode,
+    /// emit [`CaptureEvent::Frame`] markers after groups of related events
+    pub frame_grouping: bool,
+    /// discard auto-repeated key presses and leave
+    /// key repeat to the receiving side
+    pub suppress_repeat: bool,
+    /// forward the absolute position reported by devices like
+    /// drawing tablets instead of discarding it (libei only)
+    pub forward_absolute: bool,
+    /// names of the seats to capture from, all seats if `None` (libei only)
+    pub seats: Option<Vec<String>>,
+    /// forward raw, unaccelerated pointer motion instead of
+    /// the motion accelerated by the compositor (layer-shell only)
+    pub raw_motion: bool,
+    /// only capture while a window whose app id equals, or whose title
+    /// contains, the given value is focused (layer-shell only)
+    pub focus_filter: Option<String>,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            backpressure: Default::default(),
+            grab: Default::default(),
+            frame_grouping: false,
+            suppress_repeat: true,
+            forward_absolute: false,
+            seats: None,
+            raw_motion: true,
+            focus_filter: None,
+        }
+    }
+}
+
+/// key state of an auto-repeated key press (as in `wl_keyboard`),
+/// reported by backends that can tell repeats apart
+const KEY_REPEATED: u8 = 2;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Backend {
     #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
@@ -116,11 +271,17 @@ impl Display for Backend {
     }
 }
 
+/// Input capture using one of the available backends.
+/// Yields the captured events as a [`Stream`], see the [crate] documentation.
 pub struct InputCapture {
     /// capture backend
     capture: Box<dyn Capture>,
     /// keys pressed by active capture
     pressed_keys: HashSet<scancode::Linux>,
+    /// discard auto-repeated key presses
+    suppress_repeat: bool,
+    /// forward absolute pointer positions
+    forward_absolute: bool,
     /// map from position to ids
     position_map: HashMap<Position, Vec<CaptureHandle>>,
     /// map from id to position
@@ -130,7 +291,10 @@ pub struct InputCapture {
 }
 
 impl InputCapture {
-    /// create a new client with the given id
+    /// Create a capture with the given handle at the given edge.
+    ///
+    /// # Panics
+    /// if the handle already exists
     pub async fn create(&mut self, id: CaptureHandle, pos: Position) -> Result<(), CaptureError> {
         assert!(!self.id_map.contains_key(&id));
 
@@ -145,7 +309,11 @@ impl InputCapture {
         }
     }
 
-    /// destroy the client with the given id, if it exists
+    /// Destroy the capture with the given handle.
+    /// The edge is no longer captured once no handle remains on it.
+    ///
+    /// # Panics
+    /// if the handle does not exist
     pub async fn destroy(&mut self, id: CaptureHandle) -> Result<(), CaptureError> {
         let pos = self
             .id_map
@@ -165,37 +333,70 @@ impl InputCapture {
         Ok(())
     }
 
-    /// release mouse
+    /// Release the cursor back to the local device.
+    /// Keys still pressed are considered released.
     pub async fn release(&mut self) -> Result<(), CaptureError> {
         self.pressed_keys.clear();
         self.capture.release().await
     }
 
-    /// destroy the input capture
+    /// Shut down the input capture, releasing all resources of the backend.
+    /// The capture must not be used afterwards.
     pub async fn terminate(&mut self) -> Result<(), CaptureError> {
         self.capture.terminate().await
     }
 
     /// creates a new [`InputCapture`]
     pub async fn new(backend: Option<Backend>) -> Result<Self, CaptureCreationError> {
-        let capture = create(backend).await?;
+        Self::with_options(backend, Default::default()).await
+    }
+
+    /// creates a new [`InputCapture`] with the given [`CaptureOptions`]
+    pub async fn with_options(
+        backend: Option<Backend>,
+        options: CaptureOptions,
+    ) -> Result<Self, CaptureCreationError> {
+        let (suppress_repeat, forward_absolute) =
+            (options.suppress_repeat, options.forward_absolute);
+        let capture = create(backend, options).await?;
         Ok(Self {
             capture,
             id_map: Default::default(),
             pending: Default::default(),
             position_map: Default::default(),
             pressed_keys: HashSet::new(),
+            suppress_repeat,
+            forward_absolute,
         })
     }
 
+    /// number of events discarded due to the [`Backpressure`] policy
+    pub fn dropped_events(&self) -> u64 {
+        self.capture.dropped_events()
+    }
+
     /// check whether the given keys are pressed
     pub fn keys_pressed(&self, keys: &[scancode::Linux]) -> bool {
         keys.iter().all(|k| self.pressed_keys.contains(k))
     }
 
+    /// A press is a repeat if the backend marks it as such
+    /// or the key is pressed already
+    fn is_repeat(&self, key: u32, state: u8) -> bool {
+        match state {
+            KEY_REPEATED => true,
+            1 => scancode::Linux::try_from(key)
+                .map(|k| self.pressed_keys.contains(&k))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
     fn update_pressed_keys(&mut self, key: u32, state: u8) {
         if let Ok(scancode) = scancode::Linux::try_from(key) {
-            log::debug!("key: {key}, state: {state}, scancode: {scancode:?}");
+            if input_event::log_key_data() {
+                log::debug!("key: {key}, state: {state}, scancode: {scancode:?}");
+            }
             match state {
                 1 => self.pressed_keys.insert(scancode),
                 _ => self.pressed_keys.remove(&scancode),
@@ -225,14 +426,37 @@ impl Stream for InputCapture {
         };
 
         // error occurred
-        let (pos, event) = match event {
+        let (pos, mut event) = match event {
             Ok(e) => e,
             Err(e) => return Poll::Ready(Some(Err(e))),
         };
 
+        if !self.forward_absolute
+            && matches!(
+                event,
+                CaptureEvent::Input(Event::Pointer(PointerEvent::MotionAbsolute { .. }))
+            )
+        {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
         // handle key presses
-        if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, state, .. })) = event {
-            self.update_pressed_keys(key, state);
+        if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { time, key, state })) = event
+        {
+            if self.is_repeat(key, state) {
+                if self.suppress_repeat {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                event = CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key {
+                    time,
+                    key,
+                    state: 1,
+                }));
+            } else {
+                self.update_pressed_keys(key, state);
+            }
         }
 
         let len = self
@@ -276,19 +500,47 @@ trait Capture: Stream<Item = Result<(Position, CaptureEvent), CaptureError>> + U
 
     /// destroy the input capture
     async fn terminate(&mut self) -> Result<(), CaptureError>;
+
+    /// number of events discarded due to backpressure
+    fn dropped_events(&self) -> u64 {
+        0
+    }
 }
 
+#[cfg_attr(
+    not(all(
+        unix,
+        any(feature = "libei", feature = "layer_shell"),
+        not(target_os = "macos")
+    )),
+    allow(unused_variables)
+)]
 async fn create_backend(
     backend: Backend,
+    options: CaptureOptions,
 ) -> Result<
     Box<dyn Capture<Item = Result<(Position, CaptureEvent), CaptureError>>>,
     CaptureCreationError,
 > {
+    #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
+    let restricted = options.focus_filter.is_some() && backend != Backend::LayerShell;
+    #[cfg(not(all(unix, feature = "layer_shell", not(target_os = "macos"))))]
+    let restricted = options.focus_filter.is_some();
+    if restricted {
+        log::warn!("{backend} input capture can not determine the focused window, capture is not restricted");
+    }
     match backend {
         #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
-        Backend::InputCapturePortal => Ok(Box::new(libei::LibeiInputCapture::new().await?)),
+        Backend::InputCapturePortal => Ok(Box::new(
+            libei::LibeiInputCapture::new(options.backpressure, options.seats).await?,
+        )),
         #[cfg(all(unix, feature = "layer_shell", not(target_os = "macos")))]
-        Backend::LayerShell => Ok(Box::new(layer_shell::LayerShellInputCapture::new()?)),
+        Backend::LayerShell => Ok(Box::new(layer_shell::LayerShellInputCapture::new(
+            options.grab,
+            options.frame_grouping,
+            options.raw_motion,
+            options.focus_filter,
+        )?)),
         #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
         Backend::X11 => Ok(Box::new(x11::X11InputCapture::new()?)),
         #[cfg(windows)]
@@ -301,12 +553,13 @@ async fn create_backend(
 
 async fn create(
     backend: Option<Backend>,
+    options: CaptureOptions,
 ) -> Result<
     Box<dyn Capture<Item = Result<(Position, CaptureEvent), CaptureError>>>,
     CaptureCreationError,
 > {
     if let Some(backend) = backend {
-        let b = create_backend(backend).await;
+        let b = create_backend(backend, options).await;
         if b.is_ok() {
             log::info!("using capture backend: {backend}");
         }
@@ -325,7 +578,7 @@ async fn create(
         #[cfg(target_os = "macos")]
         Backend::MacOs,
     ] {
-        match create_backend(backend).await {
+        match create_backend(backend, options.clone()).await {
             Ok(b) => {
                 log::info!("using capture backend: {backend}");
                 return Ok(b);
@@ -12,23 +12,26 @@ use async_trait::async_trait;
 use futures::{FutureExt, StreamExt};
 use reis::{
     ei::{self, handshake::ContextType},
-    event::{Connection, DeviceCapability, EiEvent},
+    event::{Connection, DeviceCapability, EiEvent, Seat, SeatEvent},
     tokio::EiConvertEventStream,
 };
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     io,
     num::NonZeroU32,
     os::unix::net::UnixStream,
     pin::Pin,
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     task::{Context, Poll},
 };
 use tokio::{
     sync::{
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, error::TrySendError, Receiver, Sender},
         Notify,
     },
     task::JoinHandle,
@@ -39,7 +42,7 @@ use futures_core::Stream;
 
 use input_event::Event;
 
-use crate::CaptureEvent;
+use crate::{Backpressure, BackpressurePolicy, CaptureEvent};
 
 use super::{
     error::{CaptureError, LibeiCaptureCreationError},
@@ -66,6 +69,140 @@ pub struct LibeiInputCapture<'a> {
     notify_release: Arc<Notify>,
     cancellation_token: CancellationToken,
     terminated: bool,
+    dropped: Arc<AtomicU64>,
+    pending: PendingEvent,
+}
+
+/// newest event of a [`BackpressurePolicy::Drop`] type that did not fit
+/// into the channel. Newer events replace it, dropping the oldest.
+type PendingEvent = Arc<Mutex<Option<(Position, CaptureEvent)>>>;
+
+/// events from the ei event stream are forwarded
+/// according to the [`Backpressure`] policy
+#[derive(Clone)]
+struct EventSender {
+    tx: Sender<(Position, CaptureEvent)>,
+    backpressure: Backpressure,
+    dropped: Arc<AtomicU64>,
+    /// droppable event waiting for space in the channel
+    pending: PendingEvent,
+    /// names of the seats to capture from, all seats if `None`
+    seats: Option<Vec<String>>,
+}
+
+impl EventSender {
+    async fn send(&self, event: (Position, CaptureEvent)) {
+        match self.backpressure.policy(&event.1) {
+            BackpressurePolicy::Block => {
+                // a pending event is older and has to be delivered first
+                let pending = self.pending.lock().expect("lock").take();
+                if let Some(pending) = pending {
+                    self.tx.send(pending).await.expect("no channel");
+                }
+                self.tx.send(event).await.expect("no channel");
+            }
+            BackpressurePolicy::Drop => {
+                let mut pending = self.pending.lock().expect("lock");
+                // the channel is still full, replace the older pending event
+                if pending.is_some() {
+                    *pending = Some(event);
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                match self.tx.try_send(event) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(event)) => *pending = Some(event),
+                    Err(TrySendError::Closed(_)) => panic!("no channel"),
+                }
+            }
+        }
+    }
+}
+
+/// seats input is captured from. Each bound seat gets an origin id,
+/// which is reported via [`CaptureEvent::Origin`] whenever
+/// the seat of the captured input changes.
+struct Seats {
+    /// names of the seats to capture from, all seats if `None`
+    enabled: Option<Vec<String>>,
+    /// origin ids of the bound seats
+    ids: HashMap<Seat, u32>,
+    next_id: u32,
+    /// last origin reported and the client it was reported to
+    current: Option<(Position, u32)>,
+}
+
+impl Seats {
+    fn new(enabled: Option<Vec<String>>) -> Self {
+        Self {
+            enabled,
+            ids: Default::default(),
+            next_id: 0,
+            current: None,
+        }
+    }
+
+    fn is_enabled(&self, seat: &Seat) -> bool {
+        match &self.enabled {
+            Some(names) => seat.name().is_some_and(|n| names.iter().any(|s| s == n)),
+            None => true,
+        }
+    }
+
+    /// registers a new seat, returns false if it is disabled
+    fn add(&mut self, seat: &Seat) -> bool {
+        let name = seat.name().unwrap_or("<unnamed>");
+        if !self.is_enabled(seat) {
+            log::info!("not capturing from disabled seat {name}");
+            return false;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        log::info!("capturing from seat {name} (origin {id})");
+        self.ids.insert(seat.clone(), id);
+        true
+    }
+
+    fn remove(&mut self, seat: &Seat) {
+        self.ids.remove(seat);
+    }
+
+    /// Forget the last reported origin, so the first event
+    /// of the next capture reports its origin again.
+    fn reset_origin(&mut self) {
+        self.current = None;
+    }
+
+    /// the origin to report to the client at `pos` before
+    /// an event of `seat`, if it changed since the last event
+    fn origin_change(&mut self, pos: Position, seat: &Seat) -> Option<u32> {
+        // input of a single seat needs no tagging
+        if self.ids.len() < 2 {
+            return None;
+        }
+        let id = *self.ids.get(seat)?;
+        if self.current == Some((pos, id)) {
+            return None;
+        }
+        self.current = Some((pos, id));
+        Some(id)
+    }
+}
+
+/// the seat an input event originates from
+fn seat_of(event: &EiEvent) -> Option<&Seat> {
+    match event {
+        EiEvent::KeyboardModifiers(e) => Some(e.seat()),
+        EiEvent::PointerMotion(e) => Some(e.seat()),
+        EiEvent::PointerMotionAbsolute(e) => Some(e.seat()),
+        EiEvent::Button(e) => Some(e.seat()),
+        EiEvent::ScrollDelta(e) => Some(e.seat()),
+        EiEvent::ScrollStop(e) => Some(e.seat()),
+        EiEvent::ScrollCancel(e) => Some(e.seat()),
+        EiEvent::ScrollDiscrete(e) => Some(e.seat()),
+        EiEvent::KeyboardKey(e) => Some(e.seat()),
+        _ => None,
+    }
 }
 
 /// returns (start pos, end pos), inclusive
@@ -186,23 +323,41 @@ async fn connect_to_eis(
 async fn libei_event_handler(
     mut ei_event_stream: EiConvertEventStream,
     context: ei::Context,
-    event_tx: Sender<(Position, CaptureEvent)>,
+    event_tx: EventSender,
     release_session: Arc<Notify>,
     current_pos: Rc<Cell<Option<Position>>>,
+    seats: Rc<RefCell<Seats>>,
 ) -> Result<(), CaptureError> {
     loop {
         let ei_event = ei_event_stream
             .next()
             .await
             .ok_or(CaptureError::EndOfStream)??;
-        log::trace!("from ei: {ei_event:?}");
+        // key codes are only logged if explicitly enabled
+        match ei_event {
+            EiEvent::KeyboardKey(_) if !input_event::log_key_data() => {
+                log::trace!("from ei: <redacted key event>")
+            }
+            _ => log::trace!("from ei: {ei_event:?}"),
+        }
         let client = current_pos.get();
-        handle_ei_event(ei_event, client, &context, &event_tx, &release_session).await?;
+        handle_ei_event(
+            ei_event,
+            client,
+            &context,
+            &event_tx,
+            &release_session,
+            &seats,
+        )
+        .await?;
     }
 }
 
 impl LibeiInputCapture<'_> {
-    pub async fn new() -> std::result::Result<Self, LibeiCaptureCreationError> {
+    pub async fn new(
+        backpressure: Backpressure,
+        seats: Option<Vec<String>>,
+    ) -> std::result::Result<Self, LibeiCaptureCreationError> {
         let input_capture = Box::pin(InputCapture::new().await?);
         let input_capture_ptr = input_capture.as_ref().get_ref() as *const InputCapture<'static>;
         let first_session = Some(create_session(unsafe { &*input_capture_ptr }).await?);
@@ -212,6 +367,15 @@ impl LibeiInputCapture<'_> {
         let notify_release = Arc::new(Notify::new());
 
         let cancellation_token = CancellationToken::new();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let pending: PendingEvent = Default::default();
+        let event_tx = EventSender {
+            tx: event_tx,
+            backpressure,
+            dropped: dropped.clone(),
+            pending: pending.clone(),
+            seats,
+        };
 
         let capture = do_capture(
             input_capture_ptr,
@@ -231,6 +395,8 @@ impl LibeiInputCapture<'_> {
             notify_release,
             cancellation_token,
             terminated: false,
+            dropped,
+            pending,
         };
 
         Ok(producer)
@@ -242,7 +408,7 @@ async fn do_capture(
     mut capture_event: Receiver<LibeiNotifyEvent>,
     notify_release: Arc<Notify>,
     session: Option<(Session<'_, InputCapture<'_>>, BitFlags<Capabilities>)>,
-    event_tx: Sender<(Position, CaptureEvent)>,
+    event_tx: EventSender,
     cancellation_token: CancellationToken,
 ) -> Result<(), CaptureError> {
     let mut session = session.map(|s| s.0);
@@ -338,7 +504,7 @@ async fn do_capture(
 async fn do_capture_session(
     input_capture: &InputCapture<'_>,
     session: &mut Session<'_, InputCapture<'_>>,
-    event_tx: &Sender<(Position, CaptureEvent)>,
+    event_tx: &EventSender,
     active_clients: &[Position],
     next_barrier_id: &mut NonZeroU32,
     notify_release: &Notify,
@@ -347,6 +513,7 @@ async fn do_capture_session(
     let (cancel_session, cancel_update) = cancel;
     // current client
     let current_pos = Rc::new(Cell::new(None));
+    let seats = Rc::new(RefCell::new(Seats::new(event_tx.seats.clone())));
 
     // connect to eis server
     let (context, _conn, ei_event_stream) = connect_to_eis(input_capture, session).await?;
@@ -365,6 +532,7 @@ async fn do_capture_session(
     let cancel_ei_handler = CancellationToken::new();
     let event_chan = event_tx.clone();
     let pos = current_pos.clone();
+    let ei_seats = seats.clone();
     let cancel_session_clone = cancel_session.clone();
     let release_session_clone = release_session.clone();
     let cancel_ei_handler_clone = cancel_ei_handler.clone();
@@ -376,6 +544,7 @@ async fn do_capture_session(
                 event_chan,
                 release_session_clone,
                 pos,
+                ei_seats,
             ) => {
                 log::debug!("libei exited: {r:?} cancelling session task");
                 cancel_session_clone.cancel();
@@ -405,9 +574,10 @@ async fn do_capture_session(
                     // find client corresponding to barrier
                     let pos = *pos_for_barrier_id.get(&barrier_id).expect("invalid barrier id");
                     current_pos.replace(Some(pos));
+                    seats.borrow_mut().reset_origin();
 
                     // client entered => send event
-                    event_tx.send((pos, CaptureEvent::Begin)).await.expect("no channel");
+                    event_tx.send((pos, CaptureEvent::Begin)).await;
 
                     tokio::select! {
                         _ = notify_release.notified() => { /* capture release */
@@ -424,6 +594,7 @@ async fn do_capture_session(
                     }
 
                     release_capture(input_capture, session, activated, pos).await?;
+                    seats.borrow_mut().reset_origin();
 
                 }
                 _ = notify_release.notified() => { /* capture release -> we are not capturing anyway, so ignore */
@@ -531,15 +702,24 @@ async fn handle_ei_event(
     ei_event: EiEvent,
     current_client: Option<Position>,
     context: &ei::Context,
-    event_tx: &Sender<(Position, CaptureEvent)>,
+    event_tx: &EventSender,
     release_session: &Notify,
+    seats: &RefCell<Seats>,
 ) -> Result<(), CaptureError> {
     match ei_event {
         EiEvent::SeatAdded(s) => {
-            s.seat.bind_capabilities(ALL_CAPABILITIES);
-            context.flush().map_err(|e| io::Error::new(e.kind(), e))?;
+            if seats.borrow_mut().add(&s.seat) {
+                s.seat.bind_capabilities(ALL_CAPABILITIES);
+                context.flush().map_err(|e| io::Error::new(e.kind(), e))?;
+            }
+        }
+        EiEvent::SeatRemoved(ref s) => {
+            seats.borrow_mut().remove(&s.seat);
+            log::debug!("releasing session: {ei_event:?}");
+            release_session.notify_waiters();
         }
-        EiEvent::SeatRemoved(_) | /* EiEvent::DeviceAdded(_) | */ EiEvent::DeviceRemoved(_) => {
+        /* EiEvent::DeviceAdded(_) | */
+        EiEvent::DeviceRemoved(_) => {
             log::debug!("releasing session: {ei_event:?}");
             release_session.notify_waiters();
         }
@@ -551,8 +731,13 @@ async fn handle_ei_event(
         }
         _ => {
             if let Some(pos) = current_client {
+                let origin =
+                    seat_of(&ei_event).and_then(|s| seats.borrow_mut().origin_change(pos, s));
+                if let Some(id) = origin {
+                    event_tx.send((pos, CaptureEvent::Origin(id))).await;
+                }
                 for event in Event::from_ei_event(ei_event) {
-                    event_tx.send((pos, CaptureEvent::Input(event))).await.expect("no channel");
+                    event_tx.send((pos, CaptureEvent::Input(event))).await;
                 }
             }
         }
@@ -592,6 +777,10 @@ impl LanMouseInputCapture for LibeiInputCapture<'_> {
         self.terminated = true;
         res
     }
+
+    fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
 }
 
 impl Drop for LibeiInputCapture<'_> {
@@ -612,7 +801,14 @@ impl Stream for LibeiInputCapture<'_> {
                 Ok(()) => Poll::Ready(None),
                 Err(e) => Poll::Ready(Some(Err(e))),
             },
-            Poll::Pending => self.event_rx.poll_recv(cx).map(|e| e.map(Result::Ok)),
+            Poll::Pending => match self.event_rx.poll_recv(cx) {
+                // the pending event is newer than the events in the channel
+                Poll::Pending => match self.pending.lock().expect("lock").take() {
+                    Some(event) => Poll::Ready(Some(Ok(event))),
+                    None => Poll::Pending,
+                },
+                r => r.map(|e| e.map(Result::Ok)),
+            },
         }
     }
 }
@@ -14,7 +14,9 @@ use core_graphics::event::{
 };
 use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
 use futures_core::Stream;
-use input_event::{Event, KeyboardEvent, PointerEvent, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT};
+use input_event::{
+    Event, KeyboardEvent, PointerEvent, ScrollSource, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT,
+};
 use keycode::{KeyMap, KeyMapping};
 use libc::c_void;
 use once_cell::unsync::Lazy;
@@ -200,10 +202,12 @@ fn get_events(
     match ev_type {
         CGEventType::KeyDown => {
             let k = map_key(ev)?;
+            // auto-repeated presses are reported as repeated (2)
+            let repeat = ev.get_integer_value_field(EventField::KEYBOARD_EVENT_AUTOREPEAT) != 0;
             result.push(CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key {
                 time: 0,
                 key: k,
-                state: 1,
+                state: if repeat { 2 } else { 1 },
             })));
         }
         CGEventType::KeyUp => {
@@ -302,11 +306,17 @@ fn get_events(
         CGEventType::ScrollWheel => {
             let v = ev.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_1);
             let h = ev.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_POINT_DELTA_AXIS_2);
+            let source =
+                match ev.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_IS_CONTINUOUS) {
+                    0 => ScrollSource::Wheel,
+                    _ => ScrollSource::Finger,
+                };
             if v != 0 {
                 result.push(CaptureEvent::Input(Event::Pointer(PointerEvent::Axis {
                     time: 0,
                     axis: 0, // Vertical
                     value: v as f64,
+                    source,
                 })));
             }
             if h != 0 {
@@ -314,6 +324,7 @@ fn get_events(
                     time: 0,
                     axis: 1, // Horizontal
                     value: h as f64,
+                    source,
                 })));
             }
         }
@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Source of time of the timer based features of an
+/// [`InputEmulation`](crate::InputEmulation), i.e. pacing, frame rate,
+/// smoothing, scroll throttling, kinetic scrolling and the motion
+/// rate limit of the libei backend.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    /// the current time
+    fn now(&self) -> Instant;
+
+    /// wait for the given duration
+    async fn sleep(&self, duration: Duration);
+}
+
+/// the real monotonic clock, used unless another clock is set
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// Virtual time that only passes when advanced, so timer based
+/// emission can be tested deterministically: advance the clock,
+/// call [`InputEmulation::step`](crate::InputEmulation::step)
+/// and check the emitted events.
+/// Waiting on the clock advances it by the waited duration.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+    /// virtual clock starting at the current time
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// let the given duration pass
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
@@ -41,6 +41,8 @@ pub enum EmulationError {
     Ashpd(#[from] ashpd::Error),
     #[error("io error: `{0}`")]
     Io(#[from] io::Error),
+    #[error("background task failed: `{0}`")]
+    Task(#[from] tokio::task::JoinError),
 }
 
 #[derive(Debug, Error)]
@@ -57,6 +59,9 @@ pub enum EmulationCreationError {
     #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
     #[error("x11: `{0}`")]
     X11(#[from] X11EmulationCreationError),
+    #[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+    #[error("uinput: `{0}`")]
+    Uinput(#[from] UinputEmulationCreationError),
     #[cfg(target_os = "macos")]
     #[error("macos: `{0}`")]
     MacOs(#[from] MacOSEmulationCreationError),
@@ -133,6 +138,57 @@ pub enum LibeiEmulationCreationError {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Reis(#[from] reis::Error),
+    #[error(transparent)]
+    Handshake(#[from] LibeiHandshakeError),
+}
+
+/// reasons for a failed handshake with the EIS implementation
+#[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+#[derive(Debug, Error)]
+pub enum LibeiHandshakeError {
+    #[error("EIS implementation did not complete the handshake within {0:?} - is the remote desktop portal responsive?")]
+    Timeout(std::time::Duration),
+    #[error("EIS implementation offered unsupported version {version} of `{interface}` - the compositor's libei is incompatible with lan-mouse")]
+    VersionMismatch {
+        interface: &'static str,
+        version: u32,
+    },
+    #[error("EIS implementation did not offer a required interface ({0}) - the compositor's libei is too old or incomplete")]
+    MissingInterface(reis::handshake::HandshakeError),
+    #[error("EIS implementation closed the connection during the handshake - check the compositor's log")]
+    Closed,
+    #[error("malformed handshake: `{0}`")]
+    Protocol(reis::Error),
+}
+
+#[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+impl From<reis::Error> for LibeiHandshakeError {
+    fn from(e: reis::Error) -> Self {
+        use reis::handshake::HandshakeError;
+        match e {
+            reis::Error::InvalidInterfaceVersion(interface, version) => {
+                Self::VersionMismatch { interface, version }
+            }
+            reis::Error::Handshake(
+                e @ (HandshakeError::MissingInterface | HandshakeError::NoContextType),
+            ) => Self::MissingInterface(e),
+            reis::Error::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Self::Closed,
+            e => Self::Protocol(e),
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+#[derive(Debug, Error)]
+pub enum LibeiReplayError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Reis(#[from] reis::Error),
+    #[error(transparent)]
+    Handshake(#[from] LibeiHandshakeError),
+    #[error(transparent)]
+    Emulation(#[from] EmulationError),
 }
 
 #[cfg(all(unix, feature = "remote_desktop_portal", not(target_os = "macos")))]
@@ -149,6 +205,15 @@ pub enum X11EmulationCreationError {
     OpenDisplay,
 }
 
+#[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+#[derive(Debug, Error)]
+pub enum UinputEmulationCreationError {
+    #[error("could not open /dev/uinput: `{0}`")]
+    Open(io::Error),
+    #[error("could not set up uinput device: `{0}`")]
+    Ioctl(#[from] rustix::io::Errno),
+}
+
 #[cfg(target_os = "macos")]
 #[derive(Debug, Error)]
 pub enum MacOSEmulationCreationError {
@@ -0,0 +1,57 @@
+use tokio::sync::oneshot;
+
+/// Keeps the screen saver from activating (via the inhibit portal)
+/// until dropped. Without portal support this does nothing.
+pub struct IdleInhibitor {
+    _release: oneshot::Sender<()>,
+}
+
+impl IdleInhibitor {
+    /// inhibit idling in the background, showing `reason` to the user
+    pub fn new(reason: &str) -> Self {
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(inhibit(reason.to_owned(), rx));
+        Self { _release: tx }
+    }
+}
+
+#[cfg(all(
+    unix,
+    any(feature = "remote_desktop_portal", feature = "libei"),
+    not(target_os = "macos")
+))]
+async fn inhibit(reason: String, release: oneshot::Receiver<()>) {
+    use ashpd::desktop::inhibit::{InhibitFlags, InhibitProxy};
+
+    let request = match InhibitProxy::new().await {
+        Ok(proxy) => {
+            proxy
+                .inhibit(None, InhibitFlags::Idle.into(), &reason)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            log::warn!("could not inhibit the screen saver: {e}");
+            return;
+        }
+    };
+    log::debug!("screen saver inhibited");
+    // sender dropped
+    let _ = release.await;
+    match request.close().await {
+        Ok(()) => log::debug!("screen saver no longer inhibited"),
+        Err(e) => log::warn!("could not release screen saver inhibition: {e}"),
+    }
+}
+
+#[cfg(not(all(
+    unix,
+    any(feature = "remote_desktop_portal", feature = "libei"),
+    not(target_os = "macos")
+)))]
+async fn inhibit(_reason: String, _release: oneshot::Receiver<()>) {
+    log::debug!("inhibiting the screen saver is not supported on this platform");
+}
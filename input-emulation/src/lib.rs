@@ -2,9 +2,11 @@ use async_trait::async_trait;
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use input_event::{Event, KeyboardEvent};
+use input_event::{Event, KeyboardEvent, PointerEvent, ScrollSource};
 
 pub use self::error::{EmulationCreationError, EmulationError, InputEmulationError};
 
@@ -14,6 +16,9 @@ mod windows;
 #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
 mod x11;
 
+#[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+mod uinput;
+
 #[cfg(all(unix, feature = "wlroots", not(target_os = "macos")))]
 mod wlroots;
 
@@ -23,12 +28,27 @@ mod xdg_desktop_portal;
 #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
 mod libei;
 
+#[cfg(all(
+    unix,
+    any(feature = "remote_desktop_portal", feature = "libei"),
+    not(target_os = "macos")
+))]
+mod portal;
+
+#[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+pub use libei::{LibeiEmulation, LibeiEmulationBuilder};
+
 #[cfg(target_os = "macos")]
 mod macos;
 
+mod clock;
 /// fallback input emulation (logs events)
 mod dummy;
 mod error;
+mod inhibit;
+
+pub use clock::{Clock, SystemClock, VirtualClock};
+pub use inhibit::IdleInhibitor;
 
 pub type EmulationHandle = u64;
 
@@ -42,6 +62,8 @@ pub enum Backend {
     Xdp,
     #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
     X11,
+    #[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+    Uinput,
     #[cfg(windows)]
     Windows,
     #[cfg(target_os = "macos")]
@@ -60,6 +82,8 @@ impl Display for Backend {
             Backend::Xdp => write!(f, "xdg-desktop-portal"),
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             Backend::X11 => write!(f, "X11"),
+            #[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+            Backend::Uinput => write!(f, "uinput"),
             #[cfg(windows)]
             Backend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
@@ -69,21 +93,757 @@ impl Display for Backend {
     }
 }
 
+/// options applied to an [`InputEmulation`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EmulationOptions {
+    /// wait up to the given duration for all devices
+    /// to be bound when creating the emulation
+    pub warmup: Option<Duration>,
+    /// minimum time between two emulated events
+    pub min_spacing: Option<Duration>,
+    /// maximum duration of synthesized kinetic scrolling
+    /// for backends without native support (disabled if `None`)
+    pub max_fling: Option<Duration>,
+    /// emit pointer motion at most at this rate (in Hz),
+    /// coalescing the motion in between (per-event if `None`)
+    pub frame_rate: Option<u32>,
+    /// emit whole pixels of pointer motion at the frame rate (or
+    /// [`PRECISE_MOTION_FRAME_RATE`]), carrying sub-pixel motion over
+    /// to the next frame instead of losing it to integer backends
+    pub precise_motion: bool,
+    /// strength of the low-pass filter smoothing pointer motion
+    /// in percent (unfiltered if `None`)
+    pub motion_smoothing: Option<u32>,
+    /// how to react to the removal of a device in use
+    pub device_removal: DeviceRemoval,
+    /// scaling of discrete scroll events (unscaled if `None`)
+    pub discrete_scroll_scale: Option<DiscreteScrollScale>,
+    /// maximum distance of a single motion event in pixels,
+    /// larger motion is shortened (unlimited if `None`)
+    pub max_motion: Option<u32>,
+    /// sum up scroll events within the given window and emit
+    /// them as a single scroll event (per-event if `None`)
+    pub scroll_throttle: Option<Duration>,
+    /// how long to retry sending events to a backend that can not
+    /// keep up before giving up (backend default if `None`, libei only)
+    pub flush_retry: Option<Duration>,
+    /// how long to wait for the backend to complete its handshake
+    /// (backend default if `None`, libei only)
+    pub handshake_timeout: Option<Duration>,
+    /// buffer pointer motion received before the pointer device is bound
+    /// and emit it once it is, dropping motion older than the given
+    /// duration (motion without a device is lost if `None`, libei only)
+    pub early_motion_buffer: Option<Duration>,
+    /// emit empty frames at this interval, keeping the event stream
+    /// alive while there is no input (disabled if `None`, libei only)
+    pub idle_frame_interval: Option<Duration>,
+    /// log the serial and frame counters at this interval
+    /// (disabled if `None`, libei only)
+    pub counter_log_interval: Option<Duration>,
+    /// emulate the input of each handle with separate devices
+    /// (uinput only, other backends can not create devices)
+    pub device_per_handle: bool,
+    /// keep the screen saver from activating while a sending
+    /// device has an active session (see [`IdleInhibitor`])
+    pub inhibit_idle: bool,
+    /// retry creating the emulation at this interval after its backend
+    /// failed at runtime, e.g. because the session of the compositor
+    /// ended (not retried if `None`, applied by the application)
+    pub recreate_interval: Option<Duration>,
+    /// how to handle events of handles that were never created
+    pub unknown_handle: UnknownHandle,
+    /// how to handle events of capabilities without a device
+    pub degradation: Degradation,
+    /// scroll event applied if a frame carries both a continuous
+    /// and a discrete event for the same wheel motion
+    pub scroll_preference: ScrollPreference,
+    /// emulate motion and scroll events without any delta,
+    /// which are skipped by default (frames are emitted regardless)
+    pub emit_zero_deltas: bool,
+}
+
+/// Scaling of discrete scroll events, which carry 120 units per detent.
+/// Values are multiplied by `multiplier` and divided by `divisor`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiscreteScrollScale {
+    pub multiplier: i32,
+    pub divisor: i32,
+}
+
+/// scales discrete scroll events,
+/// carrying remainders over to the next event
+struct DiscreteScroll {
+    scale: DiscreteScrollScale,
+    remainder: HashMap<(EmulationHandle, u8), i64>,
+}
+
+impl DiscreteScroll {
+    fn new(scale: DiscreteScrollScale) -> Self {
+        Self {
+            scale: DiscreteScrollScale {
+                multiplier: scale.multiplier,
+                divisor: scale.divisor.max(1),
+            },
+            remainder: HashMap::new(),
+        }
+    }
+
+    /// scaled value of a discrete scroll event (0 if less than one unit)
+    fn apply(&mut self, handle: EmulationHandle, axis: u8, value: i32) -> i32 {
+        let remainder = self.remainder.entry((handle, axis)).or_default();
+        let scaled = value as i64 * self.scale.multiplier as i64;
+        // start over when the direction changes
+        if remainder.signum() * scaled.signum() < 0 {
+            *remainder = 0;
+        }
+        let scaled = *remainder + scaled;
+        let divisor = self.scale.divisor as i64;
+        *remainder = scaled % divisor;
+        (scaled / divisor).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// Whether the event moves the pointer or scrolls by nothing.
+fn is_zero_delta(event: &Event) -> bool {
+    match event {
+        Event::Pointer(PointerEvent::Motion { dx, dy, .. }) => *dx == 0. && *dy == 0.,
+        Event::Pointer(PointerEvent::Axis { value, .. }) => *value == 0.,
+        Event::Pointer(PointerEvent::AxisDiscrete120 { value, .. }) => *value == 0,
+        _ => false,
+    }
+}
+
+/// Shorten motion longer than `max`, keeping its direction.
+/// Motion that is not finite is discarded.
+fn clamp_motion(dx: f64, dy: f64, max: f64) -> (f64, f64) {
+    if !dx.is_finite() || !dy.is_finite() {
+        log::warn!("discarding invalid motion ({dx}, {dy})");
+        return (0., 0.);
+    }
+    let distance = dx.hypot(dy);
+    if distance <= max {
+        return (dx, dy);
+    }
+    log::warn!("clamping motion ({dx:.0}, {dy:.0}) to {max} pixels");
+    let scale = max / distance;
+    (dx * scale, dy * scale)
+}
+
+/// reaction of the emulation to the removal of a device it is using
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DeviceRemoval {
+    /// wait for the backend to offer a new device
+    Wait,
+    /// request new devices for the capabilities of the removed device
+    #[default]
+    Rebind,
+}
+
+/// handling of events for an [`EmulationHandle`]
+/// that was not created with [`InputEmulation::create`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownHandle {
+    /// create the handle with the first event
+    #[default]
+    Create,
+    /// drop the events and log the handle
+    Reject,
+}
+
+/// Reaction to events of a capability the backend has no device for.
+/// Key events without a keyboard are always dropped.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Degradation {
+    pub scroll: ScrollFallback,
+    pub absolute: AbsoluteFallback,
+}
+
+/// handling of scroll events without a scroll device
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ScrollFallback {
+    #[default]
+    Drop,
+    /// press and release a button for each detent scrolled,
+    /// dropped if there is no button device either
+    Buttons(ScrollButtons),
+}
+
+/// buttons (linux button codes) pressed for a detent
+/// scrolled in each direction, see [`ScrollFallback::Buttons`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ScrollButtons {
+    pub up: u32,
+    pub down: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Default for ScrollButtons {
+    /// the side, extra, forward and back buttons (`BTN_SIDE` to `BTN_BACK`),
+    /// which can be bound to scrolling on the target
+    fn default() -> Self {
+        Self {
+            up: 0x113,
+            down: 0x114,
+            left: 0x115,
+            right: 0x116,
+        }
+    }
+}
+
+/// handling of absolute motion without an absolute pointer device
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AbsoluteFallback {
+    #[default]
+    Drop,
+    /// move the pointer relatively by the change of the position,
+    /// assuming an area of the given size in pixels
+    Relative { width: u32, height: u32 },
+}
+
+/// Scroll event to apply if a frame carries a continuous and a discrete
+/// scroll event for the same axis, which some sources send for the same
+/// wheel motion. Only applies to handles sending frame markers.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ScrollPreference {
+    #[default]
+    Discrete,
+    Continuous,
+    /// apply both, possibly scrolling twice as far
+    Both,
+}
+
+/// applies only one of a continuous and a discrete
+/// scroll event for the same axis within a frame
+struct ScrollPairing {
+    prefer_discrete: bool,
+    /// the preferred kind of scroll event was seen in the current frame
+    preferred_seen: HashSet<(EmulationHandle, u8)>,
+    /// events of the other kind held back until the end of the frame
+    held: HashMap<EmulationHandle, Vec<PointerEvent>>,
+}
+
+impl ScrollPairing {
+    fn new(preference: ScrollPreference) -> Option<Self> {
+        let prefer_discrete = match preference {
+            ScrollPreference::Discrete => true,
+            ScrollPreference::Continuous => false,
+            ScrollPreference::Both => return None,
+        };
+        Some(Self {
+            prefer_discrete,
+            preferred_seen: Default::default(),
+            held: Default::default(),
+        })
+    }
+
+    /// Returns whether the event is applied right away,
+    /// otherwise it is held back or dropped.
+    fn pass(&mut self, handle: EmulationHandle, event: PointerEvent) -> bool {
+        let (axis, discrete) = match event {
+            PointerEvent::AxisDiscrete120 { axis, .. } => (axis, true),
+            PointerEvent::Axis {
+                axis,
+                source: ScrollSource::Wheel | ScrollSource::WheelTilt,
+                ..
+            } => (axis, false),
+            _ => return true,
+        };
+        if discrete == self.prefer_discrete {
+            self.preferred_seen.insert((handle, axis));
+            if let Some(held) = self.held.get_mut(&handle) {
+                held.retain(|e| !is_axis(e, axis));
+            }
+            true
+        } else {
+            if !self.preferred_seen.contains(&(handle, axis)) {
+                self.held.entry(handle).or_default().push(event);
+            }
+            false
+        }
+    }
+
+    /// end of the frame: returns the held back events without a counterpart
+    fn end_frame(&mut self, handle: EmulationHandle) -> Vec<PointerEvent> {
+        self.preferred_seen.retain(|&(h, _)| h != handle);
+        self.held.remove(&handle).unwrap_or_default()
+    }
+}
+
+fn is_axis(event: &PointerEvent, axis: u8) -> bool {
+    matches!(
+        event,
+        PointerEvent::Axis { axis: a, .. } | PointerEvent::AxisDiscrete120 { axis: a, .. } if *a == axis
+    )
+}
+
+/// units of discrete scroll events per detent
+const DETENT: f64 = 120.;
+
+/// units of discrete scroll per unit of smooth scrolling
+/// (a detent scrolls 15 units on most systems)
+const SMOOTH_TO_DISCRETE: f64 = 8.;
+
+/// interval between synthesized kinetic scroll events
+const FLING_STEP: Duration = Duration::from_millis(16);
+
+/// time constant of the exponential decay of a fling
+const FLING_DECAY: Duration = Duration::from_millis(325);
+
+/// flings slower than this (in scroll units per second) are not synthesized / stopped
+const MIN_FLING_VELOCITY: f64 = 50.;
+
+/// finger scroll events further apart than this start a new velocity estimate
+const FLING_SAMPLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// velocity estimate of finger scrolling on one axis
+#[derive(Clone, Copy, Debug)]
+struct ScrollVelocity {
+    last: Instant,
+    velocity: f64,
+}
+
+/// synthesized kinetic scrolling
+#[derive(Clone, Copy, Debug)]
+struct Fling {
+    handle: EmulationHandle,
+    axis: u8,
+    velocity: f64,
+    started: Instant,
+    last_step: Instant,
+}
+
+/// maximum delay added by pacing within a single burst of events.
+/// Once exceeded, events of the burst are emitted immediately
+/// so the delay does not accumulate.
+const MAX_PACING_DELAY: Duration = Duration::from_millis(100);
+
+/// paces emitted events to a minimum spacing
+struct Pacing {
+    spacing: Duration,
+    last: Option<Instant>,
+    /// delay added in the current burst
+    delay: Duration,
+}
+
+impl Pacing {
+    fn new(spacing: Duration) -> Self {
+        Self {
+            spacing,
+            last: None,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// wait until the next event may be emitted
+    async fn wait(&mut self, clock: &dyn Clock) {
+        if let Some(last) = self.last {
+            let elapsed = clock.now().duration_since(last);
+            if elapsed >= self.spacing {
+                // burst ended
+                self.delay = Duration::ZERO;
+            } else if self.delay < MAX_PACING_DELAY {
+                let remaining = self.spacing - elapsed;
+                self.delay += remaining;
+                clock.sleep(remaining).await;
+            }
+        }
+        self.last = Some(clock.now());
+    }
+}
+
+/// keys and buttons the emulation believes to be held for a handle
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeldState {
+    /// pressed keys (linux scancodes)
+    pub keys: HashSet<u32>,
+    /// pressed pointer buttons
+    pub buttons: HashSet<u32>,
+}
+
+/// an input device used by the emulation backend
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceInfo {
+    /// capability the device is used for
+    pub capability: &'static str,
+    /// name of the device, if any
+    pub name: Option<String>,
+    /// type of the device as reported by the backend
+    pub device_type: String,
+}
+
+impl Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.name.as_deref().unwrap_or("unnamed");
+        write!(f, "{}: {name} ({})", self.capability, self.device_type)
+    }
+}
+
+/// protocol counters of backends sending frames (libei)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameCounters {
+    /// the last serial received from the compositor
+    pub serial: u32,
+    /// number of frames sent
+    pub frames: u64,
+}
+
+impl Display for FrameCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "serial {}, {} frames", self.serial, self.frames)
+    }
+}
+
+/// information about the active emulation backend
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BackendInfo {
+    /// the backend in use
+    pub backend: Backend,
+    /// devices chosen by the backend
+    pub devices: Vec<DeviceInfo>,
+    /// protocol counters, if the backend has any
+    pub counters: Option<FrameCounters>,
+    /// device types the user granted access to,
+    /// if the backend asks for permission
+    pub granted: Option<Vec<&'static str>>,
+    /// fallbacks in effect for capabilities without a device
+    pub degraded: Vec<&'static str>,
+}
+
+impl Display for BackendInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.backend)?;
+        for device in self.devices.iter() {
+            write!(f, ", {device}")?;
+        }
+        if let Some(counters) = self.counters {
+            write!(f, ", {counters}")?;
+        }
+        if let Some(granted) = &self.granted {
+            write!(f, ", granted: {}", granted.join("+"))?;
+        }
+        if !self.degraded.is_empty() {
+            write!(f, ", degraded: {}", self.degraded.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 pub struct InputEmulation {
+    backend: Backend,
     emulation: Box<dyn Emulation>,
     handles: HashSet<EmulationHandle>,
+    unknown_handle: UnknownHandle,
+    /// unknown handles whose events were dropped
+    rejected: HashSet<EmulationHandle>,
+    degradation: Degradation,
+    /// fallbacks already reported
+    degraded: HashSet<&'static str>,
+    /// scroll not yet emulated with buttons (in discrete units)
+    scroll_buttons: HashMap<(EmulationHandle, u8), f64>,
+    /// axes of handles that sent discrete scroll events,
+    /// only these are emulated with buttons
+    discrete_axes: HashSet<(EmulationHandle, u8)>,
+    /// last absolute position for relative fallback motion
+    last_absolute: HashMap<EmulationHandle, (f64, f64)>,
+    scroll_pairing: Option<ScrollPairing>,
+    /// handles sending frame markers
+    framed: HashSet<EmulationHandle>,
     pressed_keys: HashMap<EmulationHandle, HashSet<u32>>,
+    pressed_buttons: HashMap<EmulationHandle, HashSet<u32>>,
+    pacing: Option<Pacing>,
+    max_fling: Option<Duration>,
+    scroll_velocity: HashMap<(EmulationHandle, u8), ScrollVelocity>,
+    flings: Vec<Fling>,
+    frame_clock: Option<FrameClock>,
+    motion_filter: Option<MotionFilter>,
+    discrete_scroll: Option<DiscreteScroll>,
+    max_motion: Option<f64>,
+    scroll_throttle: Option<ScrollThrottle>,
+    /// emulate motion and scroll events without any delta
+    emit_zero_deltas: bool,
+    /// interval of empty frames and the time the last one was emitted
+    idle_frames: Option<(Duration, Instant)>,
+    /// interval of counter logs and the time of the last one
+    counter_log: Option<(Duration, Instant)>,
+    /// source of time of the timer based features
+    clock: Arc<dyn Clock>,
+    /// keys and buttons held by each handle when emulation was paused,
+    /// `None` unless paused
+    paused: Option<HashMap<EmulationHandle, HeldState>>,
+}
+
+/// frame rate of [`EmulationOptions::precise_motion`]
+/// if no frame rate is configured
+pub const PRECISE_MOTION_FRAME_RATE: u32 = 250;
+
+/// paces pointer motion to a fixed frame rate
+struct FrameClock {
+    interval: Duration,
+    last_frame: Option<Instant>,
+    /// coalesced motion not yet emitted
+    pending: HashMap<EmulationHandle, (f64, f64)>,
+    /// sub-pixel motion carried over to the next frame,
+    /// if only whole pixels are emitted
+    remainder: Option<HashMap<EmulationHandle, (f64, f64)>>,
+}
+
+impl FrameClock {
+    fn new(frame_rate: u32, precise: bool) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / frame_rate.max(1),
+            last_frame: None,
+            pending: HashMap::new(),
+            remainder: precise.then(HashMap::new),
+        }
+    }
+
+    /// Whole pixels of the motion of a handle plus its remainder,
+    /// keeping the sub-pixel part as the new remainder.
+    /// Returns the motion as is, if not precise.
+    fn split(&mut self, handle: EmulationHandle, dx: f64, dy: f64) -> Option<(f64, f64)> {
+        let Some(remainder) = self.remainder.as_mut() else {
+            return Some((dx, dy));
+        };
+        let (rdx, rdy) = remainder.remove(&handle).unwrap_or_default();
+        let (dx, dy) = (dx + rdx, dy + rdy);
+        let (wdx, wdy) = (dx.trunc(), dy.trunc());
+        if dx != wdx || dy != wdy {
+            remainder.insert(handle, (dx - wdx, dy - wdy));
+        }
+        (wdx != 0. || wdy != 0.).then_some((wdx, wdy))
+    }
+
+    /// take the coalesced motion of a handle
+    fn take_handle(&mut self, handle: EmulationHandle) -> Option<(f64, f64)> {
+        let (dx, dy) = self.pending.remove(&handle)?;
+        self.split(handle, dx, dy)
+    }
+
+    /// forget the motion of a handle
+    fn remove(&mut self, handle: EmulationHandle) {
+        self.pending.remove(&handle);
+        if let Some(remainder) = self.remainder.as_mut() {
+            remainder.remove(&handle);
+        }
+    }
+
+    /// time at which the coalesced motion is due
+    fn deadline(&self) -> Option<Instant> {
+        match self.pending.is_empty() {
+            true => None,
+            false => self.last_frame.map(|t| t + self.interval),
+        }
+    }
+
+    /// Add motion of a handle.
+    /// Returns true if a frame is due and the motion should be emitted.
+    fn add_motion(&mut self, handle: EmulationHandle, dx: f64, dy: f64, now: Instant) -> bool {
+        let (pdx, pdy) = self.pending.entry(handle).or_default();
+        *pdx += dx;
+        *pdy += dy;
+        self.last_frame
+            .is_none_or(|last| now.duration_since(last) >= self.interval)
+    }
+
+    /// take the coalesced motion of all handles, starting a new frame
+    fn take(&mut self, now: Instant) -> Vec<(EmulationHandle, (f64, f64))> {
+        self.last_frame = Some(now);
+        let pending = std::mem::take(&mut self.pending);
+        pending
+            .into_iter()
+            .filter_map(|(h, (dx, dy))| Some((h, self.split(h, dx, dy)?)))
+            .collect()
+    }
+}
+
+/// scroll of one axis summed up within a throttle window
+#[derive(Clone, Copy, Debug, Default)]
+struct PendingScroll {
+    value: f64,
+    /// source of the latest event
+    source: ScrollSource,
+    discrete: i32,
+}
+
+/// coalesces scroll events, independent of pointer motion
+struct ScrollThrottle {
+    window: Duration,
+    last_emit: Option<Instant>,
+    /// summed scroll not yet emitted, no fraction is lost
+    pending: HashMap<(EmulationHandle, u8), PendingScroll>,
+}
+
+impl ScrollThrottle {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_emit: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// time at which the summed scroll is due
+    fn deadline(&self) -> Option<Instant> {
+        match self.pending.is_empty() {
+            true => None,
+            false => self.last_emit.map(|t| t + self.window),
+        }
+    }
+
+    /// Add a scroll event of a handle.
+    /// Returns true if the window elapsed and the scroll should be emitted.
+    fn add(&mut self, handle: EmulationHandle, event: PointerEvent, now: Instant) -> bool {
+        match event {
+            PointerEvent::Axis {
+                axis,
+                value,
+                source,
+                ..
+            } => {
+                let pending = self.pending.entry((handle, axis)).or_default();
+                pending.value += value;
+                pending.source = source;
+            }
+            PointerEvent::AxisDiscrete120 { axis, value } => {
+                let pending = self.pending.entry((handle, axis)).or_default();
+                pending.discrete = pending.discrete.saturating_add(value);
+            }
+            _ => return false,
+        }
+        self.last_emit
+            .is_none_or(|last| now.duration_since(last) >= self.window)
+    }
+
+    /// take the summed scroll of the handles matching `filter`
+    fn take(
+        &mut self,
+        filter: impl Fn(EmulationHandle) -> bool,
+        now: Instant,
+    ) -> Vec<(EmulationHandle, Event)> {
+        let taken = self
+            .pending
+            .extract_if(|&(h, _), _| filter(h))
+            .collect::<Vec<_>>();
+        if !taken.is_empty() {
+            self.last_emit = Some(now);
+        }
+        let mut events = vec![];
+        for ((handle, axis), pending) in taken {
+            if pending.discrete != 0 {
+                let event = PointerEvent::AxisDiscrete120 {
+                    axis,
+                    value: pending.discrete,
+                };
+                events.push((handle, Event::Pointer(event)));
+            }
+            if pending.value != 0. {
+                let event = PointerEvent::Axis {
+                    time: 0,
+                    axis,
+                    value: pending.value,
+                    source: pending.source,
+                };
+                events.push((handle, Event::Pointer(event)));
+            }
+        }
+        events
+    }
+}
+
+/// interval at which motion held back by the smoothing filter is released
+const SMOOTHING_STEP: Duration = Duration::from_millis(8);
+
+/// held back motion smaller than this (in pixels) is released at once
+const MIN_SMOOTHING_RESIDUAL: f64 = 0.5;
+
+/// maximum smoothing strength in percent, keeping the filter responsive
+const MAX_SMOOTHING: u32 = 90;
+
+/// low-pass filter smoothing pointer motion.
+/// Motion that is held back is kept as a residual
+/// and released gradually, so no motion is lost.
+struct MotionFilter {
+    /// fraction of the motion held back per event
+    retain: f64,
+    last_step: Instant,
+    residual: HashMap<EmulationHandle, (f64, f64)>,
+}
+
+impl MotionFilter {
+    fn new(strength: u32) -> Self {
+        Self {
+            retain: strength.min(MAX_SMOOTHING) as f64 / 100.,
+            last_step: Instant::now(),
+            residual: HashMap::new(),
+        }
+    }
+
+    /// filter motion of a handle
+    fn apply(&mut self, handle: EmulationHandle, dx: f64, dy: f64, now: Instant) -> (f64, f64) {
+        self.last_step = now;
+        let (rx, ry) = self.residual.entry(handle).or_default();
+        let (x, y) = (*rx + dx, *ry + dy);
+        let (ox, oy) = match x.hypot(y) < MIN_SMOOTHING_RESIDUAL {
+            true => (x, y),
+            false => (x * (1. - self.retain), y * (1. - self.retain)),
+        };
+        (*rx, *ry) = (x - ox, y - oy);
+        if *rx == 0. && *ry == 0. {
+            self.residual.remove(&handle);
+        }
+        (ox, oy)
+    }
+
+    /// take the motion held back for a handle
+    fn take(&mut self, handle: EmulationHandle) -> Option<(f64, f64)> {
+        self.residual.remove(&handle)
+    }
+
+    /// time at which held back motion is due
+    fn deadline(&self) -> Option<Instant> {
+        match self.residual.is_empty() {
+            true => None,
+            false => Some(self.last_step + SMOOTHING_STEP),
+        }
+    }
+
+    /// release part of the motion held back for all handles
+    fn step(&mut self, now: Instant) -> Vec<(EmulationHandle, (f64, f64))> {
+        let handles = self.residual.keys().copied().collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| (h, self.apply(h, 0., 0., now)))
+            .collect()
+    }
 }
 
 impl InputEmulation {
-    async fn with_backend(backend: Backend) -> Result<InputEmulation, EmulationCreationError> {
+    async fn with_backend(
+        backend: Backend,
+        options: EmulationOptions,
+    ) -> Result<InputEmulation, EmulationCreationError> {
         let emulation: Box<dyn Emulation> = match backend {
             #[cfg(all(unix, feature = "wlroots", not(target_os = "macos")))]
             Backend::Wlroots => Box::new(wlroots::WlrootsEmulation::new()?),
             #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
-            Backend::Libei => Box::new(libei::LibeiEmulation::new().await?),
+            Backend::Libei => {
+                let mut builder = libei::LibeiEmulationBuilder::new()
+                    .warmup(options.warmup)
+                    .early_motion_buffer(options.early_motion_buffer)
+                    .device_removal(options.device_removal);
+                if let Some(flush_retry) = options.flush_retry {
+                    builder = builder.flush_retry(flush_retry);
+                }
+                if let Some(handshake_timeout) = options.handshake_timeout {
+                    builder = builder.handshake_timeout(handshake_timeout);
+                }
+                Box::new(builder.build().await?)
+            }
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             Backend::X11 => Box::new(x11::X11Emulation::new()?),
+            #[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+            Backend::Uinput => Box::new(uinput::UinputEmulation::new(options.device_per_handle)?),
             #[cfg(all(unix, feature = "remote_desktop_portal", not(target_os = "macos")))]
             Backend::Xdp => Box::new(xdg_desktop_portal::DesktopPortalEmulation::new().await?),
             #[cfg(windows)]
@@ -92,16 +852,67 @@ impl InputEmulation {
             Backend::MacOs => Box::new(macos::MacOSEmulation::new()?),
             Backend::Dummy => Box::new(dummy::DummyEmulation::new()),
         };
-        Ok(Self {
+        Ok(Self::from_emulation(backend, emulation, options))
+    }
+
+    fn from_emulation(
+        backend: Backend,
+        emulation: Box<dyn Emulation>,
+        options: EmulationOptions,
+    ) -> Self {
+        Self {
+            backend,
             emulation,
             handles: HashSet::new(),
+            unknown_handle: options.unknown_handle,
+            rejected: HashSet::new(),
+            degradation: options.degradation,
+            degraded: HashSet::new(),
+            scroll_buttons: HashMap::new(),
+            discrete_axes: HashSet::new(),
+            last_absolute: HashMap::new(),
+            scroll_pairing: ScrollPairing::new(options.scroll_preference),
+            framed: HashSet::new(),
             pressed_keys: HashMap::new(),
-        })
+            pressed_buttons: HashMap::new(),
+            pacing: options.min_spacing.map(Pacing::new),
+            max_fling: options.max_fling,
+            scroll_velocity: HashMap::new(),
+            flings: vec![],
+            frame_clock: match (options.frame_rate, options.precise_motion) {
+                (Some(rate), precise) => Some(FrameClock::new(rate, precise)),
+                (None, true) => Some(FrameClock::new(PRECISE_MOTION_FRAME_RATE, true)),
+                (None, false) => None,
+            },
+            motion_filter: options
+                .motion_smoothing
+                .filter(|&s| s > 0)
+                .map(MotionFilter::new),
+            discrete_scroll: options.discrete_scroll_scale.map(DiscreteScroll::new),
+            max_motion: options.max_motion.map(f64::from),
+            scroll_throttle: options.scroll_throttle.map(ScrollThrottle::new),
+            emit_zero_deltas: options.emit_zero_deltas,
+            idle_frames: options
+                .idle_frame_interval
+                .map(|interval| (interval, Instant::now())),
+            counter_log: options
+                .counter_log_interval
+                .map(|interval| (interval, Instant::now())),
+            clock: Arc::new(SystemClock),
+            paused: None,
+        }
     }
 
     pub async fn new(backend: Option<Backend>) -> Result<InputEmulation, EmulationCreationError> {
+        Self::with_options(backend, Default::default()).await
+    }
+
+    pub async fn with_options(
+        backend: Option<Backend>,
+        options: EmulationOptions,
+    ) -> Result<InputEmulation, EmulationCreationError> {
         if let Some(backend) = backend {
-            let b = Self::with_backend(backend).await;
+            let b = Self::with_backend(backend, options).await;
             if b.is_ok() {
                 log::info!("using emulation backend: {backend}");
             }
@@ -117,13 +928,15 @@ impl InputEmulation {
             Backend::Xdp,
             #[cfg(all(unix, feature = "x11", not(target_os = "macos")))]
             Backend::X11,
+            #[cfg(all(unix, feature = "uinput", not(target_os = "macos")))]
+            Backend::Uinput,
             #[cfg(windows)]
             Backend::Windows,
             #[cfg(target_os = "macos")]
             Backend::MacOs,
             Backend::Dummy,
         ] {
-            match Self::with_backend(backend).await {
+            match Self::with_backend(backend, options).await {
                 Ok(b) => {
                     log::info!("using emulation backend: {backend}");
                     return Ok(b);
@@ -141,6 +954,116 @@ impl InputEmulation {
         event: Event,
         handle: EmulationHandle,
     ) -> Result<(), EmulationError> {
+        // backends would misinterpret out-of-range values,
+        // e.g. treat any nonzero button state as a press
+        if let Err(e) = event.validate() {
+            log::warn!("dropping invalid event: {e}");
+            return Ok(());
+        }
+        if self.paused.is_some() {
+            self.consume_paused(event, handle);
+            return Ok(());
+        }
+        if !self.handles.contains(&handle) {
+            match self.unknown_handle {
+                UnknownHandle::Create => {
+                    log::debug!("creating handle {handle} for its first event");
+                    self.create(handle).await;
+                }
+                UnknownHandle::Reject => {
+                    if self.rejected.insert(handle) {
+                        log::warn!("dropping events of unknown handle {handle}");
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        if let (Event::Pointer(p), Some(pairing)) = (event, self.scroll_pairing.as_mut()) {
+            if self.framed.contains(&handle) && !pairing.pass(handle, p) {
+                return Ok(());
+            }
+        }
+        self.consume_paired(event, handle).await
+    }
+
+    async fn consume_paired(
+        &mut self,
+        event: Event,
+        handle: EmulationHandle,
+    ) -> Result<(), EmulationError> {
+        let Some(event) = self.degrade(event, handle).await? else {
+            return Ok(());
+        };
+        // nothing to emulate, the frame is still emitted
+        if !self.emit_zero_deltas && is_zero_delta(&event) {
+            log::trace!("skipping zero delta event {event}");
+            return Ok(());
+        }
+        if let Some(pacing) = self.pacing.as_mut() {
+            pacing.wait(self.clock.as_ref()).await;
+        }
+        let event = match (event, self.max_motion) {
+            (Event::Pointer(PointerEvent::Motion { time, dx, dy }), Some(max)) => {
+                let (dx, dy) = clamp_motion(dx, dy, max);
+                Event::Pointer(PointerEvent::Motion { time, dx, dy })
+            }
+            (event, _) => event,
+        };
+        if let Event::Pointer(p) = event {
+            if self.track_scroll(p, handle) {
+                return Ok(());
+            }
+        }
+        let event = match (event, self.discrete_scroll.as_mut()) {
+            (Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value }), Some(scroll)) => {
+                match scroll.apply(handle, axis, value) {
+                    0 => return Ok(()),
+                    value => Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value }),
+                }
+            }
+            (event, _) => event,
+        };
+        if let Some(throttle) = self.scroll_throttle.as_mut() {
+            match event {
+                Event::Pointer(
+                    p @ (PointerEvent::Axis { .. } | PointerEvent::AxisDiscrete120 { .. }),
+                ) => {
+                    if throttle.add(handle, p, self.clock.now()) {
+                        self.emit_scroll(|_| true).await?;
+                    }
+                    return Ok(());
+                }
+                // keep the order of scroll and other events
+                _ => self.emit_scroll(|h| h == handle).await?,
+            }
+        }
+        let event = match (event, self.motion_filter.as_mut()) {
+            (Event::Pointer(PointerEvent::Motion { time, dx, dy }), Some(filter)) => {
+                let (dx, dy) = filter.apply(handle, dx, dy, self.clock.now());
+                Event::Pointer(PointerEvent::Motion { time, dx, dy })
+            }
+            (event, Some(filter)) => {
+                // arrive at the final position before other events
+                if let Some((dx, dy)) = filter.take(handle) {
+                    self.emit_motion(handle, dx, dy).await?;
+                }
+                event
+            }
+            (event, None) => event,
+        };
+        if let Some(frame_clock) = self.frame_clock.as_mut() {
+            if let Event::Pointer(PointerEvent::Motion { dx, dy, .. }) = event {
+                if frame_clock.add_motion(handle, dx, dy, self.clock.now()) {
+                    self.emit_frame().await?;
+                }
+                return Ok(());
+            }
+            // keep the order of motion and other events
+            if let Some((dx, dy)) = frame_clock.take_handle(handle) {
+                let motion = Event::Pointer(PointerEvent::Motion { time: 0, dx, dy });
+                self.emulation.consume(motion, handle).await?;
+            }
+        }
         match event {
             Event::Keyboard(KeyboardEvent::Key { key, state, .. }) => {
                 // prevent double pressed / released keys
@@ -149,13 +1072,270 @@ impl InputEmulation {
                 }
                 Ok(())
             }
+            Event::Pointer(PointerEvent::Button { button, state, .. }) => {
+                if let Some(pressed_buttons) = self.pressed_buttons.get_mut(&handle) {
+                    if state == 0 {
+                        pressed_buttons.remove(&button);
+                    } else {
+                        pressed_buttons.insert(button);
+                    }
+                }
+                self.emulation.consume(event, handle).await
+            }
             _ => self.emulation.consume(event, handle).await,
         }
     }
 
+    /// Presses are discarded while paused,
+    /// releases are applied on [`InputEmulation::resume`].
+    fn consume_paused(&mut self, event: Event, handle: EmulationHandle) {
+        let held = match event {
+            Event::Keyboard(KeyboardEvent::Key { key, state: 0, .. }) => {
+                self.pressed_keys.get_mut(&handle).map(|k| k.remove(&key))
+            }
+            Event::Pointer(PointerEvent::Button {
+                button, state: 0, ..
+            }) => self
+                .pressed_buttons
+                .get_mut(&handle)
+                .map(|b| b.remove(&button)),
+            _ => None,
+        };
+        if held != Some(true) {
+            log::trace!("emulation paused, discarding {event}");
+        }
+    }
+
+    /// Suspend emulation without closing the session of the backend,
+    /// keeping its devices. Until [`InputEmulation::resume`] is called,
+    /// presses and other events are discarded, while releases of held
+    /// keys and buttons are applied once emulation resumes.
+    pub fn pause(&mut self) -> Result<(), EmulationError> {
+        if self.paused.is_some() {
+            return Ok(());
+        }
+        self.emulation.pause()?;
+        let held = self
+            .handles
+            .iter()
+            .filter_map(|&handle| Some((handle, self.held_state(handle)?)))
+            .collect();
+        self.paused = Some(held);
+        log::info!("input emulation paused");
+        Ok(())
+    }
+
+    /// Resume emulation suspended by [`InputEmulation::pause`],
+    /// releasing the keys and buttons released in the meantime.
+    pub async fn resume(&mut self) -> Result<(), EmulationError> {
+        let Some(paused) = self.paused.take() else {
+            return Ok(());
+        };
+        self.emulation.resume()?;
+        log::info!("input emulation resumed");
+        for (handle, held) in paused {
+            // handles destroyed in the meantime hold nothing
+            let current = self.held_state(handle).unwrap_or_default();
+            self.release_difference(handle, &held, &current).await?;
+        }
+        Ok(())
+    }
+
+    /// emit releases of the keys and buttons `held` but not in `current`
+    async fn release_difference(
+        &mut self,
+        handle: EmulationHandle,
+        held: &HeldState,
+        current: &HeldState,
+    ) -> Result<(), EmulationError> {
+        for &key in held.keys.difference(&current.keys) {
+            let event = Event::Keyboard(KeyboardEvent::Key {
+                time: 0,
+                key,
+                state: 0,
+            });
+            self.emulation.consume(event, handle).await?;
+        }
+        for &button in held.buttons.difference(&current.buttons) {
+            let event = Event::Pointer(PointerEvent::Button {
+                time: 0,
+                button,
+                state: 0,
+            });
+            self.emulation.consume(event, handle).await?;
+        }
+        Ok(())
+    }
+
+    /// whether emulation is suspended, see [`InputEmulation::pause`]
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// end of a group of events that should be applied atomically
+    pub async fn frame(&mut self, handle: EmulationHandle) -> Result<(), EmulationError> {
+        if !self.handles.contains(&handle) {
+            return Ok(());
+        }
+        self.framed.insert(handle);
+        if let Some(pairing) = self.scroll_pairing.as_mut() {
+            for event in pairing.end_frame(handle) {
+                self.consume_paired(Event::Pointer(event), handle).await?;
+            }
+        }
+        self.emulation.frame(handle).await
+    }
+
+    /// Apply the fallback of a capability without a device.
+    /// Returns the event to emulate instead, if any.
+    async fn degrade(
+        &mut self,
+        event: Event,
+        handle: EmulationHandle,
+    ) -> Result<Option<Event>, EmulationError> {
+        let missing = |emulation: &dyn Emulation, capability| {
+            emulation.missing_devices().contains(&capability)
+        };
+        match event {
+            Event::Keyboard(_) if missing(&*self.emulation, "keyboard") => {
+                self.report_degraded("keys dropped");
+                Ok(None)
+            }
+            Event::Pointer(
+                p @ (PointerEvent::Axis { axis, .. } | PointerEvent::AxisDiscrete120 { axis, .. }),
+            ) if missing(&*self.emulation, "scroll") => match self.degradation.scroll {
+                ScrollFallback::Buttons(buttons) if !missing(&*self.emulation, "button") => {
+                    self.report_degraded("scroll as buttons");
+                    // the same wheel motion may be sent as continuous and
+                    // discrete event: only one kind is counted per axis
+                    let value = match p {
+                        PointerEvent::AxisDiscrete120 { value, .. } => {
+                            if self.discrete_axes.insert((handle, axis)) {
+                                self.scroll_buttons.remove(&(handle, axis));
+                            }
+                            value as f64
+                        }
+                        PointerEvent::Axis { .. }
+                            if self.discrete_axes.contains(&(handle, axis)) =>
+                        {
+                            return Ok(None);
+                        }
+                        PointerEvent::Axis { value, .. } => value * SMOOTH_TO_DISCRETE,
+                        _ => unreachable!(),
+                    };
+                    let remainder = self.scroll_buttons.entry((handle, axis)).or_default();
+                    *remainder += value;
+                    let detents = (*remainder / DETENT).trunc();
+                    *remainder -= detents * DETENT;
+                    let button = match (axis, detents > 0.) {
+                        (0, true) => buttons.down,
+                        (0, false) => buttons.up,
+                        (_, true) => buttons.right,
+                        (_, false) => buttons.left,
+                    };
+                    for _ in 0..detents.abs() as u32 {
+                        for state in [1, 0] {
+                            let button = PointerEvent::Button {
+                                time: 0,
+                                button,
+                                state,
+                            };
+                            self.emulation
+                                .consume(Event::Pointer(button), handle)
+                                .await?;
+                        }
+                    }
+                    Ok(None)
+                }
+                ScrollFallback::Drop | ScrollFallback::Buttons(_) => {
+                    self.report_degraded("scroll dropped");
+                    Ok(None)
+                }
+            },
+            Event::Pointer(PointerEvent::MotionAbsolute { time, x, y })
+                if !self.emulation.absolute_motion() =>
+            {
+                match self.degradation.absolute {
+                    AbsoluteFallback::Drop => {
+                        self.report_degraded("absolute motion dropped");
+                        Ok(None)
+                    }
+                    AbsoluteFallback::Relative { width, height } => {
+                        self.report_degraded("absolute motion as relative");
+                        // the first position only serves as reference
+                        Ok(self.last_absolute.insert(handle, (x, y)).map(|(lx, ly)| {
+                            Event::Pointer(PointerEvent::Motion {
+                                time,
+                                dx: (x - lx) * width as f64,
+                                dy: (y - ly) * height as f64,
+                            })
+                        }))
+                    }
+                }
+            }
+            event => Ok(Some(event)),
+        }
+    }
+
+    /// log a fallback the first time it is applied
+    fn report_degraded(&mut self, degradation: &'static str) {
+        if self.degraded.insert(degradation) {
+            log::warn!("{}: {degradation} (missing device)", self.backend);
+        }
+    }
+
+    /// fallbacks that apply to the capabilities currently without a device
+    fn degradations(&self) -> Vec<&'static str> {
+        let missing = self.emulation.missing_devices();
+        let mut degraded = vec![];
+        if missing.contains(&"keyboard") {
+            degraded.push("keys dropped");
+        }
+        if missing.contains(&"scroll") {
+            degraded.push(match self.degradation.scroll {
+                ScrollFallback::Buttons(_) if !missing.contains(&"button") => "scroll as buttons",
+                ScrollFallback::Drop | ScrollFallback::Buttons(_) => "scroll dropped",
+            });
+        }
+        if !self.emulation.absolute_motion() {
+            degraded.push(match self.degradation.absolute {
+                AbsoluteFallback::Drop => "absolute motion dropped",
+                AbsoluteFallback::Relative { .. } => "absolute motion as relative",
+            });
+        }
+        degraded
+    }
+
+    /// Type `text` by pressing and releasing the keys producing it
+    /// on a US keyboard layout, see [`input_event::text`].
+    /// Returns the characters without a key on the US layout, which are skipped.
+    pub async fn type_text(
+        &mut self,
+        text: &str,
+        handle: EmulationHandle,
+    ) -> Result<Vec<char>, EmulationError> {
+        let (events, skipped) = input_event::text::key_events(text);
+        for event in events {
+            self.consume(Event::Keyboard(event), handle).await?;
+            self.frame(handle).await?;
+        }
+        Ok(skipped)
+    }
+
+    /// Forget the last absolute position of the handle, so the next one
+    /// only serves as reference of [`AbsoluteFallback::Relative`],
+    /// e.g. once the pointer entered this device again.
+    pub fn reset_absolute(&mut self, handle: EmulationHandle) {
+        self.last_absolute.remove(&handle);
+    }
+
+    /// Create the devices of `handle` in the backend.
+    /// Returns `false` if the handle already exists.
     pub async fn create(&mut self, handle: EmulationHandle) -> bool {
+        self.rejected.remove(&handle);
         if self.handles.insert(handle) {
             self.pressed_keys.insert(handle, HashSet::new());
+            self.pressed_buttons.insert(handle, HashSet::new());
             self.emulation.create(handle).await;
             true
         } else {
@@ -165,8 +1345,30 @@ impl InputEmulation {
 
     pub async fn destroy(&mut self, handle: EmulationHandle) {
         let _ = self.release_keys(handle).await;
+        self.flings.retain(|f| f.handle != handle);
+        self.scroll_velocity.retain(|&(h, _), _| h != handle);
+        if let Some(filter) = self.motion_filter.as_mut() {
+            filter.residual.remove(&handle);
+        }
+        if let Some(scroll) = self.discrete_scroll.as_mut() {
+            scroll.remainder.retain(|&(h, _), _| h != handle);
+        }
+        if let Some(frame_clock) = self.frame_clock.as_mut() {
+            frame_clock.remove(handle);
+        }
+        if let Some(throttle) = self.scroll_throttle.as_mut() {
+            throttle.pending.retain(|&(h, _), _| h != handle);
+        }
+        self.scroll_buttons.retain(|&(h, _), _| h != handle);
+        self.discrete_axes.retain(|&(h, _)| h != handle);
+        self.last_absolute.remove(&handle);
+        self.framed.remove(&handle);
+        if let Some(pairing) = self.scroll_pairing.as_mut() {
+            pairing.end_frame(handle);
+        }
         if self.handles.remove(&handle) {
             self.pressed_keys.remove(&handle);
+            self.pressed_buttons.remove(&handle);
             self.emulation.destroy(handle).await
         }
     }
@@ -194,6 +1396,19 @@ impl InputEmulation {
             }
         }
 
+        if let Some(buttons) = self.pressed_buttons.get_mut(&handle) {
+            let buttons = buttons.drain().collect::<Vec<_>>();
+            for button in buttons {
+                let event = Event::Pointer(PointerEvent::Button {
+                    time: 0,
+                    button,
+                    state: 0,
+                });
+                self.emulation.consume(event, handle).await?;
+                log::warn!("releasing stuck button: {button:#x}");
+            }
+        }
+
         let event = Event::Keyboard(KeyboardEvent::Modifiers {
             depressed: 0,
             latched: 0,
@@ -204,6 +1419,322 @@ impl InputEmulation {
         Ok(())
     }
 
+    /// Use the given clock for the timer based features instead of the
+    /// [`SystemClock`], e.g. a [`VirtualClock`] in tests.
+    /// Timers started before are restarted at the time of the clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        if let Some(filter) = self.motion_filter.as_mut() {
+            filter.last_step = now;
+        }
+        for (_, last) in self
+            .idle_frames
+            .iter_mut()
+            .chain(self.counter_log.iter_mut())
+        {
+            *last = now;
+        }
+        self.emulation.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// the backend in use and the devices it emulates input with
+    pub fn backend_info(&self) -> BackendInfo {
+        BackendInfo {
+            backend: self.backend,
+            devices: self.emulation.devices(),
+            counters: self.emulation.counters(),
+            granted: self.emulation.granted_devices(),
+            degraded: self.degradations(),
+        }
+    }
+
+    /// Track finger scrolling to synthesize kinetic scrolling
+    /// when the backend does not support it natively.
+    /// Returns whether the event was handled.
+    fn track_scroll(&mut self, event: PointerEvent, handle: EmulationHandle) -> bool {
+        match event {
+            PointerEvent::ScrollStop { axis, .. } => {
+                let velocity = self.scroll_velocity.remove(&(handle, axis));
+                if self.emulation.kinetic_scroll() {
+                    return false;
+                }
+                if let (Some(v), Some(_)) = (velocity, self.max_fling) {
+                    let now = self.clock.now();
+                    if v.velocity.abs() >= MIN_FLING_VELOCITY
+                        && now.duration_since(v.last) < FLING_SAMPLE_TIMEOUT
+                    {
+                        self.flings.push(Fling {
+                            handle,
+                            axis,
+                            velocity: v.velocity,
+                            started: now,
+                            last_step: now,
+                        });
+                    }
+                }
+                // backend has no notion of a scroll stop
+                true
+            }
+            PointerEvent::ScrollCancel { axis, .. } => {
+                // aborted scrolling must not fling
+                self.scroll_velocity.remove(&(handle, axis));
+                !self.emulation.kinetic_scroll()
+            }
+            PointerEvent::Axis {
+                axis,
+                value,
+                source: ScrollSource::Finger,
+                ..
+            } => {
+                self.flings.retain(|f| f.handle != handle);
+                let now = self.clock.now();
+                let v = self
+                    .scroll_velocity
+                    .entry((handle, axis))
+                    .or_insert(ScrollVelocity {
+                        last: now,
+                        velocity: 0.,
+                    });
+                let dt = now.duration_since(v.last);
+                if dt >= FLING_SAMPLE_TIMEOUT {
+                    v.velocity = 0.;
+                } else if !dt.is_zero() {
+                    // smoothed estimate
+                    v.velocity = 0.5 * v.velocity + 0.5 * value / dt.as_secs_f64();
+                }
+                v.last = now;
+                false
+            }
+            PointerEvent::Axis { .. }
+            | PointerEvent::AxisDiscrete120 { .. }
+            | PointerEvent::Button { .. } => {
+                self.flings.retain(|f| f.handle != handle);
+                false
+            }
+            PointerEvent::Motion { .. } | PointerEvent::MotionAbsolute { .. } => false,
+        }
+    }
+
+    /// Time at which the next synthesized kinetic scroll event,
+    /// the next frame of coalesced motion, smoothed motion,
+    /// throttled scroll, empty frame, counter log or
+    /// event held back by the backend is due.
+    /// [`InputEmulation::step`] should be called then.
+    pub fn deadline(&self) -> Option<Instant> {
+        let fling = self.flings.iter().map(|f| f.last_step + FLING_STEP).min();
+        let frame = self.frame_clock.as_ref().and_then(|c| c.deadline());
+        let smoothing = self.motion_filter.as_ref().and_then(|f| f.deadline());
+        let scroll = self.scroll_throttle.as_ref().and_then(|t| t.deadline());
+        let idle_frame = self.idle_frames.map(|(interval, last)| last + interval);
+        let counter_log = self.counter_log.map(|(interval, last)| last + interval);
+        let backend = self.emulation.deadline();
+        fling
+            .into_iter()
+            .chain(frame)
+            .chain(smoothing)
+            .chain(scroll)
+            .chain(idle_frame)
+            .chain(counter_log)
+            .chain(backend)
+            .min()
+    }
+
+    /// emit the events that are due, see [`InputEmulation::deadline`]
+    pub async fn step(&mut self) -> Result<(), EmulationError> {
+        let now = self.clock.now();
+        if let Some(filter) = self.motion_filter.as_mut() {
+            if filter.deadline().is_some_and(|d| d <= now) {
+                for (handle, (dx, dy)) in filter.step(now) {
+                    self.emit_motion(handle, dx, dy).await?;
+                }
+            }
+        }
+        if self
+            .frame_clock
+            .as_ref()
+            .and_then(|c| c.deadline())
+            .is_some_and(|d| d <= now)
+        {
+            self.emit_frame().await?;
+        }
+        if self
+            .scroll_throttle
+            .as_ref()
+            .and_then(|t| t.deadline())
+            .is_some_and(|d| d <= now)
+        {
+            self.emit_scroll(|_| true).await?;
+        }
+        if self.emulation.deadline().is_some_and(|d| d <= now) {
+            self.emulation.step().await?;
+        }
+        if let Some((interval, last)) = self.idle_frames.as_mut() {
+            if *last + *interval <= now {
+                *last = now;
+                self.emulation.idle_frame().await?;
+            }
+        }
+        if let Some((interval, last)) = self.counter_log.as_mut() {
+            if *last + *interval <= now {
+                *last = now;
+                if let Some(counters) = self.emulation.counters() {
+                    log::info!("{} emulation: {counters}", self.backend);
+                }
+            }
+        }
+        self.fling_step().await
+    }
+
+    /// emit the throttled scroll of the handles matching `filter`,
+    /// each followed by a frame
+    async fn emit_scroll(
+        &mut self,
+        filter: impl Fn(EmulationHandle) -> bool,
+    ) -> Result<(), EmulationError> {
+        let Some(throttle) = self.scroll_throttle.as_mut() else {
+            return Ok(());
+        };
+        let events = throttle.take(filter, self.clock.now());
+        let mut handles = HashSet::new();
+        for (handle, event) in events {
+            self.emulation.consume(event, handle).await?;
+            handles.insert(handle);
+        }
+        // not a frame of the sender, held back scroll is kept
+        for handle in handles.into_iter().filter(|h| self.handles.contains(h)) {
+            self.emulation.frame(handle).await?;
+        }
+        Ok(())
+    }
+
+    /// emit motion, coalesced by the frame clock if enabled
+    async fn emit_motion(
+        &mut self,
+        handle: EmulationHandle,
+        dx: f64,
+        dy: f64,
+    ) -> Result<(), EmulationError> {
+        match self.frame_clock.as_mut() {
+            Some(frame_clock) => {
+                if frame_clock.add_motion(handle, dx, dy, self.clock.now()) {
+                    self.emit_frame().await?;
+                }
+            }
+            None => {
+                let motion = Event::Pointer(PointerEvent::Motion { time: 0, dx, dy });
+                self.emulation.consume(motion, handle).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// emit the coalesced motion of all handles
+    async fn emit_frame(&mut self) -> Result<(), EmulationError> {
+        let Some(frame_clock) = self.frame_clock.as_mut() else {
+            return Ok(());
+        };
+        for (handle, (dx, dy)) in frame_clock.take(self.clock.now()) {
+            let motion = Event::Pointer(PointerEvent::Motion { time: 0, dx, dy });
+            self.emulation.consume(motion, handle).await?;
+        }
+        Ok(())
+    }
+
+    /// emit the synthesized kinetic scroll events that are due
+    async fn fling_step(&mut self) -> Result<(), EmulationError> {
+        let Some(max_fling) = self.max_fling else {
+            self.flings.clear();
+            return Ok(());
+        };
+        let now = self.clock.now();
+        let mut flings = std::mem::take(&mut self.flings);
+        for fling in flings.iter_mut() {
+            let dt = now.duration_since(fling.last_step);
+            if dt < FLING_STEP {
+                continue;
+            }
+            let event = Event::Pointer(PointerEvent::Axis {
+                time: 0,
+                axis: fling.axis,
+                value: fling.velocity * dt.as_secs_f64(),
+                source: ScrollSource::Continuous,
+            });
+            self.emulation.consume(event, fling.handle).await?;
+            fling.velocity *= (-dt.as_secs_f64() / FLING_DECAY.as_secs_f64()).exp();
+            fling.last_step = now;
+        }
+        flings.retain(|f| {
+            f.velocity.abs() >= MIN_FLING_VELOCITY && now.duration_since(f.started) < max_fling
+        });
+        self.flings = flings;
+        Ok(())
+    }
+
+    /// capabilities for which the backend has no device (yet)
+    pub fn missing_devices(&self) -> Vec<&'static str> {
+        self.emulation.missing_devices()
+    }
+
+    /// Wait until the backend added or removed a device.
+    /// Keys and buttons held on a removed device are considered released.
+    /// Returns the capabilities still missing a device
+    /// or an error if the backend failed in the background.
+    pub async fn devices_changed(&mut self) -> Result<Vec<&'static str>, EmulationError> {
+        for capability in self.emulation.devices_changed().await? {
+            let held = match capability {
+                "keyboard" => &mut self.pressed_keys,
+                "button" => &mut self.pressed_buttons,
+                _ => continue,
+            };
+            for (handle, held) in held.iter_mut().filter(|(_, h)| !h.is_empty()) {
+                if capability == "keyboard" && !input_event::log_key_data() {
+                    let n = held.len();
+                    log::warn!("{capability} device removed, releasing {n} keys of {handle}");
+                } else {
+                    log::warn!("{capability} device removed, releasing {held:?} of {handle}");
+                }
+                held.clear();
+            }
+        }
+        // report fallbacks again once devices are lost again
+        let degraded = self.degradations();
+        self.degraded.retain(|d| degraded.contains(d));
+        Ok(self.missing_devices())
+    }
+
+    /// keys and buttons currently believed to be held for the handle
+    pub fn held_state(&self, handle: EmulationHandle) -> Option<HeldState> {
+        let keys = self.pressed_keys.get(&handle)?.clone();
+        let buttons = self.pressed_buttons.get(&handle)?.clone();
+        Some(HeldState { keys, buttons })
+    }
+
+    /// Overwrite the keys and buttons believed to be held for the handle,
+    /// e.g. to recover from a diverged state.
+    /// Held keys and buttons missing from `state` are released,
+    /// added ones are released on their next release event.
+    /// Returns false if the handle does not exist.
+    pub async fn force_held_state(
+        &mut self,
+        handle: EmulationHandle,
+        state: HeldState,
+    ) -> Result<bool, EmulationError> {
+        let Some(held) = self.held_state(handle) else {
+            return Ok(false);
+        };
+        if input_event::log_key_data() {
+            log::warn!("forcing held state of {handle}: {state:?}");
+        } else {
+            let (keys, buttons) = (state.keys.len(), &state.buttons);
+            log::warn!("forcing held state of {handle}: {keys} keys, buttons {buttons:?}");
+        }
+        self.release_difference(handle, &held, &state).await?;
+        self.pressed_keys.insert(handle, state.keys);
+        self.pressed_buttons.insert(handle, state.buttons);
+        Ok(true)
+    }
+
     pub fn has_pressed_keys(&self, handle: EmulationHandle) -> bool {
         self.pressed_keys
             .get(&handle)
@@ -227,6 +1758,13 @@ impl InputEmulation {
     }
 }
 
+#[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
+impl From<LibeiEmulation<'static>> for InputEmulation {
+    fn from(emulation: LibeiEmulation<'static>) -> Self {
+        Self::from_emulation(Backend::Libei, Box::new(emulation), Default::default())
+    }
+}
+
 #[async_trait]
 trait Emulation: Send {
     async fn consume(
@@ -234,7 +1772,407 @@ trait Emulation: Send {
         event: Event,
         handle: EmulationHandle,
     ) -> Result<(), EmulationError>;
+    /// Set up the devices of a new handle. Events of a handle are only
+    /// consumed between its creation and [`Emulation::destroy`].
+    /// Backends either map each handle to its own devices (uinput) or
+    /// to devices shared by all handles (every other backend).
     async fn create(&mut self, handle: EmulationHandle);
+    /// Release the devices of a handle and any state tied to it.
     async fn destroy(&mut self, handle: EmulationHandle);
     async fn terminate(&mut self);
+
+    /// Marks the end of a group of events.
+    /// Backends framing each event on their own can ignore this.
+    async fn frame(&mut self, _handle: EmulationHandle) -> Result<(), EmulationError> {
+        Ok(())
+    }
+
+    /// Time at which events held back by the backend are due,
+    /// see [`Emulation::step`].
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Emit the events held back by the backend that are due.
+    /// Backends emitting every event right away can ignore this.
+    async fn step(&mut self) -> Result<(), EmulationError> {
+        Ok(())
+    }
+
+    /// Emit an empty frame to keep the event stream alive.
+    /// Backends without frames can ignore this.
+    async fn idle_frame(&mut self) -> Result<(), EmulationError> {
+        Ok(())
+    }
+
+    /// devices used for emulation
+    fn devices(&self) -> Vec<DeviceInfo> {
+        vec![]
+    }
+
+    /// protocol counters for debugging, if the backend has any
+    fn counters(&self) -> Option<FrameCounters> {
+        None
+    }
+
+    /// Use the given clock for the timers of the backend.
+    /// Backends without timers can ignore this.
+    fn set_clock(&mut self, _clock: Arc<dyn Clock>) {}
+
+    /// Suspend emulation, keeping the session and devices of the backend.
+    /// Backends without a session can ignore this.
+    fn pause(&mut self) -> Result<(), EmulationError> {
+        Ok(())
+    }
+
+    /// Resume emulation suspended by [`Emulation::pause`].
+    fn resume(&mut self) -> Result<(), EmulationError> {
+        Ok(())
+    }
+
+    /// device types the user granted access to,
+    /// `None` if the backend does not ask for permission
+    fn granted_devices(&self) -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// capabilities still waiting for a device
+    fn missing_devices(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// Wait until a device was added or removed.
+    /// Returns the capabilities that lost their device since the last call
+    /// or an error if a background task of the backend exited unexpectedly,
+    /// leaving the emulation inoperable.
+    /// Backends with a fixed set of devices never return.
+    async fn devices_changed(&mut self) -> Result<Vec<&'static str>, EmulationError> {
+        futures::future::pending().await
+    }
+
+    /// whether the backend forwards scroll stops
+    /// so the target can perform kinetic scrolling itself
+    fn kinetic_scroll(&self) -> bool {
+        false
+    }
+
+    /// whether the backend can position the pointer absolutely
+    fn absolute_motion(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// backend recording the events it emulates
+    #[derive(Clone, Default)]
+    struct RecordingEmulation {
+        events: Arc<Mutex<Vec<Event>>>,
+        /// capabilities without a device (`absolute` for absolute motion)
+        missing: Vec<&'static str>,
+    }
+
+    impl RecordingEmulation {
+        fn take(&self) -> Vec<Event> {
+            std::mem::take(&mut self.events.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl Emulation for RecordingEmulation {
+        async fn consume(
+            &mut self,
+            event: Event,
+            _handle: EmulationHandle,
+        ) -> Result<(), EmulationError> {
+            self.events.lock().unwrap().push(event);
+            Ok(())
+        }
+        async fn create(&mut self, _handle: EmulationHandle) {}
+        async fn destroy(&mut self, _handle: EmulationHandle) {}
+        async fn terminate(&mut self) {}
+        fn missing_devices(&self) -> Vec<&'static str> {
+            self.missing.clone()
+        }
+        fn absolute_motion(&self) -> bool {
+            !self.missing.contains(&"absolute")
+        }
+    }
+
+    fn motion(dx: f64, dy: f64) -> Event {
+        Event::Pointer(PointerEvent::Motion { time: 0, dx, dy })
+    }
+
+    #[tokio::test]
+    async fn frame_clock_coalesces_motion_in_virtual_time() {
+        let backend = RecordingEmulation::default();
+        let options = EmulationOptions {
+            frame_rate: Some(100),
+            ..Default::default()
+        };
+        let mut emulation =
+            InputEmulation::from_emulation(Backend::Dummy, Box::new(backend.clone()), options);
+        let clock = VirtualClock::new();
+        emulation.set_clock(Arc::new(clock.clone()));
+        emulation.create(0).await;
+
+        // the first motion starts a frame
+        emulation.consume(motion(1., 0.), 0).await.unwrap();
+        assert_eq!(backend.take(), [motion(1., 0.)]);
+
+        // motion within the frame is held back until it ends
+        emulation.consume(motion(2., 1.), 0).await.unwrap();
+        emulation.consume(motion(3., 1.), 0).await.unwrap();
+        assert!(backend.take().is_empty());
+        let frame = Duration::from_millis(10);
+        assert_eq!(emulation.deadline(), Some(clock.now() + frame));
+
+        clock.advance(frame - Duration::from_millis(1));
+        emulation.step().await.unwrap();
+        assert!(backend.take().is_empty());
+
+        clock.advance(Duration::from_millis(1));
+        emulation.step().await.unwrap();
+        assert_eq!(backend.take(), [motion(5., 2.)]);
+        assert_eq!(emulation.deadline(), None);
+    }
+
+    #[tokio::test]
+    async fn zero_deltas_do_not_start_a_frame() {
+        let backend = RecordingEmulation::default();
+        let options = EmulationOptions {
+            frame_rate: Some(100),
+            ..Default::default()
+        };
+        let mut emulation =
+            InputEmulation::from_emulation(Backend::Dummy, Box::new(backend.clone()), options);
+        emulation.set_clock(Arc::new(VirtualClock::new()));
+        emulation.create(0).await;
+
+        emulation.consume(motion(0., 0.), 0).await.unwrap();
+        assert_eq!(emulation.deadline(), None);
+        emulation.consume(motion(1., 0.), 0).await.unwrap();
+        assert_eq!(backend.take(), [motion(1., 0.)]);
+    }
+
+    fn key(key: u32, state: u8) -> Event {
+        Event::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key,
+            state,
+        })
+    }
+
+    fn button(button: u32, state: u32) -> Event {
+        Event::Pointer(PointerEvent::Button {
+            time: 0,
+            button,
+            state,
+        })
+    }
+
+    #[tokio::test]
+    async fn releases_applied_on_resume() {
+        let backend = RecordingEmulation::default();
+        let mut emulation = InputEmulation::from_emulation(
+            Backend::Dummy,
+            Box::new(backend.clone()),
+            Default::default(),
+        );
+        emulation.create(0).await;
+        emulation.consume(key(30, 1), 0).await.unwrap();
+        emulation.consume(key(31, 1), 0).await.unwrap();
+        assert_eq!(backend.take(), [key(30, 1), key(31, 1)]);
+
+        emulation.pause().unwrap();
+        emulation.consume(key(30, 0), 0).await.unwrap();
+        emulation.consume(key(32, 1), 0).await.unwrap();
+        emulation.consume(motion(1., 0.), 0).await.unwrap();
+        assert!(backend.take().is_empty());
+
+        emulation.resume().await.unwrap();
+        assert_eq!(backend.take(), [key(30, 0)]);
+        // discarded while paused
+        emulation.consume(key(32, 0), 0).await.unwrap();
+        emulation.consume(key(31, 0), 0).await.unwrap();
+        assert_eq!(backend.take(), [key(31, 0)]);
+    }
+
+    #[tokio::test]
+    async fn forced_held_state_releases_removed_keys() {
+        let backend = RecordingEmulation::default();
+        let mut emulation = InputEmulation::from_emulation(
+            Backend::Dummy,
+            Box::new(backend.clone()),
+            Default::default(),
+        );
+        emulation.create(0).await;
+        emulation.consume(key(30, 1), 0).await.unwrap();
+        emulation.consume(key(31, 1), 0).await.unwrap();
+        backend.take();
+
+        let state = HeldState {
+            keys: HashSet::from([31, 32]),
+            buttons: Default::default(),
+        };
+        assert!(emulation.force_held_state(0, state.clone()).await.unwrap());
+        assert_eq!(backend.take(), [key(30, 0)]);
+        assert_eq!(emulation.held_state(0), Some(state));
+        // a key believed to be held is released
+        emulation.consume(key(32, 0), 0).await.unwrap();
+        assert_eq!(backend.take(), [key(32, 0)]);
+        assert!(!emulation
+            .force_held_state(1, Default::default())
+            .await
+            .unwrap());
+    }
+
+    fn absolute(x: f64, y: f64) -> Event {
+        Event::Pointer(PointerEvent::MotionAbsolute { time: 0, x, y })
+    }
+
+    #[tokio::test]
+    async fn absolute_motion_degrades_to_relative() {
+        let backend = RecordingEmulation {
+            missing: vec!["absolute"],
+            ..Default::default()
+        };
+        let options = EmulationOptions {
+            degradation: Degradation {
+                absolute: AbsoluteFallback::Relative {
+                    width: 1000,
+                    height: 500,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut emulation =
+            InputEmulation::from_emulation(Backend::Dummy, Box::new(backend.clone()), options);
+        emulation.create(0).await;
+
+        emulation.consume(absolute(0.25, 0.25), 0).await.unwrap();
+        emulation.consume(absolute(0.5, 0.75), 0).await.unwrap();
+        assert_eq!(backend.take(), [motion(250., 250.)]);
+        // entered again: no jump from the previous position
+        emulation.reset_absolute(0);
+        emulation.consume(absolute(0.25, 0.25), 0).await.unwrap();
+        emulation.consume(absolute(0.5, 0.25), 0).await.unwrap();
+        assert_eq!(backend.take(), [motion(250., 0.)]);
+    }
+
+    #[tokio::test]
+    async fn missing_devices_degrade() {
+        let backend = RecordingEmulation {
+            missing: vec!["keyboard", "scroll", "absolute"],
+            ..Default::default()
+        };
+        let options = EmulationOptions {
+            degradation: Degradation {
+                scroll: ScrollFallback::Buttons(Default::default()),
+                absolute: AbsoluteFallback::Drop,
+            },
+            ..Default::default()
+        };
+        let mut emulation =
+            InputEmulation::from_emulation(Backend::Dummy, Box::new(backend.clone()), options);
+        emulation.create(0).await;
+
+        emulation.consume(key(30, 1), 0).await.unwrap();
+        emulation.consume(absolute(0.5, 0.5), 0).await.unwrap();
+        emulation.consume(absolute(0.25, 0.5), 0).await.unwrap();
+        assert!(backend.take().is_empty());
+
+        let scroll = |value| Event::Pointer(PointerEvent::AxisDiscrete120 { axis: 0, value });
+        let down = ScrollButtons::default().down;
+        emulation.consume(scroll(180), 0).await.unwrap();
+        assert_eq!(backend.take(), [button(down, 1), button(down, 0)]);
+        // remainder of the previous detent
+        emulation.consume(scroll(60), 0).await.unwrap();
+        assert_eq!(backend.take(), [button(down, 1), button(down, 0)]);
+        assert_eq!(
+            emulation.degradations(),
+            [
+                "keys dropped",
+                "scroll as buttons",
+                "absolute motion dropped"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn scroll_buttons_count_one_kind_per_axis() {
+        let backend = RecordingEmulation {
+            missing: vec!["scroll"],
+            ..Default::default()
+        };
+        let options = EmulationOptions {
+            degradation: Degradation {
+                scroll: ScrollFallback::Buttons(Default::default()),
+                ..Default::default()
+            },
+            scroll_preference: ScrollPreference::Both,
+            ..Default::default()
+        };
+        let mut emulation =
+            InputEmulation::from_emulation(Backend::Dummy, Box::new(backend.clone()), options);
+        emulation.create(0).await;
+
+        let smooth = |axis, value| {
+            Event::Pointer(PointerEvent::Axis {
+                time: 0,
+                axis,
+                value,
+                source: ScrollSource::Wheel,
+            })
+        };
+        let discrete = |axis, value| Event::Pointer(PointerEvent::AxisDiscrete120 { axis, value });
+        let ScrollButtons { up, right, .. } = ScrollButtons::default();
+        // one notch sent as discrete and continuous event
+        for _ in 0..2 {
+            emulation.consume(discrete(0, -120), 0).await.unwrap();
+            emulation.consume(smooth(0, -15.), 0).await.unwrap();
+            assert_eq!(backend.take(), [button(up, 1), button(up, 0)]);
+        }
+        // continuous scrolling only
+        emulation.consume(smooth(1, 10.), 0).await.unwrap();
+        assert!(backend.take().is_empty());
+        emulation.consume(smooth(1, 5.), 0).await.unwrap();
+        assert_eq!(backend.take(), [button(right, 1), button(right, 0)]);
+    }
+
+    /// one detent of discrete scroll events
+    const NOTCH: i32 = DETENT as i32;
+
+    #[test]
+    fn discrete_scroll_scales_one_detent() {
+        let scale = |multiplier, divisor| {
+            DiscreteScroll::new(DiscreteScrollScale {
+                multiplier,
+                divisor,
+            })
+        };
+        assert_eq!(scale(1, 1).apply(0, 0, NOTCH), 120);
+        assert_eq!(scale(3, 1).apply(0, 0, NOTCH), 360);
+        assert_eq!(scale(1, 2).apply(0, 0, NOTCH), 60);
+        assert_eq!(scale(1, 2).apply(0, 0, -NOTCH), -60);
+        assert_eq!(scale(3, 2).apply(0, 0, NOTCH), 180);
+    }
+
+    #[test]
+    fn discrete_scroll_carries_remainders() {
+        let mut scroll = DiscreteScroll::new(DiscreteScrollScale {
+            multiplier: 1,
+            divisor: 240,
+        });
+        assert_eq!(scroll.apply(0, 0, NOTCH), 0);
+        assert_eq!(scroll.apply(0, 0, NOTCH), 1);
+        assert_eq!(scroll.apply(0, 0, NOTCH), 0);
+        // a change of direction discards the remainder
+        assert_eq!(scroll.apply(0, 0, -NOTCH), 0);
+        assert_eq!(scroll.apply(0, 0, -NOTCH), -1);
+    }
 }
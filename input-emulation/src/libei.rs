@@ -1,35 +1,52 @@
 use futures::{future, StreamExt};
 use std::{
+    collections::{HashSet, VecDeque},
+    future::Future,
+    hash::Hash,
     io,
     os::{fd::OwnedFd, unix::net::UnixStream},
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
         Arc, Mutex, RwLock,
     },
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{watch, Notify},
+    task::JoinHandle,
 };
-use tokio::task::JoinHandle;
 
-use ashpd::desktop::{
-    remote_desktop::{DeviceType, RemoteDesktop},
-    PersistMode, Session,
+use ashpd::{
+    desktop::{
+        remote_desktop::{DeviceType, RemoteDesktop},
+        PersistMode, Session,
+    },
+    enumflags2::BitFlags,
 };
 use async_trait::async_trait;
 
 use reis::{
     ei::{
         self, button::ButtonState, handshake::ContextType, keyboard::KeyState, Button, Keyboard,
-        Pointer, Scroll,
+        Pointer, PointerAbsolute, Scroll,
     },
-    event::{self, Connection, DeviceCapability, DeviceEvent, EiEvent, SeatEvent},
+    event::{self, DeviceCapability, DeviceEvent, EiEvent, SeatEvent},
     tokio::EiConvertEventStream,
+    Interface,
 };
 
 use input_event::{Event, KeyboardEvent, PointerEvent};
 
 use crate::error::EmulationError;
 
-use super::{error::LibeiEmulationCreationError, Emulation, EmulationHandle};
+mod recording;
+
+use super::{
+    error::{LibeiEmulationCreationError, LibeiHandshakeError, LibeiReplayError},
+    portal, Clock, DeviceInfo, DeviceRemoval, Emulation, EmulationHandle, FrameCounters,
+    SystemClock,
+};
 
 #[derive(Clone, Default)]
 struct Devices {
@@ -37,21 +54,579 @@ struct Devices {
     scroll: Arc<RwLock<Option<(ei::Device, ei::Scroll)>>>,
     button: Arc<RwLock<Option<(ei::Device, ei::Button)>>>,
     keyboard: Arc<RwLock<Option<(ei::Device, ei::Keyboard)>>>,
+    /// optional, only used for absolute motion
+    pointer_absolute: Arc<RwLock<Option<(ei::Device, ei::PointerAbsolute)>>>,
+    /// devices chosen for each capability
+    chosen: Arc<RwLock<Vec<(DeviceCapability, event::Device)>>>,
+    /// notified when a device was added
+    added: Arc<Notify>,
+    /// notified when a device was added or removed
+    changed: Arc<Notify>,
+    /// capabilities that lost their device since the last change was reported
+    removed: Arc<Mutex<Vec<&'static str>>>,
+    /// sequence number of the last `start_emulating` request,
+    /// shared between pausing / resuming and device resume events
+    sequence: Arc<AtomicU32>,
+    /// devices paused by the compositor (devices start out paused)
+    suspended: Arc<Mutex<HashSet<ei::Device>>>,
+    /// device types the user granted access to (all if `None`)
+    granted: Option<BitFlags<DeviceType>>,
+    /// capabilities bound on each seat (all if `None`)
+    requested: Option<Vec<DeviceCapability>>,
+}
+
+impl Devices {
+    /// Assign `device` to the slot of the given capability.
+    /// Virtual devices are preferred, since physical devices
+    /// represent actual hardware of the emulating host.
+    /// Returns whether the device was assigned.
+    fn assign(&self, capability: DeviceCapability, device: &event::Device) -> bool {
+        let mut chosen = self.chosen.write().unwrap();
+        let current = chosen.iter().position(|(c, _)| *c == capability);
+        if let Some(i) = current {
+            let (_, current) = &chosen[i];
+            if current.device_type() == ei::device::DeviceType::Virtual
+                && device.device_type() != ei::device::DeviceType::Virtual
+            {
+                log::info!(
+                    "{capability:?}: keeping virtual device {:?} over {:?} ({:?})",
+                    current.name(),
+                    device.name(),
+                    device.device_type(),
+                );
+                return false;
+            }
+            chosen.remove(i);
+        }
+        if device.device_type() != ei::device::DeviceType::Virtual {
+            log::warn!(
+                "{capability:?}: using {:?} device {:?} - no virtual device available",
+                device.device_type(),
+                device.name(),
+            );
+        }
+        chosen.push((capability, device.clone()));
+        true
+    }
+
+    /// Clear the slots the device is used for.
+    /// Returns the capabilities that lost their device.
+    fn remove(&self, device: &event::Device) -> Vec<&'static str> {
+        self.chosen.write().unwrap().retain(|(_, d)| d != device);
+        let device = device.device();
+        self.suspended.lock().unwrap().remove(device);
+        let mut removed = vec![];
+        if clear(&self.pointer, device) {
+            removed.push("pointer");
+        }
+        if clear(&self.keyboard, device) {
+            removed.push("keyboard");
+        }
+        if clear(&self.scroll, device) {
+            removed.push("scroll");
+        }
+        if clear(&self.button, device) {
+            removed.push("button");
+        }
+        clear(&self.pointer_absolute, device);
+        if !removed.is_empty() {
+            self.removed.lock().unwrap().extend(removed.iter().copied());
+            self.changed.notify_one();
+        }
+        removed
+    }
+
+    /// whether the user granted access to devices of the given type
+    fn is_granted(&self, device_type: DeviceType) -> bool {
+        self.granted.is_none_or(|g| g.contains(device_type))
+    }
+
+    /// whether the capability is bound on the seats
+    fn is_requested(&self, capability: DeviceCapability) -> bool {
+        self.requested
+            .as_ref()
+            .is_none_or(|r| r.contains(&capability))
+    }
+
+    /// capabilities without a device, leaving out those that were
+    /// not requested or the user did not grant access to
+    fn missing(&self) -> Vec<&'static str> {
+        let slots = [
+            (
+                "pointer",
+                DeviceCapability::Pointer,
+                DeviceType::Pointer,
+                self.pointer.read().unwrap().is_none(),
+            ),
+            (
+                "keyboard",
+                DeviceCapability::Keyboard,
+                DeviceType::Keyboard,
+                self.keyboard.read().unwrap().is_none(),
+            ),
+            (
+                "scroll",
+                DeviceCapability::Scroll,
+                DeviceType::Pointer,
+                self.scroll.read().unwrap().is_none(),
+            ),
+            (
+                "button",
+                DeviceCapability::Button,
+                DeviceType::Pointer,
+                self.button.read().unwrap().is_none(),
+            ),
+        ];
+        slots
+            .into_iter()
+            .filter(|&(_, capability, device_type, unbound)| {
+                unbound && self.is_requested(capability) && self.is_granted(device_type)
+            })
+            .map(|(name, ..)| name)
+            .collect()
+    }
+
+    /// bounding box (x, y, width, height) of the regions
+    /// of the device used for absolute motion
+    fn absolute_area(&self) -> Option<(f64, f64, f64, f64)> {
+        let chosen = self.chosen.read().unwrap();
+        let (_, device) = chosen
+            .iter()
+            .find(|(c, _)| *c == DeviceCapability::PointerAbsolute)?;
+        let regions = device.regions();
+        let x = regions.iter().map(|r| r.x).min()?;
+        let y = regions.iter().map(|r| r.y).min()?;
+        let x_max = regions.iter().map(|r| r.x + r.width).max()?;
+        let y_max = regions.iter().map(|r| r.y + r.height).max()?;
+        Some((x as f64, y as f64, (x_max - x) as f64, (y_max - y) as f64))
+    }
+
+    /// the devices chosen for each capability
+    fn info(&self) -> Vec<DeviceInfo> {
+        self.chosen
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(capability, device)| DeviceInfo {
+                capability: match capability {
+                    DeviceCapability::Pointer => "pointer",
+                    DeviceCapability::PointerAbsolute => "pointer-absolute",
+                    DeviceCapability::Keyboard => "keyboard",
+                    DeviceCapability::Touch => "touch",
+                    DeviceCapability::Scroll => "scroll",
+                    DeviceCapability::Button => "button",
+                },
+                name: device.name().map(|n| n.to_owned()),
+                device_type: format!("{:?}", device.device_type()).to_lowercase(),
+            })
+            .collect()
+    }
+
+    /// sequence number for the next `start_emulating` request
+    fn next_sequence(&self) -> u32 {
+        self.sequence.fetch_add(1, Ordering::SeqCst).wrapping_add(1)
+    }
+
+    /// the devices in use for any capability, that
+    /// are not paused by the compositor
+    fn bound(&self) -> HashSet<ei::Device> {
+        let suspended = self.suspended.lock().unwrap();
+        [
+            bound(&self.pointer),
+            bound(&self.scroll),
+            bound(&self.button),
+            bound(&self.keyboard),
+            bound(&self.pointer_absolute),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|d| !suspended.contains(d))
+        .collect()
+    }
+
+    /// wait until there is a device for every capability
+    async fn ready(&self) {
+        while !self.missing().is_empty() {
+            self.added.notified().await;
+        }
+    }
 }
 
-pub(crate) struct LibeiEmulation<'a> {
+/// default of [`LibeiEmulationBuilder::flush_retry`]
+const DEFAULT_FLUSH_RETRY: Duration = Duration::from_millis(50);
+
+/// default of [`LibeiEmulationBuilder::handshake_timeout`]
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// maximum number of motion events buffered while no pointer device is bound,
+/// older motion is dropped
+const MAX_EARLY_MOTION: usize = 256;
+
+/// perform the ei handshake, giving up after `timeout`
+async fn handshake(
+    context: &ei::Context,
+    app_name: &str,
+    context_type: ContextType,
+    timeout: Duration,
+) -> Result<(event::Connection, EiConvertEventStream), LibeiHandshakeError> {
+    let (conn, events) =
+        tokio::time::timeout(timeout, context.handshake_tokio(app_name, context_type))
+            .await
+            .map_err(|_| LibeiHandshakeError::Timeout(timeout))??;
+    let connection = conn.connection().as_object();
+    log::info!(
+        "ei handshake complete: {} v{} (serial {})",
+        connection.interface(),
+        connection.version(),
+        conn.serial(),
+    );
+    Ok((conn, events))
+}
+
+/// time between two attempts to flush
+const FLUSH_RETRY_INTERVAL: Duration = Duration::from_millis(2);
+
+/// whether a failed flush may succeed when retried
+fn is_transient(e: rustix::io::Errno) -> bool {
+    use rustix::io::Errno;
+    matches!(e, Errno::AGAIN | Errno::INTR | Errno::NOBUFS)
+}
+
+/// the device held by the slot
+fn bound<T>(slot: &RwLock<Option<(ei::Device, T)>>) -> Option<ei::Device> {
+    slot.read().unwrap().as_ref().map(|(d, _)| d.clone())
+}
+
+/// clear the slot if it holds the given device
+fn clear<T>(slot: &RwLock<Option<(ei::Device, T)>>, device: &ei::Device) -> bool {
+    let mut slot = slot.write().unwrap();
+    let used = slot.as_ref().is_some_and(|(d, _)| d == device);
+    if used {
+        slot.take();
+    }
+    used
+}
+
+/// Builder for a [`LibeiEmulation`].
+/// The result can be turned into an [`crate::InputEmulation`] via [`From`].
+#[derive(Clone, Debug)]
+pub struct LibeiEmulationBuilder {
+    app_name: String,
+    context_type: ContextType,
+    capabilities: Vec<DeviceCapability>,
+    warmup: Option<Duration>,
+    early_motion_buffer: Option<Duration>,
+    max_motion_rate: Option<u32>,
+    flush_coalesced_motion: bool,
+    invert_scroll: bool,
+    device_removal: DeviceRemoval,
+    record: Option<PathBuf>,
+    flush_retry: Duration,
+    handshake_timeout: Duration,
+}
+
+impl Default for LibeiEmulationBuilder {
+    fn default() -> Self {
+        Self {
+            app_name: "de.feschber.LanMouse".into(),
+            context_type: ContextType::Sender,
+            capabilities: vec![
+                DeviceCapability::Pointer,
+                DeviceCapability::PointerAbsolute,
+                DeviceCapability::Keyboard,
+                DeviceCapability::Touch,
+                DeviceCapability::Scroll,
+                DeviceCapability::Button,
+            ],
+            warmup: None,
+            early_motion_buffer: None,
+            max_motion_rate: None,
+            flush_coalesced_motion: true,
+            invert_scroll: false,
+            device_removal: DeviceRemoval::default(),
+            record: None,
+            flush_retry: DEFAULT_FLUSH_RETRY,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+}
+
+impl LibeiEmulationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// name announced to the EIS implementation during the handshake
+    /// (defaults to `de.feschber.LanMouse`)
+    pub fn app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = app_name.into();
+        self
+    }
+
+    /// type of the ei context (defaults to [`ContextType::Sender`])
+    pub fn context_type(mut self, context_type: ContextType) -> Self {
+        self.context_type = context_type;
+        self
+    }
+
+    /// capabilities bound on each seat (defaults to all capabilities).
+    /// Keyboard and pointer access is only requested from the portal if needed.
+    pub fn capabilities(mut self, capabilities: &[DeviceCapability]) -> Self {
+        self.capabilities = capabilities.to_vec();
+        self
+    }
+
+    /// wait up to the given duration for all devices to be bound
+    /// before returning from [`LibeiEmulationBuilder::build`]
+    pub fn warmup(mut self, warmup: Option<Duration>) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Buffer relative motion received before the pointer device is bound
+    /// and emit it with the first event once it is. Motion older than the
+    /// given duration is dropped (lost instead of buffered by default).
+    pub fn early_motion_buffer(mut self, early_motion_buffer: Option<Duration>) -> Self {
+        self.early_motion_buffer = early_motion_buffer.filter(|d| !d.is_zero());
+        self
+    }
+
+    /// Maximum number of motion events per second.
+    /// Faster motion is coalesced until the rate allows emitting it,
+    /// see [`LibeiEmulationBuilder::flush_coalesced_motion`]
+    /// (unlimited by default).
+    pub fn max_motion_rate(mut self, max_motion_rate: Option<u32>) -> Self {
+        self.max_motion_rate = max_motion_rate.filter(|&r| r > 0);
+        self
+    }
+
+    /// Emit motion coalesced by [`LibeiEmulationBuilder::max_motion_rate`]
+    /// once the rate allows it, even if no further event follows
+    /// (enabled by default). Otherwise it waits for the next event.
+    pub fn flush_coalesced_motion(mut self, flush_coalesced_motion: bool) -> Self {
+        self.flush_coalesced_motion = flush_coalesced_motion;
+        self
+    }
+
+    /// Retry sending events for up to the given duration if the
+    /// EIS implementation can not keep up (defaults to 50ms).
+    /// Other errors end the emulation immediately.
+    pub fn flush_retry(mut self, flush_retry: Duration) -> Self {
+        self.flush_retry = flush_retry;
+        self
+    }
+
+    /// Give up connecting if the EIS implementation does not
+    /// complete the handshake within the given duration (defaults to 5s).
+    pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    /// invert the direction of scroll events (disabled by default)
+    pub fn invert_scroll(mut self, invert_scroll: bool) -> Self {
+        self.invert_scroll = invert_scroll;
+        self
+    }
+
+    /// how to react to the removal of a device in use
+    /// (defaults to [`DeviceRemoval::Rebind`])
+    pub fn device_removal(mut self, device_removal: DeviceRemoval) -> Self {
+        self.device_removal = device_removal;
+        self
+    }
+
+    /// record everything the EIS implementation sends to the given file,
+    /// see [`LibeiEmulationBuilder::replay`] (disabled by default)
+    pub fn record(mut self, path: Option<PathBuf>) -> Self {
+        self.record = path;
+        self
+    }
+
+    /// Replay a session recorded with [`LibeiEmulationBuilder::record`]
+    /// instead of connecting to the remote desktop portal.
+    /// The settings of the builder should match the recorded session.
+    /// Returns the devices chosen for emulation once the recording ends.
+    pub async fn replay(self, path: impl AsRef<Path>) -> Result<Vec<DeviceInfo>, LibeiReplayError> {
+        let (stream, eis) = UnixStream::pair()?;
+        recording::replay(path.as_ref(), eis)?;
+        stream.set_nonblocking(true)?;
+        let context = ei::Context::new(stream)?;
+        let (_conn, mut events) = handshake(
+            &context,
+            &self.app_name,
+            self.context_type,
+            self.handshake_timeout,
+        )
+        .await?;
+        let devices = Devices::default();
+        let (_paused, mut paused_rx) = watch::channel(false);
+        match ei_event_handler(
+            &mut events,
+            &context,
+            &devices,
+            &self.capabilities,
+            self.device_removal,
+            &mut paused_rx,
+        )
+        .await
+        {
+            Ok(()) | Err(EmulationError::EndOfStream) => Ok(devices.info()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// connect to the EIS implementation of the remote desktop portal
+    pub async fn build<'a>(self) -> Result<LibeiEmulation<'a>, LibeiEmulationCreationError> {
+        let requested = self.device_types();
+        let (_remote_desktop, session, eifd, granted) = get_ei_fd(requested).await?;
+        portal::log_granted(requested, granted);
+        let eifd = match self.record.as_deref() {
+            Some(path) => {
+                log::info!("recording libei session to {}", path.display());
+                recording::record(eifd, path)?
+            }
+            None => eifd,
+        };
+        let stream = UnixStream::from(eifd);
+        stream.set_nonblocking(true)?;
+        let context = ei::Context::new(stream)?;
+        let (conn, events) = handshake(
+            &context,
+            &self.app_name,
+            self.context_type,
+            self.handshake_timeout,
+        )
+        .await?;
+        let devices = Devices {
+            granted: Some(granted),
+            requested: Some(self.capabilities.clone()),
+            ..Default::default()
+        };
+        let libei_error = Arc::new(AtomicBool::default());
+        let error = Arc::new(Mutex::new(None));
+        let (paused, mut paused_rx) = watch::channel(false);
+        let handler = {
+            let (context, devices) = (context.clone(), devices.clone());
+            let mut events = events;
+            async move {
+                ei_event_handler(
+                    &mut events,
+                    &context,
+                    &devices,
+                    &self.capabilities,
+                    self.device_removal,
+                    &mut paused_rx,
+                )
+                .await
+            }
+        };
+        let ei_handler = ei_task(handler, libei_error.clone(), error.clone());
+        let ei_task = tokio::task::spawn_local(ei_handler);
+
+        // bind devices ahead of the first event
+        if let Some(timeout) = self.warmup {
+            log::info!("waiting for emulation devices ...");
+            match tokio::time::timeout(timeout, devices.ready()).await {
+                Ok(()) => log::info!("emulation devices ready"),
+                Err(_) => log::warn!(
+                    "emulation devices not ready after {timeout:?}, missing: {}",
+                    devices.missing().join(", ")
+                ),
+            }
+        }
+
+        Ok(LibeiEmulation {
+            context,
+            conn,
+            devices,
+            ei_task: Some(ei_task),
+            paused,
+            error,
+            libei_error,
+            grouped: Default::default(),
+            pending_frame: Default::default(),
+            motion_interval: self.max_motion_rate.map(|r| Duration::from_secs(1) / r),
+            flush_coalesced_motion: self.flush_coalesced_motion,
+            last_motion: None,
+            pending_motion: (0., 0.),
+            early_motion_buffer: self.early_motion_buffer,
+            early_motion: Default::default(),
+            invert_scroll: self.invert_scroll,
+            flush_retry: self.flush_retry,
+            frames: Default::default(),
+            clock: Arc::new(SystemClock),
+            granted,
+            _remote_desktop,
+            session,
+        })
+    }
+
+    /// device types to request from the portal
+    /// (touch input is not emulated)
+    fn device_types(&self) -> BitFlags<DeviceType> {
+        let mut device_types = BitFlags::empty();
+        for capability in self.capabilities.iter() {
+            match capability {
+                DeviceCapability::Keyboard => device_types |= DeviceType::Keyboard,
+                DeviceCapability::Touch => {}
+                _ => device_types |= DeviceType::Pointer,
+            }
+        }
+        device_types
+    }
+}
+
+/// input emulation through libei
+pub struct LibeiEmulation<'a> {
     context: ei::Context,
     conn: event::Connection,
     devices: Devices,
-    ei_task: JoinHandle<()>,
+    /// event handler, `None` once it exited
+    ei_task: Option<JoinHandle<()>>,
+    /// suspends the event handler and event consumption
+    paused: watch::Sender<bool>,
     error: Arc<Mutex<Option<EmulationError>>>,
     libei_error: Arc<AtomicBool>,
+    /// handles sending frame markers: their pointer events
+    /// are framed on [`Emulation::frame`] instead of individually
+    grouped: HashSet<EmulationHandle>,
+    /// devices with events awaiting the end of the current frame
+    pending_frame: HashSet<ei::Device>,
+    /// minimum time between two motion events
+    motion_interval: Option<Duration>,
+    /// emit coalesced motion once it is due, see [`Emulation::deadline`]
+    flush_coalesced_motion: bool,
+    last_motion: Option<Instant>,
+    /// coalesced motion not yet emitted
+    pending_motion: (f64, f64),
+    /// how long motion without a pointer device is buffered
+    early_motion_buffer: Option<Duration>,
+    /// motion received before the pointer device was bound
+    early_motion: VecDeque<(Instant, f64, f64)>,
+    invert_scroll: bool,
+    /// how long to retry a flush failing with a transient error
+    flush_retry: Duration,
+    /// number of frames sent
+    frames: AtomicU64,
+    /// source of time of the motion rate limit and the early motion buffer
+    clock: Arc<dyn Clock>,
+    /// device types the user granted access to
+    granted: BitFlags<DeviceType>,
     _remote_desktop: RemoteDesktop<'a>,
     session: Session<'a, RemoteDesktop<'a>>,
 }
 
-async fn get_ei_fd<'a>(
-) -> Result<(RemoteDesktop<'a>, Session<'a, RemoteDesktop<'a>>, OwnedFd), ashpd::Error> {
+/// remote desktop session connected to the EIS implementation
+/// and the device types the user granted access to
+type EiSession<'a> = (
+    RemoteDesktop<'a>,
+    Session<'a, RemoteDesktop<'a>>,
+    OwnedFd,
+    BitFlags<DeviceType>,
+);
+
+async fn get_ei_fd<'a>(device_types: BitFlags<DeviceType>) -> Result<EiSession<'a>, ashpd::Error> {
     let remote_desktop = RemoteDesktop::new().await?;
 
     log::debug!("creating session ...");
@@ -59,59 +634,215 @@ async fn get_ei_fd<'a>(
 
     log::debug!("selecting devices ...");
     remote_desktop
-        .select_devices(
-            &session,
-            DeviceType::Keyboard | DeviceType::Pointer,
-            None,
-            PersistMode::ExplicitlyRevoked,
-        )
+        .select_devices(&session, device_types, None, PersistMode::ExplicitlyRevoked)
         .await?;
 
     log::info!("requesting permission for input emulation");
-    let _devices = remote_desktop.start(&session, None).await?.response()?;
+    let granted = remote_desktop
+        .start(&session, None)
+        .await?
+        .response()?
+        .devices();
 
     let fd = remote_desktop.connect_to_eis(&session).await?;
-    Ok((remote_desktop, session, fd))
+    Ok((remote_desktop, session, fd, granted))
 }
 
 impl LibeiEmulation<'_> {
-    pub(crate) async fn new() -> Result<Self, LibeiEmulationCreationError> {
-        let (_remote_desktop, session, eifd) = get_ei_fd().await?;
-        let stream = UnixStream::from(eifd);
-        stream.set_nonblocking(true)?;
-        let context = ei::Context::new(stream)?;
-        let (conn, events) = context
-            .handshake_tokio("de.feschber.LanMouse", ContextType::Sender)
-            .await?;
-        let devices = Devices::default();
-        let libei_error = Arc::new(AtomicBool::default());
-        let error = Arc::new(Mutex::new(None));
-        let ei_handler = ei_task(
-            events,
-            conn.clone(),
-            context.clone(),
-            devices.clone(),
-            libei_error.clone(),
-            error.clone(),
+    /// create a libei emulation with the default [`LibeiEmulationBuilder`] settings
+    pub async fn new() -> Result<Self, LibeiEmulationCreationError> {
+        LibeiEmulationBuilder::default().build().await
+    }
+}
+
+impl LibeiEmulation<'_> {
+    /// whether emulation is suspended, see [`Emulation::pause`]
+    fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// frame all devices with pending events
+    async fn flush_frame(&mut self) -> Result<(), EmulationError> {
+        end_frame(
+            &mut self.pending_frame,
+            self.conn.serial(),
+            now(),
+            &self.frames,
         );
-        let ei_task = tokio::task::spawn_local(ei_handler);
+        self.flush().await
+    }
 
-        Ok(Self {
-            context,
-            conn,
-            devices,
-            ei_task,
-            error,
-            libei_error,
-            _remote_desktop,
-            session,
-        })
+    /// Send buffered requests to the EIS implementation,
+    /// retrying for up to `flush_retry` if it can not keep up.
+    async fn flush(&self) -> Result<(), EmulationError> {
+        let start = Instant::now();
+        let mut retries = 0;
+        loop {
+            match self.context.flush() {
+                Ok(()) => break,
+                Err(e) if is_transient(e) && start.elapsed() < self.flush_retry => {
+                    retries += 1;
+                    log::debug!("flush failed ({e}), retrying ...");
+                    tokio::time::sleep(FLUSH_RETRY_INTERVAL).await;
+                }
+                Err(e) => {
+                    log::warn!("flush failed: {e}");
+                    return Err(io::Error::new(e.kind(), e).into());
+                }
+            }
+        }
+        if retries > 0 {
+            log::info!("flush succeeded after {retries} retries");
+        }
+        Ok(())
+    }
+
+    /// Apply the motion rate limit.
+    /// Returns the accumulated motion if it can be emitted now.
+    fn coalesce_motion(&mut self, dx: f64, dy: f64) -> Option<(f64, f64)> {
+        let (pdx, pdy) = std::mem::take(&mut self.pending_motion);
+        let (dx, dy) = (pdx + dx, pdy + dy);
+        if let (Some(interval), Some(last)) = (self.motion_interval, self.last_motion) {
+            if self.clock.now().duration_since(last) < interval {
+                self.pending_motion = (dx, dy);
+                return None;
+            }
+        }
+        self.last_motion = Some(self.clock.now());
+        Some((dx, dy))
+    }
+
+    /// time at which motion held back by the rate limit is due
+    fn pending_motion_deadline(&self) -> Option<Instant> {
+        if !self.flush_coalesced_motion || self.pending_motion == (0., 0.) || self.is_paused() {
+            return None;
+        }
+        Some(self.last_motion? + self.motion_interval?)
+    }
+
+    /// emit motion held back by the rate limit
+    fn emit_pending_motion(&mut self, now: u64, grouped: bool) {
+        let (dx, dy) = std::mem::take(&mut self.pending_motion);
+        if dx == 0. && dy == 0. {
+            return;
+        }
+        let pointer_device = self.devices.pointer.read().unwrap();
+        if let Some((d, p)) = pointer_device.as_ref() {
+            p.motion_relative(dx as f32, dy as f32);
+            frame(
+                d,
+                self.conn.serial(),
+                now,
+                grouped.then_some(&mut self.pending_frame),
+                &self.frames,
+            );
+        }
+        self.last_motion = Some(self.clock.now());
+    }
+
+    /// buffer motion received while no pointer device is bound
+    fn buffer_early_motion(&mut self, dx: f64, dy: f64) {
+        if self.early_motion_buffer.is_none() {
+            return;
+        }
+        if self.early_motion.len() == MAX_EARLY_MOTION {
+            self.early_motion.pop_front();
+        }
+        if self.early_motion.is_empty() {
+            log::debug!("no pointer device bound yet, buffering motion");
+        }
+        self.early_motion.push_back((self.clock.now(), dx, dy));
+    }
+
+    /// emit the buffered motion once the pointer device is bound,
+    /// dropping motion older than the buffer window
+    fn emit_early_motion(&mut self, now: u64, grouped: bool) {
+        let Some(window) = self.early_motion_buffer else {
+            return;
+        };
+        let count = self.early_motion.len();
+        let time = self.clock.now();
+        self.early_motion
+            .retain(|(t, _, _)| time.duration_since(*t) <= window);
+        let stale = count - self.early_motion.len();
+        if stale > 0 {
+            log::warn!("pointer device not bound within {window:?}, dropped {stale} motion events");
+        }
+        if self.early_motion.is_empty() {
+            return;
+        }
+        let pointer_device = self.devices.pointer.read().unwrap();
+        let Some((d, p)) = pointer_device.as_ref() else {
+            return;
+        };
+        let (dx, dy) = self
+            .early_motion
+            .drain(..)
+            .fold((0., 0.), |(x, y), (_, dx, dy)| (x + dx, y + dy));
+        log::debug!("pointer device bound, emitting buffered motion ({dx}, {dy})");
+        p.motion_relative(dx as f32, dy as f32);
+        frame(
+            d,
+            self.conn.serial(),
+            now,
+            grouped.then_some(&mut self.pending_frame),
+            &self.frames,
+        );
+    }
+}
+
+/// device the end of a group of events is signaled to
+trait FrameDevice: Clone + Eq + Hash {
+    fn frame(&self, serial: u32, time: u64);
+}
+
+impl FrameDevice for ei::Device {
+    fn frame(&self, serial: u32, time: u64) {
+        ei::Device::frame(self, serial, time);
     }
 }
 
+/// Frame the device or defer the frame until the end of the current group:
+/// events in one group, e.g. chorded buttons, share a single frame
+/// and appear simultaneous on the target.
+fn frame<D: FrameDevice>(
+    d: &D,
+    serial: u32,
+    now: u64,
+    pending: Option<&mut HashSet<D>>,
+    frames: &AtomicU64,
+) {
+    match pending {
+        Some(pending) => {
+            pending.insert(d.clone());
+        }
+        None => {
+            d.frame(serial, now);
+            frames.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// frame the devices with events deferred until the end of the group
+fn end_frame<D: FrameDevice>(pending: &mut HashSet<D>, serial: u32, now: u64, frames: &AtomicU64) {
+    for d in pending.drain() {
+        d.frame(serial, now);
+        frames.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as u64
+}
+
 impl Drop for LibeiEmulation<'_> {
     fn drop(&mut self) {
-        self.ei_task.abort();
+        if let Some(ei_task) = &self.ei_task {
+            ei_task.abort();
+        }
     }
 }
 
@@ -120,25 +851,60 @@ impl Emulation for LibeiEmulation<'_> {
     async fn consume(
         &mut self,
         event: Event,
-        _handle: EmulationHandle,
+        handle: EmulationHandle,
     ) -> Result<(), EmulationError> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as u64;
+        if self.is_paused() {
+            log::trace!("emulation paused, discarding {event}");
+            return Ok(());
+        }
+        let now = now();
+        let grouped = self.grouped.contains(&handle);
         if self.libei_error.load(Ordering::SeqCst) {
             // don't break sending additional events but signal error
             if let Some(e) = self.error.lock().unwrap().take() {
                 return Err(e);
             }
         }
+        self.emit_early_motion(now, grouped);
         match event {
             Event::Pointer(p) => match p {
                 PointerEvent::Motion { time: _, dx, dy } => {
+                    let Some((dx, dy)) = self.coalesce_motion(dx, dy) else {
+                        return Ok(());
+                    };
                     let pointer_device = self.devices.pointer.read().unwrap();
                     if let Some((d, p)) = pointer_device.as_ref() {
                         p.motion_relative(dx as f32, dy as f32);
-                        d.frame(self.conn.serial(), now);
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
+                    } else {
+                        drop(pointer_device);
+                        self.buffer_early_motion(dx, dy);
+                    }
+                }
+                PointerEvent::MotionAbsolute { time: _, x, y } => {
+                    self.emit_pending_motion(now, grouped);
+                    let Some((area_x, area_y, width, height)) = self.devices.absolute_area() else {
+                        log::debug!("no device for absolute motion");
+                        return Ok(());
+                    };
+                    let pointer_device = self.devices.pointer_absolute.read().unwrap();
+                    if let Some((d, p)) = pointer_device.as_ref() {
+                        let x = area_x + x * width;
+                        let y = area_y + y * height;
+                        p.motion_absolute(x as f32, y as f32);
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
                     }
                 }
                 PointerEvent::Button {
@@ -146,6 +912,7 @@ impl Emulation for LibeiEmulation<'_> {
                     button,
                     state,
                 } => {
+                    self.emit_pending_motion(now, grouped);
                     let button_device = self.devices.button.read().unwrap();
                     if let Some((d, b)) = button_device.as_ref() {
                         b.button(
@@ -155,31 +922,70 @@ impl Emulation for LibeiEmulation<'_> {
                                 _ => ButtonState::Press,
                             },
                         );
-                        d.frame(self.conn.serial(), now);
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
                     }
                 }
                 PointerEvent::Axis {
                     time: _,
                     axis,
                     value,
+                    source: _,
                 } => {
+                    let value = if self.invert_scroll { -value } else { value };
                     let scroll_device = self.devices.scroll.read().unwrap();
                     if let Some((d, s)) = scroll_device.as_ref() {
                         match axis {
                             0 => s.scroll(0., value as f32),
                             _ => s.scroll(value as f32, 0.),
                         }
-                        d.frame(self.conn.serial(), now);
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
+                    }
+                }
+                PointerEvent::ScrollStop { time: _, axis }
+                | PointerEvent::ScrollCancel { time: _, axis } => {
+                    let cancel = matches!(p, PointerEvent::ScrollCancel { .. }) as u32;
+                    let scroll_device = self.devices.scroll.read().unwrap();
+                    if let Some((d, s)) = scroll_device.as_ref() {
+                        match axis {
+                            0 => s.scroll_stop(0, 1, cancel),
+                            _ => s.scroll_stop(1, 0, cancel),
+                        }
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
                     }
                 }
                 PointerEvent::AxisDiscrete120 { axis, value } => {
+                    let value = if self.invert_scroll { -value } else { value };
                     let scroll_device = self.devices.scroll.read().unwrap();
                     if let Some((d, s)) = scroll_device.as_ref() {
                         match axis {
                             0 => s.scroll_discrete(0, value),
                             _ => s.scroll_discrete(value, 0),
                         }
-                        d.frame(self.conn.serial(), now);
+                        frame(
+                            d,
+                            self.conn.serial(),
+                            now,
+                            grouped.then_some(&mut self.pending_frame),
+                            &self.frames,
+                        );
                     }
                 }
             },
@@ -199,62 +1005,196 @@ impl Emulation for LibeiEmulation<'_> {
                             },
                         );
                         d.frame(self.conn.serial(), now);
+                        self.frames.fetch_add(1, Ordering::Relaxed);
                     }
                 }
                 KeyboardEvent::Modifiers { .. } => {}
             },
         }
+        self.flush().await
+    }
+
+    async fn frame(&mut self, handle: EmulationHandle) -> Result<(), EmulationError> {
+        if self.grouped.insert(handle) {
+            log::debug!("grouping events of {handle} into frames");
+        }
+        self.flush_frame().await
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending_motion_deadline()
+    }
+
+    async fn step(&mut self) -> Result<(), EmulationError> {
+        if self
+            .pending_motion_deadline()
+            .is_none_or(|d| d > self.clock.now())
+        {
+            return Ok(());
+        }
+        // join a group in progress, frame on its own otherwise
+        let grouped = !self.pending_frame.is_empty();
+        self.emit_pending_motion(now(), grouped);
+        self.flush().await
+    }
+
+    async fn idle_frame(&mut self) -> Result<(), EmulationError> {
+        if self.is_paused() {
+            return Ok(());
+        }
+        let now = now();
+        self.emit_early_motion(now, false);
+        // devices in the middle of a group are framed at its end
+        for d in self.devices.bound() {
+            if !self.pending_frame.contains(&d) {
+                d.frame(self.conn.serial(), now);
+                self.frames.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.flush().await
+    }
+
+    async fn create(&mut self, handle: EmulationHandle) {
+        // devices belong to the seat and are shared by all handles
+        log::debug!("handle {handle}: using devices {:?}", self.devices.info());
+    }
+
+    async fn destroy(&mut self, handle: EmulationHandle) {
+        if self.grouped.remove(&handle) {
+            let _ = self.flush_frame().await;
+        }
+    }
+
+    async fn terminate(&mut self) {
+        let _ = self.session.close().await;
+        if let Some(ei_task) = &self.ei_task {
+            ei_task.abort();
+        }
+    }
+
+    fn missing_devices(&self) -> Vec<&'static str> {
+        self.devices.missing()
+    }
+
+    /// events are discarded and the event handler stops processing
+    /// libei events until resumed, keeping the bound devices
+    fn pause(&mut self) -> Result<(), EmulationError> {
+        if self.paused.send_replace(true) {
+            return Ok(());
+        }
+        log::info!("pausing libei emulation");
+        self.pending_frame.clear();
+        self.pending_motion = (0., 0.);
+        for device in self.devices.bound() {
+            device.stop_emulating(self.conn.serial());
+        }
         self.context
             .flush()
             .map_err(|e| io::Error::new(e.kind(), e))?;
         Ok(())
     }
 
-    async fn create(&mut self, _: EmulationHandle) {}
-    async fn destroy(&mut self, _: EmulationHandle) {}
+    /// restarts emulation on the bound devices
+    fn resume(&mut self) -> Result<(), EmulationError> {
+        if !self.paused.send_replace(false) {
+            return Ok(());
+        }
+        log::info!("resuming libei emulation");
+        for device in self.devices.bound() {
+            let sequence = self.devices.next_sequence();
+            device.start_emulating(self.conn.serial(), sequence);
+        }
+        self.context
+            .flush()
+            .map_err(|e| io::Error::new(e.kind(), e))?;
+        Ok(())
+    }
 
-    async fn terminate(&mut self) {
-        let _ = self.session.close().await;
-        self.ei_task.abort();
+    async fn devices_changed(&mut self) -> Result<Vec<&'static str>, EmulationError> {
+        tokio::select! {
+            _ = self.devices.changed.notified() => {
+                Ok(std::mem::take(&mut *self.devices.removed.lock().unwrap()))
+            }
+            e = task_exited(&mut self.ei_task, &self.error) => Err(e),
+        }
+    }
+
+    fn kinetic_scroll(&self) -> bool {
+        true
+    }
+
+    fn absolute_motion(&self) -> bool {
+        self.devices.absolute_area().is_some()
+    }
+
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.devices.info()
+    }
+
+    fn granted_devices(&self) -> Option<Vec<&'static str>> {
+        Some(portal::device_names(self.granted))
+    }
+
+    fn counters(&self) -> Option<FrameCounters> {
+        Some(FrameCounters {
+            serial: self.conn.serial(),
+            frames: self.frames.load(Ordering::Relaxed),
+        })
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+/// Wait until the event handler exited and return the reason.
+/// Never returns once it has been observed.
+async fn task_exited(
+    ei_task: &mut Option<JoinHandle<()>>,
+    error: &Mutex<Option<EmulationError>>,
+) -> EmulationError {
+    let Some(task) = ei_task.as_mut() else {
+        return future::pending().await;
+    };
+    let result = task.await;
+    *ei_task = None;
+    match result {
+        Ok(()) => error
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or(EmulationError::EndOfStream),
+        Err(e) => e.into(),
     }
 }
 
+/// run the event handler, storing the error it exits with
 async fn ei_task(
-    mut events: EiConvertEventStream,
-    _conn: Connection,
-    context: ei::Context,
-    devices: Devices,
+    handler: impl Future<Output = Result<(), EmulationError>>,
     libei_error: Arc<AtomicBool>,
     error: Arc<Mutex<Option<EmulationError>>>,
 ) {
-    loop {
-        match ei_event_handler(&mut events, &context, &devices).await {
-            Ok(()) => {}
-            Err(e) => {
-                libei_error.store(true, Ordering::SeqCst);
-                error.lock().unwrap().replace(e);
-                // wait for termination -> otherwise we will loop forever
-                future::pending::<()>().await;
-            }
-        }
-    }
+    let e = handler.await.err().unwrap_or(EmulationError::EndOfStream);
+    log::warn!("libei event handler exited: {e}");
+    libei_error.store(true, Ordering::SeqCst);
+    error.lock().unwrap().replace(e);
 }
 
 async fn ei_event_handler(
     events: &mut EiConvertEventStream,
     context: &ei::Context,
     devices: &Devices,
+    capabilities: &[DeviceCapability],
+    device_removal: DeviceRemoval,
+    paused: &mut watch::Receiver<bool>,
 ) -> Result<(), EmulationError> {
     loop {
+        // events stay queued while paused
+        if paused.wait_for(|&p| !p).await.is_err() {
+            // emulation dropped
+            return Err(EmulationError::EndOfStream);
+        }
         let event = events.next().await.ok_or(EmulationError::EndOfStream)??;
-        const CAPABILITIES: &[DeviceCapability] = &[
-            DeviceCapability::Pointer,
-            DeviceCapability::PointerAbsolute,
-            DeviceCapability::Keyboard,
-            DeviceCapability::Touch,
-            DeviceCapability::Scroll,
-            DeviceCapability::Button,
-        ];
         log::debug!("{event:?}");
         match event {
             EiEvent::Disconnected(e) => {
@@ -262,54 +1202,113 @@ async fn ei_event_handler(
                 return Err(EmulationError::EndOfStream);
             }
             EiEvent::SeatAdded(e) => {
-                e.seat().bind_capabilities(CAPABILITIES);
+                e.seat().bind_capabilities(capabilities);
             }
             EiEvent::SeatRemoved(e) => {
                 log::debug!("seat removed: {:?}", e.seat());
             }
             EiEvent::DeviceAdded(e) => {
-                let device_type = e.device().device_type();
-                log::debug!("device added: {device_type:?}");
-                e.device().device();
                 let device = e.device();
-                if let Some(pointer) = e.device().interface::<Pointer>() {
-                    devices
-                        .pointer
-                        .write()
-                        .unwrap()
-                        .replace((device.device().clone(), pointer));
+                let device_type = device.device_type();
+                log::debug!("device added: {:?} ({device_type:?})", device.name());
+                // emulation starts once the device is resumed
+                devices
+                    .suspended
+                    .lock()
+                    .unwrap()
+                    .insert(device.device().clone());
+                if let Some(pointer) = device.interface::<Pointer>() {
+                    if devices.assign(DeviceCapability::Pointer, device) {
+                        devices
+                            .pointer
+                            .write()
+                            .unwrap()
+                            .replace((device.device().clone(), pointer));
+                    }
                 }
-                if let Some(keyboard) = e.device().interface::<Keyboard>() {
-                    devices
-                        .keyboard
-                        .write()
-                        .unwrap()
-                        .replace((device.device().clone(), keyboard));
+                if let Some(pointer) = device.interface::<PointerAbsolute>() {
+                    if devices.assign(DeviceCapability::PointerAbsolute, device) {
+                        devices
+                            .pointer_absolute
+                            .write()
+                            .unwrap()
+                            .replace((device.device().clone(), pointer));
+                    }
                 }
-                if let Some(scroll) = e.device().interface::<Scroll>() {
-                    devices
-                        .scroll
-                        .write()
-                        .unwrap()
-                        .replace((device.device().clone(), scroll));
+                if let Some(keyboard) = device.interface::<Keyboard>() {
+                    if devices.assign(DeviceCapability::Keyboard, device) {
+                        devices
+                            .keyboard
+                            .write()
+                            .unwrap()
+                            .replace((device.device().clone(), keyboard));
+                    }
+                }
+                if let Some(scroll) = device.interface::<Scroll>() {
+                    if devices.assign(DeviceCapability::Scroll, device) {
+                        devices
+                            .scroll
+                            .write()
+                            .unwrap()
+                            .replace((device.device().clone(), scroll));
+                    }
                 }
-                if let Some(button) = e.device().interface::<Button>() {
-                    devices
-                        .button
-                        .write()
-                        .unwrap()
-                        .replace((device.device().clone(), button));
+                if let Some(button) = device.interface::<Button>() {
+                    if devices.assign(DeviceCapability::Button, device) {
+                        devices
+                            .button
+                            .write()
+                            .unwrap()
+                            .replace((device.device().clone(), button));
+                    }
                 }
+                devices.added.notify_one();
+                devices.changed.notify_one();
             }
             EiEvent::DeviceRemoved(e) => {
-                log::debug!("device removed: {:?}", e.device().device_type());
+                let device = e.device();
+                log::debug!("device removed: {:?}", device.device_type());
+                let removed = devices.remove(device);
+                if !removed.is_empty() {
+                    log::warn!(
+                        "device {:?} in use for {} was removed",
+                        device.name(),
+                        removed.join(", ")
+                    );
+                    if device_removal == DeviceRemoval::Rebind {
+                        log::info!("rebinding capabilities of seat {:?}", device.seat());
+                        device.seat().bind_capabilities(capabilities);
+                    }
+                }
             }
             EiEvent::DevicePaused(e) => {
-                log::debug!("device paused: {:?}", e.device().device_type());
+                devices
+                    .suspended
+                    .lock()
+                    .unwrap()
+                    .insert(e.device().device().clone());
+                log::info!(
+                    "device {:?} paused (serial {})",
+                    e.device().name(),
+                    e.serial
+                );
             }
             EiEvent::DeviceResumed(e) => {
-                log::debug!("device resumed: {:?}", e.device().device_type());
-                e.device().device().start_emulating(0, 0);
+                // emulation starts over after a resume: the request refers to
+                // the serial of the resume event and needs a new sequence number,
+                // otherwise the compositor may discard the following events
+                devices
+                    .suspended
+                    .lock()
+                    .unwrap()
+                    .remove(e.device().device());
+                let sequence = devices.next_sequence();
+                log::info!(
+                    "device {:?} resumed (serial {}), starting emulation (sequence {sequence})",
+                    e.device().name(),
+                    e.serial
+                );
+                e.device().device().start_emulating(e.serial, sequence);
             }
             EiEvent::KeyboardModifiers(e) => {
                 log::debug!("modifiers: {e:?}");
@@ -334,3 +1333,71 @@ async fn ei_event_handler(
         context.flush().map_err(|e| io::Error::new(e.kind(), e))?;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// frames of the test devices
+        static FRAMES: Cell<u32> = const { Cell::new(0) };
+    }
+
+    /// device counting its frames in [`FRAMES`]
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct TestDevice(u32);
+
+    impl FrameDevice for TestDevice {
+        fn frame(&self, _serial: u32, _time: u64) {
+            FRAMES.set(FRAMES.get() + 1);
+        }
+    }
+
+    #[test]
+    fn chorded_buttons_share_a_frame() {
+        let button = TestDevice(0);
+        let frames = AtomicU64::new(0);
+        let mut pending = HashSet::new();
+        // left and right button pressed within one frame
+        frame(&button, 0, 0, Some(&mut pending), &frames);
+        frame(&button, 1, 0, Some(&mut pending), &frames);
+        assert_eq!(FRAMES.get(), 0);
+        end_frame(&mut pending, 2, 0, &frames);
+        assert_eq!(FRAMES.get(), 1);
+        assert_eq!(frames.load(Ordering::Relaxed), 1);
+        // without frame markers every event is framed on its own
+        frame(&button, 3, 0, None, &frames);
+        frame(&button, 4, 0, None, &frames);
+        assert_eq!(FRAMES.get(), 3);
+    }
+
+    /// Session of an EIS implementation offering a physical and a virtual
+    /// pointer (pointer, button and scroll interfaces) and a virtual keyboard.
+    const SESSION: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/libei/testdata/session.bin"
+    );
+
+    #[tokio::test]
+    async fn replay_binds_virtual_devices() {
+        let devices = LibeiEmulationBuilder::new()
+            .replay(SESSION)
+            .await
+            .expect("replay failed");
+        let device = |capability, name: &str| DeviceInfo {
+            capability,
+            name: Some(name.into()),
+            device_type: "virtual".into(),
+        };
+        assert_eq!(
+            devices,
+            [
+                device("pointer", "virtual pointer"),
+                device("scroll", "virtual pointer"),
+                device("button", "virtual pointer"),
+                device("keyboard", "virtual keyboard"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Recording and replay of libei sessions.
+//!
+//! A recording contains everything the EIS implementation sent during a session,
+//! so the device binding of the libei backend can be reproduced without a compositor.
+//! Each read from the EIS socket is stored as the length of the data and the
+//! number of file descriptors passed along with it (both u32, little endian),
+//! followed by the data itself.
+//! File descriptors are not recorded and are replaced with `/dev/null` on replay.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    path::Path,
+    thread,
+};
+
+use rustix::{
+    event::{poll, PollFd, PollFlags},
+    io::{retry_on_intr, IoSlice, IoSliceMut},
+    net::{
+        recvmsg, sendmsg, RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags,
+        SendAncillaryBuffer, SendAncillaryMessage, SendFlags,
+    },
+};
+
+const BUF_SIZE: usize = 4096;
+
+/// maximum number of file descriptors passed with a single message
+const MAX_FDS: usize = 32;
+
+/// a single read from the EIS socket
+struct Record {
+    data: Vec<u8>,
+    fds: u32,
+}
+
+/// Proxy the connection to the EIS implementation,
+/// recording everything it sends to the given file.
+/// Returns the socket to use in place of `eis`.
+pub(super) fn record(eis: OwnedFd, path: &Path) -> io::Result<OwnedFd> {
+    let file = BufWriter::new(File::create(path)?);
+    let eis = UnixStream::from(eis);
+    eis.set_nonblocking(false)?;
+    let (client, proxy) = UnixStream::pair()?;
+    thread::Builder::new()
+        .name("libei-recorder".into())
+        .spawn(move || {
+            if let Err(e) = proxy_session(eis, proxy, file) {
+                log::warn!("libei recording stopped: {e}");
+            }
+        })?;
+    Ok(client.into())
+}
+
+fn proxy_session(eis: UnixStream, client: UnixStream, mut file: BufWriter<File>) -> io::Result<()> {
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        let mut fds = [
+            PollFd::new(&eis, PollFlags::IN),
+            PollFd::new(&client, PollFlags::IN),
+        ];
+        retry_on_intr(|| poll(&mut fds, -1))?;
+        let (eis_ready, client_ready) =
+            (!fds[0].revents().is_empty(), !fds[1].revents().is_empty());
+        if eis_ready {
+            let (n, fds) = recv(&eis, &mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            file.write_all(&(n as u32).to_le_bytes())?;
+            file.write_all(&(fds.len() as u32).to_le_bytes())?;
+            file.write_all(&buf[..n])?;
+            file.flush()?;
+            send(&client, &buf[..n], fds)?;
+        }
+        if client_ready {
+            let (n, fds) = recv(&client, &mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            send(&eis, &buf[..n], fds)?;
+        }
+    }
+}
+
+/// Replay a recording into `client`.
+/// Everything sent by the client is discarded,
+/// the replay ends once the client hangs up.
+pub(super) fn replay(path: &Path, client: UnixStream) -> io::Result<()> {
+    let records = read_records(path)?;
+    thread::Builder::new()
+        .name("libei-replay".into())
+        .spawn(move || {
+            if let Err(e) = replay_session(records, client) {
+                log::warn!("libei replay stopped: {e}");
+            }
+        })?;
+    Ok(())
+}
+
+/// Send the recorded data, draining requests until the client hangs up.
+fn replay_session(records: Vec<Record>, client: UnixStream) -> io::Result<()> {
+    thread::scope(|s| {
+        let drain = s.spawn(|| io::copy(&mut &client, &mut io::sink()));
+        let replayed = records.iter().try_for_each(|record| {
+            let fds = (0..record.fds)
+                .map(|_| File::open("/dev/null").map(OwnedFd::from))
+                .collect::<io::Result<Vec<_>>>()?;
+            send(&client, &record.data, fds)
+        });
+        // unblock the drain if the replay failed
+        let shutdown = match replayed {
+            Ok(()) => std::net::Shutdown::Write,
+            Err(_) => std::net::Shutdown::Both,
+        };
+        client.shutdown(shutdown)?;
+        let drained = drain.join().expect("drain thread panicked");
+        replayed.and(drained.map(|_| ()))
+    })
+}
+
+fn read_records(path: &Path) -> io::Result<Vec<Record>> {
+    let mut file = io::BufReader::new(File::open(path)?);
+    let mut records = vec![];
+    let mut header = [0u8; 8];
+    loop {
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(records),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let fds = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if len as usize > BUF_SIZE || fds as usize > MAX_FDS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid libei recording",
+            ));
+        }
+        let mut data = vec![0; len as usize];
+        file.read_exact(&mut data)?;
+        records.push(Record { data, fds });
+    }
+}
+
+fn recv(socket: &UnixStream, buf: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+    let mut cmsg_space = vec![0; rustix::cmsg_space!(ScmRights(MAX_FDS))];
+    let mut cmsg_buffer = RecvAncillaryBuffer::new(&mut cmsg_space);
+    let msg = retry_on_intr(|| {
+        recvmsg(
+            socket,
+            &mut [IoSliceMut::new(buf)],
+            &mut cmsg_buffer,
+            RecvFlags::CMSG_CLOEXEC,
+        )
+    })?;
+    let fds = cmsg_buffer
+        .drain()
+        .filter_map(|msg| match msg {
+            RecvAncillaryMessage::ScmRights(fds) => Some(fds),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    Ok((msg.bytes, fds))
+}
+
+/// send all of `data`, passing the file descriptors with the first message
+fn send(socket: &UnixStream, mut data: &[u8], fds: Vec<OwnedFd>) -> io::Result<()> {
+    let fds = fds.iter().map(|fd| fd.as_fd()).collect::<Vec<_>>();
+    let mut cmsg_space = vec![0; rustix::cmsg_space!(ScmRights(fds.len()))];
+    let mut cmsg_buffer = SendAncillaryBuffer::new(&mut cmsg_space);
+    cmsg_buffer.push(SendAncillaryMessage::ScmRights(&fds));
+    let n = retry_on_intr(|| {
+        sendmsg(
+            socket,
+            &[IoSlice::new(data)],
+            &mut cmsg_buffer,
+            SendFlags::NOSIGNAL,
+        )
+    })?;
+    data = &data[n..];
+    while !data.is_empty() {
+        let n = retry_on_intr(|| rustix::net::send(socket, data, SendFlags::NOSIGNAL))?;
+        data = &data[n..];
+    }
+    Ok(())
+}
@@ -265,6 +265,9 @@ impl Emulation for MacOSEmulation {
                     event.set_integer_value_field(EventField::MOUSE_EVENT_DELTA_Y, dy as i64);
                     event.post(CGEventTapLocation::HID);
                 }
+                PointerEvent::MotionAbsolute { .. } => {
+                    log::debug!("absolute motion is not supported on macos");
+                }
                 PointerEvent::Button {
                     time: _,
                     button,
@@ -316,6 +319,7 @@ impl Emulation for MacOSEmulation {
                     time: _,
                     axis,
                     value,
+                    source: _,
                 } => {
                     let value = value as i32;
                     let (count, wheel1, wheel2, wheel3) = match axis {
@@ -367,6 +371,9 @@ impl Emulation for MacOSEmulation {
                     };
                     event.post(CGEventTapLocation::HID);
                 }
+                PointerEvent::ScrollStop { .. } | PointerEvent::ScrollCancel { .. } => {
+                    log::debug!("ending scroll sequences is not supported on macos");
+                }
             },
             Event::Keyboard(keyboard_event) => match keyboard_event {
                 KeyboardEvent::Key {
@@ -414,6 +421,10 @@ impl Emulation for MacOSEmulation {
     async fn destroy(&mut self, _handle: EmulationHandle) {}
 
     async fn terminate(&mut self) {}
+
+    fn absolute_motion(&self) -> bool {
+        false
+    }
 }
 
 fn update_modifiers(modifiers: &Cell<XMods>, key: u32, state: u8) -> bool {
@@ -0,0 +1,28 @@
+use ashpd::{desktop::remote_desktop::DeviceType, enumflags2::BitFlags};
+
+/// names of the device types, as used for missing devices
+pub(crate) fn device_names(devices: BitFlags<DeviceType>) -> Vec<&'static str> {
+    devices
+        .iter()
+        .map(|d| match d {
+            DeviceType::Keyboard => "keyboard",
+            DeviceType::Pointer => "pointer",
+            DeviceType::Touchscreen => "touchscreen",
+        })
+        .collect()
+}
+
+/// log the device types granted by the user, warning about the ones denied
+pub(crate) fn log_granted(requested: BitFlags<DeviceType>, granted: BitFlags<DeviceType>) {
+    log::info!(
+        "remote desktop portal: granted access to {}",
+        device_names(granted).join(", ")
+    );
+    let denied = requested & !granted;
+    if !denied.is_empty() {
+        log::warn!(
+            "remote desktop portal: access to {} was not granted, its input is discarded",
+            device_names(denied).join(", ")
+        );
+    }
+}
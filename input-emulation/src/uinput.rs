@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    mem::size_of,
+    os::unix::fs::OpenOptionsExt,
+};
+
+use rustix::ioctl::{self, IntegerSetter, NoArg, NoneOpcode, Setter, WriteOpcode};
+
+use input_event::{Event, KeyboardEvent, PointerEvent};
+
+use crate::error::EmulationError;
+
+use super::{error::UinputEmulationCreationError, Emulation, EmulationHandle};
+
+// linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const REL_WHEEL_HI_RES: u16 = 0x0b;
+const REL_HWHEEL_HI_RES: u16 = 0x0c;
+const KEY_MAX: u16 = 0x2ff;
+const BUS_VIRTUAL: u16 = 0x06;
+
+// linux/uinput.h
+const UINPUT_IOCTL_BASE: u8 = b'U';
+type UiDevCreate = NoArg<NoneOpcode<UINPUT_IOCTL_BASE, 1, ()>>;
+type UiDevDestroy = NoArg<NoneOpcode<UINPUT_IOCTL_BASE, 2, ()>>;
+type UiDevSetup = Setter<WriteOpcode<UINPUT_IOCTL_BASE, 3, libc::uinput_setup>, libc::uinput_setup>;
+type UiSetEvBit = IntegerSetter<WriteOpcode<UINPUT_IOCTL_BASE, 100, libc::c_int>>;
+type UiSetKeyBit = IntegerSetter<WriteOpcode<UINPUT_IOCTL_BASE, 101, libc::c_int>>;
+type UiSetRelBit = IntegerSetter<WriteOpcode<UINPUT_IOCTL_BASE, 102, libc::c_int>>;
+
+const DEVICE_NAME: &str = "lan-mouse virtual input";
+
+/// scroll distance of one wheel detent in scroll units of [`PointerEvent::Axis`]
+const PIXELS_PER_DETENT: f64 = 15.;
+
+/// input emulation through virtual uinput devices,
+/// independent of the display server
+pub(crate) struct UinputEmulation {
+    /// device shared by all handles
+    device: UinputDevice,
+    /// separate devices of each handle, if enabled
+    devices: Option<HashMap<EmulationHandle, UinputDevice>>,
+}
+
+impl UinputEmulation {
+    pub(crate) fn new(device_per_handle: bool) -> Result<Self, UinputEmulationCreationError> {
+        Ok(Self {
+            device: UinputDevice::new(DEVICE_NAME)?,
+            devices: device_per_handle.then(HashMap::new),
+        })
+    }
+}
+
+/// a virtual uinput device
+struct UinputDevice {
+    device: File,
+    /// fractional motion not yet emitted
+    motion_remainder: (f64, f64),
+    /// high-resolution scroll not yet emitted as wheel detents (vertical, horizontal)
+    scroll_remainder: [i32; 2],
+    /// fractional high-resolution scroll not yet emitted
+    hi_res_remainder: [f64; 2],
+}
+
+impl UinputDevice {
+    fn new(name: &str) -> Result<Self, UinputEmulationCreationError> {
+        let device = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")
+            .map_err(UinputEmulationCreationError::Open)?;
+        unsafe {
+            ioctl::ioctl(&device, UiSetEvBit::new(EV_KEY as usize))?;
+            ioctl::ioctl(&device, UiSetEvBit::new(EV_REL as usize))?;
+            for key in 1..=KEY_MAX {
+                ioctl::ioctl(&device, UiSetKeyBit::new(key as usize))?;
+            }
+            for rel in [
+                REL_X,
+                REL_Y,
+                REL_WHEEL,
+                REL_HWHEEL,
+                REL_WHEEL_HI_RES,
+                REL_HWHEEL_HI_RES,
+            ] {
+                ioctl::ioctl(&device, UiSetRelBit::new(rel as usize))?;
+            }
+            let mut setup: libc::uinput_setup = std::mem::zeroed();
+            setup.id.bustype = BUS_VIRTUAL;
+            for (c, &b) in setup.name.iter_mut().zip(name.as_bytes()) {
+                *c = b as libc::c_char;
+            }
+            ioctl::ioctl(&device, UiDevSetup::new(setup))?;
+            ioctl::ioctl(&device, UiDevCreate::new())?;
+        }
+        Ok(Self {
+            device,
+            motion_remainder: (0., 0.),
+            scroll_remainder: [0; 2],
+            hi_res_remainder: [0.; 2],
+        })
+    }
+
+    fn write_event(&mut self, type_: u16, code: u16, value: i32) -> io::Result<()> {
+        let event = libc::input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_,
+            code,
+            value,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const libc::input_event as *const u8,
+                size_of::<libc::input_event>(),
+            )
+        };
+        self.device.write_all(bytes)
+    }
+
+    fn relative_motion(&mut self, dx: f64, dy: f64) -> io::Result<()> {
+        let (rx, ry) = self.motion_remainder;
+        let (dx, dy) = (rx + dx, ry + dy);
+        let (x, y) = (dx.trunc(), dy.trunc());
+        self.motion_remainder = (dx - x, dy - y);
+        if x != 0. {
+            self.write_event(EV_REL, REL_X, x as i32)?;
+        }
+        if y != 0. {
+            self.write_event(EV_REL, REL_Y, y as i32)?;
+        }
+        Ok(())
+    }
+
+    /// scroll by `value` in 120ths of a detent,
+    /// emitting a wheel event for every completed detent
+    fn scroll(&mut self, axis: u8, value: i32) -> io::Result<()> {
+        // positive values scroll down / right, REL_WHEEL scrolls up
+        let (i, value, hi_res, wheel) = match axis {
+            0 => (0, -value, REL_WHEEL_HI_RES, REL_WHEEL),
+            _ => (1, value, REL_HWHEEL_HI_RES, REL_HWHEEL),
+        };
+        if self.scroll_remainder[i].signum() * value.signum() < 0 {
+            self.scroll_remainder[i] = 0;
+        }
+        self.write_event(EV_REL, hi_res, value)?;
+        self.scroll_remainder[i] += value;
+        let detents = self.scroll_remainder[i] / 120;
+        self.scroll_remainder[i] %= 120;
+        if detents != 0 {
+            self.write_event(EV_REL, wheel, detents)?;
+        }
+        Ok(())
+    }
+
+    /// smooth scrolling by `value` scroll units
+    fn scroll_smooth(&mut self, axis: u8, value: f64) -> io::Result<()> {
+        let i = (axis != 0) as usize;
+        let value = self.hi_res_remainder[i] + value * 120. / PIXELS_PER_DETENT;
+        let discrete = value.trunc();
+        self.hi_res_remainder[i] = value - discrete;
+        if discrete != 0. {
+            self.scroll(axis, discrete as i32)?;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.write_event(EV_SYN, SYN_REPORT, 0)
+    }
+
+    /// emit the event, followed by a sync
+    fn emit(&mut self, event: Event) -> Result<(), EmulationError> {
+        match event {
+            Event::Pointer(pointer_event) => match pointer_event {
+                PointerEvent::Motion { time: _, dx, dy } => self.relative_motion(dx, dy)?,
+                PointerEvent::MotionAbsolute { .. } => {
+                    // the virtual device is a relative pointer
+                    log::debug!("uinput: ignoring absolute motion");
+                    return Ok(());
+                }
+                PointerEvent::Button {
+                    time: _,
+                    button,
+                    state,
+                } => self.write_event(EV_KEY, button as u16, (state != 0) as i32)?,
+                PointerEvent::Axis {
+                    time: _,
+                    axis,
+                    value,
+                    source: _,
+                } => self.scroll_smooth(axis, value)?,
+                PointerEvent::AxisDiscrete120 { axis, value } => self.scroll(axis, value)?,
+                // scroll sequences are not reported to uinput devices
+                PointerEvent::ScrollStop { .. } | PointerEvent::ScrollCancel { .. } => {
+                    return Ok(())
+                }
+            },
+            Event::Keyboard(KeyboardEvent::Key {
+                time: _,
+                key,
+                state,
+            }) => self.write_event(EV_KEY, key as u16, (state != 0) as i32)?,
+            Event::Keyboard(KeyboardEvent::Modifiers { .. }) => return Ok(()),
+        }
+        self.sync()?;
+        Ok(())
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        let _ = unsafe { ioctl::ioctl(&self.device, UiDevDestroy::new()) };
+    }
+}
+
+#[async_trait]
+impl Emulation for UinputEmulation {
+    async fn consume(
+        &mut self,
+        event: Event,
+        handle: EmulationHandle,
+    ) -> Result<(), EmulationError> {
+        let device = match self.devices.as_mut().and_then(|d| d.get_mut(&handle)) {
+            Some(device) => device,
+            None => &mut self.device,
+        };
+        device.emit(event)
+    }
+
+    async fn create(&mut self, handle: EmulationHandle) {
+        let Some(devices) = self.devices.as_mut() else {
+            // all handles share a device
+            return;
+        };
+        match UinputDevice::new(&format!("{DEVICE_NAME} {handle}")) {
+            Ok(device) => {
+                log::info!("created uinput device for handle {handle}");
+                devices.insert(handle, device);
+            }
+            Err(e) => log::warn!("uinput device for handle {handle}: {e}, using shared device"),
+        }
+    }
+
+    async fn destroy(&mut self, handle: EmulationHandle) {
+        if let Some(devices) = self.devices.as_mut() {
+            devices.remove(&handle);
+        }
+    }
+
+    async fn terminate(&mut self) {
+        /* nothing to do */
+    }
+
+    fn absolute_motion(&self) -> bool {
+        false
+    }
+}
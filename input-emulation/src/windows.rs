@@ -42,6 +42,9 @@ impl Emulation for WindowsEmulation {
                 PointerEvent::Motion { time: _, dx, dy } => {
                     rel_mouse(dx as i32, dy as i32);
                 }
+                PointerEvent::MotionAbsolute { .. } => {
+                    log::debug!("absolute motion is not supported on windows");
+                }
                 PointerEvent::Button {
                     time: _,
                     button,
@@ -51,8 +54,12 @@ impl Emulation for WindowsEmulation {
                     time: _,
                     axis,
                     value,
+                    source: _,
                 } => scroll(axis, value as i32),
                 PointerEvent::AxisDiscrete120 { axis, value } => scroll(axis, value),
+                PointerEvent::ScrollStop { .. } | PointerEvent::ScrollCancel { .. } => {
+                    log::debug!("ending scroll sequences is not supported on windows");
+                }
             },
             Event::Keyboard(keyboard_event) => match keyboard_event {
                 KeyboardEvent::Key {
@@ -80,6 +87,10 @@ impl Emulation for WindowsEmulation {
     async fn destroy(&mut self, _handle: EmulationHandle) {}
 
     async fn terminate(&mut self) {}
+
+    fn absolute_motion(&self) -> bool {
+        false
+    }
 }
 
 impl WindowsEmulation {
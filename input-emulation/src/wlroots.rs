@@ -12,7 +12,7 @@ use wayland_client::backend::WaylandError;
 use wayland_client::WEnum;
 
 use wayland_client::protocol::wl_keyboard::{self, WlKeyboard};
-use wayland_client::protocol::wl_pointer::{Axis, ButtonState};
+use wayland_client::protocol::wl_pointer::{Axis, AxisSource, ButtonState};
 use wayland_client::protocol::wl_seat::WlSeat;
 use wayland_protocols_wlr::virtual_pointer::v1::client::{
     zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1 as VpManager,
@@ -31,11 +31,15 @@ use wayland_client::{
     Connection, Dispatch, EventQueue, QueueHandle,
 };
 
-use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent, ScrollSource};
 
 use super::error::WaylandBindError;
 use super::EmulationHandle;
 
+/// resolution of absolute motion, positions are
+/// fractions of this extent on both axes
+const ABSOLUTE_EXTENT: u32 = 0xffff;
+
 struct State {
     keymap: Option<(u32, OwnedFd, u32)>,
     input_for_client: HashMap<EmulationHandle, VirtualInput>,
@@ -109,6 +113,8 @@ impl State {
             pointer,
             keyboard,
             modifiers: Arc::new(Mutex::new(XMods::empty())),
+            grouped: false,
+            pending_frame: false,
         };
 
         self.input_for_client.insert(client, vinput);
@@ -129,7 +135,7 @@ impl Emulation for WlrootsEmulation {
         event: Event,
         handle: EmulationHandle,
     ) -> Result<(), EmulationError> {
-        if let Some(virtual_input) = self.state.input_for_client.get(&handle) {
+        if let Some(virtual_input) = self.state.input_for_client.get_mut(&handle) {
             if self.last_flush_failed {
                 match self.queue.flush() {
                     Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
@@ -138,7 +144,7 @@ impl Emulation for WlrootsEmulation {
                          * will overwhelm the output buffer and leave the
                          * wayland connection in a broken state
                          */
-                        log::warn!("can't keep up, discarding event: ({handle}) - {event:?}");
+                        log::warn!("can't keep up, discarding event: ({handle}) - {event}");
                         return Ok(());
                     }
                     _ => {}
@@ -146,11 +152,11 @@ impl Emulation for WlrootsEmulation {
             }
             virtual_input
                 .consume_event(event)
-                .unwrap_or_else(|_| panic!("failed to convert event: {event:?}"));
+                .unwrap_or_else(|_| panic!("failed to convert event: {event}"));
             match self.queue.flush() {
                 Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
                     self.last_flush_failed = true;
-                    log::warn!("can't keep up, discarding event: ({handle}) - {event:?}");
+                    log::warn!("can't keep up, discarding event: ({handle}) - {event}");
                 }
                 Err(WaylandError::Protocol(e)) => panic!("wayland protocol violation: {e}"),
                 Ok(()) => self.last_flush_failed = false,
@@ -160,6 +166,20 @@ impl Emulation for WlrootsEmulation {
         Ok(())
     }
 
+    async fn frame(&mut self, handle: EmulationHandle) -> Result<(), EmulationError> {
+        if let Some(virtual_input) = self.state.input_for_client.get_mut(&handle) {
+            if !virtual_input.grouped {
+                log::debug!("grouping button events of {handle} into frames");
+                virtual_input.grouped = true;
+            }
+            if std::mem::take(&mut virtual_input.pending_frame) {
+                virtual_input.pointer.frame();
+                self.queue.flush()?;
+            }
+        }
+        Ok(())
+    }
+
     async fn create(&mut self, handle: EmulationHandle) {
         self.state.add_client(handle);
         if let Err(e) = self.queue.flush() {
@@ -175,16 +195,25 @@ impl Emulation for WlrootsEmulation {
     async fn terminate(&mut self) {
         /* nothing to do */
     }
+
+    fn kinetic_scroll(&self) -> bool {
+        true
+    }
 }
 
 struct VirtualInput {
     pointer: Vp,
     keyboard: Vk,
     modifiers: Arc<Mutex<XMods>>,
+    /// the client sends frame markers: button events are framed
+    /// on [`Emulation::frame`] so chorded buttons are simultaneous
+    grouped: bool,
+    /// button events awaiting the end of the current frame
+    pending_frame: bool,
 }
 
 impl VirtualInput {
-    fn consume_event(&self, event: Event) -> Result<(), ()> {
+    fn consume_event(&mut self, event: Event) -> Result<(), ()> {
         let now: u32 = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
@@ -194,6 +223,16 @@ impl VirtualInput {
             Event::Pointer(e) => {
                 match e {
                     PointerEvent::Motion { time, dx, dy } => self.pointer.motion(time, dx, dy),
+                    PointerEvent::MotionAbsolute { time, x, y } => {
+                        let extent = ABSOLUTE_EXTENT as f64;
+                        self.pointer.motion_absolute(
+                            time,
+                            (x * extent).round() as u32,
+                            (y * extent).round() as u32,
+                            ABSOLUTE_EXTENT,
+                            ABSOLUTE_EXTENT,
+                        )
+                    }
                     PointerEvent::Button {
                         time,
                         button,
@@ -201,12 +240,30 @@ impl VirtualInput {
                     } => {
                         let state: ButtonState = state.try_into()?;
                         self.pointer.button(time, button, state);
+                        if self.grouped {
+                            self.pending_frame = true;
+                            return Ok(());
+                        }
                     }
-                    PointerEvent::Axis { time, axis, value } => {
+                    PointerEvent::Axis {
+                        time,
+                        axis,
+                        value,
+                        source,
+                    } => {
                         let axis: Axis = (axis as u32).try_into()?;
+                        self.pointer.axis_source(to_axis_source(source));
                         self.pointer.axis(time, axis, value);
                         self.pointer.frame();
                     }
+                    PointerEvent::ScrollStop { time, axis }
+                    | PointerEvent::ScrollCancel { time, axis } => {
+                        // wl_pointer has no notion of cancelling
+                        let axis: Axis = (axis as u32).try_into()?;
+                        self.pointer.axis_source(AxisSource::Finger);
+                        self.pointer.axis_stop(time, axis);
+                        self.pointer.frame();
+                    }
                     PointerEvent::AxisDiscrete120 { axis, value } => {
                         let axis: Axis = (axis as u32).try_into()?;
                         self.pointer
@@ -215,6 +272,7 @@ impl VirtualInput {
                     }
                 }
                 self.pointer.frame();
+                self.pending_frame = false;
             }
             Event::Keyboard(e) => match e {
                 KeyboardEvent::Key { time, key, state } => {
@@ -372,3 +430,12 @@ impl XMods {
         *self & (XMods::ShiftMask | XMods::ControlMask | XMods::Mod1Mask | XMods::Mod4Mask)
     }
 }
+
+fn to_axis_source(source: ScrollSource) -> AxisSource {
+    match source {
+        ScrollSource::Finger => AxisSource::Finger,
+        ScrollSource::Wheel => AxisSource::Wheel,
+        ScrollSource::Continuous => AxisSource::Continuous,
+        ScrollSource::WheelTilt => AxisSource::WheelTilt,
+    }
+}
@@ -38,6 +38,18 @@ impl X11Emulation {
         }
     }
 
+    /// move the pointer to the given fraction of the default screen
+    fn absolute_motion(&self, x: f64, y: f64) {
+        unsafe {
+            let screen = xlib::XDefaultScreen(self.display);
+            let width = xlib::XDisplayWidth(self.display, screen);
+            let height = xlib::XDisplayHeight(self.display, screen);
+            let x = (x * (width - 1) as f64).round() as i32;
+            let y = (y * (height - 1) as f64).round() as i32;
+            xtest::XTestFakeMotionEvent(self.display, screen, x, y, 0);
+        }
+    }
+
     fn emulate_mouse_button(&self, button: u32, state: u32) {
         unsafe {
             let x11_button = match button {
@@ -106,6 +118,9 @@ impl Emulation for X11Emulation {
                 PointerEvent::Motion { time: _, dx, dy } => {
                     self.relative_motion(dx as i32, dy as i32);
                 }
+                PointerEvent::MotionAbsolute { time: _, x, y } => {
+                    self.absolute_motion(x, y);
+                }
                 PointerEvent::Button {
                     time: _,
                     button,
@@ -117,12 +132,14 @@ impl Emulation for X11Emulation {
                     time: _,
                     axis,
                     value,
+                    source: _,
                 } => {
                     self.emulate_scroll(axis, value);
                 }
                 PointerEvent::AxisDiscrete120 { axis, value } => {
                     self.emulate_scroll(axis, value as f64);
                 }
+                PointerEvent::ScrollStop { .. } | PointerEvent::ScrollCancel { .. } => {}
             },
             Event::Keyboard(KeyboardEvent::Key {
                 time: _,
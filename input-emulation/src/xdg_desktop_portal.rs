@@ -3,6 +3,7 @@ use ashpd::{
         remote_desktop::{Axis, DeviceType, KeyState, RemoteDesktop},
         PersistMode, Session,
     },
+    enumflags2::BitFlags,
     zbus::AsyncDrop,
 };
 use async_trait::async_trait;
@@ -15,11 +16,13 @@ use input_event::{
 
 use crate::error::EmulationError;
 
-use super::{error::XdpEmulationCreationError, Emulation, EmulationHandle};
+use super::{error::XdpEmulationCreationError, portal, Emulation, EmulationHandle};
 
 pub(crate) struct DesktopPortalEmulation<'a> {
     proxy: RemoteDesktop<'a>,
     session: Session<'a, RemoteDesktop<'a>>,
+    /// device types the user granted access to
+    granted: BitFlags<DeviceType>,
 }
 
 impl<'a> DesktopPortalEmulation<'a> {
@@ -32,22 +35,23 @@ impl<'a> DesktopPortalEmulation<'a> {
         let session = proxy.create_session().await?;
 
         log::debug!("selecting devices ...");
+        let requested = DeviceType::Keyboard | DeviceType::Pointer;
         proxy
-            .select_devices(
-                &session,
-                DeviceType::Keyboard | DeviceType::Pointer,
-                None,
-                PersistMode::ExplicitlyRevoked,
-            )
+            .select_devices(&session, requested, None, PersistMode::ExplicitlyRevoked)
             .await?;
 
         log::info!("requesting permission for input emulation");
-        let _devices = proxy.start(&session, None).await?.response()?;
+        let granted = proxy.start(&session, None).await?.response()?.devices();
+        portal::log_granted(requested, granted);
 
         log::debug!("started session");
         let session = session;
 
-        Ok(Self { proxy, session })
+        Ok(Self {
+            proxy,
+            session,
+            granted,
+        })
     }
 }
 
@@ -58,6 +62,14 @@ impl Emulation for DesktopPortalEmulation<'_> {
         event: input_event::Event,
         _client: EmulationHandle,
     ) -> Result<(), EmulationError> {
+        let device_type = match event {
+            Pointer(_) => DeviceType::Pointer,
+            Keyboard(_) => DeviceType::Keyboard,
+        };
+        if !self.granted.contains(device_type) {
+            log::trace!("remote desktop portal: no access to {device_type:?}, ignoring {event}");
+            return Ok(());
+        }
         match event {
             Pointer(p) => match p {
                 PointerEvent::Motion { time: _, dx, dy } => {
@@ -65,6 +77,10 @@ impl Emulation for DesktopPortalEmulation<'_> {
                         .notify_pointer_motion(&self.session, dx, dy)
                         .await?;
                 }
+                PointerEvent::MotionAbsolute { .. } => {
+                    // requires a screencast stream to position the pointer in
+                    log::debug!("remote desktop portal: ignoring absolute motion");
+                }
                 PointerEvent::Button {
                     time: _,
                     button,
@@ -91,6 +107,7 @@ impl Emulation for DesktopPortalEmulation<'_> {
                     time: _,
                     axis,
                     value,
+                    source: _,
                 } => {
                     let axis = match axis {
                         0 => Axis::Vertical,
@@ -104,6 +121,12 @@ impl Emulation for DesktopPortalEmulation<'_> {
                         .notify_pointer_axis(&self.session, dx, dy, true)
                         .await?;
                 }
+                PointerEvent::ScrollStop { .. } | PointerEvent::ScrollCancel { .. } => {
+                    // the portal only knows about finished scrolling
+                    self.proxy
+                        .notify_pointer_axis(&self.session, 0., 0., true)
+                        .await?;
+                }
             },
             Keyboard(k) => {
                 match k {
@@ -131,6 +154,11 @@ impl Emulation for DesktopPortalEmulation<'_> {
 
     async fn create(&mut self, _client: EmulationHandle) {}
     async fn destroy(&mut self, _client: EmulationHandle) {}
+
+    fn granted_devices(&self) -> Option<Vec<&'static str>> {
+        Some(portal::device_names(self.granted))
+    }
+
     async fn terminate(&mut self) {
         if let Err(e) = self.session.close().await {
             log::warn!("session.close(): {e}");
@@ -139,6 +167,10 @@ impl Emulation for DesktopPortalEmulation<'_> {
             log::warn!("session.receive_closed(): {e}");
         };
     }
+
+    fn absolute_motion(&self) -> bool {
+        false
+    }
 }
 
 impl AsyncDrop for DesktopPortalEmulation<'_> {
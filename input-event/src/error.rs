@@ -1 +1,18 @@
+use thiserror::Error;
 
+/// an event with a value outside of its valid range
+#[derive(Debug, Error, Clone, Copy, PartialEq)]
+pub enum InvalidEventError {
+    /// scroll axis other than vertical (0) or horizontal (1)
+    #[error("invalid scroll axis: `{0}`")]
+    Axis(u8),
+    /// button state other than released (0) or pressed (1)
+    #[error("invalid button state: `{0}`")]
+    ButtonState(u32),
+    /// key state other than released (0) or pressed (1)
+    #[error("invalid key state: `{0}`")]
+    KeyState(u8),
+    /// absolute position outside of `0.0..=1.0` (or NaN)
+    #[error("invalid absolute position: `({0}, {1})`")]
+    AbsolutePosition(f64, f64),
+}
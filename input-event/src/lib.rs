@@ -1,11 +1,31 @@
-use std::fmt::{self, Display};
+use error::InvalidEventError;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 pub mod error;
 pub mod scancode;
+pub mod text;
 
 #[cfg(all(unix, feature = "libei", not(target_os = "macos")))]
 mod libei;
 
+/// whether key codes are shown when displaying a [`KeyboardEvent`]
+static LOG_KEY_DATA: AtomicBool = AtomicBool::new(false);
+
+/// Show key codes when displaying [`KeyboardEvent`]s.
+/// They are redacted by default so logs do not reveal what was typed.
+pub fn set_log_key_data(enabled: bool) {
+    LOG_KEY_DATA.store(enabled, Ordering::Relaxed);
+}
+
+/// whether key codes may be logged, see [`set_log_key_data`]
+pub fn log_key_data() -> bool {
+    LOG_KEY_DATA.load(Ordering::Relaxed)
+}
+
 // FIXME
 pub const BTN_LEFT: u32 = 0x110;
 pub const BTN_RIGHT: u32 = 0x111;
@@ -13,16 +33,61 @@ pub const BTN_MIDDLE: u32 = 0x112;
 pub const BTN_BACK: u32 = 0x113;
 pub const BTN_FORWARD: u32 = 0x114;
 
+/// source of a scroll event
+/// (see `wl_pointer.axis_source` for the semantics and values).
+/// Older versions not sending the source send a zero byte: `Wheel`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum ScrollSource {
+    /// (stepped) scroll wheel
+    #[default]
+    Wheel = 0,
+    /// finger on a touch surface (kinetic scrolling)
+    Finger = 1,
+    /// continuous coordinate space, e.g. button + pointer motion
+    Continuous = 2,
+    /// sideways tilt of a scroll wheel
+    WheelTilt = 3,
+}
+
+impl Display for ScrollSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            ScrollSource::Finger => "finger",
+            ScrollSource::Wheel => "wheel",
+            ScrollSource::Continuous => "continuous",
+            ScrollSource::WheelTilt => "wheel-tilt",
+        };
+        write!(f, "{source}")
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum PointerEvent {
     /// relative motion event
     Motion { time: u32, dx: f64, dy: f64 },
+    /// absolute position, e.g. of a drawing tablet, in the range
+    /// `0.0..=1.0` of the area of the device reporting it
+    MotionAbsolute { time: u32, x: f64, y: f64 },
     /// mouse button event
     Button { time: u32, button: u32, state: u32 },
     /// axis event, scroll event for touchpads
-    Axis { time: u32, axis: u8, value: f64 },
-    /// discrete axis event, scroll event for mice - 120 = one scroll tick
+    Axis {
+        time: u32,
+        axis: u8,
+        value: f64,
+        source: ScrollSource,
+    },
+    /// discrete axis event, scroll event for mice - 120 = one scroll tick.
+    /// High-resolution wheels send fractions of 120 per event.
     AxisDiscrete120 { axis: u8, value: i32 },
+    /// end of a finger scroll sequence on the given axis,
+    /// e.g. the fingers were lifted from the touchpad
+    ScrollStop { time: u32, axis: u8 },
+    /// a finger scroll sequence on the given axis was aborted
+    /// (e.g. turned into a gesture) and should not cause kinetic scrolling.
+    /// Treated as a scroll stop where cancelling is not supported.
+    ScrollCancel { time: u32, axis: u8 },
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -46,10 +111,39 @@ pub enum Event {
     Keyboard(KeyboardEvent),
 }
 
+impl Event {
+    /// check that axes and button / key states are in range
+    pub fn validate(&self) -> Result<(), InvalidEventError> {
+        match *self {
+            Event::Pointer(PointerEvent::Button { state, .. }) if state > 1 => {
+                Err(InvalidEventError::ButtonState(state))
+            }
+            Event::Pointer(
+                PointerEvent::Axis { axis, .. }
+                | PointerEvent::AxisDiscrete120 { axis, .. }
+                | PointerEvent::ScrollStop { axis, .. }
+                | PointerEvent::ScrollCancel { axis, .. },
+            ) if axis > 1 => Err(InvalidEventError::Axis(axis)),
+            Event::Keyboard(KeyboardEvent::Key { state, .. }) if state > 1 => {
+                Err(InvalidEventError::KeyState(state))
+            }
+            Event::Pointer(PointerEvent::MotionAbsolute { x, y, .. })
+                if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) =>
+            {
+                Err(InvalidEventError::AbsolutePosition(x, y))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Display for PointerEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PointerEvent::Motion { time: _, dx, dy } => write!(f, "motion({dx},{dy})"),
+            PointerEvent::MotionAbsolute { time: _, x, y } => {
+                write!(f, "motion-absolute({x},{y})")
+            }
             PointerEvent::Button {
                 time: _,
                 button,
@@ -73,10 +167,13 @@ impl Display for PointerEvent {
                 time: _,
                 axis,
                 value,
-            } => write!(f, "scroll({axis}, {value})"),
+                source,
+            } => write!(f, "scroll({axis}, {value}, {source})"),
             PointerEvent::AxisDiscrete120 { axis, value } => {
                 write!(f, "scroll-120 ({axis}, {value})")
             }
+            PointerEvent::ScrollStop { time: _, axis } => write!(f, "scroll-stop({axis})"),
+            PointerEvent::ScrollCancel { time: _, axis } => write!(f, "scroll-cancel({axis})"),
         }
     }
 }
@@ -90,7 +187,9 @@ impl Display for KeyboardEvent {
                 state,
             } => {
                 let scan = scancode::Linux::try_from(*key);
-                if let Ok(scan) = scan {
+                if !log_key_data() {
+                    write!(f, "key(<redacted>, {state})")
+                } else if let Ok(scan) = scan {
                     write!(f, "key({scan:?}, {state})")
                 } else {
                     write!(f, "key({key}, {state})")
@@ -3,7 +3,7 @@ use reis::{
     event::EiEvent,
 };
 
-use crate::{Event, KeyboardEvent, PointerEvent};
+use crate::{Event, KeyboardEvent, PointerEvent, ScrollSource};
 
 impl Event {
     pub fn from_ei_event(ei_event: EiEvent) -> impl Iterator<Item = Self> {
@@ -73,7 +73,29 @@ fn to_input_events(ei_event: EiEvent) -> Events {
             };
             Events::One(Event::Pointer(motion_event))
         }
-        EiEvent::PointerMotionAbsolute(_) => Events::None,
+        EiEvent::PointerMotionAbsolute(motion) => {
+            // normalize to the area covered by the regions of the device
+            let regions = motion.device.regions();
+            let (Some(x_min), Some(y_min), Some(x_max), Some(y_max)) = (
+                regions.iter().map(|r| r.x).min(),
+                regions.iter().map(|r| r.y).min(),
+                regions.iter().map(|r| r.x + r.width).max(),
+                regions.iter().map(|r| r.y + r.height).max(),
+            ) else {
+                return Events::None;
+            };
+            let (x_min, y_min) = (x_min as f64, y_min as f64);
+            let (width, height) = (x_max as f64 - x_min, y_max as f64 - y_min);
+            if width <= 0. || height <= 0. {
+                return Events::None;
+            }
+            let motion_event = PointerEvent::MotionAbsolute {
+                time: motion.time as u32,
+                x: ((motion.dx_absolute as f64 - x_min) / width).clamp(0., 1.),
+                y: ((motion.dy_absolute as f64 - y_min) / height).clamp(0., 1.),
+            };
+            Events::One(Event::Pointer(motion_event))
+        }
         EiEvent::Button(button) => {
             let button_event = PointerEvent::Button {
                 time: button.time as u32,
@@ -90,11 +112,13 @@ fn to_input_events(ei_event: EiEvent) -> Events {
                 time: 0,
                 axis: 0,
                 value: delta.dy as f64,
+                source: ScrollSource::Finger,
             });
             let dx = Event::Pointer(PointerEvent::Axis {
                 time: 0,
                 axis: 1,
                 value: delta.dx as f64,
+                source: ScrollSource::Finger,
             });
             if delta.dy != 0. && delta.dx != 0. {
                 Events::Two(dy, dx)
@@ -106,8 +130,26 @@ fn to_input_events(ei_event: EiEvent) -> Events {
                 Events::None
             }
         }
-        EiEvent::ScrollStop(_) => Events::None,   /* TODO */
-        EiEvent::ScrollCancel(_) => Events::None, /* TODO */
+        EiEvent::ScrollStop(stop) => {
+            let dy = Event::Pointer(PointerEvent::ScrollStop { time: 0, axis: 0 });
+            let dx = Event::Pointer(PointerEvent::ScrollStop { time: 0, axis: 1 });
+            match (stop.y, stop.x) {
+                (true, true) => Events::Two(dy, dx),
+                (true, false) => Events::One(dy),
+                (false, true) => Events::One(dx),
+                (false, false) => Events::None,
+            }
+        }
+        EiEvent::ScrollCancel(cancel) => {
+            let dy = Event::Pointer(PointerEvent::ScrollCancel { time: 0, axis: 0 });
+            let dx = Event::Pointer(PointerEvent::ScrollCancel { time: 0, axis: 1 });
+            match (cancel.y, cancel.x) {
+                (true, true) => Events::Two(dy, dx),
+                (true, false) => Events::One(dy),
+                (false, true) => Events::One(dx),
+                (false, false) => Events::None,
+            }
+        }
         EiEvent::ScrollDiscrete(scroll) => {
             let dy = Event::Pointer(PointerEvent::AxisDiscrete120 {
                 axis: 0,
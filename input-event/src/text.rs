@@ -0,0 +1,206 @@
+//! Translation of text into key events.
+//!
+//! Characters are mapped to the keys producing them on a US (QWERTY)
+//! keyboard layout. Targets using a different layout receive the same
+//! keys and may therefore produce different characters.
+
+use crate::{scancode::Linux, KeyboardEvent};
+
+/// key of the US layout producing `c` and whether shift has to be held
+pub fn keystroke(c: char) -> Option<(Linux, bool)> {
+    use Linux::*;
+    let key = match c.to_ascii_lowercase() {
+        'a' => KeyA,
+        'b' => KeyB,
+        'c' => KeyC,
+        'd' => KeyD,
+        'e' => KeyE,
+        'f' => KeyF,
+        'g' => KeyG,
+        'h' => KeyH,
+        'i' => KeyI,
+        'j' => KeyJ,
+        'k' => KeyK,
+        'l' => KeyL,
+        'm' => KeyM,
+        'n' => KeyN,
+        'o' => KeyO,
+        'p' => KeyP,
+        'q' => KeyQ,
+        'r' => KeyR,
+        's' => KeyS,
+        't' => KeyT,
+        'u' => KeyU,
+        'v' => KeyV,
+        'w' => KeyW,
+        'x' => KeyX,
+        'y' => KeyY,
+        'z' => KeyZ,
+        _ => {
+            return shifted(c)
+                .map(|k| (k, true))
+                .or_else(|| unshifted(c).map(|k| (k, false)))
+        }
+    };
+    Some((key, c.is_ascii_uppercase()))
+}
+
+fn unshifted(c: char) -> Option<Linux> {
+    use Linux::*;
+    let key = match c {
+        '1' => Key1,
+        '2' => Key2,
+        '3' => Key3,
+        '4' => Key4,
+        '5' => Key5,
+        '6' => Key6,
+        '7' => Key7,
+        '8' => Key8,
+        '9' => Key9,
+        '0' => Key0,
+        '-' => KeyMinus,
+        '=' => KeyEqual,
+        '[' => KeyLeftbrace,
+        ']' => KeyRightbrace,
+        ';' => KeySemicolon,
+        '\'' => KeyApostrophe,
+        '`' => KeyGrave,
+        '\\' => KeyBackslash,
+        ',' => KeyComma,
+        '.' => KeyDot,
+        '/' => KeySlash,
+        ' ' => KeySpace,
+        '\t' => KeyTab,
+        '\n' => KeyEnter,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn shifted(c: char) -> Option<Linux> {
+    use Linux::*;
+    let key = match c {
+        '!' => Key1,
+        '@' => Key2,
+        '#' => Key3,
+        '$' => Key4,
+        '%' => Key5,
+        '^' => Key6,
+        '&' => Key7,
+        '*' => Key8,
+        '(' => Key9,
+        ')' => Key0,
+        '_' => KeyMinus,
+        '+' => KeyEqual,
+        '{' => KeyLeftbrace,
+        '}' => KeyRightbrace,
+        ':' => KeySemicolon,
+        '"' => KeyApostrophe,
+        '~' => KeyGrave,
+        '|' => KeyBackslash,
+        '<' => KeyComma,
+        '>' => KeyDot,
+        '?' => KeySlash,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Key press and release events typing `text` and the characters
+/// without a key on the US layout, which are skipped.
+pub fn key_events(text: &str) -> (Vec<KeyboardEvent>, Vec<char>) {
+    let key = |key: Linux, state: u8| KeyboardEvent::Key {
+        time: 0,
+        key: key as u32,
+        state,
+    };
+    let mut events = vec![];
+    let mut skipped = vec![];
+    for c in text.chars() {
+        // "\r\n" is a single line break
+        if c == '\r' {
+            continue;
+        }
+        let Some((k, shift)) = keystroke(c) else {
+            skipped.push(c);
+            continue;
+        };
+        if shift {
+            events.push(key(Linux::KeyLeftShift, 1));
+        }
+        events.push(key(k, 1));
+        events.push(key(k, 0));
+        if shift {
+            events.push(key(Linux::KeyLeftShift, 0));
+        }
+    }
+    (events, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(events: &[KeyboardEvent]) -> Vec<(u32, u8)> {
+        events
+            .iter()
+            .map(|e| match *e {
+                KeyboardEvent::Key { key, state, .. } => (key, state),
+                _ => panic!("not a key event: {e:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shift_is_held_for_shifted_characters() {
+        let (events, skipped) = key_events("a!");
+        assert!(skipped.is_empty());
+        assert_eq!(
+            keys(&events),
+            [
+                (Linux::KeyA as u32, 1),
+                (Linux::KeyA as u32, 0),
+                (Linux::KeyLeftShift as u32, 1),
+                (Linux::Key1 as u32, 1),
+                (Linux::Key1 as u32, 0),
+                (Linux::KeyLeftShift as u32, 0),
+            ]
+        );
+        let (events, _) = key_events("A");
+        assert_eq!(
+            keys(&events),
+            [
+                (Linux::KeyLeftShift as u32, 1),
+                (Linux::KeyA as u32, 1),
+                (Linux::KeyA as u32, 0),
+                (Linux::KeyLeftShift as u32, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_is_a_single_line_break() {
+        let (crlf, _) = key_events("\r\n");
+        let (lf, _) = key_events("\n");
+        assert_eq!(keys(&crlf), keys(&lf));
+        assert_eq!(
+            keys(&lf),
+            [(Linux::KeyEnter as u32, 1), (Linux::KeyEnter as u32, 0)]
+        );
+    }
+
+    #[test]
+    fn characters_without_key_are_skipped() {
+        let (events, skipped) = key_events("a\u{e4}\u{20ac}b");
+        assert_eq!(skipped, ['\u{e4}', '\u{20ac}']);
+        assert_eq!(
+            keys(&events),
+            [
+                (Linux::KeyA as u32, 1),
+                (Linux::KeyA as u32, 0),
+                (Linux::KeyB as u32, 1),
+                (Linux::KeyB as u32, 0),
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
 use futures::StreamExt;
 
 use std::{net::IpAddr, time::Duration};
@@ -6,7 +6,7 @@ use thiserror::Error;
 
 use lan_mouse_ipc::{
     connect_async, ClientHandle, ConnectionError, FrontendEvent, FrontendRequest, IpcError,
-    Position,
+    PointerMode, Position, WireFormat,
 };
 
 #[derive(Debug, Error)]
@@ -60,10 +60,27 @@ enum CliSubcommand {
     SetPosition { id: ClientHandle, pos: Position },
     /// set ips
     SetIps { id: ClientHandle, ips: Vec<IpAddr> },
+    /// keep forwarding input to a client once entered (true | false)
+    SetAlwaysForward {
+        id: ClientHandle,
+        #[arg(action = ArgAction::Set)]
+        enabled: bool,
+    },
+    /// set the wire format (auto | legacy | v1)
+    SetWireFormat {
+        id: ClientHandle,
+        format: WireFormat,
+    },
+    /// set the pointer mode (absolute | relative)
+    SetPointerMode { id: ClientHandle, mode: PointerMode },
     /// re-enable capture
     EnableCapture,
     /// re-enable emulation
     EnableEmulation,
+    /// suspend emulation, keeping its session
+    PauseEmulation,
+    /// resume suspended emulation
+    ResumeEmulation,
     /// authorize a public key
     AuthorizeKey {
         description: String,
@@ -71,6 +88,10 @@ enum CliSubcommand {
     },
     /// deauthorize a public key
     RemoveAuthorizedKey { sha256_fingerprint: String },
+    /// write the current configuration (including runtime changes) to the config file
+    SaveConfig,
+    /// type text on a client (characters of the US keyboard layout)
+    TypeText { id: ClientHandle, text: String },
 }
 
 pub async fn run(args: CliArgs) -> Result<(), CliError> {
@@ -89,7 +110,12 @@ async fn execute(cmd: CliSubcommand) -> Result<(), CliError> {
         }) => {
             tx.request(FrontendRequest::Create).await?;
             while let Some(e) = rx.next().await {
-                if let FrontendEvent::Created(handle, _, _) = e? {
+                let e = e?;
+                if let FrontendEvent::ClientLimitReached(max) = e {
+                    eprintln!("can not add client: at most {max} clients are allowed");
+                    break;
+                }
+                if let FrontendEvent::Created(handle, _, _) = e {
                     if let Some(hostname) = hostname {
                         tx.request(FrontendRequest::UpdateHostname(handle, Some(hostname)))
                             .await?;
@@ -120,13 +146,17 @@ async fn execute(cmd: CliSubcommand) -> Result<(), CliError> {
             while let Some(e) = rx.next().await {
                 if let FrontendEvent::Enumerate(clients) = e? {
                     for (handle, config, state) in clients {
+                        let uuid = config.uuid;
                         let host = config.hostname.unwrap_or("unknown".to_owned());
                         let port = config.port;
                         let pos = config.pos;
                         let active = state.active;
+                        let always_forward = config.always_forward;
+                        let wire_format = config.wire_format;
+                        let wire_version = state.wire_version;
                         let ips = state.ips;
                         println!(
-                            "id {handle}: {host}:{port} ({pos}) active: {active}, ips: {ips:?}"
+                            "id {handle} ({uuid}): {host}:{port} ({pos}) active: {active}, always-forward: {always_forward}, wire-format: {wire_format} (peer version {wire_version}), ips: {ips:?}"
                         );
                     }
                     break;
@@ -146,8 +176,24 @@ async fn execute(cmd: CliSubcommand) -> Result<(), CliError> {
         CliSubcommand::SetIps { id, ips } => {
             tx.request(FrontendRequest::UpdateFixIps(id, ips)).await?
         }
+        CliSubcommand::SetAlwaysForward { id, enabled } => {
+            tx.request(FrontendRequest::UpdateAlwaysForward(id, enabled))
+                .await?
+        }
+        CliSubcommand::SetWireFormat { id, format } => {
+            tx.request(FrontendRequest::UpdateWireFormat(id, format))
+                .await?
+        }
+        CliSubcommand::SetPointerMode { id, mode } => {
+            tx.request(FrontendRequest::UpdatePointerMode(id, mode))
+                .await?
+        }
         CliSubcommand::EnableCapture => tx.request(FrontendRequest::EnableCapture).await?,
         CliSubcommand::EnableEmulation => tx.request(FrontendRequest::EnableEmulation).await?,
+        CliSubcommand::PauseEmulation => tx.request(FrontendRequest::PauseEmulation(true)).await?,
+        CliSubcommand::ResumeEmulation => {
+            tx.request(FrontendRequest::PauseEmulation(false)).await?
+        }
         CliSubcommand::AuthorizeKey {
             description,
             sha256_fingerprint,
@@ -162,6 +208,41 @@ async fn execute(cmd: CliSubcommand) -> Result<(), CliError> {
             tx.request(FrontendRequest::RemoveAuthorizedKey(sha256_fingerprint))
                 .await?
         }
+        CliSubcommand::SaveConfig => {
+            tx.request(FrontendRequest::SaveConfig).await?;
+            while let Some(e) = rx.next().await {
+                match e? {
+                    FrontendEvent::ConfigSaved(path) => {
+                        println!("saved config to {}", path.display());
+                        break;
+                    }
+                    FrontendEvent::Error(e) => {
+                        eprintln!("{e}");
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        CliSubcommand::TypeText { id, text } => {
+            tx.request(FrontendRequest::TypeText(id, text)).await?;
+            while let Some(e) = rx.next().await {
+                match e? {
+                    FrontendEvent::TextTyped(handle, skipped) if handle == id => {
+                        if !skipped.is_empty() {
+                            let skipped = skipped.into_iter().collect::<String>();
+                            eprintln!("could not type {skipped:?}: no key on the US layout");
+                        }
+                        break;
+                    }
+                    FrontendEvent::Error(e) => {
+                        eprintln!("{e}");
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
     }
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use glib::Properties;
 use gtk::glib;
@@ -12,6 +12,9 @@ pub struct KeyObject {
     pub description: RefCell<String>,
     #[property(name = "fingerprint", get, set, type = String)]
     pub fingerprint: RefCell<String>,
+    /// duplicate events dropped from the device
+    #[property(name = "duplicates", get, set, type = u64)]
+    pub duplicates: Cell<u64>,
 }
 
 #[glib::object_subclass]
@@ -36,8 +36,15 @@ impl KeyRow {
             .sync_create()
             .build();
 
+        let duplicates_binding = key_object
+            .bind_property("duplicates", self, "tooltip-text")
+            .transform_to(|_, v: u64| Some(format!("{v} duplicate events dropped")))
+            .sync_create()
+            .build();
+
         bindings.push(title_binding);
         bindings.push(subtitle_binding);
+        bindings.push(duplicates_binding);
     }
 
     pub fn unbind(&self) {
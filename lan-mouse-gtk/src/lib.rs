@@ -140,12 +140,46 @@ fn build_ui(app: &Application) {
                         window.update_client_state(handle, state);
                     }
                     FrontendEvent::NoSuchClient(_) => {}
+                    FrontendEvent::ClientLimitReached(max) => window.show_toast(
+                        format!("can not add client: at most {max} clients are allowed").as_str(),
+                    ),
                     FrontendEvent::Error(e) => window.show_toast(e.as_str()),
                     FrontendEvent::Enumerate(clients) => window.update_client_list(clients),
                     FrontendEvent::PortChanged(port, msg) => window.update_port(port, msg),
                     FrontendEvent::CaptureStatus(s) => window.set_capture(s.into()),
                     FrontendEvent::EmulationStatus(s) => window.set_emulation(s.into()),
+                    FrontendEvent::EmulationPaused(paused) => match paused {
+                        true => window.show_toast("input emulation paused"),
+                        false => window.show_toast("input emulation resumed"),
+                    },
+                    FrontendEvent::EmulationMissingDevices(missing) => match missing.is_empty() {
+                        true => window.show_toast("input emulation ready"),
+                        false => window.show_toast(
+                            format!("input emulation: no {} device", missing.join(", ")).as_str(),
+                        ),
+                    },
+                    FrontendEvent::EmulationGrantedDevices(granted) => {
+                        let denied = ["pointer", "keyboard"]
+                            .into_iter()
+                            .filter(|d| !granted.iter().any(|g| g == d))
+                            .collect::<Vec<_>>();
+                        if !denied.is_empty() {
+                            window.show_toast(
+                                format!("input emulation: no access to {}", denied.join(", "))
+                                    .as_str(),
+                            );
+                        }
+                    }
+                    FrontendEvent::EmulationFailed(e) => {
+                        window.show_toast(format!("input emulation failed: {e}").as_str());
+                    }
+                    FrontendEvent::CaptureClosed(e) => {
+                        window.show_toast(format!("input capture closed: {e}").as_str());
+                    }
                     FrontendEvent::AuthorizedUpdated(keys) => window.set_authorized_keys(keys),
+                    FrontendEvent::DuplicatesDropped { fingerprint, count } => {
+                        window.set_duplicates_dropped(&fingerprint, count);
+                    }
                     FrontendEvent::PublicKeyFingerprint(fp) => window.set_pk_fp(&fp),
                     FrontendEvent::ConnectionAttempt { fingerprint } => {
                         window.request_authorization(&fingerprint);
@@ -166,6 +200,51 @@ fn build_ui(app: &Application) {
                     FrontendEvent::IncomingDisconnected(addr) => {
                         window.show_toast(format!("{addr} disconnected").as_str());
                     }
+                    FrontendEvent::ReauthRequired(addr) => {
+                        window.show_toast(
+                            format!("session of {addr} expired, re-authentication required")
+                                .as_str(),
+                        );
+                    }
+                    FrontendEvent::ClientEntered(_)
+                    | FrontendEvent::SessionStarted(_)
+                    | FrontendEvent::SessionEnded(_)
+                    | FrontendEvent::TextTyped(..) => {}
+                    FrontendEvent::ConfigSaved(path) => {
+                        window.show_toast(format!("saved config to {}", path.display()).as_str());
+                    }
+                    FrontendEvent::Reassociated(handle, addr) => {
+                        window.show_toast(format!("client {handle} reappeared at {addr}").as_str());
+                    }
+                    FrontendEvent::Failover(handle, Some(fallback)) => {
+                        window.show_toast(
+                            format!("client {handle} is unreachable, using client {fallback}")
+                                .as_str(),
+                        );
+                    }
+                    FrontendEvent::Failover(handle, None) => {
+                        window.show_toast(format!("switched back to client {handle}").as_str());
+                    }
+                    FrontendEvent::Recovered(handle) => {
+                        window.show_toast(format!("client {handle} is reachable again").as_str());
+                    }
+                    FrontendEvent::SwitchPending(handle, countdown) => {
+                        window.show_toast(
+                            format!(
+                                "entering client {handle} in {countdown}ms - move back to cancel"
+                            )
+                            .as_str(),
+                        );
+                    }
+                    FrontendEvent::SwitchCancelled(handle) => {
+                        window.show_toast(format!("switch to client {handle} cancelled").as_str());
+                    }
+                    FrontendEvent::IncompatibleVersion(handle, mismatch) => {
+                        window.show_toast(
+                            format!("client {handle} is incompatible ({mismatch}), please upgrade")
+                                .as_str(),
+                        );
+                    }
                 }
             }
         }
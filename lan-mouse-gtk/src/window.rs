@@ -469,6 +469,15 @@ impl Window {
         self.update_auth_placeholder_visibility();
     }
 
+    pub(super) fn set_duplicates_dropped(&self, fingerprint: &str, count: u64) {
+        let key_obj = (0..self.authorized().n_items())
+            .filter_map(|idx| self.authorized_by_idx(idx))
+            .find(|k| k.get_fingerprint() == fingerprint);
+        if let Some(key_obj) = key_obj {
+            key_obj.set_duplicates(count);
+        }
+    }
+
     pub(super) fn set_pk_fp(&self, fingerprint: &str) {
         self.imp().fingerprint_row.set_subtitle(fingerprint);
     }
@@ -4,15 +4,13 @@ use std::{
     fmt::Display,
     io,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     str::FromStr,
 };
 use thiserror::Error;
 
 #[cfg(unix)]
-use std::{
-    env,
-    path::{Path, PathBuf},
-};
+use std::{env, path::Path};
 
 use serde::{Deserialize, Serialize};
 
@@ -128,8 +126,224 @@ impl TryFrom<&str> for Position {
     }
 }
 
+/// address family of an ip address
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// address family of the given address
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+}
+
+impl Display for AddressFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "ipv4"),
+            AddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+/// wire format used to encode events sent to a client
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// format understood by older lan-mouse versions,
+    /// until the client reports to understand a newer format
+    #[default]
+    Auto,
+    /// format understood by older lan-mouse versions
+    Legacy,
+    /// versioned format
+    V1,
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid wire format: {format}")]
+pub struct WireFormatParseError {
+    format: String,
+}
+
+impl FromStr for WireFormat {
+    type Err = WireFormatParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "legacy" => Ok(Self::Legacy),
+            "v1" => Ok(Self::V1),
+            _ => Err(WireFormatParseError { format: s.into() }),
+        }
+    }
+}
+
+impl Display for WireFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireFormat::Auto => write!(f, "auto"),
+            WireFormat::Legacy => write!(f, "legacy"),
+            WireFormat::V1 => write!(f, "v1"),
+        }
+    }
+}
+
+/// how pointer positions are forwarded to a client
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointerMode {
+    /// absolute positions (e.g. of drawing tablets) are forwarded
+    /// as such, for drawing applications
+    #[default]
+    Absolute,
+    /// absolute positions are forwarded as relative motion, e.g. for
+    /// games that warp or lock the pointer
+    Relative,
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid pointer mode: {mode}")]
+pub struct PointerModeParseError {
+    mode: String,
+}
+
+impl FromStr for PointerMode {
+    type Err = PointerModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "absolute" => Ok(Self::Absolute),
+            "relative" => Ok(Self::Relative),
+            _ => Err(PointerModeParseError { mode: s.into() }),
+        }
+    }
+}
+
+impl Display for PointerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PointerMode::Absolute => write!(f, "absolute"),
+            PointerMode::Relative => write!(f, "relative"),
+        }
+    }
+}
+
+/// whether input returns to a client once it is reachable again
+/// after it was forwarded to its fallback
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackRecovery {
+    /// the client is entered again the next time its edge is crossed
+    #[default]
+    SwitchBack,
+    /// keep forwarding to the fallback until it becomes unreachable itself
+    Stay,
+}
+
+impl Display for FallbackRecovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FallbackRecovery::SwitchBack => write!(f, "switch_back"),
+            FallbackRecovery::Stay => write!(f, "stay"),
+        }
+    }
+}
+
+/// rotation of the display of a client relative to this device.
+/// Relative motion sent to the client is rotated clockwise by this angle.
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum Orientation {
+    #[default]
+    Normal,
+    Rotated90,
+    Rotated180,
+    Rotated270,
+}
+
+impl Orientation {
+    /// rotate the relative motion `(dx, dy)` (y pointing down)
+    pub fn rotate(self, dx: f64, dy: f64) -> (f64, f64) {
+        match self {
+            Orientation::Normal => (dx, dy),
+            Orientation::Rotated90 => (-dy, dx),
+            Orientation::Rotated180 => (-dx, -dy),
+            Orientation::Rotated270 => (dy, -dx),
+        }
+    }
+}
+
+/// Stage of the pipeline transforming the input sent to a client.
+/// Stages are applied in the configured order, after
+/// the client's orientation and button map.
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Transform {
+    /// scale relative motion along each axis (in percent)
+    Scale { x: u32, y: u32 },
+    /// invert relative motion along the given axes
+    Invert { x: bool, y: bool },
+    /// rotate relative motion clockwise
+    Rotate { degrees: Orientation },
+    /// send button `from` (linux button code) as button `to`
+    Remap { from: u32, to: u32 },
+    /// shorten relative motion to at most `max` pixels per event
+    Clamp { max: u32 },
+    /// discard relative motion shorter than `min` pixels
+    DeadZone { min: u32 },
+}
+
+#[derive(Debug, Error)]
+#[error("not a valid orientation: {degrees} (expected 0, 90, 180 or 270)")]
+pub struct OrientationError {
+    degrees: u32,
+}
+
+impl TryFrom<u32> for Orientation {
+    type Error = OrientationError;
+
+    fn try_from(degrees: u32) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Self::Normal),
+            90 => Ok(Self::Rotated90),
+            180 => Ok(Self::Rotated180),
+            270 => Ok(Self::Rotated270),
+            _ => Err(OrientationError { degrees }),
+        }
+    }
+}
+
+impl From<Orientation> for u32 {
+    fn from(orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::Normal => 0,
+            Orientation::Rotated90 => 90,
+            Orientation::Rotated180 => 180,
+            Orientation::Rotated270 => 270,
+        }
+    }
+}
+
+impl Display for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}°", u32::from(*self))
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// identity of this client across restarts, independent of its
+    /// address (see [`generate_uuid`]). Only used to identify the
+    /// configured client, runtime state is kept per [`ClientHandle`].
+    pub uuid: String,
     /// hostname of this client
     pub hostname: Option<String>,
     /// fix ips, determined by the user
@@ -140,22 +354,121 @@ pub struct ClientConfig {
     pub pos: Position,
     /// enter hook
     pub cmd: Option<String>,
+    /// keep forwarding input to this client once entered,
+    /// regardless of the remote cursor leaving its screen
+    pub always_forward: bool,
+    /// wire format used for events sent to this client
+    pub wire_format: WireFormat,
+    /// buttons (linux button codes) for which a quick
+    /// click toggles a sustained press
+    pub click_lock: Vec<u32>,
+    /// increase of the scroll speed in percent per successive
+    /// wheel scroll event in quick succession (0 = no acceleration)
+    pub scroll_acceleration: u32,
+    /// buttons (linux button codes) translated to
+    /// different buttons when sent to this client
+    pub button_map: HashMap<u32, u32>,
+    /// id of the client on a relay hub:
+    /// if set, events are sent to the hub (hostname / ips)
+    /// which forwards them to the client with this id
+    pub relay_target: Option<ClientHandle>,
+    /// address family to connect with first if the client
+    /// is reachable over both ipv4 and ipv6
+    pub preferred_family: Option<AddressFamily>,
+    /// time to live (hop limit on ipv6) of the datagrams sent to this client
+    /// (system default if `None`)
+    pub ttl: Option<u8>,
+    /// DSCP marking (0-63, traffic class on ipv6) of the datagrams sent
+    /// to this client, for QoS prioritization (unmarked if `None`)
+    pub dscp: Option<u8>,
+    /// certificate fingerprint identifying the client independent of its
+    /// address: if it connects from a new address, the address is added
+    pub fingerprint: Option<String>,
+    /// rotation of the display of the client
+    pub orientation: Orientation,
+    /// keys (linux key codes) that are never sent to this client
+    pub blocked_keys: Vec<u32>,
+    /// hostname of the client input is forwarded to
+    /// while this client is unreachable
+    pub fallback: Option<String>,
+    /// behavior once this client is reachable again
+    pub fallback_recovery: FallbackRecovery,
+    /// upper bound in pixels of the motion extrapolated to
+    /// compensate for the latency to the client (0 = disabled)
+    pub motion_prediction: u32,
+    /// whether absolute pointer positions are forwarded
+    pub pointer_mode: PointerMode,
+    /// transforms applied to the input sent to this client, in order
+    pub transforms: Vec<Transform>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
+            uuid: generate_uuid(),
             port: DEFAULT_PORT,
             hostname: Default::default(),
             fix_ips: Default::default(),
             pos: Default::default(),
             cmd: None,
+            always_forward: false,
+            wire_format: Default::default(),
+            click_lock: Default::default(),
+            scroll_acceleration: 0,
+            button_map: Default::default(),
+            relay_target: None,
+            preferred_family: None,
+            ttl: None,
+            dscp: None,
+            fingerprint: None,
+            orientation: Default::default(),
+            blocked_keys: Default::default(),
+            fallback: None,
+            fallback_recovery: Default::default(),
+            motion_prediction: 0,
+            pointer_mode: Default::default(),
+            transforms: Default::default(),
         }
     }
 }
 
 pub type ClientHandle = u64;
 
+/// Random (version 4) UUID, identifying a client independent
+/// of its address and of the handle it is assigned at runtime.
+pub fn generate_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("no source of randomness");
+    format_uuid(bytes, 4)
+}
+
+/// UUID (version 8) derived from `name`, i.e. the same on every start,
+/// for clients that are not written to the config file
+/// (e.g. neighbors in a grid).
+pub fn name_uuid(name: &str) -> String {
+    // 128 bit FNV-1a
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let hash = name
+        .bytes()
+        .fold(OFFSET, |h, b| (h ^ b as u128).wrapping_mul(PRIME));
+    format_uuid(hash.to_be_bytes(), 8)
+}
+
+fn format_uuid(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ClientState {
     /// events should be sent to and received from the client
@@ -176,6 +489,35 @@ pub struct ClientState {
     pub has_pressed_keys: bool,
     /// dns resolving in progress
     pub resolving: bool,
+    /// address the client was last seen connecting from,
+    /// if not among its configured / resolved ips
+    pub reassociated_ip: Option<IpAddr>,
+    /// the client speaks a protocol version this device does not understand
+    pub incompatible: Option<VersionMismatch>,
+    /// estimated offset of the clock of the client to the clock of
+    /// this device in microseconds, if clock synchronization is enabled
+    pub clock_offset: Option<i64>,
+    /// newest wire format version the client reported to understand
+    pub wire_version: u8,
+}
+
+/// wire format versions of a client that can not be communicated with
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VersionMismatch {
+    /// newest version supported by this device
+    pub local: u8,
+    /// version used by the client
+    pub remote: u8,
+}
+
+impl Display for VersionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "local version {}, remote version {}",
+            self.local, self.remote
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -184,6 +526,8 @@ pub enum FrontendEvent {
     Created(ClientHandle, ClientConfig, ClientState),
     /// no such client
     NoSuchClient(ClientHandle),
+    /// no client was created, the maximum number of clients is reached
+    ClientLimitReached(usize),
     /// state changed
     State(ClientHandle, ClientConfig, ClientState),
     /// the client was deleted
@@ -198,6 +542,16 @@ pub enum FrontendEvent {
     CaptureStatus(Status),
     /// emulation status
     EmulationStatus(Status),
+    /// input emulation was suspended (true) or resumed (false)
+    EmulationPaused(bool),
+    /// capabilities the input emulation has no device for (empty if ready)
+    EmulationMissingDevices(Vec<String>),
+    /// device types the user granted the input emulation access to
+    EmulationGrantedDevices(Vec<String>),
+    /// input emulation failed in the background (reason)
+    EmulationFailed(String),
+    /// input capture closed unexpectedly (reason)
+    CaptureClosed(String),
     /// authorized public key fingerprints have been updated
     AuthorizedUpdated(HashMap<String, String>),
     /// public key fingerprint of this device
@@ -215,8 +569,38 @@ pub enum FrontendEvent {
     },
     /// incoming disconnected
     IncomingDisconnected(SocketAddr),
+    /// input is now forwarded to the client
+    ClientEntered(ClientHandle),
+    /// incoming device started forwarding input
+    SessionStarted(SocketAddr),
+    /// incoming device stopped forwarding input
+    SessionEnded(SocketAddr),
+    /// the session of an incoming device expired,
+    /// input is ignored until it authenticated again
+    ReauthRequired(SocketAddr),
+    /// total number of duplicate events dropped from an incoming device
+    DuplicatesDropped { fingerprint: String, count: u64 },
     /// failed connection attempt (approval for fingerprint required)
     ConnectionAttempt { fingerprint: String },
+    /// the client speaks an incompatible protocol version
+    IncompatibleVersion(ClientHandle, VersionMismatch),
+    /// the configuration was written to the given file
+    ConfigSaved(PathBuf),
+    /// the client was recognized by its fingerprint at a new address
+    Reassociated(ClientHandle, SocketAddr),
+    /// input for the client is forwarded to its fallback (`Some`)
+    /// or to the client itself again (`None`)
+    Failover(ClientHandle, Option<ClientHandle>),
+    /// the client, which was replaced by its fallback, is reachable again
+    Recovered(ClientHandle),
+    /// the client is entered once the cursor stays at the edge
+    /// for the given countdown (in milliseconds)
+    SwitchPending(ClientHandle, u64),
+    /// a pending switch to the client was cancelled
+    SwitchCancelled(ClientHandle),
+    /// text was typed on the client, skipping the characters
+    /// without a key on the US keyboard layout
+    TextTyped(ClientHandle, Vec<char>),
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -245,6 +629,8 @@ pub enum FrontendRequest {
     EnableCapture,
     /// request reenabling input emulation
     EnableEmulation,
+    /// suspend (true) or resume (false) input emulation, keeping its session
+    PauseEmulation(bool),
     /// synchronize all state
     Sync,
     /// authorize fingerprint (description, fingerprint)
@@ -253,6 +639,16 @@ pub enum FrontendRequest {
     RemoveAuthorizedKey(String),
     /// change the hook command
     UpdateEnterHook(u64, Option<String>),
+    /// pin / unpin a client to always forward mode
+    UpdateAlwaysForward(ClientHandle, bool),
+    /// change the wire format used for a client
+    UpdateWireFormat(ClientHandle, WireFormat),
+    /// switch a client between absolute and relative pointer mode
+    UpdatePointerMode(ClientHandle, PointerMode),
+    /// write the current configuration to the config file
+    SaveConfig,
+    /// type the given text on a client
+    TypeText(ClientHandle, String),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
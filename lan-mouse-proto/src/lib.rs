@@ -1,4 +1,6 @@
-use input_event::{Event as InputEvent, KeyboardEvent, PointerEvent};
+use input_event::{
+    error::InvalidEventError, Event as InputEvent, KeyboardEvent, PointerEvent, ScrollSource,
+};
 use num_enum::{IntoPrimitive, TryFromPrimitive, TryFromPrimitiveError};
 use paste::paste;
 use std::{
@@ -7,10 +9,55 @@ use std::{
 };
 use thiserror::Error;
 
-/// defines the maximum size an encoded event can take up
+/// defines the maximum size of an event payload
 /// this is currently the pointer motion event
-/// type: u8, time: u32, dx: f64, dy: f64
-pub const MAX_EVENT_SIZE: usize = size_of::<u8>() + size_of::<u32>() + 2 * size_of::<f64>();
+/// time: u32, dx: f64, dy: f64
+const MAX_PAYLOAD_SIZE: usize = size_of::<u32>() + 2 * size_of::<f64>();
+
+/// size of the [`WireFormat::V1`] header
+/// version: u8, type: u8, length: u8
+const V1_HEADER_SIZE: usize = 3 * size_of::<u8>();
+
+/// defines the maximum size an encoded event can take up
+pub const MAX_EVENT_SIZE: usize = V1_HEADER_SIZE + MAX_PAYLOAD_SIZE;
+
+/// marker byte of an event relayed by a hub
+const RELAY_MARKER: u8 = 0xff;
+
+/// size of the relay header
+/// marker: u8, destination: u64
+const RELAY_HEADER_SIZE: usize = size_of::<u8>() + size_of::<u64>();
+
+/// defines the maximum size of an event wrapped in a relay header
+pub const MAX_RELAY_SIZE: usize = RELAY_HEADER_SIZE + MAX_EVENT_SIZE;
+
+/// marker byte of a [`ControlMessage`]
+const CONTROL_MARKER: u8 = 0xfe;
+
+/// size of the [`ControlMessage`] header
+/// marker: u8, type: u8, length: u16
+const CONTROL_HEADER_SIZE: usize = 2 * size_of::<u8>() + size_of::<u16>();
+
+/// defines the maximum size of a [`ControlMessage`] payload
+pub const MAX_CONTROL_PAYLOAD_SIZE: usize = 1024;
+
+/// defines the maximum size an encoded [`ControlMessage`] can take up
+pub const MAX_CONTROL_SIZE: usize = CONTROL_HEADER_SIZE + MAX_CONTROL_PAYLOAD_SIZE;
+
+/// defines the maximum size of any packet sent over a connection
+pub const MAX_PACKET_SIZE: usize = if MAX_CONTROL_SIZE > MAX_RELAY_SIZE {
+    MAX_CONTROL_SIZE
+} else {
+    MAX_RELAY_SIZE
+};
+
+/// set in the version byte of versioned wire formats.
+/// Legacy events start with the event type,
+/// which never has the most significant bit set.
+const VERSION_FLAG: u8 = 0x80;
+
+/// version byte of [`WireFormat::V1`].
+const WIRE_FORMAT_V1: u8 = VERSION_FLAG | 1;
 
 /// error type for protocol violations
 #[derive(Debug, Error)]
@@ -21,6 +68,83 @@ pub enum ProtocolError {
     /// position type does not exist
     #[error("invalid event id: `{0}`")]
     InvalidPosition(#[from] TryFromPrimitiveError<Position>),
+    /// wire format version is not supported
+    #[error("unsupported wire format version: `{0}`")]
+    UnsupportedVersion(u8),
+    /// payload length exceeds the maximum event size
+    #[error("invalid payload length: `{0}`")]
+    InvalidLength(u8),
+    /// event type unknown to this version in a length-prefixed
+    /// wire format, i.e. the event can be skipped safely
+    #[error("unknown event type: `{0}`")]
+    UnknownEventType(u8),
+    /// control message type does not exist
+    #[error("invalid control message id: `{0}`")]
+    InvalidControlType(#[from] TryFromPrimitiveError<ControlType>),
+    /// control message is truncated or exceeds the maximum size
+    #[error("invalid control message length: `{0}`")]
+    InvalidControlLength(usize),
+    /// well-formed input event with out-of-range values
+    #[error("invalid event: `{0}`")]
+    InvalidEvent(#[from] InvalidEventError),
+    /// [`ProtoEvent::Sequenced`] wrapping an event other than a key or button event
+    #[error("event type `{0}` can not be sequenced")]
+    NotSequenceable(u8),
+    /// named action does not exist (e.g. introduced by a newer version)
+    #[error("invalid action id: `{0}`")]
+    InvalidAction(#[from] TryFromPrimitiveError<Action>),
+}
+
+/// encoding of a [`ProtoEvent`] on the wire.
+///
+/// Independent of the format, multi-byte fields are encoded in
+/// network byte order (big-endian) and `f64` fields as the big-endian
+/// bytes of their IEEE 754 representation, so devices of different
+/// architectures can talk to each other.
+///
+/// [`WireFormat::Legacy`] is understood by every version and therefore
+/// used until the peer reported a newer version in its [`ProtoEvent::Pong`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WireFormat {
+    /// unversioned format used by older lan-mouse versions:
+    /// type: u8, payload
+    #[default]
+    Legacy,
+    /// versioned format:
+    /// version: u8, type: u8, length: u8, payload
+    V1,
+}
+
+impl WireFormat {
+    /// newest wire format supported by this version
+    pub const NEWEST: WireFormat = WireFormat::V1;
+
+    /// newest wire format understood by both this device and a peer
+    /// reporting `version` as its newest version
+    pub fn negotiate(version: u8) -> Self {
+        if version >= WireFormat::V1.version() {
+            WireFormat::V1
+        } else {
+            WireFormat::Legacy
+        }
+    }
+
+    /// version number of the wire format, 0 for [`WireFormat::Legacy`]
+    pub fn version(self) -> u8 {
+        match self {
+            WireFormat::Legacy => 0,
+            WireFormat::V1 => WIRE_FORMAT_V1 & !VERSION_FLAG,
+        }
+    }
+}
+
+impl Display for WireFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireFormat::Legacy => write!(f, "legacy"),
+            WireFormat::V1 => write!(f, "v1"),
+        }
+    }
 }
 
 /// Position of a client
@@ -45,6 +169,57 @@ impl Display for Position {
     }
 }
 
+/// Named action performed by the receiving device in its own way, e.g. by
+/// a command, instead of forwarding a key chord it may interpret differently.
+/// New actions are appended, older versions ignore actions they do not know.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum Action {
+    LockScreen,
+    WorkspaceLeft,
+    WorkspaceRight,
+    WorkspaceUp,
+    WorkspaceDown,
+    ShowDesktop,
+    Overview,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::LockScreen,
+        Action::WorkspaceLeft,
+        Action::WorkspaceRight,
+        Action::WorkspaceUp,
+        Action::WorkspaceDown,
+        Action::ShowDesktop,
+        Action::Overview,
+    ];
+
+    /// name of the action, e.g. `lock-screen`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::LockScreen => "lock-screen",
+            Action::WorkspaceLeft => "workspace-left",
+            Action::WorkspaceRight => "workspace-right",
+            Action::WorkspaceUp => "workspace-up",
+            Action::WorkspaceDown => "workspace-down",
+            Action::ShowDesktop => "show-desktop",
+            Action::Overview => "overview",
+        }
+    }
+
+    /// the action with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 /// main lan-mouse protocol event type
 #[derive(Clone, Copy, Debug)]
 pub enum ProtoEvent {
@@ -61,8 +236,20 @@ pub enum ProtoEvent {
     /// Ping event for tracking unresponsive clients.
     /// A client has to respond with [`ProtoEvent::Pong`].
     Ping,
-    /// Response to [`ProtoEvent::Ping`], true if emulation is enabled / available
-    Pong(bool),
+    /// Response to [`ProtoEvent::Ping`], true if emulation is enabled / available,
+    /// followed by the newest [`WireFormat`] version understood by the client.
+    /// Versions without negotiation do not send the version, which decodes as 0.
+    Pong(bool, u8),
+    /// marks the end of a group of input events that belong together
+    /// and should be applied atomically (e.g. motion + button)
+    Frame,
+    /// the following input events originate from the seat
+    /// with the given id on the sending side
+    Origin(u32),
+    /// Key or button event that may be sent multiple times to survive
+    /// packet loss. Copies with a sequence number that was seen already
+    /// are discarded by the receiver. See [`ProtoEvent::sequenced`].
+    Sequenced(u32, InputEvent),
 }
 
 impl Display for ProtoEvent {
@@ -73,13 +260,16 @@ impl Display for ProtoEvent {
             ProtoEvent::Ack(s) => write!(f, "Ack({s})"),
             ProtoEvent::Input(e) => write!(f, "{e}"),
             ProtoEvent::Ping => write!(f, "ping"),
-            ProtoEvent::Pong(alive) => {
+            ProtoEvent::Pong(alive, version) => {
                 write!(
                     f,
-                    "pong: {}",
+                    "pong: {} (wire format version {version})",
                     if *alive { "alive" } else { "not available" }
                 )
             }
+            ProtoEvent::Frame => write!(f, "frame"),
+            ProtoEvent::Origin(id) => write!(f, "origin({id})"),
+            ProtoEvent::Sequenced(seq, e) => write!(f, "{e} (#{seq})"),
         }
     }
 }
@@ -98,17 +288,53 @@ pub enum EventType {
     Enter,
     Leave,
     Ack,
+    Frame,
+    PointerMotionAbsolute,
+    Origin,
+    PointerScrollCancel,
+    Sequenced,
+    PointerScrollStop,
 }
 
 impl ProtoEvent {
+    /// Wrap an event in a [`ProtoEvent::Sequenced`].
+    /// Only key and button events can be sequenced.
+    pub fn sequenced(seq: u32, event: InputEvent) -> Option<Self> {
+        match event {
+            InputEvent::Keyboard(KeyboardEvent::Key { .. })
+            | InputEvent::Pointer(PointerEvent::Button { .. }) => Some(Self::Sequenced(seq, event)),
+            _ => None,
+        }
+    }
+
+    /// Whether a peer using `format` understands the event.
+    /// Older versions only speaking [`WireFormat::Legacy`] drop the
+    /// connection on event types introduced after it.
+    pub fn is_supported(&self, format: WireFormat) -> bool {
+        match format {
+            WireFormat::Legacy => !matches!(
+                self.event_type(),
+                EventType::Frame
+                    | EventType::PointerMotionAbsolute
+                    | EventType::Origin
+                    | EventType::PointerScrollStop
+                    | EventType::PointerScrollCancel
+            ),
+            WireFormat::V1 => true,
+        }
+    }
+
     fn event_type(&self) -> EventType {
         match self {
             ProtoEvent::Input(e) => match e {
                 InputEvent::Pointer(p) => match p {
                     PointerEvent::Motion { .. } => EventType::PointerMotion,
+                    PointerEvent::MotionAbsolute { .. } => EventType::PointerMotionAbsolute,
                     PointerEvent::Button { .. } => EventType::PointerButton,
                     PointerEvent::Axis { .. } => EventType::PointerAxis,
                     PointerEvent::AxisDiscrete120 { .. } => EventType::PointerAxisValue120,
+                    PointerEvent::ScrollStop { .. } => EventType::PointerScrollStop,
+                    PointerEvent::ScrollCancel { .. } => EventType::PointerScrollCancel,
                 },
                 InputEvent::Keyboard(k) => match k {
                     KeyboardEvent::Key { .. } => EventType::KeyboardKey,
@@ -116,134 +342,463 @@ impl ProtoEvent {
                 },
             },
             ProtoEvent::Ping => EventType::Ping,
-            ProtoEvent::Pong(_) => EventType::Pong,
+            ProtoEvent::Pong(..) => EventType::Pong,
             ProtoEvent::Enter(_) => EventType::Enter,
             ProtoEvent::Leave(_) => EventType::Leave,
             ProtoEvent::Ack(_) => EventType::Ack,
+            ProtoEvent::Frame => EventType::Frame,
+            ProtoEvent::Origin(_) => EventType::Origin,
+            ProtoEvent::Sequenced(..) => EventType::Sequenced,
         }
     }
 }
 
-impl TryFrom<[u8; MAX_EVENT_SIZE]> for ProtoEvent {
-    type Error = ProtocolError;
+impl ProtoEvent {
+    /// decode an event, detecting its [`WireFormat`]
+    pub fn decode(buf: &[u8; MAX_EVENT_SIZE]) -> Result<(Self, WireFormat), ProtocolError> {
+        match buf[0] {
+            WIRE_FORMAT_V1 => {
+                let event_type = buf[1];
+                // sent by a newer version
+                if EventType::try_from(event_type).is_err() {
+                    return Err(ProtocolError::UnknownEventType(event_type));
+                }
+                let len = buf[2];
+                if len as usize > MAX_PAYLOAD_SIZE {
+                    return Err(ProtocolError::InvalidLength(len));
+                }
+                // shorter payloads are zero-extended
+                let mut payload = [0u8; MAX_PAYLOAD_SIZE];
+                payload[..len as usize]
+                    .copy_from_slice(&buf[V1_HEADER_SIZE..V1_HEADER_SIZE + len as usize]);
+                let event = Self::decode_payload(event_type, &payload)?;
+                Ok((event, WireFormat::V1))
+            }
+            version if version & VERSION_FLAG != 0 => {
+                Err(ProtocolError::UnsupportedVersion(version & !VERSION_FLAG))
+            }
+            event_type => {
+                let event = Self::decode_payload(event_type, &buf[1..])?;
+                Ok((event, WireFormat::Legacy))
+            }
+        }
+    }
 
-    fn try_from(buf: [u8; MAX_EVENT_SIZE]) -> Result<Self, Self::Error> {
-        let mut buf = &buf[..];
-        let event_type = decode_u8(&mut buf)?;
+    /// Decode an event that may be wrapped in a relay header.
+    /// Returns the destination client of a relayed event.
+    pub fn decode_relay(
+        buf: &[u8; MAX_RELAY_SIZE],
+    ) -> Result<(Option<u64>, Self, WireFormat), ProtocolError> {
+        let (destination, event) = match buf[0] {
+            RELAY_MARKER => {
+                let mut header = &buf[1..RELAY_HEADER_SIZE];
+                let destination = decode_u64(&mut header)?;
+                (Some(destination), &buf[RELAY_HEADER_SIZE..])
+            }
+            _ => (None, &buf[..MAX_EVENT_SIZE]),
+        };
+        let (event, format) = Self::decode(event.try_into().expect("event size"))?;
+        Ok((destination, event, format))
+    }
+
+    /// encode an event to be relayed by a hub to the client `destination`
+    pub fn encode_relay(
+        self,
+        destination: u64,
+        format: WireFormat,
+    ) -> ([u8; MAX_RELAY_SIZE], usize) {
+        let mut buf = [0u8; MAX_RELAY_SIZE];
+        let mut len = 0usize;
+        {
+            let mut buf = &mut buf[..];
+            let buf = &mut buf;
+            let len = &mut len;
+            encode_u8(buf, len, RELAY_MARKER);
+            encode_u64(buf, len, destination);
+        }
+        let (event, event_len) = self.encode(format);
+        buf[len..len + event_len].copy_from_slice(&event[..event_len]);
+        (buf, len + event_len)
+    }
+
+    /// encode an event using the given [`WireFormat`]
+    pub fn encode(self, format: WireFormat) -> ([u8; MAX_EVENT_SIZE], usize) {
+        let mut buf = [0u8; MAX_EVENT_SIZE];
+        let mut len = 0usize;
+        {
+            let mut buf = &mut buf[..];
+            let buf = &mut buf;
+            let len = &mut len;
+            match format {
+                WireFormat::Legacy => {
+                    encode_u8(buf, len, self.event_type() as u8);
+                    self.encode_payload(buf, len);
+                }
+                WireFormat::V1 => {
+                    encode_u8(buf, len, WIRE_FORMAT_V1);
+                    encode_u8(buf, len, self.event_type() as u8);
+                    let mut payload_len = 0;
+                    let (len_byte, mut payload) = std::mem::take(buf).split_at_mut(1);
+                    self.encode_payload(&mut payload, &mut payload_len);
+                    len_byte[0] = payload_len as u8;
+                    *len += 1 + payload_len;
+                }
+            }
+        }
+        (buf, len)
+    }
+
+    fn decode_payload(event_type: u8, buf: &[u8]) -> Result<Self, ProtocolError> {
+        let event = Self::decode_fields(event_type, buf)?;
+        if let Self::Input(e) | Self::Sequenced(_, e) = &event {
+            e.validate()?;
+        }
+        Ok(event)
+    }
+
+    fn decode_fields(event_type: u8, mut buf: &[u8]) -> Result<Self, ProtocolError> {
+        let buf = &mut buf;
         match EventType::try_from(event_type)? {
             EventType::PointerMotion => {
                 Ok(Self::Input(InputEvent::Pointer(PointerEvent::Motion {
-                    time: decode_u32(&mut buf)?,
-                    dx: decode_f64(&mut buf)?,
-                    dy: decode_f64(&mut buf)?,
+                    time: decode_u32(buf)?,
+                    dx: decode_f64(buf)?,
+                    dy: decode_f64(buf)?,
                 })))
             }
+            EventType::PointerMotionAbsolute => Ok(Self::Input(InputEvent::Pointer(
+                PointerEvent::MotionAbsolute {
+                    time: decode_u32(buf)?,
+                    x: decode_f64(buf)?,
+                    y: decode_f64(buf)?,
+                },
+            ))),
             EventType::PointerButton => {
                 Ok(Self::Input(InputEvent::Pointer(PointerEvent::Button {
-                    time: decode_u32(&mut buf)?,
-                    button: decode_u32(&mut buf)?,
-                    state: decode_u32(&mut buf)?,
+                    time: decode_u32(buf)?,
+                    button: decode_u32(buf)?,
+                    state: decode_u32(buf)?,
                 })))
             }
             EventType::PointerAxis => Ok(Self::Input(InputEvent::Pointer(PointerEvent::Axis {
-                time: decode_u32(&mut buf)?,
-                axis: decode_u8(&mut buf)?,
-                value: decode_f64(&mut buf)?,
+                time: decode_u32(buf)?,
+                axis: decode_u8(buf)?,
+                value: decode_f64(buf)?,
+                // peers not sending a scroll source leave this byte zeroed
+                source: ScrollSource::try_from(decode_u8(buf)?).unwrap_or_default(),
             }))),
             EventType::PointerAxisValue120 => Ok(Self::Input(InputEvent::Pointer(
                 PointerEvent::AxisDiscrete120 {
-                    axis: decode_u8(&mut buf)?,
-                    value: decode_i32(&mut buf)?,
+                    axis: decode_u8(buf)?,
+                    value: decode_i32(buf)?,
+                },
+            ))),
+            EventType::PointerScrollStop => {
+                Ok(Self::Input(InputEvent::Pointer(PointerEvent::ScrollStop {
+                    time: decode_u32(buf)?,
+                    axis: decode_u8(buf)?,
+                })))
+            }
+            EventType::PointerScrollCancel => Ok(Self::Input(InputEvent::Pointer(
+                PointerEvent::ScrollCancel {
+                    time: decode_u32(buf)?,
+                    axis: decode_u8(buf)?,
                 },
             ))),
             EventType::KeyboardKey => Ok(Self::Input(InputEvent::Keyboard(KeyboardEvent::Key {
-                time: decode_u32(&mut buf)?,
-                key: decode_u32(&mut buf)?,
-                state: decode_u8(&mut buf)?,
+                time: decode_u32(buf)?,
+                key: decode_u32(buf)?,
+                state: decode_u8(buf)?,
             }))),
             EventType::KeyboardModifiers => Ok(Self::Input(InputEvent::Keyboard(
                 KeyboardEvent::Modifiers {
-                    depressed: decode_u32(&mut buf)?,
-                    latched: decode_u32(&mut buf)?,
-                    locked: decode_u32(&mut buf)?,
-                    group: decode_u32(&mut buf)?,
+                    depressed: decode_u32(buf)?,
+                    latched: decode_u32(buf)?,
+                    locked: decode_u32(buf)?,
+                    group: decode_u32(buf)?,
                 },
             ))),
             EventType::Ping => Ok(Self::Ping),
-            EventType::Pong => Ok(Self::Pong(decode_u8(&mut buf)? != 0)),
-            EventType::Enter => Ok(Self::Enter(decode_u8(&mut buf)?.try_into()?)),
-            EventType::Leave => Ok(Self::Leave(decode_u32(&mut buf)?)),
-            EventType::Ack => Ok(Self::Ack(decode_u32(&mut buf)?)),
+            EventType::Pong => Ok(Self::Pong(decode_u8(buf)? != 0, decode_u8(buf)?)),
+            EventType::Enter => Ok(Self::Enter(decode_u8(buf)?.try_into()?)),
+            EventType::Leave => Ok(Self::Leave(decode_u32(buf)?)),
+            EventType::Ack => Ok(Self::Ack(decode_u32(buf)?)),
+            EventType::Frame => Ok(Self::Frame),
+            EventType::Origin => Ok(Self::Origin(decode_u32(buf)?)),
+            EventType::Sequenced => {
+                let seq = decode_u32(buf)?;
+                let event_type = decode_u8(buf)?;
+                match Self::decode_fields(event_type, buf)? {
+                    Self::Input(event) => Self::sequenced(seq, event)
+                        .ok_or(ProtocolError::NotSequenceable(event_type)),
+                    _ => Err(ProtocolError::NotSequenceable(event_type)),
+                }
+            }
         }
     }
-}
 
-impl From<ProtoEvent> for ([u8; MAX_EVENT_SIZE], usize) {
-    fn from(event: ProtoEvent) -> Self {
-        let mut buf = [0u8; MAX_EVENT_SIZE];
-        let mut len = 0usize;
-        {
-            let mut buf = &mut buf[..];
-            let buf = &mut buf;
-            let len = &mut len;
-            encode_u8(buf, len, event.event_type() as u8);
-            match event {
-                ProtoEvent::Input(event) => match event {
-                    InputEvent::Pointer(p) => match p {
-                        PointerEvent::Motion { time, dx, dy } => {
-                            encode_u32(buf, len, time);
-                            encode_f64(buf, len, dx);
-                            encode_f64(buf, len, dy);
-                        }
-                        PointerEvent::Button {
-                            time,
-                            button,
-                            state,
-                        } => {
-                            encode_u32(buf, len, time);
-                            encode_u32(buf, len, button);
-                            encode_u32(buf, len, state);
-                        }
-                        PointerEvent::Axis { time, axis, value } => {
-                            encode_u32(buf, len, time);
-                            encode_u8(buf, len, axis);
-                            encode_f64(buf, len, value);
-                        }
-                        PointerEvent::AxisDiscrete120 { axis, value } => {
-                            encode_u8(buf, len, axis);
-                            encode_i32(buf, len, value);
-                        }
-                    },
-                    InputEvent::Keyboard(k) => match k {
-                        KeyboardEvent::Key { time, key, state } => {
-                            encode_u32(buf, len, time);
-                            encode_u32(buf, len, key);
-                            encode_u8(buf, len, state);
-                        }
-                        KeyboardEvent::Modifiers {
-                            depressed,
-                            latched,
-                            locked,
-                            group,
-                        } => {
-                            encode_u32(buf, len, depressed);
-                            encode_u32(buf, len, latched);
-                            encode_u32(buf, len, locked);
-                            encode_u32(buf, len, group);
-                        }
-                    },
+    fn encode_payload(self, buf: &mut &mut [u8], len: &mut usize) {
+        match self {
+            ProtoEvent::Sequenced(seq, event) => {
+                let event = ProtoEvent::Input(event);
+                encode_u32(buf, len, seq);
+                encode_u8(buf, len, event.event_type() as u8);
+                event.encode_payload(buf, len);
+            }
+            ProtoEvent::Input(event) => match event {
+                InputEvent::Pointer(p) => match p {
+                    PointerEvent::Motion { time, dx, dy } => {
+                        encode_u32(buf, len, time);
+                        encode_f64(buf, len, dx);
+                        encode_f64(buf, len, dy);
+                    }
+                    PointerEvent::MotionAbsolute { time, x, y } => {
+                        encode_u32(buf, len, time);
+                        encode_f64(buf, len, x);
+                        encode_f64(buf, len, y);
+                    }
+                    PointerEvent::Button {
+                        time,
+                        button,
+                        state,
+                    } => {
+                        encode_u32(buf, len, time);
+                        encode_u32(buf, len, button);
+                        encode_u32(buf, len, state);
+                    }
+                    PointerEvent::Axis {
+                        time,
+                        axis,
+                        value,
+                        source,
+                    } => {
+                        encode_u32(buf, len, time);
+                        encode_u8(buf, len, axis);
+                        encode_f64(buf, len, value);
+                        encode_u8(buf, len, source.into());
+                    }
+                    PointerEvent::AxisDiscrete120 { axis, value } => {
+                        encode_u8(buf, len, axis);
+                        encode_i32(buf, len, value);
+                    }
+                    PointerEvent::ScrollStop { time, axis }
+                    | PointerEvent::ScrollCancel { time, axis } => {
+                        encode_u32(buf, len, time);
+                        encode_u8(buf, len, axis);
+                    }
+                },
+                InputEvent::Keyboard(k) => match k {
+                    KeyboardEvent::Key { time, key, state } => {
+                        encode_u32(buf, len, time);
+                        encode_u32(buf, len, key);
+                        encode_u8(buf, len, state);
+                    }
+                    KeyboardEvent::Modifiers {
+                        depressed,
+                        latched,
+                        locked,
+                        group,
+                    } => {
+                        encode_u32(buf, len, depressed);
+                        encode_u32(buf, len, latched);
+                        encode_u32(buf, len, locked);
+                        encode_u32(buf, len, group);
+                    }
                 },
-                ProtoEvent::Ping => {}
-                ProtoEvent::Pong(alive) => encode_u8(buf, len, alive as u8),
-                ProtoEvent::Enter(pos) => encode_u8(buf, len, pos as u8),
-                ProtoEvent::Leave(serial) => encode_u32(buf, len, serial),
-                ProtoEvent::Ack(serial) => encode_u32(buf, len, serial),
+            },
+            ProtoEvent::Ping => {}
+            ProtoEvent::Pong(alive, version) => {
+                encode_u8(buf, len, alive as u8);
+                encode_u8(buf, len, version);
             }
+            ProtoEvent::Enter(pos) => encode_u8(buf, len, pos as u8),
+            ProtoEvent::Leave(serial) => encode_u32(buf, len, serial),
+            ProtoEvent::Ack(serial) => encode_u32(buf, len, serial),
+            ProtoEvent::Frame => {}
+            ProtoEvent::Origin(id) => encode_u32(buf, len, id),
+        }
+    }
+}
+
+/// messages other than input events, sent over the same
+/// connection and distinguished by a marker byte.
+/// Like those of events, multi-byte fields are big-endian.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// keep-alive without further meaning
+    Heartbeat,
+    /// the sender started forwarding input (session id)
+    SessionStart(u32),
+    /// the sender stopped forwarding input (session id)
+    SessionEnd(u32),
+    /// request for the clock of the receiver (send time),
+    /// answered with a [`ControlMessage::ClockResponse`]
+    ClockRequest(u64),
+    /// answer to a [`ControlMessage::ClockRequest`]: its send time
+    /// and the receive and transmit time of the answering device
+    ClockResponse {
+        origin: u64,
+        receive: u64,
+        transmit: u64,
+    },
+    /// perform the named action, or emulate its key chord
+    /// (scancodes) if the receiver has no way of performing it.
+    /// Versions without the key chord only send the action.
+    Action { action: Action, keys: Vec<u32> },
+}
+
+/// Time used by clock messages: microseconds since the unix epoch.
+/// Devices with unsynchronized clocks report different times.
+pub fn clock_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+impl Display for ControlMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlMessage::Heartbeat => write!(f, "heartbeat"),
+            ControlMessage::SessionStart(id) => write!(f, "session-start({id})"),
+            ControlMessage::SessionEnd(id) => write!(f, "session-end({id})"),
+            ControlMessage::ClockRequest(t) => write!(f, "clock-request({t})"),
+            ControlMessage::ClockResponse {
+                origin,
+                receive,
+                transmit,
+            } => write!(f, "clock-response({origin}, {receive}, {transmit})"),
+            ControlMessage::Action { action, keys } => write!(f, "action({action}, {keys:?})"),
         }
-        (buf, len)
     }
 }
 
+#[derive(TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum ControlType {
+    Heartbeat,
+    SessionStart,
+    SessionEnd,
+    ClockRequest,
+    ClockResponse,
+    Action,
+}
+
+impl ControlMessage {
+    /// whether the packet is a control message (as opposed to an event)
+    pub fn is_control(buf: &[u8]) -> bool {
+        buf.first() == Some(&CONTROL_MARKER)
+    }
+
+    fn control_type(&self) -> ControlType {
+        match self {
+            ControlMessage::Heartbeat => ControlType::Heartbeat,
+            ControlMessage::SessionStart(_) => ControlType::SessionStart,
+            ControlMessage::SessionEnd(_) => ControlType::SessionEnd,
+            ControlMessage::ClockRequest(_) => ControlType::ClockRequest,
+            ControlMessage::ClockResponse { .. } => ControlType::ClockResponse,
+            ControlMessage::Action { .. } => ControlType::Action,
+        }
+    }
+
+    /// decode a control message
+    pub fn decode(buf: &[u8]) -> Result<Self, ProtocolError> {
+        if buf.len() < CONTROL_HEADER_SIZE {
+            return Err(ProtocolError::InvalidControlLength(buf.len()));
+        }
+        let (header, payload) = buf.split_at(CONTROL_HEADER_SIZE);
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if len > MAX_CONTROL_PAYLOAD_SIZE || len > payload.len() {
+            return Err(ProtocolError::InvalidControlLength(len));
+        }
+        let payload = &mut &payload[..len];
+        match ControlType::try_from(header[1])? {
+            ControlType::Heartbeat => Ok(Self::Heartbeat),
+            ControlType::SessionStart if len >= size_of::<u32>() => {
+                Ok(Self::SessionStart(decode_u32(payload)?))
+            }
+            ControlType::SessionEnd if len >= size_of::<u32>() => {
+                Ok(Self::SessionEnd(decode_u32(payload)?))
+            }
+            ControlType::ClockRequest if len >= size_of::<u64>() => {
+                Ok(Self::ClockRequest(decode_u64(payload)?))
+            }
+            ControlType::ClockResponse if len >= 3 * size_of::<u64>() => Ok(Self::ClockResponse {
+                origin: decode_u64(payload)?,
+                receive: decode_u64(payload)?,
+                transmit: decode_u64(payload)?,
+            }),
+            ControlType::Action if len >= size_of::<u8>() => {
+                let action = decode_u8(payload)?.try_into()?;
+                let count = match payload.is_empty() {
+                    true => 0,
+                    false => decode_u8(payload)? as usize,
+                };
+                if payload.len() < count * size_of::<u32>() {
+                    return Err(ProtocolError::InvalidControlLength(len));
+                }
+                let keys = (0..count)
+                    .map(|_| decode_u32(payload))
+                    .collect::<Result<_, _>>()?;
+                Ok(Self::Action { action, keys })
+            }
+            _ => Err(ProtocolError::InvalidControlLength(len)),
+        }
+    }
+
+    /// encode a control message
+    pub fn encode(&self) -> ([u8; MAX_CONTROL_SIZE], usize) {
+        let mut buf = [0u8; MAX_CONTROL_SIZE];
+        let mut payload_len = 0;
+        let payload = &mut &mut buf[CONTROL_HEADER_SIZE..];
+        match self {
+            ControlMessage::Heartbeat => {}
+            ControlMessage::SessionStart(id) | ControlMessage::SessionEnd(id) => {
+                encode_u32(payload, &mut payload_len, *id)
+            }
+            ControlMessage::ClockRequest(t) => encode_u64(payload, &mut payload_len, *t),
+            ControlMessage::ClockResponse {
+                origin,
+                receive,
+                transmit,
+            } => {
+                encode_u64(payload, &mut payload_len, *origin);
+                encode_u64(payload, &mut payload_len, *receive);
+                encode_u64(payload, &mut payload_len, *transmit);
+            }
+            ControlMessage::Action { action, keys } => {
+                let keys = &keys[..keys.len().min(u8::MAX as usize)];
+                encode_u8(payload, &mut payload_len, (*action).into());
+                encode_u8(payload, &mut payload_len, keys.len() as u8);
+                for &key in keys {
+                    encode_u32(payload, &mut payload_len, key);
+                }
+            }
+        }
+        let payload_len = payload_len as u16;
+        buf[0] = CONTROL_MARKER;
+        buf[1] = self.control_type().into();
+        buf[2..CONTROL_HEADER_SIZE].copy_from_slice(&payload_len.to_be_bytes());
+        (buf, CONTROL_HEADER_SIZE + payload_len as usize)
+    }
+}
+
+impl TryFrom<[u8; MAX_EVENT_SIZE]> for ProtoEvent {
+    type Error = ProtocolError;
+
+    fn try_from(buf: [u8; MAX_EVENT_SIZE]) -> Result<Self, Self::Error> {
+        Self::decode(&buf).map(|(event, _)| event)
+    }
+}
+
+impl From<ProtoEvent> for ([u8; MAX_EVENT_SIZE], usize) {
+    fn from(event: ProtoEvent) -> Self {
+        event.encode(WireFormat::default())
+    }
+}
+
+// all fields are encoded in network byte order (big-endian),
+// never in the native byte order of the host (see [`WireFormat`])
 macro_rules! decode_impl {
     ($t:ty) => {
         paste! {
@@ -258,6 +813,7 @@ macro_rules! decode_impl {
 
 decode_impl!(u8);
 decode_impl!(u32);
+decode_impl!(u64);
 decode_impl!(i32);
 decode_impl!(f64);
 
@@ -278,5 +834,154 @@ macro_rules! encode_impl {
 
 encode_impl!(u8);
 encode_impl!(u32);
+encode_impl!(u64);
 encode_impl!(i32);
 encode_impl!(f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> Vec<ProtoEvent> {
+        vec![
+            ProtoEvent::Enter(Position::Top),
+            ProtoEvent::Leave(7),
+            ProtoEvent::Ack(7),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Motion {
+                time: 1,
+                dx: -1.5,
+                dy: 2.25,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Button {
+                time: 2,
+                button: 0x110,
+                state: 1,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Axis {
+                time: 6,
+                axis: 1,
+                value: 4.5,
+                source: ScrollSource::Finger,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::AxisDiscrete120 {
+                axis: 0,
+                value: -120,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::ScrollStop {
+                time: 4,
+                axis: 1,
+            })),
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::ScrollCancel {
+                time: 5,
+                axis: 0,
+            })),
+            ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Key {
+                time: 3,
+                key: 30,
+                state: 1,
+            })),
+            ProtoEvent::Input(InputEvent::Keyboard(KeyboardEvent::Modifiers {
+                depressed: 1,
+                latched: 2,
+                locked: 4,
+                group: 0,
+            })),
+            ProtoEvent::Ping,
+            ProtoEvent::Pong(true, WireFormat::NEWEST.version()),
+            ProtoEvent::Frame,
+            ProtoEvent::Origin(3),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        for format in [WireFormat::Legacy, WireFormat::V1] {
+            for event in events() {
+                let (buf, _) = event.encode(format);
+                let (decoded, decoded_format) = ProtoEvent::decode(&buf).unwrap();
+                assert_eq!(format!("{decoded:?}"), format!("{event:?}"));
+                assert_eq!(decoded_format, format);
+            }
+        }
+    }
+
+    #[test]
+    fn big_endian_fields() {
+        let event = ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Motion {
+            time: 0x01020304,
+            dx: 1.0,
+            dy: -2.0,
+        }));
+        let (buf, len) = event.encode(WireFormat::V1);
+        #[rustfmt::skip]
+        let expected = [
+            WIRE_FORMAT_V1, EventType::PointerMotion as u8, 20,
+            0x01, 0x02, 0x03, 0x04,
+            0x3f, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(&buf[..len], &expected);
+
+        let event = ProtoEvent::Leave(0x0a0b0c0d);
+        let (buf, len) = event.encode(WireFormat::Legacy);
+        assert_eq!(
+            &buf[..len],
+            &[EventType::Leave as u8, 0x0a, 0x0b, 0x0c, 0x0d]
+        );
+    }
+
+    #[test]
+    fn legacy_axis_without_source() {
+        // older versions do not send the scroll source
+        let event = ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Axis {
+            time: 0,
+            axis: 0,
+            value: 15.,
+            source: ScrollSource::Finger,
+        }));
+        let (encoded, len) = event.encode(WireFormat::Legacy);
+        let mut buf = [0u8; MAX_EVENT_SIZE];
+        buf[..len - 1].copy_from_slice(&encoded[..len - 1]);
+        let (event, _) = ProtoEvent::decode(&buf).unwrap();
+        assert!(matches!(
+            event,
+            ProtoEvent::Input(InputEvent::Pointer(PointerEvent::Axis {
+                source: ScrollSource::Wheel,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn legacy_pong_without_version() {
+        // older versions only send whether emulation is available
+        let mut buf = [0u8; MAX_EVENT_SIZE];
+        buf[0] = EventType::Pong as u8;
+        buf[1] = 1;
+        let (event, _) = ProtoEvent::decode(&buf).unwrap();
+        assert!(matches!(event, ProtoEvent::Pong(true, 0)));
+        assert_eq!(WireFormat::negotiate(0), WireFormat::Legacy);
+        assert_eq!(WireFormat::negotiate(1), WireFormat::V1);
+    }
+
+    #[test]
+    fn action_key_chord() {
+        let message = ControlMessage::Action {
+            action: Action::WorkspaceLeft,
+            keys: vec![29, 56, 105],
+        };
+        let (buf, len) = message.encode();
+        assert_eq!(ControlMessage::decode(&buf[..len]).unwrap(), message);
+
+        // older versions only send the action
+        let buf = [CONTROL_MARKER, ControlType::Action.into(), 0, 1, 0];
+        let message = ControlMessage::decode(&buf).unwrap();
+        assert_eq!(
+            message,
+            ControlMessage::Action {
+                action: Action::LockScreen,
+                keys: vec![],
+            }
+        );
+    }
+}
@@ -1,20 +1,26 @@
 use std::{
     cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
     rc::Rc,
-    time::{Duration, Instant},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use futures::StreamExt;
 use input_capture::{
-    CaptureError, CaptureEvent, CaptureHandle, InputCapture, InputCaptureError, Position,
+    CaptureError, CaptureEvent, CaptureHandle, CaptureOptions, InputCapture, InputCaptureError,
+    Position,
 };
-use input_event::scancode;
-use lan_mouse_proto::ProtoEvent;
+use input_emulation::{Clock, SystemClock};
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent, ScrollSource};
+use lan_mouse_ipc::{FallbackRecovery, PointerMode, Transform};
+use lan_mouse_proto::{Action, ControlMessage, ProtoEvent};
 use local_channel::mpsc::{channel, Receiver, Sender};
 use tokio::task::{spawn_local, JoinHandle};
 use tokio_util::sync::CancellationToken;
 
-use crate::connect::LanMouseConnection;
+use crate::connect::{ConnectionEvent, LanMouseConnection};
 
 pub(crate) struct Capture {
     cancellation_token: CancellationToken,
@@ -37,6 +43,42 @@ pub(crate) enum ICaptureEvent {
     /// either the remote client leaving its device region,
     /// a new device entering the screen or the release bind.
     ClientEntered(u64),
+    /// event from a relayed client to be sent back to the device at the given address
+    RelayReply(SocketAddr, ProtoEvent),
+    /// the client is now connected at a different address
+    AddressChanged(CaptureHandle, SocketAddr),
+    /// control message received from the client
+    Control(CaptureHandle, ControlMessage),
+    /// the client became incompatible (or compatible again)
+    Incompatible(CaptureHandle, Option<lan_mouse_ipc::VersionMismatch>),
+    /// input for the client is forwarded to its fallback (or to the client again)
+    Failover(CaptureHandle, Option<CaptureHandle>),
+    /// the client, replaced by its fallback, is reachable again
+    Recovered(CaptureHandle),
+    /// the client is entered after the countdown unless cancelled
+    SwitchPending(CaptureHandle, Duration),
+    /// a pending switch to the client was cancelled
+    SwitchCancelled(CaptureHandle),
+    /// input captured while no client was entered, to be emulated on this device
+    LocalInput(Vec<Event>),
+    /// the capture stream closed unexpectedly or could not be recreated
+    /// (reason), capture stays disabled until reenabled
+    Closed(String),
+    /// text was typed on the client, skipping the given characters
+    /// (or typing failed for the given reason)
+    TextTyped(CaptureHandle, Result<Vec<char>, String>),
+}
+
+/// handling of the input capture closing unexpectedly
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum CaptureClosed {
+    /// stay disabled until capture is reenabled manually
+    #[default]
+    Disable,
+    /// recreate the capture, backing off on repeated failures
+    Recreate,
+    /// shut down the service
+    Exit,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -49,7 +91,7 @@ pub(crate) enum CaptureType {
     EnterOnly,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 enum CaptureRequest {
     /// capture must release the mouse
     Release,
@@ -59,26 +101,85 @@ enum CaptureRequest {
     Destroy(CaptureHandle),
     /// reenable input capture
     Reenable,
+    /// reenable input capture once the delay passed
+    ReenableAfter(Duration),
+    /// pin / unpin a client to always forward mode
+    AlwaysForward(CaptureHandle, bool),
+    /// set whether absolute positions are forwarded
+    PointerMode(CaptureHandle, PointerMode),
+    /// set the buttons with click-lock enabled
+    ClickLock(CaptureHandle, Vec<u32>),
+    /// set the keys that are not forwarded
+    BlockedKeys(CaptureHandle, Vec<u32>),
+    /// set the scroll acceleration in percent per successive scroll event
+    ScrollAcceleration(CaptureHandle, u32),
+    /// set the translation of buttons
+    ButtonMap(CaptureHandle, HashMap<u32, u32>),
+    /// set the upper bound of the motion prediction in pixels
+    MotionPrediction(CaptureHandle, u32),
+    /// set the rotation of relative motion
+    Orientation(CaptureHandle, lan_mouse_ipc::Orientation),
+    /// set the transform pipeline
+    Transforms(CaptureHandle, Vec<Transform>),
+    /// set the client used while a client is unreachable
+    Fallback(CaptureHandle, Option<(CaptureHandle, FallbackRecovery)>),
+    /// relay an event from the device at the given address to a client
+    Relay(CaptureHandle, ProtoEvent, SocketAddr),
+    /// type text on a client
+    TypeText(CaptureHandle, String),
+    /// set the key chords sending named actions
+    ActionBinds(Vec<(Action, Vec<scancode::Linux>)>),
 }
 
 impl Capture {
     pub(crate) fn new(
         backend: Option<input_capture::Backend>,
-        conn: LanMouseConnection,
+        mut conn: LanMouseConnection,
         release_bind: Vec<scancode::Linux>,
+        options: CaptureOptions,
+        sticky_edge: StickyEdge,
+        tap_to_switch: TapToSwitch,
+        reassert_modifiers: bool,
     ) -> Self {
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let cancellation_token = CancellationToken::new();
         let capture_task = CaptureTask {
             active_client: None,
+            always_forward: Default::default(),
+            relative_pointer: Default::default(),
+            last_absolute: Default::default(),
+            click_lock: Default::default(),
+            blocked_keys: Default::default(),
+            scroll_acceleration: Default::default(),
+            pipeline: Default::default(),
+            motion_prediction: Default::default(),
+            fallback: Default::default(),
+            failed_over: Default::default(),
+            unreachable: Default::default(),
+            relays: Default::default(),
+            sticky_edge,
+            tap_to_switch,
+            held_modifiers: reassert_modifiers.then(Default::default),
+            pushing: None,
+            clock: Arc::new(SystemClock),
+            // differs between runs, so a restarted instance is not
+            // mistaken for repeating messages of an old session
+            session: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or_default(),
+            connection_events: conn.take_events(),
             backend,
+            options,
             cancellation_token: cancellation_token.clone(),
             captures: Default::default(),
             conn,
             event_tx,
             request_rx,
             release_bind: Rc::new(RefCell::new(release_bind)),
+            action_binds: Default::default(),
+            action_keys: Default::default(),
             state: Default::default(),
         };
         let task = spawn_local(capture_task.run());
@@ -96,6 +197,14 @@ impl Capture {
             .expect("channel closed");
     }
 
+    /// reenable input capture after the given delay,
+    /// unless it is reenabled earlier
+    pub(crate) fn reenable_after(&self, delay: Duration) {
+        self.request_tx
+            .send(CaptureRequest::ReenableAfter(delay))
+            .expect("channel closed");
+    }
+
     pub(crate) async fn terminate(&mut self) {
         self.cancellation_token.cancel();
         log::debug!("terminating capture");
@@ -122,6 +231,106 @@ impl Capture {
             .expect("channel closed");
     }
 
+    /// keep forwarding input to the client once it was entered,
+    /// until the release bind is pressed or the client is unpinned
+    pub(crate) fn set_always_forward(&self, handle: CaptureHandle, always_forward: bool) {
+        self.request_tx
+            .send(CaptureRequest::AlwaysForward(handle, always_forward))
+            .expect("channel closed");
+    }
+
+    /// switch a client between absolute and relative pointer mode
+    pub(crate) fn set_pointer_mode(&self, handle: CaptureHandle, pointer_mode: PointerMode) {
+        self.request_tx
+            .send(CaptureRequest::PointerMode(handle, pointer_mode))
+            .expect("channel closed");
+    }
+
+    /// enable click-lock for the given buttons of a client
+    pub(crate) fn set_click_lock(&self, handle: CaptureHandle, buttons: Vec<u32>) {
+        self.request_tx
+            .send(CaptureRequest::ClickLock(handle, buttons))
+            .expect("channel closed");
+    }
+
+    /// never forward the given keys to a client
+    pub(crate) fn set_blocked_keys(&self, handle: CaptureHandle, keys: Vec<u32>) {
+        self.request_tx
+            .send(CaptureRequest::BlockedKeys(handle, keys))
+            .expect("channel closed");
+    }
+
+    /// translate the buttons sent to a client
+    pub(crate) fn set_button_map(&self, handle: CaptureHandle, map: HashMap<u32, u32>) {
+        self.request_tx
+            .send(CaptureRequest::ButtonMap(handle, map))
+            .expect("channel closed");
+    }
+
+    /// accelerate successive scroll events of a client
+    pub(crate) fn set_scroll_acceleration(&self, handle: CaptureHandle, percent: u32) {
+        self.request_tx
+            .send(CaptureRequest::ScrollAcceleration(handle, percent))
+            .expect("channel closed");
+    }
+
+    /// rotate relative motion sent to a client
+    pub(crate) fn set_orientation(
+        &self,
+        handle: CaptureHandle,
+        orientation: lan_mouse_ipc::Orientation,
+    ) {
+        self.request_tx
+            .send(CaptureRequest::Orientation(handle, orientation))
+            .expect("channel closed");
+    }
+
+    /// compensate the latency to a client by extrapolating motion
+    pub(crate) fn set_motion_prediction(&self, handle: CaptureHandle, max: u32) {
+        self.request_tx
+            .send(CaptureRequest::MotionPrediction(handle, max))
+            .expect("channel closed");
+    }
+
+    /// transform the input sent to a client
+    pub(crate) fn set_transforms(&self, handle: CaptureHandle, transforms: Vec<Transform>) {
+        self.request_tx
+            .send(CaptureRequest::Transforms(handle, transforms))
+            .expect("channel closed");
+    }
+
+    /// forward input to `fallback` while the client is unreachable
+    pub(crate) fn set_fallback(
+        &self,
+        handle: CaptureHandle,
+        fallback: Option<(CaptureHandle, FallbackRecovery)>,
+    ) {
+        self.request_tx
+            .send(CaptureRequest::Fallback(handle, fallback))
+            .expect("channel closed");
+    }
+
+    /// type `text` on the given client, see [`input_event::text`]
+    pub(crate) fn type_text(&self, handle: CaptureHandle, text: String) {
+        self.request_tx
+            .send(CaptureRequest::TypeText(handle, text))
+            .expect("channel closed");
+    }
+
+    /// send named actions instead of the given key chords
+    pub(crate) fn set_action_binds(&self, binds: Vec<(Action, Vec<scancode::Linux>)>) {
+        self.request_tx
+            .send(CaptureRequest::ActionBinds(binds))
+            .expect("channel closed");
+    }
+
+    /// send an event from the device at `addr` to the given client
+    pub(crate) fn relay(&self, handle: CaptureHandle, event: ProtoEvent, addr: SocketAddr) {
+        self.request_tx
+            .send(CaptureRequest::Relay(handle, event, addr))
+            .expect("channel closed");
+    }
+
     pub(crate) fn release(&self) {
         self.request_tx
             .send(CaptureRequest::Release)
@@ -152,12 +361,50 @@ macro_rules! debounce {
 
 struct CaptureTask {
     active_client: Option<CaptureHandle>,
+    always_forward: HashSet<CaptureHandle>,
+    /// clients in relative pointer mode
+    relative_pointer: HashSet<CaptureHandle>,
+    /// last absolute position sent as relative motion to each client
+    last_absolute: HashMap<CaptureHandle, (f64, f64)>,
+    click_lock: HashMap<CaptureHandle, ClickLock>,
+    /// keys that are never forwarded to a client
+    blocked_keys: HashMap<CaptureHandle, HashSet<u32>>,
+    scroll_acceleration: HashMap<CaptureHandle, ScrollAcceleration>,
+    /// orientation, button map and transforms of each client
+    pipeline: HashMap<CaptureHandle, Pipeline>,
+    motion_prediction: HashMap<CaptureHandle, MotionPrediction>,
+    /// fallback client and recovery behavior of each client
+    fallback: HashMap<CaptureHandle, (CaptureHandle, FallbackRecovery)>,
+    /// clients whose input is forwarded to their fallback
+    failed_over: HashSet<CaptureHandle>,
+    /// clients known to be unreachable
+    unreachable: HashSet<CaptureHandle>,
+    /// clients receiving relayed events and the address of the originating device
+    relays: HashMap<CaptureHandle, SocketAddr>,
+    sticky_edge: StickyEdge,
+    tap_to_switch: TapToSwitch,
+    /// modifiers held on the active client, if they are
+    /// reasserted before every other key press
+    held_modifiers: Option<HeldModifiers>,
+    /// capture pushing against a held back edge
+    pushing: Option<Push>,
+    /// source of time of the timer based features
+    clock: Arc<dyn Clock>,
+    /// id of the latest forwarding session
+    session: u32,
+    /// clients reconnected at a different address
+    connection_events: Receiver<ConnectionEvent>,
     backend: Option<input_capture::Backend>,
+    options: CaptureOptions,
     cancellation_token: CancellationToken,
     captures: Vec<(CaptureHandle, Position, CaptureType)>,
     conn: LanMouseConnection,
     event_tx: Sender<ICaptureEvent>,
     release_bind: Rc<RefCell<Vec<scancode::Linux>>>,
+    /// key chords sending a named action instead of the keys
+    action_binds: Vec<(Action, Vec<scancode::Linux>)>,
+    /// keys whose press sent an action, their release is not forwarded
+    action_keys: HashSet<u32>,
     request_rx: Receiver<CaptureRequest>,
     state: State,
 }
@@ -169,6 +416,187 @@ impl CaptureTask {
 
     fn remove_capture(&mut self, handle: CaptureHandle) {
         self.captures.retain(|&(h, ..)| handle != h);
+        self.always_forward.remove(&handle);
+        self.relative_pointer.remove(&handle);
+        self.last_absolute.remove(&handle);
+        self.click_lock.remove(&handle);
+        self.blocked_keys.remove(&handle);
+        self.scroll_acceleration.remove(&handle);
+        self.pipeline.remove(&handle);
+        self.motion_prediction.remove(&handle);
+        self.fallback.remove(&handle);
+        self.failed_over.remove(&handle);
+        self.relays.remove(&handle);
+    }
+
+    fn set_click_lock(&mut self, handle: CaptureHandle, buttons: Vec<u32>) {
+        self.click_lock.insert(handle, ClickLock::new(buttons));
+    }
+
+    fn set_blocked_keys(&mut self, handle: CaptureHandle, keys: Vec<u32>) {
+        self.blocked_keys.insert(handle, HashSet::from_iter(keys));
+    }
+
+    fn set_button_map(&mut self, handle: CaptureHandle, map: HashMap<u32, u32>) {
+        self.pipeline.entry(handle).or_default().button_map = Some(ButtonMap::new(map));
+    }
+
+    fn set_scroll_acceleration(&mut self, handle: CaptureHandle, percent: u32) {
+        self.scroll_acceleration
+            .insert(handle, ScrollAcceleration::new(percent));
+    }
+
+    fn set_orientation(&mut self, handle: CaptureHandle, orientation: lan_mouse_ipc::Orientation) {
+        self.pipeline.entry(handle).or_default().orientation = orientation;
+    }
+
+    fn set_motion_prediction(&mut self, handle: CaptureHandle, max: u32) {
+        self.motion_prediction
+            .insert(handle, MotionPrediction::new(max));
+    }
+
+    fn set_transforms(&mut self, handle: CaptureHandle, transforms: Vec<Transform>) {
+        self.pipeline.entry(handle).or_default().transforms = transforms;
+    }
+
+    fn set_fallback(
+        &mut self,
+        handle: CaptureHandle,
+        fallback: Option<(CaptureHandle, FallbackRecovery)>,
+    ) {
+        match fallback {
+            Some(fallback) => {
+                self.fallback.insert(handle, fallback);
+            }
+            None => {
+                self.fallback.remove(&handle);
+                self.failed_over.remove(&handle);
+            }
+        }
+    }
+
+    /// client the input for `handle` is sent to
+    fn target(&self, handle: CaptureHandle) -> CaptureHandle {
+        match self.fallback.get(&handle) {
+            Some(&(fallback, _)) if self.failed_over.contains(&handle) => fallback,
+            _ => handle,
+        }
+    }
+
+    /// decide whether input for the entered client goes to its fallback
+    async fn update_failover(&mut self, handle: CaptureHandle) {
+        let Some(&(fallback, recovery)) = self.fallback.get(&handle) else {
+            return;
+        };
+        let failed_over = self.failed_over.contains(&handle);
+        let use_fallback = !self.unreachable.contains(&fallback)
+            && match recovery {
+                FallbackRecovery::Stay if failed_over => true,
+                _ => self.unreachable.contains(&handle),
+            };
+        if use_fallback {
+            // the client is only found to be reachable again by connecting
+            self.conn.probe(handle).await;
+        }
+        if use_fallback == failed_over {
+            return;
+        }
+        if use_fallback {
+            log::info!("client {handle} is unreachable, forwarding to fallback {fallback}");
+            self.failed_over.insert(handle);
+        } else {
+            log::info!("switching back from fallback {fallback} to client {handle}");
+            self.failed_over.remove(&handle);
+        }
+        self.event_tx
+            .send(ICaptureEvent::Failover(
+                handle,
+                use_fallback.then_some(fallback),
+            ))
+            .expect("channel closed");
+    }
+
+    async fn relay(&mut self, handle: CaptureHandle, event: ProtoEvent, addr: SocketAddr) {
+        if self.relays.insert(handle, addr) != Some(addr) {
+            log::info!("relaying events from {addr} to client {handle}");
+        }
+        if let Err(e) = self.conn.send(event, handle).await {
+            log::debug!("relay to client {handle}: {e}");
+        }
+    }
+
+    async fn type_text(&mut self, handle: CaptureHandle, text: String) {
+        let (events, skipped) = input_event::text::key_events(&text);
+        let typed = self.type_keys(handle, events).await.map(|_| skipped);
+        if let Err(e) = &typed {
+            log::warn!("typing text on client {handle}: {e}");
+        }
+        self.event_tx
+            .send(ICaptureEvent::TextTyped(handle, typed))
+            .expect("channel closed");
+    }
+
+    async fn type_keys(
+        &mut self,
+        handle: CaptureHandle,
+        events: Vec<KeyboardEvent>,
+    ) -> Result<(), String> {
+        for event in events {
+            let event = ProtoEvent::Input(Event::Keyboard(event));
+            self.conn
+                .send(event, handle)
+                .await
+                .map_err(|e| e.to_string())?;
+            // legacy clients do not receive frames (see `encode`)
+            if self.options.frame_grouping {
+                self.conn
+                    .send(ProtoEvent::Frame, handle)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn notify_connection_event(&mut self, event: ConnectionEvent) {
+        let event = match event {
+            ConnectionEvent::Reachable(handle, true) => {
+                self.unreachable.remove(&handle);
+                if !self.failed_over.contains(&handle) {
+                    return;
+                }
+                log::info!("client {handle} is reachable again");
+                ICaptureEvent::Recovered(handle)
+            }
+            ConnectionEvent::Reachable(handle, false) => {
+                self.unreachable.insert(handle);
+                return;
+            }
+            ConnectionEvent::AddressChanged(handle, addr) => {
+                ICaptureEvent::AddressChanged(handle, addr)
+            }
+            ConnectionEvent::Incompatible(handle, mismatch) => {
+                ICaptureEvent::Incompatible(handle, mismatch)
+            }
+            ConnectionEvent::Control(handle, message) => ICaptureEvent::Control(handle, message),
+        };
+        self.event_tx.send(event).expect("channel closed");
+    }
+
+    fn set_pointer_mode(&mut self, handle: CaptureHandle, pointer_mode: PointerMode) {
+        self.last_absolute.remove(&handle);
+        match pointer_mode {
+            PointerMode::Absolute => self.relative_pointer.remove(&handle),
+            PointerMode::Relative => self.relative_pointer.insert(handle),
+        };
+    }
+
+    fn set_always_forward(&mut self, handle: CaptureHandle, always_forward: bool) {
+        if always_forward {
+            self.always_forward.insert(handle);
+        } else {
+            self.always_forward.remove(&handle);
+        }
     }
 
     fn is_default_capture_at(&self, pos: Position) -> bool {
@@ -194,28 +622,65 @@ impl CaptureTask {
     }
 
     async fn run(mut self) {
+        // capture is recreated after a delay (see `CaptureClosed::Recreate`)
+        let mut recreating = false;
         loop {
-            if let Err(e) = self.do_capture().await {
-                log::warn!("input capture exited: {e}");
+            match self.do_capture().await {
+                // terminated
+                Ok(()) => {}
+                // failed while capturing, e.g. the compositor closed the stream
+                Err(InputCaptureError::Capture(e)) => {
+                    log::warn!("input capture closed unexpectedly: {e}");
+                    self.event_tx
+                        .send(ICaptureEvent::Closed(e.to_string()))
+                        .expect("channel closed");
+                }
+                // e.g. the compositor is not back yet, the next attempt is scheduled
+                Err(InputCaptureError::Create(e)) if recreating => {
+                    log::warn!("could not recreate input capture: {e}");
+                    self.event_tx
+                        .send(ICaptureEvent::Closed(e.to_string()))
+                        .expect("channel closed");
+                }
+                Err(e) => log::warn!("input capture exited: {e}"),
             }
-            loop {
+            let mut reenable_at = None;
+            recreating = loop {
                 tokio::select! {
                     r = self.request_rx.recv() => match r.expect("channel closed") {
-                        CaptureRequest::Reenable => break,
+                        CaptureRequest::Reenable => break false,
+                        CaptureRequest::ReenableAfter(d) => reenable_at = Some(self.clock.now() + d),
                         CaptureRequest::Create(h, p, t) => self.add_capture(h, p, t),
                         CaptureRequest::Destroy(h) => self.remove_capture(h),
                         CaptureRequest::Release => { /* nothing to do */ }
+                        CaptureRequest::AlwaysForward(h, a) => self.set_always_forward(h, a),
+                        CaptureRequest::PointerMode(h, m) => self.set_pointer_mode(h, m),
+                        CaptureRequest::ClickLock(h, b) => self.set_click_lock(h, b),
+                        CaptureRequest::BlockedKeys(h, k) => self.set_blocked_keys(h, k),
+                        CaptureRequest::ScrollAcceleration(h, p) => self.set_scroll_acceleration(h, p),
+                        CaptureRequest::ButtonMap(h, m) => self.set_button_map(h, m),
+                        CaptureRequest::Orientation(h, o) => self.set_orientation(h, o),
+                        CaptureRequest::MotionPrediction(h, m) => self.set_motion_prediction(h, m),
+                        CaptureRequest::Transforms(h, t) => self.set_transforms(h, t),
+                        CaptureRequest::Fallback(h, f) => self.set_fallback(h, f),
+                        CaptureRequest::Relay(h, e, a) => self.relay(h, e, a).await,
+                        CaptureRequest::TypeText(h, t) => self.type_text(h, t).await,
+                        CaptureRequest::ActionBinds(b) => self.action_binds = b,
                     },
+                    e = self.connection_events.recv() => {
+                        self.notify_connection_event(e.expect("channel closed"));
+                    }
+                    _ = wait_until(self.clock.as_ref(), reenable_at) => break true,
                     _ = self.cancellation_token.cancelled() => return,
                 }
-            }
+            };
         }
     }
 
     async fn do_capture(&mut self) -> Result<(), InputCaptureError> {
         /* allow cancelling capture request */
         let mut capture = tokio::select! {
-            r = InputCapture::new(self.backend) => r?,
+            r = InputCapture::with_options(self.backend, self.options.clone()) => r?,
             _ = self.cancellation_token.cancelled() => return Ok(()),
         };
 
@@ -256,14 +721,24 @@ impl CaptureTask {
         capture: &mut InputCapture,
     ) -> Result<(), InputCaptureError> {
         loop {
+            let switch_deadline = self.switch_deadline();
             tokio::select! {
                 event = capture.next() => match event {
                     Some(event) => self.handle_capture_event(capture, event?).await?,
-                    None => return Ok(()),
+                    None => return Err(CaptureError::EndOfStream.into()),
                 },
                 (handle, event) = self.conn.recv() => {
+                    // events of relayed clients are sent back to the originating device
+                    if let Some(&addr) = self.relays.get(&handle) {
+                        if self.active_client != Some(handle) {
+                            self.event_tx
+                                .send(ICaptureEvent::RelayReply(addr, event))
+                                .expect("channel closed");
+                            continue
+                        }
+                    }
                     if let Some(active) = self.active_client {
-                        if handle != active {
+                        if handle != self.target(active) {
                             // we only care about events coming from the client we are currently connected to
                             // only `Ack` and `Leave` are relevant
                             continue
@@ -274,8 +749,15 @@ impl CaptureTask {
                         // connection acknowlegded => set state to Sending
                         ProtoEvent::Ack(_) => {
                             log::info!("client {handle} acknowledged the connection!");
+                            if self.state == State::WaitingForAck {
+                                self.start_session(handle).await;
+                            }
                             self.state = State::Sending;
                         }
+                        // client pinned to always forward mode => stay
+                        ProtoEvent::Leave(_) if self.always_forward.contains(&handle) => {
+                            log::debug!("client {handle} left its device region - always forward is enabled");
+                        }
                         // client disconnected
                         ProtoEvent::Leave(_) => {
                             log::info!("releasing capture: left remote client device region");
@@ -285,7 +767,7 @@ impl CaptureTask {
                     }
                 },
                 e = self.request_rx.recv() => match e.expect("channel closed") {
-                    CaptureRequest::Reenable => { /* already active */ },
+                    CaptureRequest::Reenable | CaptureRequest::ReenableAfter(_) => { /* already active */ },
                     CaptureRequest::Release => self.release_capture(capture).await?,
                     CaptureRequest::Create(h, p, t) => {
                         self.add_capture(h, p, t);
@@ -295,7 +777,30 @@ impl CaptureTask {
                         self.remove_capture(h);
                         capture.destroy(h).await?;
                     }
+                    CaptureRequest::AlwaysForward(h, a) => {
+                        self.set_always_forward(h, a);
+                        if !a && self.active_client == Some(h) {
+                            log::info!("releasing capture: always forward disabled");
+                            self.release_capture(capture).await?;
+                        }
+                    }
+                    CaptureRequest::PointerMode(h, m) => self.set_pointer_mode(h, m),
+                    CaptureRequest::ClickLock(h, b) => self.set_click_lock(h, b),
+                    CaptureRequest::BlockedKeys(h, k) => self.set_blocked_keys(h, k),
+                    CaptureRequest::ScrollAcceleration(h, p) => self.set_scroll_acceleration(h, p),
+                    CaptureRequest::ButtonMap(h, m) => self.set_button_map(h, m),
+                    CaptureRequest::Orientation(h, o) => self.set_orientation(h, o),
+                    CaptureRequest::MotionPrediction(h, m) => self.set_motion_prediction(h, m),
+                    CaptureRequest::Transforms(h, t) => self.set_transforms(h, t),
+                    CaptureRequest::Fallback(h, f) => self.set_fallback(h, f),
+                    CaptureRequest::Relay(h, e, a) => self.relay(h, e, a).await,
+                    CaptureRequest::TypeText(h, t) => self.type_text(h, t).await,
+                    CaptureRequest::ActionBinds(b) => self.action_binds = b,
                 },
+                e = self.connection_events.recv() => {
+                    self.notify_connection_event(e.expect("channel closed"));
+                }
+                _ = wait_until(self.clock.as_ref(), switch_deadline) => self.confirm_switch(capture).await?,
                 _ = self.cancellation_token.cancelled() => break,
             }
         }
@@ -308,7 +813,7 @@ impl CaptureTask {
         event: (CaptureHandle, CaptureEvent),
     ) -> Result<(), CaptureError> {
         let (handle, event) = event;
-        log::trace!("({handle}): {event:?}");
+        log::trace!("({handle}): {event}");
 
         if capture.keys_pressed(&self.release_bind.borrow()) {
             log::info!("releasing capture: release-bind pressed");
@@ -333,10 +838,22 @@ impl CaptureTask {
             return Ok(());
         }
 
+        let Some(event) = self.push_sticky_edge(capture, handle, event).await? else {
+            return Ok(());
+        };
+
         // activated a new client
         if event == CaptureEvent::Begin && Some(handle) != self.active_client {
+            if let Some(previous) = self.active_client {
+                self.end_session(previous).await;
+            }
             self.state = State::WaitingForAck;
             self.active_client.replace(handle);
+            self.last_absolute.remove(&handle);
+            self.update_failover(handle).await;
+            if let Some(modifiers) = self.held_modifiers.as_mut() {
+                modifiers.held.clear();
+            }
             self.event_tx
                 .send(ICaptureEvent::ClientEntered(handle))
                 .expect("channel closed");
@@ -344,6 +861,94 @@ impl CaptureTask {
 
         let opposite_pos = to_proto_pos(self.get_pos(handle).opposite());
 
+        // absolute positions would warp the pointer of clients in relative mode,
+        // send the change of the position instead
+        let event = match event {
+            CaptureEvent::Input(Event::Pointer(PointerEvent::MotionAbsolute { time, x, y }))
+                if self.relative_pointer.contains(&handle) =>
+            {
+                // the first position only serves as reference
+                let Some((lx, ly)) = self.last_absolute.insert(handle, (x, y)) else {
+                    return Ok(());
+                };
+                let (width, height) = RELATIVE_POINTER_AREA;
+                CaptureEvent::Input(Event::Pointer(PointerEvent::Motion {
+                    time,
+                    dx: (x - lx) * width,
+                    dy: (y - ly) * height,
+                }))
+            }
+            event => event,
+        };
+
+        // orientation, button map and transforms, ahead of click-lock
+        // so click-lock applies to the translated buttons
+        let event = match (event, self.pipeline.get_mut(&handle)) {
+            (CaptureEvent::Input(e), Some(pipeline)) => match pipeline.apply(e) {
+                Some(e) => CaptureEvent::Input(e),
+                None => return Ok(()),
+            },
+            (event, _) => event,
+        };
+
+        // blocked keys, press and release alike
+        if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, .. })) = event {
+            if self
+                .blocked_keys
+                .get(&handle)
+                .is_some_and(|keys| keys.contains(&key))
+            {
+                return Ok(());
+            }
+        }
+
+        // key chords of named actions: the press completing the chord
+        // sends the action along with the chord, which the receiver
+        // emulates if it cannot perform the action. The key itself is
+        // not forwarded, unless the client does not accept actions.
+        if let CaptureEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, state, .. })) = event {
+            if self.action_keys.contains(&key) {
+                if state == 0 {
+                    self.action_keys.remove(&key);
+                }
+                return Ok(());
+            }
+            let target = self.target(handle);
+            if state == 1 && self.state == State::Sending && self.conn.accepts_control(target) {
+                let bind = self.action_binds.iter().find(|(_, keys)| {
+                    keys.iter().any(|&k| k as u32 == key) && capture.keys_pressed(keys)
+                });
+                if let Some((action, keys)) = bind {
+                    let message = ControlMessage::Action {
+                        action: *action,
+                        keys: keys.iter().map(|&k| k as u32).collect(),
+                    };
+                    log::debug!("client {handle}: sending action `{action}`");
+                    self.action_keys.insert(key);
+                    self.send_control(target, message).await;
+                    return Ok(());
+                }
+            }
+        }
+
+        // click-lock translation
+        if let CaptureEvent::Input(Event::Pointer(PointerEvent::Button { button, state, .. })) =
+            event
+        {
+            if let Some(click_lock) = self.click_lock.get_mut(&handle) {
+                if !click_lock.filter(button, state, self.clock.now()) {
+                    return Ok(());
+                }
+            }
+        }
+
+        let event = match (event, self.scroll_acceleration.get_mut(&handle)) {
+            (CaptureEvent::Input(Event::Pointer(p)), Some(acceleration)) => {
+                CaptureEvent::Input(Event::Pointer(acceleration.apply(p, self.clock.now())))
+            }
+            (event, _) => event,
+        };
+
         let event = match event {
             CaptureEvent::Begin => ProtoEvent::Enter(opposite_pos),
             CaptureEvent::Input(e) => match self.state {
@@ -351,9 +956,64 @@ impl CaptureTask {
                 State::WaitingForAck => ProtoEvent::Enter(opposite_pos),
                 State::Sending => ProtoEvent::Input(e),
             },
+            CaptureEvent::Frame => match self.state {
+                State::WaitingForAck => return Ok(()),
+                State::Sending => ProtoEvent::Frame,
+            },
+            // sent regardless of the state, as it is only reported on change,
+            // but skipped for clients not supporting it (see `is_supported`)
+            CaptureEvent::Origin(id) => ProtoEvent::Origin(id),
         };
 
-        if let Err(e) = self.conn.send(event, handle).await {
+        // client or its fallback
+        let target = self.target(handle);
+
+        let event = match (event, self.motion_prediction.get_mut(&handle)) {
+            (
+                ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy })),
+                Some(prediction),
+            ) => {
+                let latency = self.conn.latency(target).unwrap_or_default();
+                let (dx, dy) = prediction.apply(dx, dy, latency, self.clock.now());
+                ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy }))
+            }
+            (event, _) => event,
+        };
+
+        // take back the prediction ahead of buttons and keys,
+        // so they apply where the cursor actually is
+        if let (
+            ProtoEvent::Input(
+                Event::Pointer(PointerEvent::Button { time, .. })
+                | Event::Keyboard(KeyboardEvent::Key { time, .. }),
+            ),
+            Some(prediction),
+        ) = (event, self.motion_prediction.get_mut(&handle))
+        {
+            if let Some((dx, dy)) = prediction.take_back() {
+                let motion =
+                    ProtoEvent::Input(Event::Pointer(PointerEvent::Motion { time, dx, dy }));
+                if let Err(e) = self.conn.send(motion, target).await {
+                    log::debug!("taking back motion prediction: {e}");
+                }
+            }
+        }
+
+        // guard against missed modifier events on the client
+        if let (
+            ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key { key, state, .. })),
+            Some(modifiers),
+        ) = (event, self.held_modifiers.as_mut())
+        {
+            for modifier in modifiers.reassert(key, state) {
+                let modifier = ProtoEvent::Input(Event::Keyboard(modifier));
+                if let Err(e) = self.conn.send(modifier, target).await {
+                    log::debug!("reasserting modifiers: {e}");
+                }
+            }
+        }
+
+        if let Err(e) = self.conn.send(event, target).await {
             const DUR: Duration = Duration::from_millis(500);
             debounce!(PREV_LOG, DUR, log::warn!("releasing capture: {e}"));
             capture.release().await?;
@@ -361,16 +1021,722 @@ impl CaptureTask {
         Ok(())
     }
 
+    /// Hold back entering a client with a sticky edge until the cursor
+    /// was pushed far enough beyond the edge, or with tap to switch
+    /// until the edge was tapped or clicked.
+    /// Returns the event to handle, if any.
+    async fn push_sticky_edge(
+        &mut self,
+        capture: &mut InputCapture,
+        handle: CaptureHandle,
+        event: CaptureEvent,
+    ) -> Result<Option<CaptureEvent>, CaptureError> {
+        if Some(handle) == self.active_client {
+            return Ok(Some(event));
+        }
+        let pos = self.get_pos(handle);
+        let threshold = self.sticky_edge.threshold(pos);
+        let confirm = self.sticky_edge.confirm;
+        // a confirmed switch replaces pushing and tapping
+        let tap = self.tap_to_switch.enabled(pos) && confirm.is_none();
+        if threshold <= 0. && !tap && confirm.is_none() {
+            return Ok(Some(event));
+        }
+        if event == CaptureEvent::Begin {
+            if self
+                .pushing
+                .take_if(|p| p.handle == handle && p.confirmed)
+                .is_some()
+            {
+                return Ok(Some(event));
+            }
+            log::debug!("pushing against held back edge ({pos})");
+            self.pushing = Some(Push::new(handle, self.clock.now()));
+            if let Some(countdown) = confirm {
+                self.event_tx
+                    .send(ICaptureEvent::SwitchPending(handle, countdown))
+                    .expect("channel closed");
+            }
+            return Ok(None);
+        }
+        let Some(push) = self.pushing.as_mut() else {
+            return Ok(Some(event));
+        };
+        if push.handle != handle {
+            return Ok(Some(event));
+        }
+        match event {
+            CaptureEvent::Input(Event::Pointer(PointerEvent::Motion { dx, dy, .. })) => {
+                push.distance += match pos {
+                    Position::Left => -dx,
+                    Position::Right => dx,
+                    Position::Top => -dy,
+                    Position::Bottom => dy,
+                };
+                push.max_distance = push.max_distance.max(push.distance);
+                // too late for a tap and nothing else enters the client
+                let expired = tap
+                    && threshold <= 0.
+                    && !push.clicked
+                    && !self.tap_to_switch.in_time(push, self.clock.now());
+                if expired {
+                    log::debug!("releasing capture: edge not tapped in time ({pos})");
+                    self.abort_push();
+                    capture.release().await?;
+                    return Ok(None);
+                }
+                if confirm.is_none() && threshold > 0. && push.distance >= threshold {
+                    self.pushing.take();
+                    return Ok(Some(CaptureEvent::Begin));
+                }
+                if push.distance < 0. {
+                    let tapped = tap && self.tap_to_switch.is_tap(push, self.clock.now());
+                    if tapped {
+                        self.pushing.take();
+                        log::debug!("edge tapped ({pos})");
+                        return Ok(Some(CaptureEvent::Begin));
+                    }
+                    log::debug!("releasing capture: backed away from held back edge");
+                    self.abort_push();
+                    capture.release().await?;
+                }
+            }
+            CaptureEvent::Input(Event::Pointer(PointerEvent::Button { state, .. })) if tap => {
+                // switch once the click is complete,
+                // so the client does not receive a lone release
+                if state == 0 && push.clicked {
+                    log::debug!("edge clicked ({pos})");
+                    self.pushing.take();
+                    return Ok(Some(CaptureEvent::Begin));
+                }
+                push.clicked = state != 0;
+            }
+            CaptureEvent::Frame | CaptureEvent::Origin(_) => {}
+            CaptureEvent::Input(event) => {
+                log::debug!("releasing capture: input while pushing against held back edge");
+                self.abort_push();
+                capture.release().await?;
+                self.event_tx
+                    .send(ICaptureEvent::LocalInput(local_input(event)))
+                    .expect("channel closed");
+            }
+            // handled above
+            CaptureEvent::Begin => {}
+        }
+        Ok(None)
+    }
+
+    /// end of the countdown of a pending switch, if any
+    fn switch_deadline(&self) -> Option<Instant> {
+        let confirm = self.sticky_edge.confirm?;
+        let push = self.pushing.as_ref()?;
+        Some(push.start + confirm)
+    }
+
+    /// enter the client of a pending switch after its countdown elapsed
+    async fn confirm_switch(&mut self, capture: &mut InputCapture) -> Result<(), CaptureError> {
+        let Some(push) = self.pushing.as_mut() else {
+            return Ok(());
+        };
+        log::debug!("switch to client {} confirmed", push.handle);
+        push.confirmed = true;
+        let handle = push.handle;
+        self.handle_capture_event(capture, (handle, CaptureEvent::Begin))
+            .await
+    }
+
+    /// stop pushing against a held back edge, cancelling a pending switch
+    fn abort_push(&mut self) {
+        if let Some(push) = self.pushing.take() {
+            if self.sticky_edge.confirm.is_some() {
+                self.event_tx
+                    .send(ICaptureEvent::SwitchCancelled(push.handle))
+                    .expect("channel closed");
+            }
+        }
+    }
+
     async fn release_capture(&mut self, capture: &mut InputCapture) -> Result<(), CaptureError> {
-        self.active_client.take();
+        self.abort_push();
+        if let Some(modifiers) = self.held_modifiers.as_mut() {
+            modifiers.held.clear();
+        }
+        self.action_keys.clear();
+        if let Some(handle) = self.active_client.take() {
+            self.release_locked_buttons(handle).await;
+            self.end_session(handle).await;
+        }
+        let dropped = capture.dropped_events();
+        if dropped > 0 {
+            log::debug!("{dropped} events dropped due to backpressure");
+        }
         capture.release().await
     }
+
+    /// announce the start of a new forwarding session to the client
+    async fn start_session(&mut self, handle: CaptureHandle) {
+        self.session = self.session.wrapping_add(1);
+        self.send_control(handle, ControlMessage::SessionStart(self.session))
+            .await;
+    }
+
+    /// announce the end of the forwarding session, if it was started
+    async fn end_session(&mut self, handle: CaptureHandle) {
+        if self.state == State::Sending {
+            self.send_control(
+                self.target(handle),
+                ControlMessage::SessionEnd(self.session),
+            )
+            .await;
+        }
+    }
+
+    async fn send_control(&self, handle: CaptureHandle, message: ControlMessage) {
+        if let Err(e) = self.conn.send_control(message.clone(), handle).await {
+            log::debug!("client {handle}: could not send {message}: {e}");
+        }
+    }
+
+    /// release buttons held by click-lock on the client
+    async fn release_locked_buttons(&mut self, handle: CaptureHandle) {
+        let target = self.target(handle);
+        let Some(click_lock) = self.click_lock.get_mut(&handle) else {
+            return;
+        };
+        for button in click_lock.take_held() {
+            log::debug!("click-lock: releasing button {button:#x}");
+            let event = ProtoEvent::Input(Event::Pointer(PointerEvent::Button {
+                time: 0,
+                button,
+                state: 0,
+            }));
+            if let Err(e) = self.conn.send(event, target).await {
+                log::warn!("click-lock: could not release button {button:#x}: {e}");
+            }
+        }
+    }
+}
+
+/// presses shorter than this toggle the lock of a button
+const CLICK_LOCK_THRESHOLD: Duration = Duration::from_millis(300);
+
+/// Translates a quick click of a button into a
+/// sustained press, that is released by the next press.
+struct ClickLock {
+    /// buttons with click-lock enabled
+    buttons: HashSet<u32>,
+    /// time of the last press of each button
+    pressed: HashMap<u32, Instant>,
+    /// buttons currently held by click-lock
+    locked: HashSet<u32>,
+    /// locked buttons that were pressed again,
+    /// their next release is forwarded
+    unlocking: HashSet<u32>,
+}
+
+impl ClickLock {
+    fn new(buttons: Vec<u32>) -> Self {
+        Self {
+            buttons: HashSet::from_iter(buttons),
+            pressed: Default::default(),
+            locked: Default::default(),
+            unlocking: Default::default(),
+        }
+    }
+
+    /// returns whether the button event should be forwarded
+    fn filter(&mut self, button: u32, state: u32, now: Instant) -> bool {
+        if !self.buttons.contains(&button) {
+            return true;
+        }
+        if state != 0 {
+            // pressing a locked button releases it on the following release
+            if self.locked.remove(&button) {
+                self.unlocking.insert(button);
+                return false;
+            }
+            self.pressed.insert(button, now);
+            return true;
+        }
+        if self.unlocking.remove(&button) {
+            return true;
+        }
+        match self.pressed.remove(&button) {
+            Some(t) if now.duration_since(t) < CLICK_LOCK_THRESHOLD => {
+                log::debug!("click-lock: holding button {button:#x}");
+                self.locked.insert(button);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// buttons held on the remote side due to click-lock, resets the lock state
+    fn take_held(&mut self) -> Vec<u32> {
+        self.pressed.clear();
+        self.locked.drain().chain(self.unlocking.drain()).collect()
+    }
+}
+
+/// highest linux button code of a mouse (BTN_TASK)
+const MAX_MOUSE_BUTTON: u32 = 0x117;
+
+/// area in pixels absolute positions are spread across
+/// when sent as relative motion to clients in relative pointer mode
+const RELATIVE_POINTER_AREA: (f64, f64) = (1920., 1080.);
+
+/// Pipeline transforming the input sent to a client.
+/// Stages apply in a fixed order: the `orientation` (a `rotate` stage),
+/// the `button_map` (`remap` stages applied at once, so buttons can be
+/// swapped), then the configured `transforms` in their configured order.
+/// Releases are sent as the button sent for their press,
+/// even if the pipeline changed in between.
+#[derive(Default)]
+struct Pipeline {
+    orientation: lan_mouse_ipc::Orientation,
+    button_map: Option<ButtonMap>,
+    transforms: Vec<Transform>,
+    /// button sent for each button currently pressed
+    pressed: HashMap<u32, u32>,
+}
+
+impl Pipeline {
+    /// `None` if a stage discarded the event
+    fn apply(&mut self, event: Event) -> Option<Event> {
+        if let Event::Pointer(PointerEvent::Button {
+            time,
+            button,
+            state: 0,
+        }) = event
+        {
+            if let Some(button) = self.pressed.remove(&button) {
+                return Some(Event::Pointer(PointerEvent::Button {
+                    time,
+                    button,
+                    state: 0,
+                }));
+            }
+        }
+        let rotate = Transform::Rotate {
+            degrees: self.orientation,
+        };
+        let mapped = match (transform(rotate, event)?, self.button_map.as_ref()) {
+            (
+                Event::Pointer(PointerEvent::Button {
+                    time,
+                    button,
+                    state,
+                }),
+                Some(button_map),
+            ) => Event::Pointer(PointerEvent::Button {
+                time,
+                button: button_map.translate(button),
+                state,
+            }),
+            (event, _) => event,
+        };
+        let translated = self
+            .transforms
+            .iter()
+            .try_fold(mapped, |e, t| transform(*t, e))?;
+        if let (
+            Event::Pointer(PointerEvent::Button { button, state, .. }),
+            Event::Pointer(PointerEvent::Button { button: sent, .. }),
+        ) = (event, translated)
+        {
+            if state != 0 {
+                self.pressed.insert(button, sent);
+            }
+        }
+        Some(translated)
+    }
+}
+
+/// Translates the buttons sent to a client.
+struct ButtonMap {
+    map: HashMap<u32, u32>,
+}
+
+impl ButtonMap {
+    fn new(mut map: HashMap<u32, u32>) -> Self {
+        map.retain(|&from, &mut to| {
+            let valid = (input_event::BTN_LEFT..=MAX_MOUSE_BUTTON).contains(&to);
+            if !valid {
+                log::warn!("button map: ignoring invalid target button {to:#x} for {from:#x}");
+            }
+            valid
+        });
+        Self { map }
+    }
+
+    fn translate(&self, button: u32) -> u32 {
+        match self.map.get(&button) {
+            Some(&target) => target,
+            None => {
+                log::trace!("button map: passing through unmapped button {button:#x}");
+                button
+            }
+        }
+    }
+}
+
+/// successive scroll events closer together than this accelerate
+const SCROLL_ACCELERATION_WINDOW: Duration = Duration::from_millis(100);
+
+/// upper bound of the scroll acceleration factor
+const MAX_SCROLL_ACCELERATION: f64 = 4.;
+
+/// Accelerates scroll wheel events in quick succession.
+/// Touchpad scrolling is left untouched.
+struct ScrollAcceleration {
+    /// increase of the factor per successive event
+    step: f64,
+    factor: f64,
+    last: Option<Instant>,
+}
+
+impl ScrollAcceleration {
+    fn new(percent: u32) -> Self {
+        Self {
+            step: percent as f64 / 100.,
+            factor: 1.,
+            last: None,
+        }
+    }
+
+    fn apply(&mut self, event: PointerEvent, now: Instant) -> PointerEvent {
+        match event {
+            PointerEvent::Axis {
+                time,
+                axis,
+                value,
+                source: source @ ScrollSource::Wheel,
+            } => PointerEvent::Axis {
+                time,
+                axis,
+                value: value * self.update(now),
+                source,
+            },
+            PointerEvent::AxisDiscrete120 { axis, value } => PointerEvent::AxisDiscrete120 {
+                axis,
+                value: (value as f64 * self.update(now)).round() as i32,
+            },
+            event => event,
+        }
+    }
+
+    /// factor for an event at the given time
+    fn update(&mut self, now: Instant) -> f64 {
+        self.factor = match self.last {
+            Some(last) if now.duration_since(last) < SCROLL_ACCELERATION_WINDOW => {
+                (self.factor + self.step).min(MAX_SCROLL_ACCELERATION)
+            }
+            _ => 1.,
+        };
+        self.last = Some(now);
+        self.factor
+    }
+}
+
+/// motion events further apart than this end a movement
+const PREDICTION_IDLE: Duration = Duration::from_millis(50);
+
+/// Extrapolates relative motion by the distance the pointer
+/// travels during the one-way latency to the client, so the
+/// cursor on the client is where it will be once the event arrives.
+struct MotionPrediction {
+    /// upper bound of the prediction in pixels
+    max: f64,
+    /// smoothed velocity in pixels per second
+    velocity: (f64, f64),
+    /// offset of the cursor on the client caused by the prediction
+    offset: (f64, f64),
+    last: Option<Instant>,
+}
+
+impl MotionPrediction {
+    fn new(max: u32) -> Self {
+        Self {
+            max: max as f64,
+            velocity: (0., 0.),
+            offset: (0., 0.),
+            last: None,
+        }
+    }
+
+    /// motion `(dx, dy)` including the change of the prediction
+    fn apply(&mut self, dx: f64, dy: f64, latency: Duration, now: Instant) -> (f64, f64) {
+        let elapsed = self.last.map(|last| now.duration_since(last));
+        self.last = Some(now);
+        let (vx, vy) = self.velocity;
+        let reversed = dx * vx + dy * vy < 0.;
+        self.velocity = match elapsed {
+            Some(elapsed) if elapsed < PREDICTION_IDLE && !elapsed.is_zero() && !reversed => {
+                let secs = elapsed.as_secs_f64();
+                ((vx + dx / secs) / 2., (vy + dy / secs) / 2.)
+            }
+            // the pointer stopped or changed its direction:
+            // take back the prediction instead of overshooting
+            _ => (0., 0.),
+        };
+        let ahead = latency.as_secs_f64() / 2.;
+        let (mut px, mut py) = (self.velocity.0 * ahead, self.velocity.1 * ahead);
+        let distance = px.hypot(py);
+        if distance > self.max {
+            px *= self.max / distance;
+            py *= self.max / distance;
+        }
+        let (ox, oy) = std::mem::replace(&mut self.offset, (px, py));
+        (dx + px - ox, dy + py - oy)
+    }
+
+    /// motion taking back the prediction, if any
+    fn take_back(&mut self) -> Option<(f64, f64)> {
+        self.velocity = (0., 0.);
+        self.last = None;
+        let (ox, oy) = std::mem::take(&mut self.offset);
+        (ox != 0. || oy != 0.).then_some((-ox, -oy))
+    }
+}
+
+/// Input captured at a held back edge, to be emulated on this device.
+/// Presses are released right away: their release is not captured
+/// anymore and would not reach the emulated device.
+fn local_input(event: Event) -> Vec<Event> {
+    match event {
+        Event::Keyboard(KeyboardEvent::Key {
+            time,
+            key,
+            state: 1,
+        }) => vec![
+            event,
+            Event::Keyboard(KeyboardEvent::Key {
+                time,
+                key,
+                state: 0,
+            }),
+        ],
+        Event::Pointer(PointerEvent::Button {
+            time,
+            button,
+            state: 1,
+        }) => vec![
+            event,
+            Event::Pointer(PointerEvent::Button {
+                time,
+                button,
+                state: 0,
+            }),
+        ],
+        event => vec![event],
+    }
+}
+
+/// apply a stage of the transform pipeline, `None` if the event is discarded
+fn transform(transform: Transform, event: Event) -> Option<Event> {
+    let Event::Pointer(pointer) = event else {
+        return Some(event);
+    };
+    let pointer = match (transform, pointer) {
+        (Transform::Scale { x, y }, PointerEvent::Motion { time, dx, dy }) => {
+            PointerEvent::Motion {
+                time,
+                dx: dx * x as f64 / 100.,
+                dy: dy * y as f64 / 100.,
+            }
+        }
+        (Transform::Invert { x, y }, PointerEvent::Motion { time, dx, dy }) => {
+            PointerEvent::Motion {
+                time,
+                dx: if x { -dx } else { dx },
+                dy: if y { -dy } else { dy },
+            }
+        }
+        (Transform::Rotate { degrees }, PointerEvent::Motion { time, dx, dy }) => {
+            let (dx, dy) = degrees.rotate(dx, dy);
+            PointerEvent::Motion { time, dx, dy }
+        }
+        (
+            Transform::Remap { from, to },
+            PointerEvent::Button {
+                time,
+                button,
+                state,
+            },
+        ) if button == from => PointerEvent::Button {
+            time,
+            button: to,
+            state,
+        },
+        (Transform::Clamp { max }, PointerEvent::Motion { time, dx, dy }) => {
+            let distance = dx.hypot(dy);
+            let max = max as f64;
+            match distance > max {
+                true => PointerEvent::Motion {
+                    time,
+                    dx: dx * max / distance,
+                    dy: dy * max / distance,
+                },
+                false => pointer,
+            }
+        }
+        (Transform::DeadZone { min }, PointerEvent::Motion { dx, dy, .. }) => {
+            if dx.hypot(dy) < min as f64 {
+                return None;
+            }
+            pointer
+        }
+        _ => pointer,
+    };
+    Some(Event::Pointer(pointer))
 }
 
 thread_local! {
     static PREV_LOG: Cell<Option<Instant>> = const { Cell::new(None) };
 }
 
+/// distance the cursor has to be pushed beyond
+/// the edge of each position before entering a client
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct StickyEdge {
+    pub(crate) left: f64,
+    pub(crate) right: f64,
+    pub(crate) top: f64,
+    pub(crate) bottom: f64,
+    /// countdown the cursor has to stay at the edge
+    /// before entering a client, if confirmation is required
+    pub(crate) confirm: Option<Duration>,
+}
+
+impl StickyEdge {
+    fn threshold(&self, pos: Position) -> f64 {
+        match pos {
+            Position::Left => self.left,
+            Position::Right => self.right,
+            Position::Top => self.top,
+            Position::Bottom => self.bottom,
+        }
+    }
+}
+
+const MODIFIERS: [scancode::Linux; 8] = [
+    scancode::Linux::KeyLeftShift,
+    scancode::Linux::KeyRightShift,
+    scancode::Linux::KeyLeftCtrl,
+    scancode::Linux::KeyRightCtrl,
+    scancode::Linux::KeyLeftAlt,
+    scancode::Linux::KeyRightalt,
+    scancode::Linux::KeyLeftMeta,
+    scancode::Linux::KeyRightmeta,
+];
+
+/// modifier keys forwarded to the active client
+#[derive(Debug, Default)]
+struct HeldModifiers {
+    held: HashSet<u32>,
+}
+
+impl HeldModifiers {
+    /// Track the modifiers of a forwarded key event.
+    /// Returns the events reasserting the state of every modifier,
+    /// if the event presses a key other than a modifier.
+    fn reassert(&mut self, key: u32, state: u8) -> Vec<KeyboardEvent> {
+        if MODIFIERS.iter().any(|&m| m as u32 == key) {
+            match state {
+                0 => self.held.remove(&key),
+                _ => self.held.insert(key),
+            };
+            return vec![];
+        }
+        if state != 1 {
+            return vec![];
+        }
+        MODIFIERS
+            .iter()
+            .map(|&m| KeyboardEvent::Key {
+                time: 0,
+                key: m as u32,
+                state: self.held.contains(&(m as u32)) as u8,
+            })
+            .collect()
+    }
+}
+
+/// Edges entering a client when tapped, i.e. a quick motion beyond the
+/// edge and back, or clicked while pushing against them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TapToSwitch {
+    pub(crate) left: bool,
+    pub(crate) right: bool,
+    pub(crate) top: bool,
+    pub(crate) bottom: bool,
+    /// maximum duration of a tap
+    pub(crate) timeout: Duration,
+    /// minimum distance beyond the edge before returning
+    pub(crate) distance: f64,
+}
+
+impl Default for TapToSwitch {
+    fn default() -> Self {
+        Self {
+            left: false,
+            right: false,
+            top: false,
+            bottom: false,
+            timeout: Duration::from_millis(300),
+            distance: 10.,
+        }
+    }
+}
+
+impl TapToSwitch {
+    fn enabled(&self, pos: Position) -> bool {
+        match pos {
+            Position::Left => self.left,
+            Position::Right => self.right,
+            Position::Top => self.top,
+            Position::Bottom => self.bottom,
+        }
+    }
+
+    fn is_tap(&self, push: &Push, now: Instant) -> bool {
+        push.max_distance >= self.distance && self.in_time(push, now)
+    }
+
+    /// whether the push can still become a tap
+    fn in_time(&self, push: &Push, now: Instant) -> bool {
+        now.duration_since(push.start) <= self.timeout
+    }
+}
+
+/// motion beyond a held back edge
+#[derive(Clone, Copy, Debug)]
+struct Push {
+    handle: CaptureHandle,
+    start: Instant,
+    /// distance pushed so far
+    distance: f64,
+    /// furthest distance reached
+    max_distance: f64,
+    /// a button was pressed at the edge
+    clicked: bool,
+    /// the countdown of a pending switch elapsed
+    confirmed: bool,
+}
+
+impl Push {
+    fn new(handle: CaptureHandle, start: Instant) -> Self {
+        Self {
+            handle,
+            start,
+            distance: 0.,
+            max_distance: 0.,
+            clicked: false,
+            confirmed: false,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 enum State {
     #[default]
@@ -378,6 +1744,18 @@ enum State {
     Sending,
 }
 
+/// wait until the given deadline or forever if there is none
+async fn wait_until(clock: &dyn Clock, deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => {
+            clock
+                .sleep(deadline.saturating_duration_since(clock.now()))
+                .await
+        }
+        None => futures::future::pending().await,
+    }
+}
+
 fn to_capture_pos(pos: lan_mouse_ipc::Position) -> input_capture::Position {
     match pos {
         lan_mouse_ipc::Position::Left => input_capture::Position::Left,
@@ -416,3 +1794,87 @@ impl<T> Drop for DropGuard<T> {
             .expect("channel closed");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(dx: f64, dy: f64) -> Event {
+        Event::Pointer(PointerEvent::Motion { time: 0, dx, dy })
+    }
+
+    fn button(button: u32, state: u32) -> Event {
+        Event::Pointer(PointerEvent::Button {
+            time: 0,
+            button,
+            state,
+        })
+    }
+
+    #[test]
+    fn prediction_taken_back() {
+        let mut prediction = MotionPrediction::new(100);
+        let latency = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut sent = (0., 0.);
+        for i in 0..5 {
+            let now = start + Duration::from_millis(10 * i);
+            let (dx, dy) = prediction.apply(10., 5., latency, now);
+            sent = (sent.0 + dx, sent.1 + dy);
+        }
+        assert!(sent.0 > 50. && sent.1 > 25.);
+        let (dx, dy) = prediction.take_back().expect("predicted");
+        assert_eq!((sent.0 + dx, sent.1 + dy), (50., 25.));
+        assert_eq!(prediction.take_back(), None);
+    }
+
+    #[test]
+    fn stages_apply_in_order() {
+        let scale = Transform::Scale { x: 200, y: 100 };
+        let rotate = Transform::Rotate {
+            degrees: lan_mouse_ipc::Orientation::Rotated90,
+        };
+        let dead_zone = Transform::DeadZone { min: 3 };
+        let mut pipeline = Pipeline {
+            transforms: vec![scale, rotate],
+            ..Default::default()
+        };
+        assert_eq!(pipeline.apply(motion(1., 2.)), Some(motion(-2., 2.)));
+        pipeline.transforms = vec![rotate, scale];
+        assert_eq!(pipeline.apply(motion(1., 2.)), Some(motion(-4., 1.)));
+        pipeline.transforms = vec![dead_zone, scale];
+        assert_eq!(pipeline.apply(motion(2., 0.)), None);
+        pipeline.transforms = vec![scale, dead_zone];
+        assert_eq!(pipeline.apply(motion(2., 0.)), Some(motion(4., 0.)));
+    }
+
+    #[test]
+    fn orientation_and_button_map_come_first() {
+        let (left, right, middle) = (0x110, 0x111, 0x112);
+        let mut pipeline = Pipeline {
+            orientation: lan_mouse_ipc::Orientation::Rotated90,
+            button_map: Some(ButtonMap::new(HashMap::from([
+                (left, right),
+                (right, left),
+            ]))),
+            transforms: vec![
+                Transform::Scale { x: 200, y: 100 },
+                Transform::Remap {
+                    from: left,
+                    to: middle,
+                },
+            ],
+            ..Default::default()
+        };
+        // rotated, then scaled
+        assert_eq!(pipeline.apply(motion(1., 2.)), Some(motion(-4., 1.)));
+        // swapped by the button map, then remapped
+        assert_eq!(pipeline.apply(button(right, 1)), Some(button(middle, 1)));
+        assert_eq!(pipeline.apply(button(left, 1)), Some(button(right, 1)));
+        // released like their press, after the pipeline changed
+        pipeline.button_map = None;
+        assert_eq!(pipeline.apply(button(right, 0)), Some(button(middle, 0)));
+        assert_eq!(pipeline.apply(button(left, 0)), Some(button(right, 0)));
+        assert_eq!(pipeline.apply(button(left, 1)), Some(button(middle, 1)));
+    }
+}
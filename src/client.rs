@@ -1,23 +1,44 @@
 use std::{
     cell::RefCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     net::{IpAddr, SocketAddr},
     rc::Rc,
 };
 
 use slab::Slab;
+use thiserror::Error;
 
-use lan_mouse_ipc::{ClientConfig, ClientHandle, ClientState, Position};
+use lan_mouse_ipc::{
+    AddressFamily, ClientConfig, ClientHandle, ClientState, FallbackRecovery, Orientation,
+    PointerMode, Position, Transform, VersionMismatch, WireFormat,
+};
+
+#[derive(Debug, Error)]
+#[error("maximum number of clients ({0}) reached")]
+pub struct ClientLimitError(pub usize);
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct ClientManager {
     clients: Rc<RefCell<Slab<(ClientConfig, ClientState)>>>,
+    max_clients: usize,
 }
 
 impl ClientManager {
+    /// create a manager holding at most `max_clients` clients
+    pub fn new(max_clients: usize) -> Self {
+        Self {
+            clients: Default::default(),
+            max_clients,
+        }
+    }
+
     /// add a new client to this manager
-    pub fn add_client(&self) -> ClientHandle {
-        self.clients.borrow_mut().insert(Default::default()) as ClientHandle
+    pub fn add_client(&self) -> Result<ClientHandle, ClientLimitError> {
+        let mut clients = self.clients.borrow_mut();
+        if clients.len() >= self.max_clients {
+            return Err(ClientLimitError(self.max_clients));
+        }
+        Ok(clients.insert(Default::default()) as ClientHandle)
     }
 
     /// set the config of the given client
@@ -143,6 +164,35 @@ impl ClientManager {
         self.update_ips(handle);
     }
 
+    /// find a client by its certificate fingerprint
+    pub(crate) fn get_client_by_fingerprint(&self, fingerprint: &str) -> Option<ClientHandle> {
+        self.clients
+            .borrow()
+            .iter()
+            .find(|(_, (c, _))| c.fingerprint.as_deref() == Some(fingerprint))
+            .map(|(k, _)| k as ClientHandle)
+    }
+
+    /// find a client by its uuid
+    pub fn get_client_by_uuid(&self, uuid: &str) -> Option<ClientHandle> {
+        self.clients
+            .borrow()
+            .iter()
+            .find(|(_, (c, _))| c.uuid == uuid)
+            .map(|(k, _)| k as ClientHandle)
+    }
+
+    /// associate the client with the address it was seen at.
+    /// Returns false if the address is known already
+    pub(crate) fn reassociate(&self, handle: ClientHandle, ip: IpAddr) -> bool {
+        match self.clients.borrow_mut().get_mut(handle as usize) {
+            Some((_, s)) if !s.ips.contains(&ip) => s.reassociated_ip = Some(ip),
+            _ => return false,
+        }
+        self.update_ips(handle);
+        true
+    }
+
     fn update_ips(&self, handle: ClientHandle) {
         if let Some((c, s)) = self.clients.borrow_mut().get_mut(handle as usize) {
             s.ips = c
@@ -150,6 +200,7 @@ impl ClientManager {
                 .iter()
                 .cloned()
                 .chain(s.dns_ips.iter().cloned())
+                .chain(s.reassociated_ip)
                 .collect::<HashSet<_>>();
         }
     }
@@ -206,6 +257,159 @@ impl ClientManager {
         }
     }
 
+    /// update the always forward mode of the client
+    pub(crate) fn set_always_forward(&self, handle: ClientHandle, always_forward: bool) {
+        if let Some((c, _s)) = self.clients.borrow_mut().get_mut(handle as usize) {
+            c.always_forward = always_forward;
+        }
+    }
+
+    /// whether the client is pinned to always forward mode
+    pub(crate) fn always_forward(&self, handle: ClientHandle) -> bool {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.always_forward)
+            .unwrap_or(false)
+    }
+
+    /// switch the client between absolute and relative pointer mode
+    pub(crate) fn set_pointer_mode(&self, handle: ClientHandle, pointer_mode: PointerMode) {
+        if let Some((c, _s)) = self.clients.borrow_mut().get_mut(handle as usize) {
+            c.pointer_mode = pointer_mode;
+        }
+    }
+
+    /// how pointer positions are forwarded to the client
+    pub(crate) fn pointer_mode(&self, handle: ClientHandle) -> PointerMode {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.pointer_mode)
+            .unwrap_or_default()
+    }
+
+    /// update the wire format used for the client
+    pub(crate) fn set_wire_format(&self, handle: ClientHandle, wire_format: WireFormat) {
+        if let Some((c, _s)) = self.clients.borrow_mut().get_mut(handle as usize) {
+            c.wire_format = wire_format;
+        }
+    }
+
+    /// get the wire format used for the client
+    pub(crate) fn get_wire_format(&self, handle: ClientHandle) -> Option<WireFormat> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.wire_format)
+    }
+
+    /// buttons with click-lock enabled
+    pub(crate) fn get_click_lock(&self, handle: ClientHandle) -> Vec<u32> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.click_lock.clone())
+            .unwrap_or_default()
+    }
+
+    /// keys that are not sent to the client
+    pub(crate) fn get_blocked_keys(&self, handle: ClientHandle) -> Vec<u32> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.blocked_keys.clone())
+            .unwrap_or_default()
+    }
+
+    /// translation of buttons sent to the client
+    pub(crate) fn get_button_map(&self, handle: ClientHandle) -> HashMap<u32, u32> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.button_map.clone())
+            .unwrap_or_default()
+    }
+
+    /// address family to connect with first
+    pub(crate) fn get_preferred_family(&self, handle: ClientHandle) -> Option<AddressFamily> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .and_then(|(c, _)| c.preferred_family)
+    }
+
+    /// time to live and DSCP marking of the datagrams sent to the client
+    pub(crate) fn get_socket_options(&self, handle: ClientHandle) -> (Option<u8>, Option<u8>) {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| (c.ttl, c.dscp))
+            .unwrap_or_default()
+    }
+
+    /// hostname of the client used while the client is unreachable
+    /// and the behavior once it is reachable again
+    pub(crate) fn get_fallback(&self, handle: ClientHandle) -> Option<(String, FallbackRecovery)> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .and_then(|(c, _)| Some((c.fallback.clone()?, c.fallback_recovery)))
+    }
+
+    /// find a client by its hostname
+    pub(crate) fn find_hostname(&self, hostname: &str) -> Option<ClientHandle> {
+        self.clients
+            .borrow()
+            .iter()
+            .find(|(_, (c, _))| c.hostname.as_deref() == Some(hostname))
+            .map(|(k, _)| k as ClientHandle)
+    }
+
+    /// scroll acceleration in percent per successive scroll event
+    pub(crate) fn get_scroll_acceleration(&self, handle: ClientHandle) -> u32 {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.scroll_acceleration)
+            .unwrap_or_default()
+    }
+
+    /// upper bound of the motion prediction in pixels
+    pub(crate) fn get_motion_prediction(&self, handle: ClientHandle) -> u32 {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.motion_prediction)
+            .unwrap_or_default()
+    }
+
+    /// transforms applied to the input sent to the client
+    pub(crate) fn get_transforms(&self, handle: ClientHandle) -> Vec<Transform> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.transforms.clone())
+            .unwrap_or_default()
+    }
+
+    /// rotation of the display of the client
+    pub(crate) fn get_orientation(&self, handle: ClientHandle) -> Orientation {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(c, _)| c.orientation)
+            .unwrap_or_default()
+    }
+
+    /// id of the client on the relay hub, if the client is reached through one
+    pub(crate) fn get_relay_target(&self, handle: ClientHandle) -> Option<ClientHandle> {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .and_then(|(c, _)| c.relay_target)
+    }
+
     /// set resolving status of the client
     pub(crate) fn set_resolving(&self, handle: ClientHandle, status: bool) {
         if let Some((_, s)) = self.clients.borrow_mut().get_mut(handle as usize) {
@@ -243,6 +447,50 @@ impl ClientManager {
         }
     }
 
+    /// set the estimated clock offset of the client
+    pub(crate) fn set_clock_offset(&self, handle: ClientHandle, offset: Option<i64>) {
+        if let Some((_, s)) = self.clients.borrow_mut().get_mut(handle as usize) {
+            s.clock_offset = offset;
+        }
+    }
+
+    /// newest wire format version the client reported to understand
+    pub(crate) fn get_wire_version(&self, handle: ClientHandle) -> u8 {
+        self.clients
+            .borrow()
+            .get(handle as usize)
+            .map(|(_, s)| s.wire_version)
+            .unwrap_or_default()
+    }
+
+    /// set the newest wire format version the client reported to understand.
+    /// Returns true if the state changed
+    pub(crate) fn set_wire_version(&self, handle: ClientHandle, version: u8) -> bool {
+        match self.clients.borrow_mut().get_mut(handle as usize) {
+            Some((_, s)) if s.wire_version != version => {
+                s.wire_version = version;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// set the protocol version mismatch of the client.
+    /// Returns true if the state changed
+    pub(crate) fn set_incompatible(
+        &self,
+        handle: ClientHandle,
+        incompatible: Option<VersionMismatch>,
+    ) -> bool {
+        match self.clients.borrow_mut().get_mut(handle as usize) {
+            Some((_, s)) if s.incompatible != incompatible => {
+                s.incompatible = incompatible;
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn active_addr(&self, handle: ClientHandle) -> Option<SocketAddr> {
         self.clients
             .borrow()
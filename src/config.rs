@@ -1,5 +1,11 @@
+use crate::capture::{CaptureClosed, StickyEdge, TapToSwitch};
 use crate::capture_test::TestCaptureArgs;
+use crate::emulation::{
+    ActionHandler, Deadman, EntryMonitor, EntryOffset, EntrySnap, KeyboardMerge, Monitor,
+};
 use crate::emulation_test::TestEmulationArgs;
+use crate::grid::{Grid, GridError};
+use crate::lossy::LossSimulation;
 use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,12 +14,17 @@ use std::fmt::Display;
 use std::fs;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::{collections::HashSet, io};
 use thiserror::Error;
 use toml;
 
 use lan_mouse_cli::CliArgs;
-use lan_mouse_ipc::{Position, DEFAULT_PORT};
+use lan_mouse_ipc::{
+    generate_uuid, name_uuid, AddressFamily, ClientConfig, ClientState, FallbackRecovery,
+    Orientation, PointerMode, Position, Transform, WireFormat, DEFAULT_PORT,
+};
+use lan_mouse_proto::Action;
 
 use input_event::scancode::{
     self,
@@ -27,6 +38,13 @@ shadow!(build);
 const CONFIG_FILE_NAME: &str = "config.toml";
 const CERT_FILE_NAME: &str = "lan-mouse.pem";
 
+/// maximum distance of a received motion event in pixels,
+/// well above the motion of fast, legitimate mouse movement
+const DEFAULT_MAX_MOTION: u32 = 2000;
+
+/// largest value of the 6 bit DSCP field
+const MAX_DSCP: u8 = 63;
+
 fn default_path() -> Result<PathBuf, VarError> {
     #[cfg(unix)]
     let default_path = {
@@ -44,7 +62,7 @@ fn default_path() -> Result<PathBuf, VarError> {
     Ok(PathBuf::from(default_path))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
 struct ConfigToml {
     capture_backend: Option<CaptureBackend>,
     emulation_backend: Option<EmulationBackend>,
@@ -53,10 +71,183 @@ struct ConfigToml {
     cert_path: Option<PathBuf>,
     clients: Option<Vec<TomlClient>>,
     authorized_fingerprints: Option<HashMap<String, String>>,
+    backpressure: Option<TomlBackpressure>,
+    grab: Option<GrabMode>,
+    relay: Option<bool>,
+    frame_grouping: Option<bool>,
+    raw_motion: Option<bool>,
+    suppress_repeat: Option<bool>,
+    forward_absolute_motion: Option<bool>,
+    capture_seats: Option<Vec<String>>,
+    capture_focus_filter: Option<String>,
+    reassert_modifiers: Option<bool>,
+    emulation_warmup: Option<u64>,
+    emulation_min_spacing_us: Option<u64>,
+    debug_raw_packets: Option<bool>,
+    log_key_data: Option<bool>,
+    log_connection_lifecycle: Option<bool>,
+    system_log: Option<bool>,
+    flap_damping_ms: Option<u64>,
+    retransmit_critical: Option<u32>,
+    dedup_window: Option<u32>,
+    clock_sync_interval_ms: Option<u64>,
+    ignore_unknown_events: Option<bool>,
+    ignore_invalid_events: Option<bool>,
+    max_fling_ms: Option<u64>,
+    emulation_frame_rate: Option<u32>,
+    rebind_removed_devices: Option<bool>,
+    restart_emulation_on_failure: Option<bool>,
+    emulation_recreate_interval_ms: Option<u64>,
+    on_capture_closed: Option<TomlCaptureClosed>,
+    deadman_timeout_ms: Option<u64>,
+    port_change_grace_ms: Option<u64>,
+    max_session_duration_ms: Option<u64>,
+    motion_smoothing: Option<u32>,
+    precise_motion: Option<bool>,
+    max_clients: Option<usize>,
+    discrete_scroll_multiplier: Option<i32>,
+    discrete_scroll_divisor: Option<i32>,
+    max_motion: Option<u32>,
+    scroll_throttle_ms: Option<u64>,
+    emulation_flush_retry_ms: Option<u64>,
+    emulation_handshake_timeout_ms: Option<u64>,
+    early_motion_buffer_ms: Option<u64>,
+    idle_frame_interval_ms: Option<u64>,
+    log_frame_counters_ms: Option<u64>,
+    separate_origins: Option<bool>,
+    keyboard_merge: Option<TomlKeyboardMerge>,
+    inhibit_idle: Option<bool>,
+    create_unknown_handles: Option<bool>,
+    degradation: Option<TomlDegradation>,
+    scroll_preference: Option<ScrollPreference>,
+    emit_zero_deltas: Option<bool>,
+    sticky_edge: Option<TomlStickyEdge>,
+    tap_to_switch: Option<TomlTapToSwitch>,
+    entry_snap: Option<TomlEntrySnap>,
+    grid: Option<Vec<Vec<String>>>,
+    grid_name: Option<String>,
+    edges: Option<HashMap<Position, String>>,
+    debug_loss_simulation: Option<TomlLossSimulation>,
+    actions: Option<HashMap<String, TomlAction>>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TomlStickyEdge {
+    left: Option<f64>,
+    right: Option<f64>,
+    top: Option<f64>,
+    bottom: Option<f64>,
+    confirm_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TomlTapToSwitch {
+    left: Option<bool>,
+    right: Option<bool>,
+    top: Option<bool>,
+    bottom: Option<bool>,
+    timeout_ms: Option<u64>,
+    distance: Option<f64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+struct TomlDegradation {
+    scroll: Option<ScrollFallback>,
+    scroll_buttons: Option<TomlScrollButtons>,
+    absolute: Option<AbsoluteFallback>,
+    absolute_width: Option<u32>,
+    absolute_height: Option<u32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+struct TomlScrollButtons {
+    up: Option<u32>,
+    down: Option<u32>,
+    left: Option<u32>,
+    right: Option<u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TomlEntrySnap {
+    monitors: Option<Vec<TomlMonitor>>,
+    ambiguous: Option<TomlEntryMonitor>,
+    offset: Option<TomlEntryOffset>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+struct TomlEntryOffset {
+    left: Option<i32>,
+    right: Option<i32>,
+    top: Option<i32>,
+    bottom: Option<i32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+struct TomlMonitor {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// handling of key events of origins sharing a keyboard
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+enum TomlKeyboardMerge {
+    #[serde(rename = "track")]
+    Track,
+    #[serde(rename = "raw")]
+    Raw,
+}
+
+/// handling of the input capture closing unexpectedly
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+enum TomlCaptureClosed {
+    #[serde(rename = "disable")]
+    Disable,
+    #[serde(rename = "recreate")]
+    Recreate,
+    #[serde(rename = "exit")]
+    Exit,
+}
+
+/// monitor entered if several monitors touch the entered edge
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+enum TomlEntryMonitor {
+    #[serde(rename = "first")]
+    First,
+    #[serde(rename = "last")]
+    Last,
+    #[serde(rename = "largest")]
+    Largest,
+}
+
+/// key chord and command of a named action
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+struct TomlAction {
+    keys: Option<Vec<scancode::Linux>>,
+    command: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TomlLossSimulation {
+    drop: Option<f64>,
+    delay: Option<f64>,
+    delay_ms: Option<u64>,
+    reorder: Option<f64>,
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+struct TomlBackpressure {
+    motion: Option<BackpressurePolicy>,
+    scroll: Option<BackpressurePolicy>,
+    button: Option<BackpressurePolicy>,
+    key: Option<BackpressurePolicy>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
 struct TomlClient {
+    uuid: Option<String>,
     hostname: Option<String>,
     host_name: Option<String>,
     ips: Option<Vec<IpAddr>>,
@@ -64,6 +255,23 @@ struct TomlClient {
     position: Option<Position>,
     activate_on_startup: Option<bool>,
     enter_hook: Option<String>,
+    always_forward: Option<bool>,
+    wire_format: Option<WireFormat>,
+    click_lock: Option<Vec<MouseButton>>,
+    scroll_acceleration: Option<u32>,
+    button_map: Option<HashMap<MouseButton, MouseButton>>,
+    relay_target: Option<u64>,
+    preferred_family: Option<AddressFamily>,
+    ttl: Option<u8>,
+    dscp: Option<u8>,
+    fingerprint: Option<String>,
+    orientation: Option<Orientation>,
+    blocked_keys: Option<Vec<scancode::Linux>>,
+    fallback: Option<String>,
+    fallback_recovery: Option<FallbackRecovery>,
+    motion_prediction: Option<u32>,
+    pointer_mode: Option<PointerMode>,
+    transforms: Option<Vec<Transform>>,
 }
 
 impl ConfigToml {
@@ -71,6 +279,70 @@ impl ConfigToml {
         let config = fs::read_to_string(path)?;
         Ok(toml::from_str::<_>(&config)?)
     }
+
+    /// the grid of devices, if configured
+    fn grid(config: Option<&Self>) -> Result<Option<Grid>, GridError> {
+        match config {
+            Some(ConfigToml {
+                grid: Some(rows),
+                grid_name,
+                ..
+            }) => Ok(Some(Grid::new(rows.clone(), grid_name.as_deref())?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl From<(ClientConfig, ClientState)> for TomlClient {
+    fn from((config, state): (ClientConfig, ClientState)) -> Self {
+        let click_lock = config
+            .click_lock
+            .into_iter()
+            .filter_map(|b| MouseButton::try_from(b).ok())
+            .collect::<Vec<_>>();
+        let button_map = config
+            .button_map
+            .into_iter()
+            .filter_map(|(from, to)| Some((from.try_into().ok()?, to.try_into().ok()?)))
+            .collect::<HashMap<_, _>>();
+        let blocked_keys = config
+            .blocked_keys
+            .into_iter()
+            .filter_map(|k| scancode::Linux::try_from(k).ok())
+            .collect::<Vec<_>>();
+        Self {
+            uuid: Some(config.uuid),
+            hostname: config.hostname,
+            host_name: None,
+            ips: (!config.fix_ips.is_empty()).then_some(config.fix_ips),
+            port: Some(config.port),
+            position: Some(config.pos),
+            activate_on_startup: Some(state.active),
+            enter_hook: config.cmd,
+            always_forward: Some(config.always_forward),
+            wire_format: (config.wire_format != WireFormat::Auto).then_some(config.wire_format),
+            click_lock: (!click_lock.is_empty()).then_some(click_lock),
+            scroll_acceleration: (config.scroll_acceleration > 0)
+                .then_some(config.scroll_acceleration),
+            button_map: (!button_map.is_empty()).then_some(button_map),
+            relay_target: config.relay_target,
+            preferred_family: config.preferred_family,
+            ttl: config.ttl,
+            dscp: config.dscp,
+            fingerprint: config.fingerprint,
+            orientation: (config.orientation != Orientation::Normal).then_some(config.orientation),
+            blocked_keys: (!blocked_keys.is_empty()).then_some(blocked_keys),
+            fallback_recovery: config
+                .fallback
+                .is_some()
+                .then_some(config.fallback_recovery),
+            fallback: config.fallback,
+            motion_prediction: (config.motion_prediction > 0).then_some(config.motion_prediction),
+            pointer_mode: (config.pointer_mode != PointerMode::default())
+                .then_some(config.pointer_mode),
+            transforms: (!config.transforms.is_empty()).then_some(config.transforms),
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -96,6 +368,14 @@ struct Args {
     #[arg(long)]
     cert_path: Option<PathBuf>,
 
+    /// log the raw bytes of received packets before parsing
+    #[arg(long)]
+    debug_raw_packets: bool,
+
+    /// include key codes in logged events (redacted by default)
+    #[arg(long)]
+    log_key_data: bool,
+
     /// subcommands
     #[command(subcommand)]
     command: Option<Command>,
@@ -184,6 +464,9 @@ pub enum EmulationBackend {
     #[cfg(all(unix, feature = "x11_emulation", not(target_os = "macos")))]
     #[serde(rename = "x11")]
     X11,
+    #[cfg(all(unix, feature = "uinput_emulation", not(target_os = "macos")))]
+    #[serde(rename = "uinput")]
+    Uinput,
     #[cfg(windows)]
     #[serde(rename = "windows")]
     Windows,
@@ -205,6 +488,8 @@ impl From<EmulationBackend> for input_emulation::Backend {
             EmulationBackend::Xdp => Self::Xdp,
             #[cfg(all(unix, feature = "x11_emulation", not(target_os = "macos")))]
             EmulationBackend::X11 => Self::X11,
+            #[cfg(all(unix, feature = "uinput_emulation", not(target_os = "macos")))]
+            EmulationBackend::Uinput => Self::Uinput,
             #[cfg(windows)]
             EmulationBackend::Windows => Self::Windows,
             #[cfg(target_os = "macos")]
@@ -225,6 +510,8 @@ impl Display for EmulationBackend {
             EmulationBackend::Xdp => write!(f, "xdg-desktop-portal"),
             #[cfg(all(unix, feature = "x11_emulation", not(target_os = "macos")))]
             EmulationBackend::X11 => write!(f, "X11"),
+            #[cfg(all(unix, feature = "uinput_emulation", not(target_os = "macos")))]
+            EmulationBackend::Uinput => write!(f, "uinput"),
             #[cfg(windows)]
             EmulationBackend::Windows => write!(f, "windows"),
             #[cfg(target_os = "macos")]
@@ -234,6 +521,212 @@ impl Display for EmulationBackend {
     }
 }
 
+/// policy when the capture backend can not keep up
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BackpressurePolicy {
+    /// wait until the event can be delivered
+    #[serde(rename = "block")]
+    Block,
+    /// discard the event
+    #[serde(rename = "drop")]
+    Drop,
+}
+
+impl From<BackpressurePolicy> for input_capture::BackpressurePolicy {
+    fn from(policy: BackpressurePolicy) -> Self {
+        match policy {
+            BackpressurePolicy::Block => Self::Block,
+            BackpressurePolicy::Drop => Self::Drop,
+        }
+    }
+}
+
+impl From<TomlBackpressure> for input_capture::Backpressure {
+    fn from(toml: TomlBackpressure) -> Self {
+        let default = Self::default();
+        Self {
+            motion: toml.motion.map(Into::into).unwrap_or(default.motion),
+            scroll: toml.scroll.map(Into::into).unwrap_or(default.scroll),
+            button: toml.button.map(Into::into).unwrap_or(default.button),
+            key: toml.key.map(Into::into).unwrap_or(default.key),
+        }
+    }
+}
+
+impl From<TomlStickyEdge> for StickyEdge {
+    fn from(toml: TomlStickyEdge) -> Self {
+        Self {
+            left: toml.left.unwrap_or_default(),
+            right: toml.right.unwrap_or_default(),
+            top: toml.top.unwrap_or_default(),
+            bottom: toml.bottom.unwrap_or_default(),
+            confirm: toml
+                .confirm_ms
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+        }
+    }
+}
+
+impl From<TomlTapToSwitch> for TapToSwitch {
+    fn from(toml: TomlTapToSwitch) -> Self {
+        let default = Self::default();
+        Self {
+            left: toml.left.unwrap_or_default(),
+            right: toml.right.unwrap_or_default(),
+            top: toml.top.unwrap_or_default(),
+            bottom: toml.bottom.unwrap_or_default(),
+            timeout: toml
+                .timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.timeout),
+            distance: toml.distance.unwrap_or(default.distance),
+        }
+    }
+}
+
+impl From<TomlLossSimulation> for LossSimulation {
+    fn from(toml: TomlLossSimulation) -> Self {
+        let fraction = |f: Option<f64>| f.unwrap_or_default().clamp(0., 1.);
+        Self {
+            drop: fraction(toml.drop),
+            delay_rate: fraction(toml.delay),
+            delay: Duration::from_millis(toml.delay_ms.unwrap_or(100)),
+            reorder: fraction(toml.reorder),
+            seed: toml.seed.unwrap_or(1),
+        }
+    }
+}
+
+/// pointer button
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+}
+
+impl From<MouseButton> for u32 {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => input_event::BTN_LEFT,
+            MouseButton::Right => input_event::BTN_RIGHT,
+            MouseButton::Middle => input_event::BTN_MIDDLE,
+            MouseButton::Back => input_event::BTN_BACK,
+            MouseButton::Forward => input_event::BTN_FORWARD,
+        }
+    }
+}
+
+impl TryFrom<u32> for MouseButton {
+    type Error = ();
+
+    fn try_from(button: u32) -> Result<Self, Self::Error> {
+        match button {
+            input_event::BTN_LEFT => Ok(MouseButton::Left),
+            input_event::BTN_RIGHT => Ok(MouseButton::Right),
+            input_event::BTN_MIDDLE => Ok(MouseButton::Middle),
+            input_event::BTN_BACK => Ok(MouseButton::Back),
+            input_event::BTN_FORWARD => Ok(MouseButton::Forward),
+            _ => Err(()),
+        }
+    }
+}
+
+/// scroll event applied if a frame carries both
+/// a continuous and a discrete event for the same axis
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ScrollPreference {
+    #[serde(rename = "discrete")]
+    Discrete,
+    #[serde(rename = "continuous")]
+    Continuous,
+    #[serde(rename = "both")]
+    Both,
+}
+
+impl From<ScrollPreference> for input_emulation::ScrollPreference {
+    fn from(preference: ScrollPreference) -> Self {
+        match preference {
+            ScrollPreference::Discrete => Self::Discrete,
+            ScrollPreference::Continuous => Self::Continuous,
+            ScrollPreference::Both => Self::Both,
+        }
+    }
+}
+
+/// handling of scroll events without a scroll device
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ScrollFallback {
+    #[serde(rename = "drop")]
+    Drop,
+    /// press a button per detent scrolled
+    #[serde(rename = "buttons")]
+    Buttons,
+}
+
+/// handling of absolute motion without an absolute pointer device
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum AbsoluteFallback {
+    #[serde(rename = "drop")]
+    Drop,
+    /// move the pointer by the change of the position
+    #[serde(rename = "relative")]
+    Relative,
+}
+
+impl From<TomlDegradation> for input_emulation::Degradation {
+    fn from(toml: TomlDegradation) -> Self {
+        const DEFAULT_WIDTH: u32 = 1920;
+        const DEFAULT_HEIGHT: u32 = 1080;
+        Self {
+            scroll: match toml.scroll {
+                None | Some(ScrollFallback::Drop) => input_emulation::ScrollFallback::Drop,
+                Some(ScrollFallback::Buttons) => {
+                    let defaults = input_emulation::ScrollButtons::default();
+                    let buttons = toml.scroll_buttons.unwrap_or_default();
+                    input_emulation::ScrollFallback::Buttons(input_emulation::ScrollButtons {
+                        up: buttons.up.unwrap_or(defaults.up),
+                        down: buttons.down.unwrap_or(defaults.down),
+                        left: buttons.left.unwrap_or(defaults.left),
+                        right: buttons.right.unwrap_or(defaults.right),
+                    })
+                }
+            },
+            absolute: match toml.absolute {
+                None | Some(AbsoluteFallback::Drop) => input_emulation::AbsoluteFallback::Drop,
+                Some(AbsoluteFallback::Relative) => input_emulation::AbsoluteFallback::Relative {
+                    width: toml.absolute_width.unwrap_or(DEFAULT_WIDTH),
+                    height: toml.absolute_height.unwrap_or(DEFAULT_HEIGHT),
+                },
+            },
+        }
+    }
+}
+
+/// how local input is grabbed while forwarding
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum GrabMode {
+    /// grab all input including compositor shortcuts
+    #[serde(rename = "full")]
+    Full,
+    /// let the compositor handle its keyboard shortcuts
+    #[serde(rename = "keep-shortcuts")]
+    KeepShortcuts,
+}
+
+impl From<GrabMode> for input_capture::GrabMode {
+    fn from(mode: GrabMode) -> Self {
+        match mode {
+            GrabMode::Full => Self::Full,
+            GrabMode::KeepShortcuts => Self::KeepShortcuts,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     /// command line arguments
@@ -244,32 +737,142 @@ pub struct Config {
     config_path: PathBuf,
     /// the (optional) toml config and it's path
     config_toml: Option<ConfigToml>,
+    /// layout of the devices, if configured
+    grid: Option<Grid>,
 }
 
 pub struct ConfigClient {
+    pub uuid: String,
     pub ips: HashSet<IpAddr>,
     pub hostname: Option<String>,
     pub port: u16,
     pub pos: Position,
     pub active: bool,
     pub enter_hook: Option<String>,
+    pub always_forward: bool,
+    pub wire_format: WireFormat,
+    pub click_lock: Vec<u32>,
+    pub scroll_acceleration: u32,
+    pub button_map: HashMap<u32, u32>,
+    pub relay_target: Option<u64>,
+    pub preferred_family: Option<AddressFamily>,
+    pub ttl: Option<u8>,
+    pub dscp: Option<u8>,
+    pub fingerprint: Option<String>,
+    pub orientation: Orientation,
+    pub blocked_keys: Vec<u32>,
+    pub fallback: Option<String>,
+    pub fallback_recovery: FallbackRecovery,
+    pub motion_prediction: u32,
+    pub pointer_mode: PointerMode,
+    pub transforms: Vec<Transform>,
+}
+
+impl ConfigClient {
+    /// client enabled at the given position with default settings,
+    /// its uuid is derived from the hostname to be the same on every start
+    fn neighbor(hostname: String, pos: Position) -> Self {
+        Self {
+            uuid: name_uuid(&hostname),
+            ips: Default::default(),
+            hostname: Some(hostname),
+            port: DEFAULT_PORT,
+            pos,
+            active: true,
+            enter_hook: None,
+            always_forward: false,
+            wire_format: Default::default(),
+            click_lock: vec![],
+            scroll_acceleration: 0,
+            button_map: Default::default(),
+            relay_target: None,
+            preferred_family: None,
+            ttl: None,
+            dscp: None,
+            fingerprint: None,
+            orientation: Default::default(),
+            blocked_keys: vec![],
+            fallback: None,
+            fallback_recovery: Default::default(),
+            motion_prediction: 0,
+            pointer_mode: Default::default(),
+            transforms: vec![],
+        }
+    }
 }
 
 impl From<TomlClient> for ConfigClient {
     fn from(toml: TomlClient) -> Self {
+        let uuid = toml.uuid.unwrap_or_else(|| {
+            let uuid = generate_uuid();
+            log::info!(
+                "client {:?}: generated uuid {uuid} (persisted when the config is saved)",
+                toml.hostname.as_deref().unwrap_or_default()
+            );
+            uuid
+        });
         let active = toml.activate_on_startup.unwrap_or(false);
         let enter_hook = toml.enter_hook;
         let hostname = toml.hostname;
         let ips = HashSet::from_iter(toml.ips.into_iter().flatten());
         let port = toml.port.unwrap_or(DEFAULT_PORT);
         let pos = toml.position.unwrap_or_default();
+        let always_forward = toml.always_forward.unwrap_or(false);
+        let wire_format = toml.wire_format.unwrap_or_default();
+        let click_lock = toml
+            .click_lock
+            .into_iter()
+            .flatten()
+            .map(Into::into)
+            .collect();
+        let button_map = toml
+            .button_map
+            .into_iter()
+            .flatten()
+            .map(|(from, to)| (from.into(), to.into()))
+            .collect();
+        let ttl = toml.ttl.filter(|&ttl| {
+            if ttl == 0 {
+                log::warn!("ignoring invalid ttl 0 (must be 1-255)");
+            }
+            ttl > 0
+        });
+        let dscp = toml.dscp.filter(|&dscp| {
+            if dscp > MAX_DSCP {
+                log::warn!("ignoring invalid dscp {dscp} (must be 0-{MAX_DSCP})");
+            }
+            dscp <= MAX_DSCP
+        });
         Self {
+            uuid,
             ips,
             hostname,
             port,
             pos,
             active,
             enter_hook,
+            always_forward,
+            wire_format,
+            click_lock,
+            scroll_acceleration: toml.scroll_acceleration.unwrap_or_default(),
+            button_map,
+            relay_target: toml.relay_target,
+            preferred_family: toml.preferred_family,
+            ttl,
+            dscp,
+            fingerprint: toml.fingerprint,
+            orientation: toml.orientation.unwrap_or_default(),
+            blocked_keys: toml
+                .blocked_keys
+                .into_iter()
+                .flatten()
+                .map(|k| k as u32)
+                .collect(),
+            fallback: toml.fallback,
+            fallback_recovery: toml.fallback_recovery.unwrap_or_default(),
+            motion_prediction: toml.motion_prediction.unwrap_or_default(),
+            pointer_mode: toml.pointer_mode.unwrap_or_default(),
+            transforms: toml.transforms.unwrap_or_default(),
         }
     }
 }
@@ -279,9 +882,17 @@ pub enum ConfigError {
     #[error(transparent)]
     Toml(#[from] toml::de::Error),
     #[error(transparent)]
+    TomlSer(#[from] toml::ser::Error),
+    #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
     Var(#[from] VarError),
+    #[error("invalid grid: {0}")]
+    Grid(#[from] GridError),
+    #[error("`{0}` is assigned to more than one edge")]
+    EdgeAssignedTwice(String),
+    #[error("{0} edge leads to both `{1}` and `{2}`, enable at most one of them")]
+    EdgeConflict(Position, String, String),
 }
 
 const DEFAULT_RELEASE_KEYS: [scancode::Linux; 4] =
@@ -313,12 +924,95 @@ impl Config {
             .or(config_toml.as_ref().and_then(|c| c.cert_path.clone()))
             .unwrap_or(default_path()?.join(CERT_FILE_NAME));
 
-        Ok(Config {
+        let grid = ConfigToml::grid(config_toml.as_ref())?;
+        if let (Some(grid), Some(name)) = (
+            grid.as_ref(),
+            config_toml.as_ref().and_then(|c| c.grid_name.as_deref()),
+        ) {
+            for (pos, hostname) in grid.neighbors(name) {
+                log::info!("grid: {hostname} is {pos} of {name}");
+            }
+        }
+
+        let config = Config {
             args,
             cert_path,
             config_path,
             config_toml,
-        })
+            grid,
+        };
+        config.validate_edges()?;
+        Ok(config)
+    }
+
+    /// Check that no client is assigned to more than one edge
+    /// and every edge leads to at most one client enabled on startup.
+    fn validate_edges(&self) -> Result<(), ConfigError> {
+        let mut assigned = HashSet::new();
+        for hostname in self.edges().values() {
+            if !assigned.insert(hostname) {
+                return Err(ConfigError::EdgeAssignedTwice(hostname.clone()));
+            }
+        }
+        let mut edges: HashMap<Position, String> = HashMap::new();
+        for client in self.clients().into_iter().filter(|c| c.active) {
+            let name = client.hostname.unwrap_or_else(|| {
+                let ips = client.ips.iter().map(|ip| ip.to_string());
+                ips.collect::<Vec<_>>().join(", ")
+            });
+            if let Some(other) = edges.insert(client.pos, name.clone()) {
+                return Err(ConfigError::EdgeConflict(client.pos, other, name));
+            }
+        }
+        Ok(())
+    }
+
+    /// hostnames of the clients assigned to an edge in the `[edges]` table
+    fn edges(&self) -> HashMap<Position, String> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.edges.clone())
+            .unwrap_or_default()
+    }
+
+    /// Write the runtime state (clients, port, authorized keys)
+    /// to the config file, keeping all other settings.
+    /// The port is kept as is if it was given on the command line.
+    /// Neighbors in the grid are not saved, they are derived from the
+    /// grid on every start.
+    /// Comments in the config file are not preserved.
+    pub fn save(
+        &self,
+        port: u16,
+        clients: Vec<(ClientConfig, ClientState)>,
+        authorized_fingerprints: HashMap<String, String>,
+    ) -> Result<(), ConfigError> {
+        let mut config_toml = self.config_toml.clone().unwrap_or_default();
+        // a port given on the command line only applies to this run
+        if self.args.port != Some(port) {
+            config_toml.port = Some(port);
+        }
+        let grid_clients = self
+            .grid_clients(&self.static_clients())
+            .into_iter()
+            .filter_map(|c| c.hostname)
+            .collect::<HashSet<_>>();
+        let clients = clients
+            .into_iter()
+            .filter(|(c, _)| {
+                c.hostname
+                    .as_ref()
+                    .is_none_or(|h| !grid_clients.contains(h))
+            })
+            .map(Into::into)
+            .collect();
+        config_toml.clients = Some(clients);
+        config_toml.authorized_fingerprints = Some(authorized_fingerprints);
+        if let Some(dir) = self.config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.config_path, toml::to_string(&config_toml)?)?;
+        Ok(())
     }
 
     /// the command to run
@@ -365,18 +1059,604 @@ impl Config {
             .unwrap_or(DEFAULT_PORT)
     }
 
+    /// whether raw received packets should be logged
+    pub fn debug_raw_packets(&self) -> bool {
+        self.args.debug_raw_packets
+            || self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.debug_raw_packets)
+                .unwrap_or(false)
+    }
+
+    /// whether events of types unknown to this version are skipped
+    /// instead of closing the connection
+    pub fn ignore_unknown_events(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.ignore_unknown_events)
+            .unwrap_or(true)
+    }
+
+    /// whether input events with out-of-range values are skipped
+    /// instead of closing the connection
+    pub fn ignore_invalid_events(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.ignore_invalid_events)
+            .unwrap_or(true)
+    }
+
+    /// whether key codes may appear in logs
+    pub fn log_key_data(&self) -> bool {
+        self.args.log_key_data
+            || self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.log_key_data)
+                .unwrap_or(false)
+    }
+
+    /// whether lifecycle transitions of client connections are logged
+    pub fn log_connection_lifecycle(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.log_connection_lifecycle)
+            .unwrap_or(true)
+    }
+
+    /// whether significant events are logged to journald / syslog
+    pub fn system_log(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.system_log)
+            .unwrap_or(false)
+    }
+
+    /// how long a client must stay reachable or unreachable
+    /// before the change is acted upon (disabled if `None`)
+    pub fn flap_damping(&self) -> Option<Duration> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.flap_damping_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// number of times key and button events are sent again
+    /// to survive packet loss (0 disables retransmission)
+    pub fn retransmit_critical(&self) -> u32 {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.retransmit_critical)
+            .unwrap_or(0)
+    }
+
+    /// number of sequence numbers of received key and button events
+    /// remembered to drop duplicates (0 disables deduplication)
+    pub fn dedup_window(&self) -> u32 {
+        const DEFAULT_DEDUP_WINDOW: u32 = 64;
+        const MAX_DEDUP_WINDOW: u32 = 1 << 16;
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.dedup_window)
+            .unwrap_or(DEFAULT_DEDUP_WINDOW)
+            .min(MAX_DEDUP_WINDOW)
+    }
+
+    /// interval of the clock offset estimation of
+    /// connected clients (disabled if `None`)
+    pub fn clock_sync_interval(&self) -> Option<Duration> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.clock_sync_interval_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// maximum number of clients
+    pub fn max_clients(&self) -> usize {
+        const DEFAULT_MAX_CLIENTS: usize = 64;
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.max_clients)
+            .unwrap_or(DEFAULT_MAX_CLIENTS)
+    }
+
     /// list of configured clients
     pub fn clients(&self) -> Vec<ConfigClient> {
-        self.config_toml
+        let mut clients = self.static_clients();
+        clients.extend(self.grid_clients(&clients));
+        clients
+    }
+
+    /// configured clients and clients behind an edge,
+    /// i.e. all clients except neighbors in the grid
+    fn static_clients(&self) -> Vec<ConfigClient> {
+        let mut clients: Vec<ConfigClient> = self
+            .config_toml
             .as_ref()
             .map(|c| c.clients.clone())
             .unwrap_or_default()
             .into_iter()
             .flatten()
             .map(From::<TomlClient>::from)
+            .collect();
+        for (pos, hostname) in self.edges() {
+            match clients
+                .iter_mut()
+                .find(|c| c.hostname.as_ref() == Some(&hostname))
+            {
+                Some(client) => {
+                    client.pos = pos;
+                    client.active = true;
+                }
+                None => clients.push(ConfigClient::neighbor(hostname, pos)),
+            }
+        }
+        clients
+    }
+
+    /// Neighbors of this device in the grid.
+    /// Neighbors configured as clients already are skipped.
+    fn grid_clients(&self, configured: &[ConfigClient]) -> Vec<ConfigClient> {
+        let (Some(grid), Some(name)) = (
+            self.grid.as_ref(),
+            self.config_toml
+                .as_ref()
+                .and_then(|c| c.grid_name.as_deref()),
+        ) else {
+            return vec![];
+        };
+        grid.neighbors(name)
+            .into_iter()
+            .filter(|(_, n)| !configured.iter().any(|c| c.hostname.as_ref() == Some(n)))
+            .map(|(pos, hostname)| ConfigClient::neighbor(hostname, pos))
             .collect()
     }
 
+    /// backpressure policy of the input capture per event type
+    pub fn backpressure(&self) -> input_capture::Backpressure {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.backpressure)
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// distance to push beyond each edge before entering a client
+    pub(crate) fn sticky_edge(&self) -> StickyEdge {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.sticky_edge)
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// edges entering a client when tapped or clicked
+    pub(crate) fn tap_to_switch(&self) -> TapToSwitch {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.tap_to_switch)
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// handling of key events of origins sharing a keyboard
+    pub(crate) fn keyboard_merge(&self) -> KeyboardMerge {
+        match self.config_toml.as_ref().and_then(|c| c.keyboard_merge) {
+            None => KeyboardMerge::default(),
+            Some(TomlKeyboardMerge::Track) => KeyboardMerge::Track,
+            Some(TomlKeyboardMerge::Raw) => KeyboardMerge::Raw,
+        }
+    }
+
+    /// monitor layout to snap the cursor onto when a device enters,
+    /// if configured
+    pub(crate) fn entry_snap(&self) -> Option<EntrySnap> {
+        let snap = self.config_toml.as_ref()?.entry_snap.as_ref()?;
+        let monitors = snap
+            .monitors
+            .iter()
+            .flatten()
+            .filter(|m| m.width > 0 && m.height > 0)
+            .map(|m| Monitor {
+                x: m.x,
+                y: m.y,
+                width: m.width,
+                height: m.height,
+            })
+            .collect::<Vec<_>>();
+        if monitors.is_empty() {
+            return None;
+        }
+        let ambiguous = match snap.ambiguous {
+            None => EntryMonitor::default(),
+            Some(TomlEntryMonitor::First) => EntryMonitor::First,
+            Some(TomlEntryMonitor::Last) => EntryMonitor::Last,
+            Some(TomlEntryMonitor::Largest) => EntryMonitor::Largest,
+        };
+        let offset = snap.offset.unwrap_or_default();
+        let offset = EntryOffset {
+            left: offset.left.unwrap_or_default(),
+            right: offset.right.unwrap_or_default(),
+            top: offset.top.unwrap_or_default(),
+            bottom: offset.bottom.unwrap_or_default(),
+        };
+        Some(EntrySnap {
+            monitors,
+            ambiguous,
+            offset,
+        })
+    }
+
+    /// configured named actions, unknown names are skipped
+    fn actions(&self) -> Vec<(Action, &TomlAction)> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.actions.as_ref())
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, action)| match Action::from_name(name) {
+                Some(a) => Some((a, action)),
+                None => {
+                    log::warn!("ignoring unknown action `{name}`");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// key chords sending a named action instead of the keys
+    pub(crate) fn action_binds(&self) -> Vec<(Action, Vec<scancode::Linux>)> {
+        self.actions()
+            .into_iter()
+            .filter_map(|(a, action)| Some((a, action.keys.clone()?)))
+            .filter(|(_, keys)| !keys.is_empty())
+            .collect()
+    }
+
+    /// how named actions received from other devices are performed
+    pub(crate) fn action_handlers(&self) -> HashMap<Action, ActionHandler> {
+        self.actions()
+            .into_iter()
+            .map(|(a, action)| {
+                let handler = ActionHandler {
+                    command: action.command.clone(),
+                    keys: action.keys.clone().unwrap_or_default(),
+                };
+                (a, handler)
+            })
+            .collect()
+    }
+
+    /// simulated degradation of sent packets (debug builds only)
+    pub(crate) fn loss_simulation(&self) -> Option<LossSimulation> {
+        let simulation = self.config_toml.as_ref()?.debug_loss_simulation?;
+        if !cfg!(debug_assertions) {
+            log::warn!("ignoring `debug_loss_simulation` in release build");
+            return None;
+        }
+        Some(simulation.into())
+    }
+
+    /// how local input is grabbed while forwarding
+    pub fn grab_mode(&self) -> input_capture::GrabMode {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.grab)
+            .map(Into::into)
+            .unwrap_or_default()
+    }
+
+    /// whether to relay events from connected devices to configured clients
+    pub fn relay(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.relay)
+            .unwrap_or(false)
+    }
+
+    /// time without any message from a sending device after
+    /// which the input it holds pressed is released
+    pub fn deadman_timeout(&self) -> Duration {
+        const DEFAULT_DEADMAN_TIMEOUT: Duration = Duration::from_millis(1000);
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.deadman_timeout_ms)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DEADMAN_TIMEOUT)
+    }
+
+    /// release of the input held by sending devices that stopped responding
+    pub(crate) fn deadman(&self) -> Deadman {
+        const DEFAULT_PORT_CHANGE_GRACE: Duration = Duration::from_millis(1000);
+        let port_change_grace = self
+            .config_toml
+            .as_ref()
+            .and_then(|c| c.port_change_grace_ms)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_PORT_CHANGE_GRACE);
+        Deadman {
+            timeout: self.deadman_timeout(),
+            port_change_grace,
+        }
+    }
+
+    /// duration after which a sending device has to authenticate again,
+    /// unlimited by default
+    pub fn max_session_duration(&self) -> Option<Duration> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.max_session_duration_ms)
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
+    }
+
+    /// interval at which the creation of input emulation is retried
+    /// after its backend failed in the background, if it is restarted
+    fn emulation_recreate_interval(&self) -> Option<Duration> {
+        const DEFAULT_RECREATE_INTERVAL: Duration = Duration::from_millis(1000);
+        if !self.restart_emulation_on_failure() {
+            return None;
+        }
+        match self
+            .config_toml
+            .as_ref()
+            .and_then(|c| c.emulation_recreate_interval_ms)
+        {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => Some(DEFAULT_RECREATE_INTERVAL),
+        }
+    }
+
+    /// whether input emulation is recreated after
+    /// its backend failed in the background
+    pub fn restart_emulation_on_failure(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.restart_emulation_on_failure)
+            .unwrap_or(true)
+    }
+
+    /// how to handle the input capture closing unexpectedly
+    pub(crate) fn capture_closed(&self) -> CaptureClosed {
+        match self.config_toml.as_ref().and_then(|c| c.on_capture_closed) {
+            None => CaptureClosed::default(),
+            Some(TomlCaptureClosed::Disable) => CaptureClosed::Disable,
+            Some(TomlCaptureClosed::Recreate) => CaptureClosed::Recreate,
+            Some(TomlCaptureClosed::Exit) => CaptureClosed::Exit,
+        }
+    }
+
+    /// whether to mark groups of related events (e.g. motion + button)
+    /// so the receiving side can apply them atomically
+    pub fn frame_grouping(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.frame_grouping)
+            .unwrap_or(false)
+    }
+
+    /// whether to forward pointer motion without
+    /// the acceleration applied by the local compositor
+    pub fn raw_motion(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.raw_motion)
+            .unwrap_or(true)
+    }
+
+    /// whether to discard auto-repeated key presses during capture
+    /// and leave key repeat to the receiving side
+    pub fn suppress_repeat(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.suppress_repeat)
+            .unwrap_or(true)
+    }
+
+    /// whether absolute positions of tablets and similar devices
+    /// are forwarded during capture
+    pub fn forward_absolute_motion(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.forward_absolute_motion)
+            .unwrap_or(false)
+    }
+
+    /// names of the seats input is captured from, all seats if `None`
+    pub fn capture_seats(&self) -> Option<Vec<String>> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.capture_seats.clone())
+    }
+
+    /// only capture while a matching window is focused
+    pub fn capture_focus_filter(&self) -> Option<String> {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.capture_focus_filter.clone())
+            .filter(|f| !f.is_empty())
+    }
+
+    /// whether the state of all modifiers is sent
+    /// before forwarding a key press
+    pub fn reassert_modifiers(&self) -> bool {
+        self.config_toml
+            .as_ref()
+            .and_then(|c| c.reassert_modifiers)
+            .unwrap_or(false)
+    }
+
+    /// options for the input emulation
+    pub fn emulation_options(&self) -> input_emulation::EmulationOptions {
+        input_emulation::EmulationOptions {
+            warmup: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emulation_warmup)
+                .map(Duration::from_millis),
+            min_spacing: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emulation_min_spacing_us)
+                .map(Duration::from_micros),
+            max_fling: self.max_fling(),
+            frame_rate: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emulation_frame_rate)
+                .filter(|&hz| hz > 0),
+            precise_motion: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.precise_motion)
+                .unwrap_or(false),
+            motion_smoothing: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.motion_smoothing)
+                .filter(|&s| s > 0),
+            discrete_scroll_scale: self.discrete_scroll_scale(),
+            max_motion: Some(
+                self.config_toml
+                    .as_ref()
+                    .and_then(|c| c.max_motion)
+                    .unwrap_or(DEFAULT_MAX_MOTION),
+            )
+            .filter(|&max| max > 0),
+            scroll_throttle: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.scroll_throttle_ms)
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            flush_retry: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emulation_flush_retry_ms)
+                .map(Duration::from_millis),
+            handshake_timeout: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emulation_handshake_timeout_ms)
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            early_motion_buffer: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.early_motion_buffer_ms)
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            idle_frame_interval: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.idle_frame_interval_ms)
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            counter_log_interval: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.log_frame_counters_ms)
+                .filter(|&ms| ms > 0)
+                .map(Duration::from_millis),
+            device_per_handle: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.separate_origins)
+                .unwrap_or(false),
+            recreate_interval: self.emulation_recreate_interval(),
+            inhibit_idle: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.inhibit_idle)
+                .unwrap_or(true),
+            device_removal: match self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.rebind_removed_devices)
+                .unwrap_or(true)
+            {
+                true => input_emulation::DeviceRemoval::Rebind,
+                false => input_emulation::DeviceRemoval::Wait,
+            },
+            unknown_handle: match self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.create_unknown_handles)
+                .unwrap_or(true)
+            {
+                true => input_emulation::UnknownHandle::Create,
+                false => input_emulation::UnknownHandle::Reject,
+            },
+            degradation: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.degradation)
+                .unwrap_or_default()
+                .into(),
+            scroll_preference: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.scroll_preference)
+                .map(Into::into)
+                .unwrap_or_default(),
+            emit_zero_deltas: self
+                .config_toml
+                .as_ref()
+                .and_then(|c| c.emit_zero_deltas)
+                .unwrap_or(false),
+        }
+    }
+
+    /// scaling of discrete scroll events, if any
+    fn discrete_scroll_scale(&self) -> Option<input_emulation::DiscreteScrollScale> {
+        let config = self.config_toml.as_ref()?;
+        let multiplier = config.discrete_scroll_multiplier.unwrap_or(1);
+        let divisor = config.discrete_scroll_divisor.unwrap_or(1);
+        if multiplier <= 0 {
+            log::warn!("ignoring invalid discrete_scroll_multiplier {multiplier}");
+            return None;
+        }
+        if divisor <= 0 {
+            log::warn!("ignoring invalid discrete_scroll_divisor {divisor}");
+            return None;
+        }
+        match (multiplier, divisor) {
+            (1, 1) => None,
+            _ => Some(input_emulation::DiscreteScrollScale {
+                multiplier,
+                divisor,
+            }),
+        }
+    }
+
+    /// maximum duration of synthesized kinetic scrolling
+    fn max_fling(&self) -> Option<Duration> {
+        const DEFAULT_MAX_FLING: Duration = Duration::from_millis(1000);
+        match self.config_toml.as_ref().and_then(|c| c.max_fling_ms) {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => Some(DEFAULT_MAX_FLING),
+        }
+    }
+
+    /// options for the input capture
+    pub fn capture_options(&self) -> input_capture::CaptureOptions {
+        input_capture::CaptureOptions {
+            backpressure: self.backpressure(),
+            grab: self.grab_mode(),
+            frame_grouping: self.frame_grouping(),
+            suppress_repeat: self.suppress_repeat(),
+            forward_absolute: self.forward_absolute_motion(),
+            seats: self.capture_seats(),
+            raw_motion: self.raw_motion(),
+            focus_filter: self.capture_focus_filter(),
+        }
+    }
+
     /// release bind for returning control to the host
     pub fn release_bind(&self) -> Vec<scancode::Linux> {
         self.config_toml
@@ -385,3 +1665,227 @@ impl Config {
             .unwrap_or(Vec::from_iter(DEFAULT_RELEASE_KEYS.iter().cloned()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// config loaded from `path` with the given command line
+    fn load(path: &Path, args: &[&str]) -> Config {
+        let config_toml = ConfigToml::new(path).ok();
+        Config {
+            args: Args::parse_from(std::iter::once("lan-mouse").chain(args.iter().copied())),
+            cert_path: path.with_extension("pem"),
+            config_path: path.to_owned(),
+            grid: ConfigToml::grid(config_toml.as_ref()).unwrap(),
+            config_toml,
+        }
+    }
+
+    /// runtime state of a configured client
+    fn runtime(client: ConfigClient) -> (ClientConfig, ClientState) {
+        let state = ClientState {
+            active: client.active,
+            ..Default::default()
+        };
+        let config = ClientConfig {
+            uuid: client.uuid,
+            hostname: client.hostname,
+            fix_ips: client.ips.into_iter().collect(),
+            port: client.port,
+            pos: client.pos,
+            cmd: client.enter_hook,
+            always_forward: client.always_forward,
+            wire_format: client.wire_format,
+            click_lock: client.click_lock,
+            scroll_acceleration: client.scroll_acceleration,
+            button_map: client.button_map,
+            relay_target: client.relay_target,
+            preferred_family: client.preferred_family,
+            ttl: client.ttl,
+            dscp: client.dscp,
+            fingerprint: client.fingerprint,
+            orientation: client.orientation,
+            blocked_keys: client.blocked_keys,
+            fallback: client.fallback,
+            fallback_recovery: client.fallback_recovery,
+            motion_prediction: client.motion_prediction,
+            pointer_mode: client.pointer_mode,
+            transforms: client.transforms,
+        };
+        (config, state)
+    }
+
+    /// hostnames and positions of the clients of `config`
+    fn positions(config: &Config) -> Vec<(String, Position)> {
+        let mut clients = config
+            .clients()
+            .into_iter()
+            .map(|c| (c.hostname.unwrap_or_default(), c.pos))
+            .collect::<Vec<_>>();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        clients
+    }
+
+    const GRID_CONFIG: &str = r#"
+grid = [["laptop", "desktop", "tablet"]]
+grid_name = "desktop"
+
+[[clients]]
+hostname = "server"
+position = "top"
+activate_on_startup = true
+"#;
+
+    fn config_file(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("lan-mouse-test-{}", std::process::id()));
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let path = config_file("round-trip.toml");
+        let client = ClientConfig {
+            uuid: generate_uuid(),
+            hostname: Some("laptop".into()),
+            fix_ips: vec!["192.168.1.2".parse().unwrap()],
+            port: 4343,
+            pos: Position::Top,
+            cmd: Some("notify-send entered".into()),
+            always_forward: true,
+            wire_format: WireFormat::V1,
+            click_lock: vec![input_event::BTN_MIDDLE],
+            scroll_acceleration: 25,
+            button_map: HashMap::from([(input_event::BTN_BACK, input_event::BTN_FORWARD)]),
+            relay_target: Some(7),
+            preferred_family: Some(AddressFamily::Ipv6),
+            ttl: Some(16),
+            dscp: Some(46),
+            fingerprint: Some("ab:cd".into()),
+            orientation: Orientation::Rotated90,
+            blocked_keys: vec![scancode::Linux::KeyA as u32],
+            fallback: Some("desktop".into()),
+            fallback_recovery: FallbackRecovery::Stay,
+            motion_prediction: 20,
+            pointer_mode: PointerMode::Relative,
+            transforms: vec![
+                Transform::Scale { x: 150, y: 50 },
+                Transform::Rotate {
+                    degrees: Orientation::Rotated180,
+                },
+            ],
+        };
+        let state = ClientState {
+            active: true,
+            ..Default::default()
+        };
+        let authorized = HashMap::from([("ab:cd".to_owned(), "laptop".to_owned())]);
+        load(&path, &[])
+            .save(4244, vec![(client.clone(), state)], authorized.clone())
+            .unwrap();
+
+        let config = load(&path, &[]);
+        assert_eq!(config.port(), 4244);
+        assert_eq!(config.authorized_fingerprints(), authorized);
+        let mut clients = config.clients();
+        assert_eq!(clients.len(), 1);
+        let loaded = clients.remove(0);
+        assert!(loaded.active);
+        let loaded = ClientConfig {
+            uuid: loaded.uuid,
+            hostname: loaded.hostname,
+            fix_ips: loaded.ips.into_iter().collect(),
+            port: loaded.port,
+            pos: loaded.pos,
+            cmd: loaded.enter_hook,
+            always_forward: loaded.always_forward,
+            wire_format: loaded.wire_format,
+            click_lock: loaded.click_lock,
+            scroll_acceleration: loaded.scroll_acceleration,
+            button_map: loaded.button_map,
+            relay_target: loaded.relay_target,
+            preferred_family: loaded.preferred_family,
+            ttl: loaded.ttl,
+            dscp: loaded.dscp,
+            fingerprint: loaded.fingerprint,
+            orientation: loaded.orientation,
+            blocked_keys: loaded.blocked_keys,
+            fallback: loaded.fallback,
+            fallback_recovery: loaded.fallback_recovery,
+            motion_prediction: loaded.motion_prediction,
+            pointer_mode: loaded.pointer_mode,
+            transforms: loaded.transforms,
+        };
+        assert_eq!(loaded, client);
+    }
+
+    #[test]
+    fn save_keeps_command_line_port_out() {
+        let path = config_file("port.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "port = 4243\n").unwrap();
+        let config = load(&path, &["--port", "5000"]);
+        assert_eq!(config.port(), 5000);
+        config.save(5000, vec![], HashMap::new()).unwrap();
+        assert_eq!(load(&path, &[]).port(), 4243);
+
+        // a port changed at runtime is saved
+        let config = load(&path, &["--port", "5000"]);
+        config.save(5001, vec![], HashMap::new()).unwrap();
+        assert_eq!(load(&path, &[]).port(), 5001);
+    }
+
+    #[test]
+    fn grid_neighbors_are_not_saved() {
+        let path = config_file("grid.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, GRID_CONFIG).unwrap();
+        let config = load(&path, &[]);
+        let expected = vec![
+            ("laptop".to_owned(), Position::Left),
+            ("server".to_owned(), Position::Top),
+            ("tablet".to_owned(), Position::Right),
+        ];
+        assert_eq!(positions(&config), expected);
+
+        let clients = config.clients().into_iter().map(runtime).collect();
+        config.save(4242, clients, HashMap::new()).unwrap();
+        let config = load(&path, &[]);
+        let saved = config.config_toml.as_ref().unwrap().clients.clone();
+        assert_eq!(saved.unwrap_or_default().len(), 1);
+        assert_eq!(positions(&config), expected);
+        config.validate_edges().unwrap();
+    }
+
+    #[test]
+    fn grid_edit_after_save() {
+        let path = config_file("grid-edit.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, GRID_CONFIG).unwrap();
+        let config = load(&path, &[]);
+        let clients = config.clients().into_iter().map(runtime).collect();
+        config.save(4242, clients, HashMap::new()).unwrap();
+
+        // laptop and tablet swap places
+        let mut config_toml = ConfigToml::new(&path).unwrap();
+        config_toml.grid = Some(vec![vec![
+            "tablet".to_owned(),
+            "desktop".to_owned(),
+            "laptop".to_owned(),
+        ]]);
+        fs::write(&path, toml::to_string(&config_toml).unwrap()).unwrap();
+
+        let config = load(&path, &[]);
+        config.validate_edges().unwrap();
+        assert_eq!(
+            positions(&config),
+            vec![
+                ("laptop".to_owned(), Position::Right),
+                ("server".to_owned(), Position::Top),
+                ("tablet".to_owned(), Position::Left),
+            ]
+        );
+    }
+}
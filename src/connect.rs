@@ -1,15 +1,23 @@
-use crate::client::ClientManager;
-use lan_mouse_ipc::{ClientHandle, DEFAULT_PORT};
-use lan_mouse_proto::{ProtoEvent, MAX_EVENT_SIZE};
+use crate::{
+    client::ClientManager,
+    lossy::{LossSimulation, LossyConn},
+};
+use lan_mouse_ipc::{AddressFamily, ClientHandle, VersionMismatch, DEFAULT_PORT};
+use lan_mouse_proto::{
+    ControlMessage, ProtoEvent, ProtocolError, WireFormat, MAX_EVENT_SIZE, MAX_PACKET_SIZE,
+    MAX_RELAY_SIZE,
+};
 use local_channel::mpsc::{channel, Receiver, Sender};
+use socket2::SockRef;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{HashMap, HashSet},
+    fmt::Display,
     io,
     net::SocketAddr,
     rc::Rc,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use tokio::{
@@ -42,17 +50,69 @@ pub(crate) enum LanMouseConnectionError {
 
 const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// IP options of the datagrams sent to a client
+#[derive(Clone, Copy, Debug, Default)]
+struct SocketOptions {
+    ttl: Option<u8>,
+    dscp: Option<u8>,
+}
+
+impl SocketOptions {
+    /// set the options on the socket before anything is sent
+    fn apply(&self, socket: &UdpSocket, addr: SocketAddr) {
+        if let Some(ttl) = self.ttl {
+            let result = match addr {
+                SocketAddr::V4(_) => socket.set_ttl(ttl.into()),
+                SocketAddr::V6(_) => SockRef::from(socket).set_unicast_hops_v6(ttl.into()),
+            };
+            if let Err(e) = result {
+                log::warn!("{addr}: could not set ttl {ttl}: {e}");
+            }
+        }
+        if let Some(dscp) = self.dscp {
+            // DSCP occupies the upper 6 bits of the ToS byte (traffic class on ipv6)
+            let tos = u32::from(dscp) << 2;
+            let result = match addr {
+                SocketAddr::V4(_) => socket.set_tos(tos),
+                SocketAddr::V6(_) => set_tclass_v6(socket, tos),
+            };
+            if let Err(e) = result {
+                log::warn!("{addr}: could not set dscp {dscp}: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn set_tclass_v6(socket: &UdpSocket, tclass: u32) -> io::Result<()> {
+    SockRef::from(socket).set_tclass_v6(tclass)
+}
+
+#[cfg(windows)]
+fn set_tclass_v6(_socket: &UdpSocket, _tclass: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the ipv6 traffic class is not supported on windows",
+    ))
+}
+
 async fn connect(
     addr: SocketAddr,
     cert: Certificate,
+    options: SocketOptions,
 ) -> Result<(Arc<dyn Conn + Sync + Send>, SocketAddr), (SocketAddr, LanMouseConnectionError)> {
     log::info!("connecting to {addr} ...");
+    let bind_addr = match addr {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
     let conn = Arc::new(
-        UdpSocket::bind("0.0.0.0:0")
+        UdpSocket::bind(bind_addr)
             .await
             .map_err(|e| (addr, e.into()))?,
     );
     conn.connect(addr).await.map_err(|e| (addr, e.into()))?;
+    options.apply(&conn, addr);
     let config = Config {
         certificates: vec![cert],
         server_name: "ignored".to_owned(),
@@ -70,13 +130,38 @@ async fn connect(
     }
 }
 
+/// Connect to the addresses of the preferred address family first
+/// and fall back to the remaining addresses if none is reachable.
+async fn connect_preferred(
+    addrs: &[SocketAddr],
+    preferred: Option<AddressFamily>,
+    cert: Certificate,
+    options: SocketOptions,
+) -> Result<(Arc<dyn Conn + Send + Sync>, SocketAddr), LanMouseConnectionError> {
+    let Some(preferred) = preferred else {
+        return connect_any(addrs, cert, options).await;
+    };
+    let (preferred_addrs, fallback): (Vec<_>, Vec<_>) = addrs
+        .iter()
+        .partition(|a| AddressFamily::of(&a.ip()) == preferred);
+    if !preferred_addrs.is_empty() {
+        match connect_any(&preferred_addrs, cert.clone(), options).await {
+            Ok(c) => return Ok(c),
+            Err(e) if fallback.is_empty() => return Err(e),
+            Err(_) => log::info!("no {preferred} address reachable, trying {fallback:?}"),
+        }
+    }
+    connect_any(&fallback, cert, options).await
+}
+
 async fn connect_any(
     addrs: &[SocketAddr],
     cert: Certificate,
+    options: SocketOptions,
 ) -> Result<(Arc<dyn Conn + Send + Sync>, SocketAddr), LanMouseConnectionError> {
     let mut joinset = JoinSet::new();
     for &addr in addrs {
-        joinset.spawn_local(connect(addr, cert.clone()));
+        joinset.spawn_local(connect(addr, cert.clone(), options));
     }
     loop {
         match joinset.join_next().await {
@@ -98,20 +183,202 @@ pub(crate) struct LanMouseConnection {
     connecting: Rc<Mutex<HashSet<ClientHandle>>>,
     recv_rx: Receiver<(ClientHandle, ProtoEvent)>,
     recv_tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
+    ping_response: Rc<RefCell<HashMap<SocketAddr, Instant>>>,
+    /// round trip time of the last ping of each client
+    latency: Rc<RefCell<HashMap<ClientHandle, Duration>>>,
+    events: ConnectionEvents,
+    events_rx: Option<Receiver<ConnectionEvent>>,
+    loss_simulation: Option<LossSimulation>,
+    /// number of times key and button events are sent again
+    retransmit: u32,
+    /// sequence number of the last retransmitted event
+    sequence: Cell<u32>,
+    /// interval of clock offset estimations (disabled if `None`)
+    clock_sync: Option<Duration>,
+}
+
+/// changes of the connection state of a client
+pub(crate) enum ConnectionEvent {
+    /// client was reconnected at a different address than before
+    AddressChanged(ClientHandle, SocketAddr),
+    /// client became incompatible (or compatible again)
+    Incompatible(ClientHandle, Option<VersionMismatch>),
+    /// control message received from the client
+    Control(ClientHandle, ControlMessage),
+    /// client became reachable (connected with emulation available)
+    /// or unreachable (connection failed or dropped)
+    Reachable(ClientHandle, bool),
+}
+
+/// log target of connection lifecycle transitions
+const LIFECYCLE_TARGET: &str = "lan_mouse::lifecycle";
+
+/// reports connection state changes and
+/// tracks the address each client was last connected at
+#[derive(Clone)]
+struct ConnectionEvents {
+    client_manager: ClientManager,
+    last: Rc<RefCell<HashMap<ClientHandle, SocketAddr>>>,
+    /// last reported reachability of each client
+    reachable: Rc<RefCell<HashMap<ClientHandle, bool>>>,
+    /// reachability changes not yet reported and when they occurred
+    pending: Rc<RefCell<HashMap<ClientHandle, (bool, Instant)>>>,
+    /// how long a change of reachability must persist to be reported
+    flap_damping: Option<Duration>,
+    tx: Sender<ConnectionEvent>,
+    /// log lifecycle transitions of connections
+    lifecycle: bool,
+}
+
+impl ConnectionEvents {
+    /// log a lifecycle transition of the connection to a client.
+    /// Lines start with `client <handle> (<hostname>)` to be greppable per client.
+    fn lifecycle(&self, handle: ClientHandle, transition: std::fmt::Arguments) {
+        if !self.lifecycle {
+            return;
+        }
+        let hostname = self.client_manager.get_hostname(handle);
+        let hostname = hostname.as_deref().unwrap_or("<unknown>");
+        log::info!(target: LIFECYCLE_TARGET, "client {handle} ({hostname}): {transition}");
+    }
+
+    fn connecting(&self, handle: ClientHandle, addrs: &[SocketAddr]) {
+        self.lifecycle(handle, format_args!("connecting (ips: {addrs:?})"));
+    }
+
+    fn failed(&self, handle: ClientHandle, error: &LanMouseConnectionError) {
+        self.lifecycle(handle, format_args!("connection failed: {error}"));
+        self.reachable(handle, false);
+    }
+
+    fn connected(&self, handle: ClientHandle, addr: SocketAddr) {
+        let prev = self.last.borrow_mut().insert(handle, addr);
+        match prev {
+            Some(_) => self.lifecycle(
+                handle,
+                format_args!("reconnected @ {addr} (handshake complete)"),
+            ),
+            None => self.lifecycle(
+                handle,
+                format_args!("connected @ {addr} (handshake complete)"),
+            ),
+        }
+        if let Some(prev) = prev {
+            if prev != addr {
+                log::info!("client ({handle}) switched from {prev} to {addr}");
+                self.tx
+                    .send(ConnectionEvent::AddressChanged(handle, addr))
+                    .expect("channel closed");
+            }
+        }
+    }
+
+    fn ready(&self, handle: ClientHandle, addr: SocketAddr, ready: bool) {
+        match ready {
+            true => self.lifecycle(handle, format_args!("ready @ {addr}")),
+            false => self.lifecycle(handle, format_args!("emulation unavailable @ {addr}")),
+        }
+    }
+
+    fn dropped(&self, handle: ClientHandle, addr: SocketAddr, reason: &dyn Display) {
+        self.lifecycle(handle, format_args!("dropped @ {addr}: {reason}"));
+        self.reachable(handle, false);
+    }
+
+    /// report changes of the reachability of a client,
+    /// once they persisted for the flap damping period
+    fn reachable(&self, handle: ClientHandle, reachable: bool) {
+        let Some(damping) = self.flap_damping else {
+            self.report_reachable(handle, reachable);
+            return;
+        };
+        let mut pending = self.pending.borrow_mut();
+        if let Some(&(state, since)) = pending.get(&handle) {
+            if state == reachable {
+                return;
+            }
+            pending.remove(&handle);
+            let hostname = self.client_manager.get_hostname(handle);
+            let hostname = hostname.as_deref().unwrap_or("<unknown>");
+            let state = if state { "reachable" } else { "unreachable" };
+            log::info!(
+                "client {handle} ({hostname}): flap damping suppressed transition to {state} after {:?}",
+                since.elapsed()
+            );
+        }
+        if self.reachable.borrow().get(&handle) == Some(&reachable) {
+            return;
+        }
+        let since = Instant::now();
+        pending.insert(handle, (reachable, since));
+        let events = self.clone();
+        spawn_local(async move {
+            tokio::time::sleep(damping).await;
+            let mut pending = events.pending.borrow_mut();
+            if pending.get(&handle) == Some(&(reachable, since)) {
+                pending.remove(&handle);
+                drop(pending);
+                events.report_reachable(handle, reachable);
+            }
+        });
+    }
+
+    fn report_reachable(&self, handle: ClientHandle, reachable: bool) {
+        if self.reachable.borrow_mut().insert(handle, reachable) != Some(reachable) {
+            self.tx
+                .send(ConnectionEvent::Reachable(handle, reachable))
+                .expect("channel closed");
+        }
+    }
+
+    fn incompatible(&self, handle: ClientHandle, incompatible: Option<VersionMismatch>) {
+        self.tx
+            .send(ConnectionEvent::Incompatible(handle, incompatible))
+            .expect("channel closed");
+    }
+
+    fn control(&self, handle: ClientHandle, message: ControlMessage) {
+        self.tx
+            .send(ConnectionEvent::Control(handle, message))
+            .expect("channel closed");
+    }
 }
 
 impl LanMouseConnection {
-    pub(crate) fn new(cert: Certificate, client_manager: ClientManager) -> Self {
+    pub(crate) fn new(
+        cert: Certificate,
+        client_manager: ClientManager,
+        loss_simulation: Option<LossSimulation>,
+        log_lifecycle: bool,
+        flap_damping: Option<Duration>,
+        retransmit: u32,
+        clock_sync: Option<Duration>,
+    ) -> Self {
         let (recv_tx, recv_rx) = channel();
+        let (events_tx, events_rx) = channel();
         Self {
             cert,
-            client_manager,
+            client_manager: client_manager.clone(),
             conns: Default::default(),
             connecting: Default::default(),
             recv_rx,
             recv_tx,
             ping_response: Default::default(),
+            latency: Default::default(),
+            events: ConnectionEvents {
+                client_manager,
+                last: Default::default(),
+                reachable: Default::default(),
+                pending: Default::default(),
+                flap_damping,
+                tx: events_tx,
+                lifecycle: log_lifecycle,
+            },
+            events_rx: Some(events_rx),
+            loss_simulation,
+            retransmit,
+            sequence: Default::default(),
+            clock_sync,
         }
     }
 
@@ -119,13 +386,21 @@ impl LanMouseConnection {
         self.recv_rx.recv().await.expect("channel closed")
     }
 
+    /// Receiver notified about changes of the connection
+    /// state of clients. Can only be taken once.
+    pub(crate) fn take_events(&mut self) -> Receiver<ConnectionEvent> {
+        self.events_rx
+            .take()
+            .expect("connection events already taken")
+    }
+
     pub(crate) async fn send(
         &self,
         event: ProtoEvent,
         handle: ClientHandle,
     ) -> Result<(), LanMouseConnectionError> {
-        let (buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
-        let buf = &buf[..len];
+        let (event, copies) = self.sequence(event, handle);
+        let encoded = encode(&self.client_manager, handle, event);
         if let Some(addr) = self.client_manager.active_addr(handle) {
             let conn = {
                 let conns = self.conns.lock().await;
@@ -135,10 +410,21 @@ impl LanMouseConnection {
                 if !self.client_manager.alive(handle) {
                     return Err(LanMouseConnectionError::TargetEmulationDisabled);
                 }
+                // not understood by the client, which would drop the connection
+                let Some((buf, len)) = encoded else {
+                    log::trace!("{event} not supported by client {handle}, skipped");
+                    return Ok(());
+                };
+                let buf = &buf[..len];
                 match conn.send(buf).await {
-                    Ok(_) => {}
+                    Ok(_) => {
+                        if copies > 0 {
+                            spawn_local(retransmit(conn, buf.to_vec(), copies));
+                        }
+                    }
                     Err(e) => {
                         log::warn!("client {handle} failed to send: {e}");
+                        self.events.dropped(handle, addr, &e);
                         disconnect(&self.client_manager, handle, addr, &self.conns).await;
                     }
                 }
@@ -147,82 +433,225 @@ impl LanMouseConnection {
             }
         }
 
-        // check if we are already trying to connect
+        self.connect(handle).await;
+        Err(LanMouseConnectionError::NotConnected)
+    }
+
+    /// Wrap key and button events in a [`ProtoEvent::Sequenced`]
+    /// if they are to be retransmitted.
+    /// Returns the event to send and the number of additional copies.
+    fn sequence(&self, event: ProtoEvent, handle: ClientHandle) -> (ProtoEvent, u32) {
+        let ProtoEvent::Input(input) = event else {
+            return (event, 0);
+        };
+        // not understood by legacy clients
+        if self.retransmit == 0 || wire_format(&self.client_manager, handle) == WireFormat::Legacy {
+            return (event, 0);
+        }
+        let seq = self.sequence.get().wrapping_add(1);
+        match ProtoEvent::sequenced(seq, input) {
+            Some(sequenced) => {
+                self.sequence.set(seq);
+                (sequenced, self.retransmit)
+            }
+            None => (event, 0),
+        }
+    }
+
+    /// round trip time of the last ping of the client, if connected
+    pub(crate) fn latency(&self, handle: ClientHandle) -> Option<Duration> {
+        self.latency.borrow().get(&handle).copied()
+    }
+
+    /// Connect to the client in the background, if it is not connected.
+    /// Its reachability is reported once the connection attempt finishes.
+    pub(crate) async fn probe(&self, handle: ClientHandle) {
+        if self.client_manager.active_addr(handle).is_none() {
+            self.connect(handle).await;
+        }
+    }
+
+    /// start connecting to the client, unless already trying to
+    async fn connect(&self, handle: ClientHandle) {
         let mut connecting = self.connecting.lock().await;
         if !connecting.contains(&handle) {
             connecting.insert(handle);
             // connect in the background
-            spawn_local(connect_to_handle(
-                self.client_manager.clone(),
-                self.cert.clone(),
+            let task = ConnectTask {
+                client_manager: self.client_manager.clone(),
+                cert: self.cert.clone(),
                 handle,
-                self.conns.clone(),
-                self.connecting.clone(),
-                self.recv_tx.clone(),
-                self.ping_response.clone(),
-            ));
+                conns: self.conns.clone(),
+                connecting: self.connecting.clone(),
+                tx: self.recv_tx.clone(),
+                ping_response: self.ping_response.clone(),
+                latency: self.latency.clone(),
+                events: self.events.clone(),
+                loss_simulation: self.loss_simulation,
+                clock_sync: self.clock_sync,
+            };
+            spawn_local(task.run());
         }
-        Err(LanMouseConnectionError::NotConnected)
+    }
+
+    /// whether control messages reach the client: they are
+    /// not understood by legacy clients and not relayed
+    pub(crate) fn accepts_control(&self, handle: ClientHandle) -> bool {
+        wire_format(&self.client_manager, handle) != WireFormat::Legacy
+            && self.client_manager.get_relay_target(handle).is_none()
+    }
+
+    /// Send a control message to the client, if it is connected.
+    /// Unlike events, control messages do not initiate a connection.
+    pub(crate) async fn send_control(
+        &self,
+        message: ControlMessage,
+        handle: ClientHandle,
+    ) -> Result<(), LanMouseConnectionError> {
+        if !self.accepts_control(handle) {
+            return Ok(());
+        }
+        let addr = self
+            .client_manager
+            .active_addr(handle)
+            .ok_or(LanMouseConnectionError::NotConnected)?;
+        let conn = self
+            .conns
+            .lock()
+            .await
+            .get(&addr)
+            .cloned()
+            .ok_or(LanMouseConnectionError::NotConnected)?;
+        let (buf, len) = message.encode();
+        if let Err(e) = conn.send(&buf[..len]).await {
+            log::warn!("client {handle} failed to send: {e}");
+            self.events.dropped(handle, addr, &e);
+            disconnect(&self.client_manager, handle, addr, &self.conns).await;
+        }
+        log::trace!("{message} >->->->->- {addr}");
+        Ok(())
     }
 }
 
-async fn connect_to_handle(
+/// background task connecting to a client
+struct ConnectTask {
     client_manager: ClientManager,
     cert: Certificate,
     handle: ClientHandle,
     conns: Rc<Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>>,
     connecting: Rc<Mutex<HashSet<ClientHandle>>>,
     tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
-) -> Result<(), LanMouseConnectionError> {
-    log::info!("client {handle} connecting ...");
-    // sending did not work, figure out active conn.
-    if let Some(addrs) = client_manager.get_ips(handle) {
-        let port = client_manager.get_port(handle).unwrap_or(DEFAULT_PORT);
-        let addrs = addrs
-            .into_iter()
-            .map(|a| SocketAddr::new(a, port))
-            .collect::<Vec<_>>();
-        log::info!("client ({handle}) connecting ... (ips: {addrs:?})");
-        let res = connect_any(&addrs, cert).await;
-        let (conn, addr) = match res {
-            Ok(c) => c,
-            Err(e) => {
-                connecting.lock().await.remove(&handle);
-                return Err(e);
-            }
-        };
-        log::info!("client ({handle}) connected @ {addr}");
-        client_manager.set_active_addr(handle, Some(addr));
-        conns.lock().await.insert(addr, conn.clone());
-        connecting.lock().await.remove(&handle);
-
-        // poll connection for active
-        spawn_local(ping_pong(addr, conn.clone(), ping_response.clone()));
+    ping_response: Rc<RefCell<HashMap<SocketAddr, Instant>>>,
+    latency: Rc<RefCell<HashMap<ClientHandle, Duration>>>,
+    events: ConnectionEvents,
+    loss_simulation: Option<LossSimulation>,
+    clock_sync: Option<Duration>,
+}
 
-        // receiver
-        spawn_local(receive_loop(
+impl ConnectTask {
+    async fn run(self) -> Result<(), LanMouseConnectionError> {
+        let Self {
             client_manager,
+            cert,
             handle,
-            addr,
-            conn,
             conns,
+            connecting,
             tx,
-            ping_response.clone(),
-        ));
-        return Ok(());
+            ping_response,
+            latency,
+            events,
+            loss_simulation,
+            clock_sync,
+        } = self;
+        log::info!("client {handle} connecting ...");
+        // sending did not work, figure out active conn.
+        if let Some(addrs) = client_manager.get_ips(handle) {
+            let port = client_manager.get_port(handle).unwrap_or(DEFAULT_PORT);
+            let addrs = addrs
+                .into_iter()
+                .map(|a| SocketAddr::new(a, port))
+                .collect::<Vec<_>>();
+            log::info!("client ({handle}) connecting ... (ips: {addrs:?})");
+            events.connecting(handle, &addrs);
+            let preferred = client_manager.get_preferred_family(handle);
+            let (ttl, dscp) = client_manager.get_socket_options(handle);
+            let options = SocketOptions { ttl, dscp };
+            let res = connect_preferred(&addrs, preferred, cert, options).await;
+            let (conn, addr) = match res {
+                Ok(c) => c,
+                Err(e) => {
+                    events.failed(handle, &e);
+                    connecting.lock().await.remove(&handle);
+                    return Err(e);
+                }
+            };
+            let conn: Arc<dyn Conn + Send + Sync> = match loss_simulation {
+                Some(simulation) => Arc::new(LossyConn::new(conn, simulation)),
+                None => conn,
+            };
+            log::info!(
+                "client ({handle}) connected @ {addr} (wire format: {})",
+                wire_format(&client_manager, handle)
+            );
+            client_manager.set_active_addr(handle, Some(addr));
+            conns.lock().await.insert(addr, conn.clone());
+            connecting.lock().await.remove(&handle);
+            events.connected(handle, addr);
+
+            // poll connection for active
+            spawn_local(ping_pong(
+                client_manager.clone(),
+                handle,
+                addr,
+                conn.clone(),
+                ping_response.clone(),
+                latency,
+            ));
+
+            // clock messages are not relayed
+            if let Some(interval) =
+                clock_sync.filter(|_| client_manager.get_relay_target(handle).is_none())
+            {
+                spawn_local(clock_sync_task(
+                    client_manager.clone(),
+                    handle,
+                    addr,
+                    conn.clone(),
+                    interval,
+                ));
+            }
+
+            // receiver
+            let task = ReceiveTask {
+                client_manager,
+                handle,
+                addr,
+                conn,
+                conns,
+                tx,
+                ping_response,
+                events,
+            };
+            spawn_local(task.run());
+            return Ok(());
+        }
+        connecting.lock().await.remove(&handle);
+        Err(LanMouseConnectionError::NotConnected)
     }
-    connecting.lock().await.remove(&handle);
-    Err(LanMouseConnectionError::NotConnected)
 }
 
 async fn ping_pong(
+    client_manager: ClientManager,
+    handle: ClientHandle,
     addr: SocketAddr,
     conn: Arc<dyn Conn + Send + Sync>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
+    ping_response: Rc<RefCell<HashMap<SocketAddr, Instant>>>,
+    latency: Rc<RefCell<HashMap<ClientHandle, Duration>>>,
 ) {
     loop {
-        let (buf, len) = ProtoEvent::Ping.into();
+        let (buf, len) = encode(&client_manager, handle, ProtoEvent::Ping)
+            .expect("ping is supported by every version");
+        let sent = Instant::now();
         if let Err(e) = conn.send(&buf[..len]).await {
             log::warn!("{addr}: send error `{e}`, closing connection");
             let _ = conn.close().await;
@@ -232,39 +661,199 @@ async fn ping_pong(
 
         tokio::time::sleep(Duration::from_millis(500)).await;
 
-        if !ping_response.borrow_mut().remove(&addr) {
+        let Some(received) = ping_response.borrow_mut().remove(&addr) else {
             log::warn!("{addr} did not respond, closing connection");
+            latency.borrow_mut().remove(&handle);
             let _ = conn.close().await;
             return;
+        };
+        latency
+            .borrow_mut()
+            .insert(handle, received.saturating_duration_since(sent));
+    }
+}
+
+/// periodically request the clock of the client to estimate its offset
+async fn clock_sync_task(
+    client_manager: ClientManager,
+    handle: ClientHandle,
+    addr: SocketAddr,
+    conn: Arc<dyn Conn + Send + Sync>,
+    interval: Duration,
+) {
+    loop {
+        // not understood by legacy clients
+        if wire_format(&client_manager, handle) == WireFormat::Legacy {
+            tokio::time::sleep(interval).await;
+            continue;
+        }
+        let (buf, len) = ControlMessage::ClockRequest(lan_mouse_proto::clock_timestamp()).encode();
+        if let Err(e) = conn.send(&buf[..len]).await {
+            log::debug!("{addr}: stopping clock synchronization: {e}");
+            return;
         }
+        tokio::time::sleep(interval).await;
     }
 }
 
-async fn receive_loop(
+/// background task receiving events from a connected client
+struct ReceiveTask {
     client_manager: ClientManager,
     handle: ClientHandle,
     addr: SocketAddr,
     conn: Arc<dyn Conn + Send + Sync>,
     conns: Rc<Mutex<HashMap<SocketAddr, Arc<dyn Conn + Send + Sync>>>>,
     tx: Sender<(ClientHandle, ProtoEvent)>,
-    ping_response: Rc<RefCell<HashSet<SocketAddr>>>,
-) {
-    let mut buf = [0u8; MAX_EVENT_SIZE];
-    while conn.recv(&mut buf).await.is_ok() {
-        if let Ok(event) = buf.try_into() {
+    ping_response: Rc<RefCell<HashMap<SocketAddr, Instant>>>,
+    events: ConnectionEvents,
+}
+
+impl ReceiveTask {
+    async fn run(self) {
+        let Self {
+            client_manager,
+            handle,
+            addr,
+            conn,
+            conns,
+            tx,
+            ping_response,
+            events,
+        } = self;
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        // whether the client reported emulation to be available
+        let mut ready = false;
+        let error = loop {
+            let len = match conn.recv(&mut buf).await {
+                Ok(len) => len,
+                Err(e) => break e,
+            };
+            // legacy events are decoded from a fixed size buffer:
+            // fields not sent by older versions must not be read from previous packets
+            buf[len..].fill(0);
+            if ControlMessage::is_control(&buf[..len]) {
+                match ControlMessage::decode(&buf[..len]) {
+                    Ok(ControlMessage::ClockResponse {
+                        origin,
+                        receive,
+                        transmit,
+                    }) => {
+                        let now = lan_mouse_proto::clock_timestamp() as i64;
+                        let (origin, receive, transmit) =
+                            (origin as i64, receive as i64, transmit as i64);
+                        let offset = ((receive - origin) + (transmit - now)) / 2;
+                        let round_trip = (now - origin) - (transmit - receive);
+                        log::debug!(
+                            "client ({handle}) @ {addr}: clock offset {offset}µs (round trip {round_trip}µs)"
+                        );
+                        client_manager.set_clock_offset(handle, Some(offset));
+                    }
+                    Ok(message) => {
+                        log::trace!("{addr} <==<==<== {message}");
+                        events.control(handle, message);
+                    }
+                    // unknown control messages of newer versions are ignored
+                    Err(e) => log::debug!("{addr}: ignoring control message: {e}"),
+                }
+                continue;
+            }
+            let packet: [u8; MAX_EVENT_SIZE] =
+                buf[..MAX_EVENT_SIZE].try_into().expect("packet size");
+            let event = match packet.try_into() {
+                Ok(event) => event,
+                Err(ProtocolError::UnsupportedVersion(remote)) => {
+                    let mismatch = VersionMismatch {
+                        local: WireFormat::NEWEST.version(),
+                        remote,
+                    };
+                    if client_manager.set_incompatible(handle, Some(mismatch)) {
+                        log::warn!("client ({handle}) @ {addr} is incompatible: {mismatch}");
+                        events.incompatible(handle, Some(mismatch));
+                    }
+                    continue;
+                }
+                Err(ProtocolError::UnknownEventType(t)) => {
+                    log::debug!("{addr}: ignoring event of unknown type {t}");
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            if client_manager.set_incompatible(handle, None) {
+                events.incompatible(handle, None);
+            }
             log::trace!("{addr} <==<==<== {event}");
             match event {
-                ProtoEvent::Pong(b) => {
+                ProtoEvent::Pong(b, version) => {
+                    if client_manager.set_wire_version(handle, version) {
+                        log::info!(
+                            "client ({handle}) @ {addr}: using wire format {}",
+                            wire_format(&client_manager, handle)
+                        );
+                    }
                     client_manager.set_active_addr(handle, Some(addr));
                     client_manager.set_alive(handle, b);
-                    ping_response.borrow_mut().insert(addr);
+                    ping_response.borrow_mut().insert(addr, Instant::now());
+                    if b != ready {
+                        ready = b;
+                        events.ready(handle, addr, ready);
+                    }
+                    events.reachable(handle, b);
                 }
                 event => tx.send((handle, event)).expect("channel closed"),
             }
+        };
+        log::warn!("recv error");
+        events.dropped(handle, addr, &error);
+        disconnect(&client_manager, handle, addr, &conns).await;
+    }
+}
+
+/// Encode an event for the client, wrapping it in a relay header
+/// if it is reached through a hub. Returns `None` for events
+/// the wire format of the client does not support.
+fn encode(
+    client_manager: &ClientManager,
+    handle: ClientHandle,
+    event: ProtoEvent,
+) -> Option<([u8; MAX_RELAY_SIZE], usize)> {
+    let format = wire_format(client_manager, handle);
+    if !event.is_supported(format) {
+        return None;
+    }
+    Some(match client_manager.get_relay_target(handle) {
+        Some(destination) => event.encode_relay(destination, format),
+        None => {
+            let mut buf = [0u8; MAX_RELAY_SIZE];
+            let (event, len) = event.encode(format);
+            buf[..len].copy_from_slice(&event[..len]);
+            (buf, len)
+        }
+    })
+}
+
+/// time between two copies of a retransmitted event
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(5);
+
+/// send additional copies of an already sent event
+async fn retransmit(conn: Arc<dyn Conn + Send + Sync>, buf: Vec<u8>, copies: u32) {
+    for _ in 0..copies {
+        tokio::time::sleep(RETRANSMIT_INTERVAL).await;
+        if let Err(e) = conn.send(&buf).await {
+            log::debug!("failed to retransmit event: {e}");
+            return;
         }
     }
-    log::warn!("recv error");
-    disconnect(&client_manager, handle, addr, &conns).await;
+}
+
+/// wire format used to encode events for the client
+fn wire_format(client_manager: &ClientManager, handle: ClientHandle) -> WireFormat {
+    match client_manager.get_wire_format(handle).unwrap_or_default() {
+        lan_mouse_ipc::WireFormat::Auto => {
+            WireFormat::negotiate(client_manager.get_wire_version(handle))
+        }
+        lan_mouse_ipc::WireFormat::Legacy => WireFormat::Legacy,
+        lan_mouse_ipc::WireFormat::V1 => WireFormat::V1,
+    }
 }
 
 async fn disconnect(
@@ -276,6 +865,55 @@ async fn disconnect(
     log::warn!("client ({handle}) @ {addr} connection closed");
     conns.lock().await.remove(&addr);
     client_manager.set_active_addr(handle, None);
+    client_manager.set_clock_offset(handle, None);
+    client_manager.set_wire_version(handle, 0);
     let active: Vec<SocketAddr> = conns.lock().await.keys().copied().collect();
     log::info!("active connections: {active:?}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_event::{Event, PointerEvent};
+    use lan_mouse_proto::EventType;
+
+    fn pointer(event: PointerEvent) -> ProtoEvent {
+        ProtoEvent::Input(Event::Pointer(event))
+    }
+
+    #[test]
+    fn legacy_clients_only_receive_known_types() {
+        let client_manager = ClientManager::new(1);
+        let handle = client_manager.add_client().unwrap();
+        let events = [
+            pointer(PointerEvent::Motion {
+                time: 0,
+                dx: 1.,
+                dy: 1.,
+            }),
+            pointer(PointerEvent::MotionAbsolute {
+                time: 0,
+                x: 0.5,
+                y: 0.5,
+            }),
+            pointer(PointerEvent::ScrollStop { time: 0, axis: 0 }),
+            pointer(PointerEvent::ScrollCancel { time: 0, axis: 0 }),
+            ProtoEvent::Origin(1),
+            ProtoEvent::Frame,
+        ];
+        for event in events {
+            // every client is legacy until it reported v1 support
+            if let Some((buf, _)) = encode(&client_manager, handle, event) {
+                assert!(
+                    buf[0] <= EventType::Ack as u8,
+                    "{event} sent to legacy client"
+                );
+            }
+            client_manager.set_wire_version(handle, WireFormat::V1.version());
+            assert!(encode(&client_manager, handle, event).is_some());
+            client_manager.set_wire_version(handle, 0);
+        }
+        let motion = encode(&client_manager, handle, events[0]);
+        assert!(motion.is_some());
+    }
+}
@@ -1,21 +1,28 @@
 use crate::listen::{LanMouseListener, ListenEvent, ListenerCreationError};
 use futures::StreamExt;
-use input_emulation::{EmulationHandle, InputEmulation, InputEmulationError};
-use input_event::Event;
-use lan_mouse_proto::{Position, ProtoEvent};
+use input_emulation::{
+    EmulationError, EmulationHandle, EmulationOptions, IdleInhibitor, InputEmulation,
+    InputEmulationError,
+};
+use input_event::{scancode, Event, KeyboardEvent, PointerEvent};
+use lan_mouse_proto::{Action, ControlMessage, Position, ProtoEvent, WireFormat};
 use local_channel::mpsc::{channel, Receiver, Sender};
 use std::{
     cell::Cell,
-    collections::HashMap,
-    net::SocketAddr,
+    collections::{HashMap, HashSet},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     rc::Rc,
     time::{Duration, Instant},
 };
 use tokio::{
+    process::Command,
     select,
     task::{spawn_local, JoinHandle},
 };
 
+/// pseudo address of input captured on this device and emulated locally
+const LOCAL_INPUT: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
 /// emulation handling events received from a listener
 pub(crate) struct Emulation {
     task: JoinHandle<()>,
@@ -50,13 +57,55 @@ pub(crate) enum EmulationEvent {
     EmulationDisabled,
     /// emulation was enabled
     EmulationEnabled,
+    /// emulation was paused (true) or resumed (false)
+    EmulationPaused(bool),
+    /// devices of the given capabilities were not bound during warmup
+    WarmupIncomplete(Vec<&'static str>),
+    /// the capabilities without a device changed (empty if ready)
+    MissingDevices(Vec<&'static str>),
+    /// device types the user granted access to
+    GrantedDevices(Vec<&'static str>),
+    /// the emulation backend failed in the background and was disabled
+    BackendFailed(String),
     /// capture should be released
     ReleaseNotify,
+    /// event received from `addr` to be relayed to the client `destination`
+    Relay {
+        addr: SocketAddr,
+        destination: u64,
+        event: ProtoEvent,
+    },
+    /// control message received from `addr`
+    Control {
+        addr: SocketAddr,
+        message: ControlMessage,
+    },
+    /// `addr` started forwarding input
+    SessionStarted {
+        addr: SocketAddr,
+    },
+    /// `addr` stopped forwarding input
+    SessionEnded {
+        addr: SocketAddr,
+    },
+    /// the session of `addr` expired, it has to authenticate again
+    ReauthRequired {
+        addr: SocketAddr,
+    },
+    /// total number of duplicate events dropped from the device
+    DuplicatesDropped {
+        fingerprint: String,
+        count: u64,
+    },
 }
 
 enum EmulationRequest {
     Reenable,
+    Pause(bool),
+    /// input captured on this device
+    Local(Vec<Event>),
     Release(SocketAddr),
+    Reply(SocketAddr, ProtoEvent),
     ChangePort(u16),
     Terminate,
 }
@@ -64,9 +113,15 @@ enum EmulationRequest {
 impl Emulation {
     pub(crate) fn new(
         backend: Option<input_emulation::Backend>,
+        options: EmulationOptions,
         listener: LanMouseListener,
+        deadman: Deadman,
+        entry_snap: Option<EntrySnap>,
+        keyboard_merge: KeyboardMerge,
+        actions: HashMap<Action, ActionHandler>,
     ) -> Self {
-        let emulation_proxy = EmulationProxy::new(backend);
+        let inhibit_idle = options.inhibit_idle;
+        let emulation_proxy = EmulationProxy::new(backend, options, keyboard_merge);
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let emulation_task = ListenTask {
@@ -74,6 +129,14 @@ impl Emulation {
             emulation_proxy,
             request_rx,
             event_tx,
+            sessions: Default::default(),
+            deadman,
+            deadman_suspended: None,
+            inhibit_idle,
+            idle_inhibitor: None,
+            entry_snap,
+            entered: Default::default(),
+            actions,
         };
         let task = spawn_local(emulation_task.run());
         Self {
@@ -89,12 +152,33 @@ impl Emulation {
             .expect("channel closed");
     }
 
+    /// send an event to the connection with the given address
+    pub(crate) fn reply(&self, addr: SocketAddr, event: ProtoEvent) {
+        self.request_tx
+            .send(EmulationRequest::Reply(addr, event))
+            .expect("channel closed");
+    }
+
     pub(crate) fn reenable(&self) {
         self.request_tx
             .send(EmulationRequest::Reenable)
             .expect("channel closed");
     }
 
+    /// suspend (true) or resume (false) emulation, keeping its session
+    pub(crate) fn pause(&self, paused: bool) {
+        self.request_tx
+            .send(EmulationRequest::Pause(paused))
+            .expect("channel closed");
+    }
+
+    /// emulate input captured on this device
+    pub(crate) fn emulate_local(&self, events: Vec<Event>) {
+        self.request_tx
+            .send(EmulationRequest::Local(events))
+            .expect("channel closed");
+    }
+
     pub(crate) fn request_port_change(&self, port: u16) {
         self.request_tx
             .send(EmulationRequest::ChangePort(port))
@@ -122,11 +206,176 @@ struct ListenTask {
     emulation_proxy: EmulationProxy,
     request_rx: Receiver<EmulationRequest>,
     event_tx: Sender<EmulationEvent>,
+    /// latest session announced by each sending device
+    sessions: HashMap<SocketAddr, Session>,
+    /// release of the input held by devices that stopped responding
+    deadman: Deadman,
+    /// end of the grace period after a port change
+    deadman_suspended: Option<Instant>,
+    /// whether to inhibit the screen saver during sessions
+    inhibit_idle: bool,
+    /// held while any session is active
+    idle_inhibitor: Option<IdleInhibitor>,
+    /// monitor layout to snap the cursor onto when entered
+    entry_snap: Option<EntrySnap>,
+    /// devices that entered this device and did not leave yet
+    entered: HashSet<SocketAddr>,
+    /// how named actions are performed
+    actions: HashMap<Action, ActionHandler>,
+}
+
+/// Release of the input held by sending devices that stopped responding
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Deadman {
+    /// silence after which the input held by a device is released
+    pub(crate) timeout: Duration,
+    /// Time after a port change during which no device is released,
+    /// as their messages to the old port are lost until they reconnect.
+    pub(crate) port_change_grace: Duration,
+}
+
+/// rectangle of a monitor in the layout of this device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Monitor {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// monitor to enter if several monitors touch the entered edge
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum EntryMonitor {
+    /// the topmost or leftmost monitor
+    #[default]
+    First,
+    /// the bottommost or rightmost monitor
+    Last,
+    /// the monitor sharing the longest part of the edge
+    Largest,
+}
+
+/// Handling of key events of several origins (e.g. seats) of a sending
+/// device that share a keyboard, i.e. unless origins are emulated
+/// with separate devices.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum KeyboardMerge {
+    /// a key is pressed by the first and released by the last origin
+    /// holding it, releases of origins not holding the key are dropped
+    #[default]
+    Track,
+    /// emulate key events as received
+    Raw,
+}
+
+/// How a named action received from another device is performed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ActionHandler {
+    /// shell command performing the action
+    pub(crate) command: Option<String>,
+    /// key chord emulated instead if there is no command
+    pub(crate) keys: Vec<scancode::Linux>,
+}
+
+/// Snaps the cursor onto the monitor at the entered edge of the layout,
+/// so it does not appear on whichever monitor it was left on.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct EntrySnap {
+    pub(crate) monitors: Vec<Monitor>,
+    pub(crate) ambiguous: EntryMonitor,
+    pub(crate) offset: EntryOffset,
+}
+
+/// Offset (in pixels) of the entry point along each edge, for devices
+/// whose monitors are not physically aligned with the entered monitor.
+/// Positive values move the entry point down (left and right edge)
+/// or to the right (top and bottom edge).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct EntryOffset {
+    pub(crate) left: i32,
+    pub(crate) right: i32,
+    pub(crate) top: i32,
+    pub(crate) bottom: i32,
+}
+
+impl EntryOffset {
+    fn get(&self, pos: Position) -> i32 {
+        match pos {
+            Position::Left => self.left,
+            Position::Right => self.right,
+            Position::Top => self.top,
+            Position::Bottom => self.bottom,
+        }
+    }
+}
+
+impl EntrySnap {
+    /// Center of the edge of the monitor entered at `pos`, moved by the
+    /// offset of that edge within the monitor, relative to the area of
+    /// all monitors (`0.0..=1.0`).
+    fn position(&self, pos: Position) -> Option<(f64, f64)> {
+        let left = self.monitors.iter().map(|m| m.x).min()?;
+        let top = self.monitors.iter().map(|m| m.y).min()?;
+        let right = self.monitors.iter().map(|m| m.x + m.width as i32).max()?;
+        let bottom = self.monitors.iter().map(|m| m.y + m.height as i32).max()?;
+        let candidates = self.monitors.iter().filter(|m| match pos {
+            Position::Left => m.x == left,
+            Position::Right => m.x + m.width as i32 == right,
+            Position::Top => m.y == top,
+            Position::Bottom => m.y + m.height as i32 == bottom,
+        });
+        // (offset along the edge, length of the edge)
+        let along = |m: &&Monitor| match pos {
+            Position::Left | Position::Right => (m.y, m.height),
+            Position::Top | Position::Bottom => (m.x, m.width),
+        };
+        let monitor = match self.ambiguous {
+            EntryMonitor::First => candidates.min_by_key(|m| along(m).0),
+            EntryMonitor::Last => candidates.max_by_key(|m| along(m).0),
+            EntryMonitor::Largest => candidates.max_by_key(|m| along(m).1),
+        }?;
+        let offset = self.offset.get(pos);
+        let entry = |start: i32, len: u32| {
+            (start + len as i32 / 2)
+                .saturating_add(offset)
+                .clamp(start, start + len as i32 - 1)
+        };
+        let (x, y) = match pos {
+            Position::Left => (monitor.x, entry(monitor.y, monitor.height)),
+            Position::Right => (
+                monitor.x + monitor.width as i32 - 1,
+                entry(monitor.y, monitor.height),
+            ),
+            Position::Top => (entry(monitor.x, monitor.width), monitor.y),
+            Position::Bottom => (
+                entry(monitor.x, monitor.width),
+                monitor.y + monitor.height as i32 - 1,
+            ),
+        };
+        let width = (right - left).max(1) as f64;
+        let height = (bottom - top).max(1) as f64;
+        Some(((x - left) as f64 / width, (y - top) as f64 / height))
+    }
+}
+
+/// forwarding session announced by a sending device
+#[derive(Clone, Copy, Debug)]
+struct Session {
+    id: u32,
+    active: bool,
+}
+
+/// whether session `a` was started after session `b`
+fn is_newer(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
 }
 
 impl ListenTask {
     async fn run(mut self) {
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        // check often enough to release shortly after the timeout
+        let period =
+            (self.deadman.timeout / 2).clamp(Duration::from_millis(100), Duration::from_secs(5));
+        let mut interval = tokio::time::interval(period);
         let mut last_response = HashMap::new();
         loop {
             select! {
@@ -140,24 +389,63 @@ impl ListenTask {
                                     log::info!("releasing capture: {addr} entered this device");
                                     self.event_tx.send(EmulationEvent::ReleaseNotify).expect("channel closed");
                                     self.listener.reply(addr, ProtoEvent::Ack(0)).await;
+                                    // repeated until acknowledged, snap only once
+                                    if self.entered.insert(addr) {
+                                        self.emulation_proxy.enter(addr);
+                                        self.snap_cursor(addr, pos);
+                                    }
                                     self.event_tx.send(EmulationEvent::Entered{addr, pos: to_ipc_pos(pos), fingerprint}).expect("channel closed");
                                 }
                             }
                             ProtoEvent::Leave(_) => {
+                                self.entered.remove(&addr);
                                 self.emulation_proxy.remove(addr);
                                 self.listener.reply(addr, ProtoEvent::Ack(0)).await;
                             }
                             ProtoEvent::Input(event) => self.emulation_proxy.consume(event, addr),
-                            ProtoEvent::Ping => self.listener.reply(addr, ProtoEvent::Pong(self.emulation_proxy.emulation_active.get())).await,
+                            ProtoEvent::Frame => self.emulation_proxy.frame(addr),
+                            ProtoEvent::Origin(id) => self.emulation_proxy.origin(addr, id),
+                            ProtoEvent::Ping => self.listener.reply(addr, ProtoEvent::Pong(self.emulation_proxy.emulation_active.get(), WireFormat::NEWEST.version())).await,
                             _ => {}
                         }
                     }
+                    Some(ListenEvent::Relay { destination, event, addr }) => {
+                        log::trace!("{event} <-<-<-<-<- {addr} (relay to {destination})");
+                        last_response.insert(addr, Instant::now());
+                        self.event_tx.send(EmulationEvent::Relay { addr, destination, event }).expect("channel closed");
+                    }
+                    Some(ListenEvent::Control { message, addr }) => {
+                        log::trace!("{message} <-<-<-<-<- {addr}");
+                        last_response.insert(addr, Instant::now());
+                        match message {
+                            ControlMessage::SessionStart(id) => self.update_session(addr, id, true),
+                            ControlMessage::SessionEnd(id) => self.update_session(addr, id, false),
+                            ControlMessage::ClockRequest(origin) => {
+                                let receive = lan_mouse_proto::clock_timestamp();
+                                let transmit = lan_mouse_proto::clock_timestamp();
+                                self.listener.reply_control(addr, ControlMessage::ClockResponse { origin, receive, transmit }).await;
+                            }
+                            ControlMessage::Action { action, keys } => self.perform_action(addr, action, keys),
+                            message => self.event_tx.send(EmulationEvent::Control { addr, message }).expect("channel closed"),
+                        }
+                    }
                     Some(ListenEvent::Accept { addr, fingerprint }) => {
                         self.event_tx.send(EmulationEvent::Connected { addr, fingerprint }).expect("channel closed");
                     }
                     Some(ListenEvent::Rejected { fingerprint }) => {
                         self.event_tx.send(EmulationEvent::ConnectionAttempt { fingerprint }).expect("channel closed");
                     }
+                    Some(ListenEvent::Expired { addr }) => {
+                        // stop emulating until the peer authenticated again
+                        self.entered.remove(&addr);
+                        self.sessions.remove(&addr);
+                        self.emulation_proxy.remove(addr);
+                        self.update_idle_inhibitor();
+                        self.event_tx.send(EmulationEvent::ReauthRequired { addr }).expect("channel closed");
+                    }
+                    Some(ListenEvent::DuplicatesDropped { fingerprint, count }) => {
+                        self.event_tx.send(EmulationEvent::DuplicatesDropped { fingerprint, count }).expect("channel closed");
+                    }
                     None => break
                 }}
                 event = self.emulation_proxy.event() => {
@@ -166,32 +454,162 @@ impl ListenTask {
                 request = self.request_rx.recv() => match request.expect("channel closed") {
                     // reenable emulation
                     EmulationRequest::Reenable => self.emulation_proxy.reenable(),
+                    EmulationRequest::Pause(paused) => self.emulation_proxy.pause(paused),
+                    EmulationRequest::Local(events) => {
+                        for event in events {
+                            self.emulation_proxy.consume(event, LOCAL_INPUT);
+                        }
+                        self.emulation_proxy.frame(LOCAL_INPUT);
+                    }
                     // notify the other end that we hit a barrier (should release capture)
                     EmulationRequest::Release(addr) => self.listener.reply(addr, ProtoEvent::Leave(0)).await,
+                    EmulationRequest::Reply(addr, event) => self.listener.reply(addr, event).await,
                     EmulationRequest::ChangePort(port) => {
+                        let grace = self.deadman.port_change_grace;
+                        if !grace.is_zero() {
+                            log::info!("port change: suspending deadman timeout for {grace:?}");
+                        }
                         self.listener.request_port_change(port);
                         let result = self.listener.port_changed().await;
+                        if !grace.is_zero() {
+                            // silence during the rebind does not count
+                            let now = Instant::now();
+                            last_response.values_mut().for_each(|t| *t = now);
+                            self.deadman_suspended = Some(now + grace);
+                        }
                         self.event_tx.send(EmulationEvent::PortChanged(result)).expect("channel closed");
                     }
                     EmulationRequest::Terminate => break,
                 },
                 _ = interval.tick() => {
+                    if let Some(until) = self.deadman_suspended {
+                        if Instant::now() < until {
+                            continue;
+                        }
+                        log::info!("port change: resuming deadman timeout");
+                        self.deadman_suspended = None;
+                        // devices that did not reconnect in time are released below
+                    }
+                    let timeout = self.deadman.timeout;
                     last_response.retain(|&addr,instant| {
-                        if instant.elapsed() > Duration::from_secs(1) {
-                            log::warn!("releasing keys: {addr} not responding!");
+                        if instant.elapsed() > timeout {
+                            log::warn!("releasing keys: {addr} not responding for {timeout:?}!");
                             self.emulation_proxy.remove(addr);
+                            self.sessions.remove(&addr);
+                            self.entered.remove(&addr);
                             self.event_tx.send(EmulationEvent::Disconnected { addr }).expect("channel closed");
                             false
                         } else {
                             true
                         }
                     });
+                    self.update_idle_inhibitor();
                 }
             }
         }
         self.listener.terminate().await;
         self.emulation_proxy.terminate().await;
     }
+
+    /// move the cursor onto the monitor at the edge entered by `addr`
+    fn snap_cursor(&mut self, addr: SocketAddr, pos: Position) {
+        let Some((x, y)) = self.entry_snap.as_ref().and_then(|s| s.position(pos)) else {
+            return;
+        };
+        log::debug!("{addr} entered at {pos}: snapping cursor to ({x:.3}, {y:.3})");
+        let event = Event::Pointer(PointerEvent::MotionAbsolute { time: 0, x, y });
+        self.emulation_proxy.consume(event, addr);
+    }
+
+    /// Perform a named action sent by `addr` with its command or,
+    /// without a command, by emulating its configured key chord.
+    /// Actions that are not configured degrade to the key chord
+    /// `sent` along with the action.
+    fn perform_action(&mut self, addr: SocketAddr, action: Action, sent: Vec<u32>) {
+        let handler = self.actions.get(&action).cloned().unwrap_or_default();
+        if let Some(cmd) = handler.command {
+            log::info!("{addr}: performing `{action}`: {cmd}");
+            spawn_local(async move {
+                match Command::new("sh")
+                    .arg("-c")
+                    .arg(cmd.as_str())
+                    .status()
+                    .await
+                {
+                    Ok(s) if s.success() => {}
+                    Ok(s) => log::warn!("{cmd} exited with {s}"),
+                    Err(e) => log::warn!("could not execute {cmd}: {e}"),
+                }
+            });
+            return;
+        }
+        let keys = match handler.keys.is_empty() {
+            false => handler.keys.iter().map(|&k| k as u32).collect(),
+            true => sent,
+        };
+        if keys.is_empty() {
+            log::warn!("{addr}: action `{action}` is not configured and has no key chord");
+            return;
+        }
+        log::info!("{addr}: performing `{action}` as key chord {keys:?}");
+        let presses = keys.iter().map(|&k| (k, 1));
+        let releases = keys.iter().rev().map(|&k| (k, 0));
+        for (key, state) in presses.chain(releases) {
+            let event = KeyboardEvent::Key {
+                time: 0,
+                key,
+                state,
+            };
+            self.emulation_proxy.consume(Event::Keyboard(event), addr);
+        }
+        self.emulation_proxy.frame(addr);
+    }
+
+    /// Start or end the session of a sending device.
+    /// Messages of sessions older than the latest one are ignored,
+    /// so a reordered start does not revive an ended session.
+    /// Emulation state is only reset when a session ends, as the
+    /// start may arrive after the first input of its session.
+    fn update_session(&mut self, addr: SocketAddr, id: u32, start: bool) {
+        let current = self.sessions.get(&addr).copied();
+        let accept = match current {
+            None => true,
+            Some(s) if is_newer(id, s.id) => true,
+            Some(s) => !start && id == s.id && s.active,
+        };
+        if !accept {
+            log::debug!("{addr}: ignoring outdated message of session {id}");
+            return;
+        }
+        // ended explicitly or implicitly by a newer session
+        if current.is_some_and(|s| s.active) {
+            log::debug!("{addr}: session ended");
+            self.emulation_proxy.remove(addr);
+            self.event_tx
+                .send(EmulationEvent::SessionEnded { addr })
+                .expect("channel closed");
+        }
+        self.sessions.insert(addr, Session { id, active: start });
+        if start {
+            log::debug!("{addr}: session {id} started");
+            self.event_tx
+                .send(EmulationEvent::SessionStarted { addr })
+                .expect("channel closed");
+        }
+        self.update_idle_inhibitor();
+    }
+
+    /// inhibit the screen saver while any session is active
+    fn update_idle_inhibitor(&mut self) {
+        let active = self.inhibit_idle && self.sessions.values().any(|s| s.active);
+        match (active, self.idle_inhibitor.is_some()) {
+            (true, false) => {
+                self.idle_inhibitor = Some(IdleInhibitor::new("remote input via lan-mouse"))
+            }
+            (false, true) => self.idle_inhibitor = None,
+            _ => {}
+        }
+    }
 }
 
 /// proxy handling the actual input emulation,
@@ -206,24 +624,40 @@ pub(crate) struct EmulationProxy {
 
 enum ProxyRequest {
     Input(Event, SocketAddr),
+    Frame(SocketAddr),
+    /// following input of the address originates from the given device
+    Origin(SocketAddr, u32),
     Remove(SocketAddr),
+    /// the pointer of the address entered this device
+    Enter(SocketAddr),
     Terminate,
     Reenable,
+    Pause(bool),
 }
 
 impl EmulationProxy {
-    fn new(backend: Option<input_emulation::Backend>) -> Self {
+    fn new(
+        backend: Option<input_emulation::Backend>,
+        options: EmulationOptions,
+        keyboard_merge: KeyboardMerge,
+    ) -> Self {
         let (request_tx, request_rx) = channel();
         let (event_tx, event_rx) = channel();
         let emulation_active = Rc::new(Cell::new(false));
         let exit_requested = Rc::new(Cell::new(false));
         let emulation_task = EmulationTask {
             backend,
+            options,
             exit_requested: exit_requested.clone(),
             request_rx,
             event_tx,
             handles: Default::default(),
+            origins: Default::default(),
             next_id: 0,
+            keyboard_merge,
+            key_holders: Default::default(),
+            paused: false,
+            backend_lost: false,
         };
         let task = spawn_local(emulation_task.run());
         Self {
@@ -255,18 +689,44 @@ impl EmulationProxy {
         }
     }
 
+    fn frame(&self, addr: SocketAddr) {
+        if self.emulation_active.get() {
+            self.request_tx
+                .send(ProxyRequest::Frame(addr))
+                .expect("channel closed");
+        }
+    }
+
+    fn origin(&self, addr: SocketAddr, origin: u32) {
+        self.request_tx
+            .send(ProxyRequest::Origin(addr, origin))
+            .expect("channel closed");
+    }
+
     fn remove(&self, addr: SocketAddr) {
         self.request_tx
             .send(ProxyRequest::Remove(addr))
             .expect("channel closed");
     }
 
+    fn enter(&self, addr: SocketAddr) {
+        self.request_tx
+            .send(ProxyRequest::Enter(addr))
+            .expect("channel closed");
+    }
+
     fn reenable(&self) {
         self.request_tx
             .send(ProxyRequest::Reenable)
             .expect("channel closed");
     }
 
+    fn pause(&self, paused: bool) {
+        self.request_tx
+            .send(ProxyRequest::Pause(paused))
+            .expect("channel closed");
+    }
+
     async fn terminate(&mut self) {
         self.exit_requested.replace(true);
         self.request_tx
@@ -276,16 +736,108 @@ impl EmulationProxy {
     }
 }
 
+/// Origins holding each key pressed on the keyboard shared by the
+/// origins of a sending device (see [`KeyboardMerge::Track`]).
+#[derive(Default)]
+struct KeyHolders {
+    /// origins announced by each address
+    announced: HashMap<SocketAddr, HashSet<u32>>,
+    /// origins holding each key pressed on the merged keyboard of an address
+    holders: HashMap<(SocketAddr, u32), HashSet<u32>>,
+}
+
+impl KeyHolders {
+    fn announce(&mut self, addr: SocketAddr, origin: u32) {
+        self.announced.entry(addr).or_default().insert(origin);
+    }
+
+    /// Whether a key event of `origin` is emulated. Presses of a key held
+    /// by another origin and releases of a key still held by another origin
+    /// are merged. Presses of an origin already holding the key (key repeat)
+    /// and all events of addresses with a single origin pass.
+    fn pass(&mut self, addr: SocketAddr, origin: u32, key: u32, state: u8) -> bool {
+        // tracked in any case, as another origin may be announced any time
+        let holders = self.holders.entry((addr, key)).or_default();
+        let pass = match state {
+            0 => holders.remove(&origin) && holders.is_empty(),
+            _ => {
+                let first = holders.is_empty();
+                let repeat = !holders.insert(origin);
+                first || repeat
+            }
+        };
+        if holders.is_empty() {
+            self.holders.remove(&(addr, key));
+        }
+        let single = self.announced.get(&addr).is_none_or(|o| o.len() < 2);
+        pass || single
+    }
+
+    /// forget all held keys, e.g. when they were released
+    fn release_all(&mut self) {
+        self.holders.clear();
+    }
+
+    fn remove(&mut self, addr: SocketAddr) {
+        self.announced.remove(&addr);
+        self.holders.retain(|&(a, _), _| a != addr);
+    }
+}
+
 struct EmulationTask {
     backend: Option<input_emulation::Backend>,
+    options: EmulationOptions,
     exit_requested: Rc<Cell<bool>>,
     request_rx: Receiver<ProxyRequest>,
     event_tx: Sender<EmulationEvent>,
-    handles: HashMap<SocketAddr, EmulationHandle>,
+    /// emulation handles per address and origin device
+    handles: HashMap<(SocketAddr, u32), EmulationHandle>,
+    /// current origin device of each address
+    origins: HashMap<SocketAddr, u32>,
     next_id: EmulationHandle,
+    keyboard_merge: KeyboardMerge,
+    key_holders: KeyHolders,
+    /// the backend failed at runtime and was not recreated yet
+    backend_lost: bool,
+    /// emulation was paused, also applied to a recreated emulation
+    paused: bool,
 }
 
 impl EmulationTask {
+    /// the address and current origin of its input,
+    /// all origins are collapsed unless devices are emulated per handle
+    fn origin_of(&self, addr: SocketAddr) -> (SocketAddr, u32) {
+        match self.options.device_per_handle {
+            true => (addr, self.origins.get(&addr).copied().unwrap_or_default()),
+            false => (addr, 0),
+        }
+    }
+
+    /// whether a key event of `addr` is emulated on the keyboard
+    /// shared by all of its origins (see [`KeyboardMerge::Track`])
+    fn pass_key(&mut self, addr: SocketAddr, event: Event) -> bool {
+        let Event::Keyboard(KeyboardEvent::Key { key, state, .. }) = event else {
+            return true;
+        };
+        if self.options.device_per_handle || self.keyboard_merge == KeyboardMerge::Raw {
+            return true;
+        }
+        let origin = self.origins.get(&addr).copied().unwrap_or_default();
+        let pass = self.key_holders.pass(addr, origin, key, state);
+        if !pass {
+            log::debug!("{addr}: key {key} (state {state}) of origin {origin} merged");
+        }
+        pass
+    }
+
+    /// report a failure of the running emulation backend
+    fn backend_failed(&mut self, e: &EmulationError) {
+        self.backend_lost = true;
+        self.event_tx
+            .send(EmulationEvent::BackendFailed(e.to_string()))
+            .expect("channel closed");
+    }
+
     async fn run(mut self) {
         loop {
             if let Err(e) = self.do_emulation().await {
@@ -294,13 +846,31 @@ impl EmulationTask {
             if self.exit_requested.get() {
                 break;
             }
+            // the session of the backend may have changed, retry
+            // until the backend of the new session is available
+            let retry = match self.backend_lost {
+                true => self.options.recreate_interval,
+                false => None,
+            };
+            if let Some(interval) = retry {
+                log::info!("input emulation lost, recreating in {interval:?} ...");
+            }
+            let deadline = retry.map(|interval| Instant::now() + interval);
             // wait for reenable request
             loop {
-                match self.request_rx.recv().await.expect("channel closed") {
+                let request = tokio::select! {
+                    r = self.request_rx.recv() => r.expect("channel closed"),
+                    _ = wait_until(deadline) => break,
+                };
+                match request {
                     ProxyRequest::Reenable => break,
                     ProxyRequest::Terminate => return,
                     ProxyRequest::Input(..) => { /* emulation inactive => ignore */ }
+                    ProxyRequest::Frame(..) => { /* emulation inactive => ignore */ }
+                    ProxyRequest::Origin(addr, origin) => self.announce_origin(addr, origin),
                     ProxyRequest::Remove(..) => { /* emulation inactive => ignore */ }
+                    ProxyRequest::Enter(..) => { /* emulation inactive => ignore */ }
+                    ProxyRequest::Pause(paused) => self.set_paused(paused),
                 }
             }
         }
@@ -309,10 +879,13 @@ impl EmulationTask {
     async fn do_emulation(&mut self) -> Result<(), InputEmulationError> {
         log::info!("creating input emulation ...");
         let mut emulation = tokio::select! {
-            r = InputEmulation::new(self.backend) => r?,
+            r = InputEmulation::with_options(self.backend, self.options) => r?,
             // allow termination event while requesting input emulation
-            _ = wait_for_termination(&mut self.request_rx) => return Ok(()),
+            _ = self.wait_for_termination() => return Ok(()),
         };
+        if std::mem::take(&mut self.backend_lost) {
+            log::info!("input emulation recreated");
+        }
 
         // used to send enabled and disabled events
         let _emulation_guard = DropGuard::new(
@@ -320,12 +893,35 @@ impl EmulationTask {
             EmulationEvent::EmulationEnabled,
             EmulationEvent::EmulationDisabled,
         );
+        // keys were released with the previous emulation
+        self.key_holders.release_all();
+
+        if let Some(granted) = emulation.backend_info().granted {
+            self.event_tx
+                .send(EmulationEvent::GrantedDevices(granted))
+                .expect("channel closed");
+        }
+
+        if self.options.warmup.is_some() {
+            let missing = emulation.missing_devices();
+            if !missing.is_empty() {
+                self.event_tx
+                    .send(EmulationEvent::WarmupIncomplete(missing))
+                    .expect("channel closed");
+            }
+        }
 
         // create active handles
         if let Err(e) = self.create_clients(&mut emulation).await {
             emulation.terminate().await;
             return Err(e);
         }
+        if self.paused {
+            if let Err(e) = emulation.pause() {
+                emulation.terminate().await;
+                return Err(e.into());
+            }
+        }
 
         let res = self.do_emulation_session(&mut emulation).await;
         // FIXME replace with async drop when stabilized
@@ -337,40 +933,134 @@ impl EmulationTask {
         &mut self,
         emulation: &mut InputEmulation,
     ) -> Result<(), InputEmulationError> {
-        for handle in self.handles.values() {
+        let handles = self.handles.values().copied().collect::<Vec<_>>();
+        for handle in handles {
             tokio::select! {
-                _ = emulation.create(*handle) => {},
-                _ = wait_for_termination(&mut self.request_rx) => return Ok(()),
+                _ = emulation.create(handle) => {},
+                _ = self.wait_for_termination() => return Ok(()),
             }
         }
         Ok(())
     }
 
+    /// following input of `addr` originates from the device `origin`
+    fn announce_origin(&mut self, addr: SocketAddr, origin: u32) {
+        self.origins.insert(addr, origin);
+        self.key_holders.announce(addr, origin);
+    }
+
+    /// pause (or resume) the emulation once it is created
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.event_tx
+            .send(EmulationEvent::EmulationPaused(paused))
+            .expect("channel closed");
+    }
+
+    /// Wait for a termination request while the emulation is created.
+    /// Origins are recorded, as they are only announced once they change,
+    /// and pausing is applied to the emulation once it is created.
+    async fn wait_for_termination(&mut self) {
+        loop {
+            match self.request_rx.recv().await.expect("channel closed") {
+                ProxyRequest::Terminate => return,
+                ProxyRequest::Input(_, _) => continue,
+                ProxyRequest::Frame(_) => continue,
+                ProxyRequest::Origin(addr, origin) => self.announce_origin(addr, origin),
+                ProxyRequest::Remove(_) => continue,
+                ProxyRequest::Enter(_) => continue,
+                ProxyRequest::Reenable => continue,
+                ProxyRequest::Pause(paused) => self.set_paused(paused),
+            }
+        }
+    }
+
     async fn do_emulation_session(
         &mut self,
         emulation: &mut InputEmulation,
     ) -> Result<(), InputEmulationError> {
+        let mut missing = emulation.missing_devices();
         loop {
+            let deadline = emulation.deadline();
             tokio::select! {
+                _ = wait_until(deadline) => emulation.step().await?,
+                m = emulation.devices_changed() => match m {
+                    Ok(m) if m != missing => {
+                        missing = m;
+                        self.event_tx
+                            .send(EmulationEvent::MissingDevices(missing.clone()))
+                            .expect("channel closed");
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.backend_failed(&e);
+                        break Err(e.into());
+                    }
+                },
                 e = self.request_rx.recv() => match e.expect("channel closed") {
                     ProxyRequest::Input(event, addr) => {
-                        let handle = match self.handles.get(&addr) {
+                        if !self.pass_key(addr, event) {
+                            continue;
+                        }
+                        let origin = self.origin_of(addr);
+                        let handle = match self.handles.get(&origin) {
                             Some(&handle) => handle,
                             None => {
                                 let handle = self.next_id;
                                 self.next_id += 1;
+                                log::debug!("emulation handle {handle}: {addr}, origin {}", origin.1);
                                 emulation.create(handle).await;
-                                self.handles.insert(addr, handle);
+                                self.handles.insert(origin, handle);
                                 handle
                             }
                         };
-                        emulation.consume(event, handle).await?;
+                        if let Err(e) = emulation.consume(event, handle).await {
+                            self.backend_failed(&e);
+                            break Err(e.into());
+                        }
                     },
+                    ProxyRequest::Frame(addr) => {
+                        if let Some(&handle) = self.handles.get(&self.origin_of(addr)) {
+                            if let Err(e) = emulation.frame(handle).await {
+                                self.backend_failed(&e);
+                                break Err(e.into());
+                            }
+                        }
+                    }
+                    ProxyRequest::Origin(addr, origin) => self.announce_origin(addr, origin),
                     ProxyRequest::Remove(addr) => {
-                        if let Some(handle) = self.handles.remove(&addr) {
+                        self.origins.remove(&addr);
+                        self.key_holders.remove(addr);
+                        let removed = self
+                            .handles
+                            .iter()
+                            .filter(|((a, _), _)| *a == addr)
+                            .map(|(&key, &handle)| (key, handle))
+                            .collect::<Vec<_>>();
+                        for (key, handle) in removed {
+                            self.handles.remove(&key);
                             emulation.destroy(handle).await;
                         }
                     }
+                    ProxyRequest::Enter(addr) => {
+                        for (_, &handle) in self.handles.iter().filter(|((a, _), _)| *a == addr) {
+                            emulation.reset_absolute(handle);
+                        }
+                    }
+                    ProxyRequest::Pause(paused) => {
+                        self.paused = paused;
+                        let result = match paused {
+                            true => emulation.pause(),
+                            false => emulation.resume().await,
+                        };
+                        if let Err(e) = result {
+                            self.backend_failed(&e);
+                            break Err(e.into());
+                        }
+                        self.event_tx
+                            .send(EmulationEvent::EmulationPaused(paused))
+                            .expect("channel closed");
+                    }
                     ProxyRequest::Terminate => break Ok(()),
                     ProxyRequest::Reenable => continue,
                 },
@@ -388,14 +1078,11 @@ fn to_ipc_pos(pos: Position) -> lan_mouse_ipc::Position {
     }
 }
 
-async fn wait_for_termination(rx: &mut Receiver<ProxyRequest>) {
-    loop {
-        match rx.recv().await.expect("channel closed") {
-            ProxyRequest::Terminate => return,
-            ProxyRequest::Input(_, _) => continue,
-            ProxyRequest::Remove(_) => continue,
-            ProxyRequest::Reenable => continue,
-        }
+/// wait until the given deadline or forever if there is none
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => futures::future::pending().await,
     }
 }
 
@@ -419,3 +1106,131 @@ impl<T> Drop for DropGuard<T> {
             .expect("channel closed");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(offset: EntryOffset) -> EntrySnap {
+        EntrySnap {
+            monitors: vec![
+                Monitor {
+                    x: 0,
+                    y: 0,
+                    width: 2000,
+                    height: 1000,
+                },
+                Monitor {
+                    x: 2000,
+                    y: 200,
+                    width: 1000,
+                    height: 800,
+                },
+            ],
+            ambiguous: EntryMonitor::First,
+            offset,
+        }
+    }
+
+    #[test]
+    fn single_origin_keys_pass() {
+        let addr = "127.0.0.1:4242".parse().unwrap();
+        let mut holders = KeyHolders::default();
+        holders.announce(addr, 0);
+        assert!(holders.pass(addr, 0, 30, 1));
+        // key repeat
+        assert!(holders.pass(addr, 0, 30, 1));
+        assert!(holders.pass(addr, 0, 30, 0));
+        // release of a key pressed before the emulation was recreated
+        assert!(holders.pass(addr, 0, 31, 0));
+    }
+
+    #[test]
+    fn merged_origins_press_and_release_once() {
+        let addr = "127.0.0.1:4242".parse().unwrap();
+        let mut holders = KeyHolders::default();
+        holders.announce(addr, 0);
+        holders.announce(addr, 1);
+        assert!(holders.pass(addr, 0, 30, 1));
+        assert!(!holders.pass(addr, 1, 30, 1));
+        // key repeat of an origin holding the key
+        assert!(holders.pass(addr, 0, 30, 1));
+        assert!(!holders.pass(addr, 0, 30, 0));
+        assert!(holders.pass(addr, 1, 30, 0));
+        // not held by any origin
+        assert!(!holders.pass(addr, 1, 30, 0));
+    }
+
+    #[test]
+    fn entry_snaps_to_edge_center() {
+        let snap = snap(EntryOffset::default());
+        assert_eq!(snap.position(Position::Left), Some((0., 0.5)));
+        assert_eq!(snap.position(Position::Right), Some((2999. / 3000., 0.6)));
+    }
+
+    #[test]
+    fn entry_offset_stays_on_monitor() {
+        let snap = snap(EntryOffset {
+            left: -100,
+            right: 1000,
+            ..Default::default()
+        });
+        assert_eq!(snap.position(Position::Left), Some((0., 0.4)));
+        assert_eq!(snap.position(Position::Right), Some((2999. / 3000., 0.999)));
+    }
+
+    fn task(
+        options: EmulationOptions,
+    ) -> (
+        EmulationTask,
+        Sender<ProxyRequest>,
+        Receiver<EmulationEvent>,
+    ) {
+        let (request_tx, request_rx) = channel();
+        let (event_tx, event_rx) = channel();
+        let task = EmulationTask {
+            backend: None,
+            options,
+            exit_requested: Default::default(),
+            request_rx,
+            event_tx,
+            handles: Default::default(),
+            origins: Default::default(),
+            next_id: 0,
+            keyboard_merge: Default::default(),
+            key_holders: Default::default(),
+            paused: false,
+            backend_lost: false,
+        };
+        (task, request_tx, event_rx)
+    }
+
+    #[tokio::test]
+    async fn origin_announced_during_creation_is_kept() {
+        let addr = "127.0.0.1:4242".parse().unwrap();
+        let options = EmulationOptions {
+            device_per_handle: true,
+            ..Default::default()
+        };
+        let (mut task, request_tx, _event_rx) = task(options);
+        // announced while the backend is created
+        request_tx.send(ProxyRequest::Origin(addr, 1)).unwrap();
+        request_tx.send(ProxyRequest::Terminate).unwrap();
+        task.wait_for_termination().await;
+        assert_eq!(task.origin_of(addr), (addr, 1));
+        assert!(task.key_holders.announced[&addr].contains(&1));
+    }
+
+    #[tokio::test]
+    async fn pause_during_creation_is_applied() {
+        let (mut task, request_tx, mut event_rx) = task(Default::default());
+        request_tx.send(ProxyRequest::Pause(true)).unwrap();
+        request_tx.send(ProxyRequest::Terminate).unwrap();
+        task.wait_for_termination().await;
+        assert!(task.paused);
+        assert!(matches!(
+            event_rx.recv().await,
+            Some(EmulationEvent::EmulationPaused(true))
+        ));
+    }
+}
@@ -27,9 +27,15 @@ pub async fn run(config: Config, _args: TestEmulationArgs) -> Result<(), InputEm
 
     let start = Instant::now();
     let mut offset = (0, 0);
+    let mut info_logged = false;
     loop {
         tokio::time::sleep(Duration::from_millis(1)).await;
         let elapsed = start.elapsed();
+        // devices may be announced asynchronously by the backend
+        if !info_logged && elapsed > Duration::from_secs(1) {
+            log::info!("emulation backend: {}", emulation.backend_info());
+            info_logged = true;
+        }
         let elapsed_sec_f64 = elapsed.as_secs_f64();
         let second_fraction = elapsed_sec_f64 - elapsed_sec_f64 as u64 as f64;
         let radians = second_fraction * 2. * PI * FREQUENCY_HZ;
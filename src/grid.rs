@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use lan_mouse_ipc::Position;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GridError {
+    #[error("`grid_name` is required to use a grid")]
+    NoName,
+    #[error("`{0}` is not part of the grid")]
+    NotInGrid(String),
+    #[error("`{0}` appears more than once in the grid")]
+    Duplicate(String),
+}
+
+/// Layout of devices in rows and columns.
+/// Every device running with the same grid forwards input
+/// to its direct neighbors, so the cursor can travel across
+/// the grid one device at a time. Empty names are gaps.
+#[derive(Clone, Debug)]
+pub(crate) struct Grid {
+    rows: Vec<Vec<String>>,
+}
+
+impl Grid {
+    /// check that `name` and every other device appears exactly once
+    pub(crate) fn new(rows: Vec<Vec<String>>, name: Option<&str>) -> Result<Self, GridError> {
+        let name = name.ok_or(GridError::NoName)?;
+        let mut names = HashSet::new();
+        for cell in rows.iter().flatten().filter(|c| !c.is_empty()) {
+            if !names.insert(cell.as_str()) {
+                return Err(GridError::Duplicate(cell.clone()));
+            }
+        }
+        if !names.contains(name) {
+            return Err(GridError::NotInGrid(name.to_owned()));
+        }
+        Ok(Self { rows })
+    }
+
+    /// the devices adjacent to `name` and their position relative to it
+    pub(crate) fn neighbors(&self, name: &str) -> Vec<(Position, String)> {
+        let Some((row, col)) = self.find(name) else {
+            return vec![];
+        };
+        let mut neighbors = vec![];
+        if col > 0 {
+            neighbors.extend(self.cell(row, col - 1).map(|n| (Position::Left, n)));
+        }
+        neighbors.extend(self.cell(row, col + 1).map(|n| (Position::Right, n)));
+        if row > 0 {
+            neighbors.extend(self.cell(row - 1, col).map(|n| (Position::Top, n)));
+        }
+        neighbors.extend(self.cell(row + 1, col).map(|n| (Position::Bottom, n)));
+        neighbors
+    }
+
+    fn find(&self, name: &str) -> Option<(usize, usize)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .find_map(|(r, row)| row.iter().position(|cell| cell == name).map(|c| (r, c)))
+    }
+
+    fn cell(&self, row: usize, col: usize) -> Option<String> {
+        self.rows
+            .get(row)
+            .and_then(|r| r.get(col))
+            .filter(|cell| !cell.is_empty())
+            .cloned()
+    }
+}
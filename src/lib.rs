@@ -7,5 +7,8 @@ mod crypto;
 mod dns;
 mod emulation;
 pub mod emulation_test;
+mod grid;
 mod listen;
+mod lossy;
 pub mod service;
+mod system_log;
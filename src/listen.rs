@@ -1,13 +1,17 @@
 use futures::{Stream, StreamExt};
-use lan_mouse_proto::{ProtoEvent, MAX_EVENT_SIZE};
+use input_event::{Event, KeyboardEvent, PointerEvent};
+use lan_mouse_proto::{
+    ControlMessage, ProtoEvent, ProtocolError, WireFormat, MAX_PACKET_SIZE, MAX_RELAY_SIZE,
+};
 use local_channel::mpsc::{channel, Receiver, Sender};
 use rustls::pki_types::CertificateDer;
 use std::{
+    cell::RefCell,
     collections::{HashMap, VecDeque},
     net::SocketAddr,
     rc::Rc,
     sync::{Arc, Mutex, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use tokio::{
@@ -34,11 +38,221 @@ pub enum ListenerCreationError {
 
 type ArcConn = Arc<dyn Conn + Send + Sync>;
 
+/// wire format last received from each connection
+type WireFormats = Rc<RefCell<HashMap<SocketAddr, WireFormat>>>;
+
+/// which undecodable events are skipped instead of closing the connection
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SkippedEvents {
+    /// events of types introduced by newer versions
+    pub(crate) unknown: bool,
+    /// input events with out-of-range values, e.g. a button state of 2
+    pub(crate) invalid: bool,
+}
+
+/// hook inspecting the raw bytes received from a connection before they are parsed
+pub(crate) type RawHook = Rc<dyn Fn(SocketAddr, &[u8])>;
+
+/// [`RawHook`] logging the received bytes.
+/// Key events are redacted unless logging key data is enabled.
+pub(crate) fn log_raw_packet(addr: SocketAddr, buf: &[u8]) {
+    if !input_event::log_key_data() && is_key_event(buf) {
+        log::info!("{addr}: received {} bytes: <redacted key event>", buf.len());
+        return;
+    }
+    let bytes = buf
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    log::info!("{addr}: received {} bytes: {bytes}", buf.len());
+}
+
+fn is_key_event(buf: &[u8]) -> bool {
+    let mut packet = [0u8; MAX_RELAY_SIZE];
+    let len = buf.len().min(MAX_RELAY_SIZE);
+    packet[..len].copy_from_slice(&buf[..len]);
+    matches!(
+        ProtoEvent::decode_relay(&packet),
+        Ok((
+            _,
+            ProtoEvent::Input(Event::Keyboard(KeyboardEvent::Key { .. }))
+                | ProtoEvent::Sequenced(_, Event::Keyboard(KeyboardEvent::Key { .. })),
+            _
+        ))
+    )
+}
+
+/// key or button a [`ProtoEvent::Sequenced`] event belongs to
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum SequencedInput {
+    Key(u32),
+    Button(u32),
+}
+
+impl SequencedInput {
+    fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::Keyboard(KeyboardEvent::Key { key, .. }) => Some(Self::Key(*key)),
+            Event::Pointer(PointerEvent::Button { button, .. }) => Some(Self::Button(*button)),
+            _ => None,
+        }
+    }
+}
+
+/// whether sequence number `a` was assigned after `b`
+fn is_newer(a: u32, b: u32) -> bool {
+    let ahead = a.wrapping_sub(b);
+    ahead != 0 && ahead < u32::MAX / 2
+}
+
+/// outcome of receiving a [`ProtoEvent::Sequenced`] event
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Received {
+    /// the event is to be applied
+    New,
+    /// a copy of the event was received already
+    Duplicate,
+    /// the event is older than the remembered sequence numbers
+    /// or than the event last applied for the same key or button
+    Outdated,
+}
+
+/// recently seen sequence numbers of [`ProtoEvent::Sequenced`] events
+struct SequenceWindow {
+    /// highest sequence number seen
+    latest: Option<u32>,
+    /// sequence number last seen in each slot (`seq % len`),
+    /// duplicates are not dropped if there are no slots
+    seen: Vec<Option<u32>>,
+    /// sequence number of the event last applied for each key and button
+    applied: HashMap<SequencedInput, u32>,
+    /// number of duplicates dropped
+    dropped: u64,
+    /// number of dropped duplicates last reported and when
+    reported: (u64, Option<Instant>),
+}
+
+impl SequenceWindow {
+    /// window remembering the given number of sequence numbers
+    fn new(size: u32) -> Self {
+        Self {
+            latest: None,
+            seen: vec![None; size as usize],
+            applied: HashMap::new(),
+            dropped: 0,
+            reported: (0, None),
+        }
+    }
+
+    /// Forget all sequence numbers, e.g. of a sender that restarted
+    /// and numbers its events from the start again.
+    /// Dropped duplicates are still counted.
+    fn reset(&mut self) {
+        self.latest = None;
+        self.seen.fill(None);
+        self.applied.clear();
+    }
+
+    /// Whether the event is new and not older than the event
+    /// last applied for the same key or button:
+    /// A retransmitted press arriving after its release would leave the key stuck.
+    fn accept(&mut self, seq: u32, event: &Event) -> Received {
+        match self.accept_seq(seq) {
+            Received::New => {}
+            Received::Duplicate => {
+                self.dropped += 1;
+                return Received::Duplicate;
+            }
+            Received::Outdated => return Received::Outdated,
+        }
+        let Some(input) = SequencedInput::of(event) else {
+            return Received::New;
+        };
+        match self.applied.get(&input) {
+            Some(&applied) if is_newer(applied, seq) => Received::Outdated,
+            _ => {
+                self.applied.insert(input, seq);
+                Received::New
+            }
+        }
+    }
+
+    /// Whether the sequence number was not seen before.
+    /// Numbers too old to be remembered are outdated.
+    fn accept_seq(&mut self, seq: u32) -> Received {
+        let size = self.seen.len() as u32;
+        if size == 0 {
+            return Received::New;
+        }
+        let slot = (seq % size) as usize;
+        let Some(latest) = self.latest else {
+            (self.latest, self.seen[slot]) = (Some(seq), Some(seq));
+            return Received::New;
+        };
+        if is_newer(seq, latest) {
+            (self.latest, self.seen[slot]) = (Some(seq), Some(seq));
+            return Received::New;
+        }
+        if latest.wrapping_sub(seq) >= size {
+            return Received::Outdated;
+        }
+        if self.seen[slot] == Some(seq) {
+            return Received::Duplicate;
+        }
+        self.seen[slot] = Some(seq);
+        Received::New
+    }
+
+    /// Number of dropped duplicates, if it changed since it was last reported
+    /// and reports are not rate limited (unless `force`d).
+    fn report(&mut self, force: bool) -> Option<u64> {
+        let (reported, at) = self.reported;
+        let due = at.is_none_or(|at| at.elapsed() >= DUPLICATES_REPORT_INTERVAL);
+        if self.dropped == reported || !(due || force) {
+            return None;
+        }
+        self.reported = (self.dropped, Some(Instant::now()));
+        Some(self.dropped)
+    }
+}
+
+/// minimum interval between reports of dropped duplicates
+const DUPLICATES_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// [`SequenceWindow`] of each sending device, by certificate fingerprint.
+/// Shared by all connections, so copies arriving on another connection
+/// of the same device (e.g. after falling back to the other address family)
+/// are recognized as well. Reset when the device connects again, as a
+/// restarted device numbers its events from the start.
+type SequenceWindows = Rc<RefCell<HashMap<String, SequenceWindow>>>;
+
+/// handling of the data received from a connection
+#[derive(Clone)]
+struct ReadOptions {
+    raw_hook: Option<RawHook>,
+    skipped_events: SkippedEvents,
+    /// number of sequence numbers remembered to drop duplicates
+    dedup_window: u32,
+    sequence_windows: SequenceWindows,
+}
+
 pub(crate) enum ListenEvent {
     Msg {
         event: ProtoEvent,
         addr: SocketAddr,
     },
+    /// event to be relayed to the client `destination`
+    Relay {
+        destination: u64,
+        event: ProtoEvent,
+        addr: SocketAddr,
+    },
+    /// control message (not an input event)
+    Control {
+        message: ControlMessage,
+        addr: SocketAddr,
+    },
     Accept {
         addr: SocketAddr,
         fingerprint: String,
@@ -46,6 +260,16 @@ pub(crate) enum ListenEvent {
     Rejected {
         fingerprint: String,
     },
+    /// the session of `addr` exceeded the maximum session duration
+    /// and was closed, the peer has to authenticate again
+    Expired {
+        addr: SocketAddr,
+    },
+    /// total number of duplicate events dropped from the device
+    DuplicatesDropped {
+        fingerprint: String,
+        count: u64,
+    },
 }
 
 pub(crate) struct LanMouseListener {
@@ -53,6 +277,7 @@ pub(crate) struct LanMouseListener {
     listen_tx: Sender<ListenEvent>,
     listen_task: JoinHandle<()>,
     conns: Rc<AsyncMutex<Vec<(SocketAddr, ArcConn)>>>,
+    wire_formats: WireFormats,
     request_port_change: Sender<u16>,
     port_changed: Receiver<Result<u16, ListenerCreationError>>,
 }
@@ -68,6 +293,10 @@ impl LanMouseListener {
         port: u16,
         cert: Certificate,
         authorized_keys: Arc<RwLock<HashMap<String, String>>>,
+        raw_hook: Option<RawHook>,
+        skipped_events: SkippedEvents,
+        dedup_window: u32,
+        max_session_duration: Option<Duration>,
     ) -> Result<Self, ListenerCreationError> {
         let (listen_tx, listen_rx) = channel();
         let (request_port_change, mut request_port_change_rx) = channel();
@@ -115,7 +344,16 @@ impl LanMouseListener {
         let conns: Rc<AsyncMutex<Vec<(SocketAddr, ArcConn)>>> =
             Rc::new(AsyncMutex::new(Vec::new()));
 
+        let wire_formats: WireFormats = Default::default();
+
+        let read_options = ReadOptions {
+            raw_hook,
+            skipped_events,
+            dedup_window,
+            sequence_windows: Default::default(),
+        };
         let conns_clone = conns.clone();
+        let wire_formats_clone = wire_formats.clone();
         let listen_task: JoinHandle<()> = {
             let listen_tx = listen_tx.clone();
             let connection_attempts = connection_attempts.clone();
@@ -134,8 +372,14 @@ impl LanMouseListener {
                                 let certs = dtls_conn.connection_state().await.peer_certificates;
                                 let cert = certs.first().expect("cert");
                                 let fingerprint = crypto::generate_fingerprint(cert);
-                                listen_tx.send(ListenEvent::Accept { addr, fingerprint }).expect("channel closed");
-                                spawn_local(read_loop(conns_clone.clone(), addr, conn, listen_tx.clone()));
+                                if let Some(window) = read_options.sequence_windows.borrow_mut().get_mut(&fingerprint) {
+                                    window.reset();
+                                }
+                                listen_tx.send(ListenEvent::Accept { addr, fingerprint: fingerprint.clone() }).expect("channel closed");
+                                if let Some(duration) = max_session_duration {
+                                    spawn_local(expire_session(conns_clone.clone(), addr, conn.clone(), duration, listen_tx.clone()));
+                                }
+                                spawn_local(read_loop(conns_clone.clone(), wire_formats_clone.clone(), read_options.clone(), addr, fingerprint, conn, listen_tx.clone()));
                             },
                             Err(e) => {
                                 if let Error::Std(ref e) = e {
@@ -178,6 +422,7 @@ impl LanMouseListener {
 
         Ok(Self {
             conns,
+            wire_formats,
             listen_rx,
             listen_tx,
             listen_task,
@@ -205,7 +450,25 @@ impl LanMouseListener {
 
     pub(crate) async fn reply(&self, addr: SocketAddr, event: ProtoEvent) {
         log::trace!("reply {event} >=>=>=>=>=> {addr}");
-        let (buf, len): ([u8; MAX_EVENT_SIZE], usize) = event.into();
+        // reply in the format used by the peer
+        let wire_format = self
+            .wire_formats
+            .borrow()
+            .get(&addr)
+            .copied()
+            .unwrap_or_default();
+        let (buf, len) = event.encode(wire_format);
+        let conns = self.conns.lock().await;
+        for (a, conn) in conns.iter() {
+            if *a == addr {
+                let _ = conn.send(&buf[..len]).await;
+            }
+        }
+    }
+
+    pub(crate) async fn reply_control(&self, addr: SocketAddr, message: ControlMessage) {
+        log::trace!("reply {message} >=>=>=>=>=> {addr}");
+        let (buf, len) = message.encode();
         let conns = self.conns.lock().await;
         for (a, conn) in conns.iter() {
             if *a == addr {
@@ -245,19 +508,129 @@ impl Stream for LanMouseListener {
     }
 }
 
+/// closes the connection once it has been open for `duration`,
+/// so that the peer has to pass the certificate check again
+async fn expire_session(
+    conns: Rc<AsyncMutex<Vec<(SocketAddr, ArcConn)>>>,
+    addr: SocketAddr,
+    conn: ArcConn,
+    duration: Duration,
+    dtls_tx: Sender<ListenEvent>,
+) {
+    tokio::time::sleep(duration).await;
+    // the connection may have been closed and replaced in the meantime
+    let open = conns
+        .lock()
+        .await
+        .iter()
+        .any(|(a, c)| *a == addr && Arc::ptr_eq(c, &conn));
+    if !open {
+        return;
+    }
+    log::info!("{addr}: session expired after {duration:?}, re-authentication required");
+    dtls_tx
+        .send(ListenEvent::Expired { addr })
+        .expect("channel closed");
+    if let Err(e) = conn.close().await {
+        log::warn!("{addr}: failed to close expired session: {e}");
+    }
+}
+
 async fn read_loop(
     conns: Rc<AsyncMutex<Vec<(SocketAddr, ArcConn)>>>,
+    wire_formats: WireFormats,
+    options: ReadOptions,
     addr: SocketAddr,
+    fingerprint: String,
     conn: ArcConn,
     dtls_tx: Sender<ListenEvent>,
 ) -> Result<(), Error> {
-    let mut b = [0u8; MAX_EVENT_SIZE];
+    let ReadOptions {
+        raw_hook,
+        skipped_events,
+        dedup_window,
+        sequence_windows,
+    } = options;
+    let mut b = [0u8; MAX_PACKET_SIZE];
+    let report_duplicates = |force: bool| {
+        let count = sequence_windows
+            .borrow_mut()
+            .get_mut(&fingerprint)
+            .and_then(|w| w.report(force));
+        if let Some(count) = count {
+            dtls_tx
+                .send(ListenEvent::DuplicatesDropped {
+                    fingerprint: fingerprint.clone(),
+                    count,
+                })
+                .expect("channel closed");
+        }
+    };
 
-    while conn.recv(&mut b).await.is_ok() {
-        match b.try_into() {
-            Ok(event) => dtls_tx
-                .send(ListenEvent::Msg { event, addr })
-                .expect("channel closed"),
+    while let Ok(len) = conn.recv(&mut b).await {
+        // legacy events are decoded from a fixed size buffer:
+        // fields not sent by older versions must not be read from previous packets
+        b[len..].fill(0);
+        if let Some(hook) = raw_hook.as_ref() {
+            hook(addr, &b[..len]);
+        }
+        if ControlMessage::is_control(&b[..len]) {
+            match ControlMessage::decode(&b[..len]) {
+                Ok(message) => dtls_tx
+                    .send(ListenEvent::Control { message, addr })
+                    .expect("channel closed"),
+                // unknown control messages of newer versions are ignored
+                Err(e) => log::debug!("{addr}: ignoring control message: {e}"),
+            }
+            continue;
+        }
+        let packet = b[..MAX_RELAY_SIZE].try_into().expect("packet size");
+        match ProtoEvent::decode_relay(packet) {
+            Ok((destination, event, wire_format)) => {
+                let prev = wire_formats.borrow_mut().insert(addr, wire_format);
+                if prev != Some(wire_format) {
+                    log::info!("{addr} uses wire format {wire_format}");
+                }
+                let event = match event {
+                    ProtoEvent::Sequenced(seq, input) => {
+                        let received = sequence_windows
+                            .borrow_mut()
+                            .entry(fingerprint.clone())
+                            .or_insert_with(|| SequenceWindow::new(dedup_window))
+                            .accept(seq, &input);
+                        match received {
+                            Received::New => {}
+                            Received::Duplicate => {
+                                log::trace!("{addr}: discarding duplicate event #{seq}");
+                                report_duplicates(false);
+                                continue;
+                            }
+                            Received::Outdated => {
+                                log::debug!("{addr}: discarding outdated event #{seq}");
+                                continue;
+                            }
+                        }
+                        // relayed events are sequenced again by this device
+                        ProtoEvent::Input(input)
+                    }
+                    event => event,
+                };
+                let event = match destination {
+                    Some(destination) => ListenEvent::Relay {
+                        destination,
+                        event,
+                        addr,
+                    },
+                    None => ListenEvent::Msg { event, addr },
+                };
+                dtls_tx.send(event).expect("channel closed")
+            }
+            Err(ProtocolError::UnknownEventType(t)) if skipped_events.unknown => {
+                log::debug!("{addr}: ignoring event of unknown type {t}");
+            }
+            Err(ProtocolError::InvalidEvent(e)) if skipped_events.invalid => {
+                log::warn!("{addr}: ignoring invalid event: {e}");
+            }
             Err(e) => {
                 log::warn!("error receiving event: {e}");
                 break;
@@ -265,6 +638,8 @@ async fn read_loop(
         }
     }
     log::info!("dtls client disconnected {:?}", addr);
+    report_duplicates(true);
+    wire_formats.borrow_mut().remove(&addr);
     let mut conns = conns.lock().await;
     let index = conns
         .iter()
@@ -273,3 +648,124 @@ async fn read_loop(
     conns.remove(index);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_event::ScrollSource;
+
+    fn key(key: u32, state: u8) -> Event {
+        Event::Keyboard(KeyboardEvent::Key {
+            time: 0,
+            key,
+            state,
+        })
+    }
+
+    fn button(button: u32, state: u32) -> Event {
+        Event::Pointer(PointerEvent::Button {
+            time: 0,
+            button,
+            state,
+        })
+    }
+
+    #[test]
+    fn drops_duplicates() {
+        let mut window = SequenceWindow::new(4);
+        assert_eq!(window.accept(1, &key(30, 1)), Received::New);
+        assert_eq!(window.accept(1, &key(30, 1)), Received::Duplicate);
+        assert_eq!(window.accept(2, &key(30, 0)), Received::New);
+        assert_eq!(window.accept(2, &key(30, 0)), Received::Duplicate);
+        assert_eq!(window.dropped, 2);
+    }
+
+    #[test]
+    fn drops_events_older_than_applied() {
+        let mut window = SequenceWindow::new(4);
+        // the press (#1) is lost, the release (#2) arrives first
+        assert_eq!(window.accept(2, &key(30, 0)), Received::New);
+        // the retransmitted press must not be applied after its release
+        assert_eq!(window.accept(1, &key(30, 1)), Received::Outdated);
+        // older events of other keys and buttons are still applied
+        assert_eq!(window.accept(3, &button(0x110, 1)), Received::New);
+        assert_eq!(window.accept(0, &key(31, 1)), Received::New);
+        assert_eq!(window.accept(0, &key(31, 1)), Received::Duplicate);
+    }
+
+    #[test]
+    fn outdated_events_are_not_duplicates() {
+        let mut window = SequenceWindow::new(4);
+        assert_eq!(window.accept(10, &key(30, 1)), Received::New);
+        // too old to be remembered
+        assert_eq!(window.accept(2, &key(31, 1)), Received::Outdated);
+        assert_eq!(window.dropped, 0);
+        assert_eq!(window.report(false), None);
+        assert_eq!(window.accept(10, &key(30, 1)), Received::Duplicate);
+        assert_eq!(window.report(false), Some(1));
+        // rate limited
+        assert_eq!(window.accept(10, &key(30, 1)), Received::Duplicate);
+        assert_eq!(window.report(false), None);
+        assert_eq!(window.report(true), Some(2));
+    }
+
+    #[test]
+    fn restarted_sender_starts_again() {
+        let mut window = SequenceWindow::new(4);
+        for seq in 1..=100 {
+            assert_eq!(window.accept(seq, &key(30, seq as u8 % 2)), Received::New);
+        }
+        // without a reset, the events of the restarted sender are outdated
+        assert_eq!(window.accept(1, &key(30, 1)), Received::Outdated);
+        window.reset();
+        assert_eq!(window.accept(1, &key(30, 1)), Received::New);
+        assert_eq!(window.accept(2, &key(30, 0)), Received::New);
+        assert_eq!(window.accept(1, &key(30, 1)), Received::Duplicate);
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut window = SequenceWindow::new(4);
+        assert_eq!(window.accept(u32::MAX, &key(30, 1)), Received::New);
+        assert_eq!(window.accept(0, &key(30, 0)), Received::New);
+        assert_eq!(window.accept(u32::MAX, &key(30, 1)), Received::Duplicate);
+    }
+
+    #[test]
+    fn legacy_axis_after_longer_packet() {
+        let mut b = [0u8; MAX_PACKET_SIZE];
+        // the second byte of dy is where a legacy axis event has no source
+        let motion = ProtoEvent::Input(Event::Pointer(PointerEvent::Motion {
+            time: 0,
+            dx: 0.,
+            dy: f64::from_bits(0x4001 << 48),
+        }));
+        let (packet, len) = motion.encode(WireFormat::Legacy);
+        b[..len].copy_from_slice(&packet[..len]);
+        // legacy peers do not send the scroll source
+        let axis = ProtoEvent::Input(Event::Pointer(PointerEvent::Axis {
+            time: 0,
+            axis: 0,
+            value: 15.,
+            source: ScrollSource::Continuous,
+        }));
+        let (packet, len) = axis.encode(WireFormat::Legacy);
+        let len = len - 1;
+        b[..len].copy_from_slice(&packet[..len]);
+        let decode = |b: &[u8; MAX_PACKET_SIZE]| {
+            let packet = b[..MAX_RELAY_SIZE].try_into().expect("packet size");
+            match ProtoEvent::decode_relay(packet) {
+                Ok((
+                    _,
+                    ProtoEvent::Input(Event::Pointer(PointerEvent::Axis { source, .. })),
+                    _,
+                )) => source,
+                e => panic!("not an axis event: {e:?}"),
+            }
+        };
+        assert_eq!(decode(&b), ScrollSource::Finger);
+        // as done by the read loop after receiving a packet
+        b[len..].fill(0);
+        assert_eq!(decode(&b), ScrollSource::Wheel);
+    }
+}
@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use webrtc_util::{Conn, Result};
+
+/// time after which a packet held back for reordering is sent
+/// if no further packet overtakes it
+const REORDER_WINDOW: Duration = Duration::from_millis(50);
+
+/// Degradation of outgoing packets for testing
+/// reconnection and recovery from lost or reordered events.
+/// Fractions are in the range `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LossSimulation {
+    /// fraction of packets dropped
+    pub(crate) drop: f64,
+    /// fraction of packets delayed by `delay`
+    pub(crate) delay_rate: f64,
+    pub(crate) delay: Duration,
+    /// fraction of packets overtaken by the packet following them
+    pub(crate) reorder: f64,
+    /// seed of the random sequence, the same seed
+    /// degrades the same packets on every run
+    pub(crate) seed: u64,
+}
+
+/// [`Conn`] applying a [`LossSimulation`] to every packet sent
+pub(crate) struct LossyConn {
+    inner: Arc<dyn Conn + Send + Sync>,
+    simulation: LossSimulation,
+    rng: Mutex<XorShift>,
+    /// packet held back to be sent after the next one
+    held: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl LossyConn {
+    pub(crate) fn new(inner: Arc<dyn Conn + Send + Sync>, simulation: LossSimulation) -> Self {
+        log::warn!("simulating packet loss: {simulation:?}");
+        Self {
+            inner,
+            simulation,
+            rng: Mutex::new(XorShift::new(simulation.seed)),
+            held: Default::default(),
+        }
+    }
+
+    fn roll(&self, fraction: f64) -> bool {
+        fraction > 0. && self.rng.lock().expect("lock").next() < fraction
+    }
+
+    fn hold(&self, buf: &[u8]) {
+        self.held.lock().expect("lock").replace(buf.to_vec());
+        let (held, inner) = (self.held.clone(), self.inner.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(REORDER_WINDOW).await;
+            let packet = held.lock().expect("lock").take();
+            if let Some(packet) = packet {
+                let _ = inner.send(&packet).await;
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Conn for LossyConn {
+    async fn connect(&self, addr: SocketAddr) -> Result<()> {
+        self.inner.connect(addr).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.recv(buf).await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf).await
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        if self.roll(self.simulation.drop) {
+            log::debug!("simulation: dropping packet of {} bytes", buf.len());
+            return Ok(buf.len());
+        }
+        let held = self.held.lock().expect("lock").take();
+        if held.is_none() && self.roll(self.simulation.reorder) {
+            log::debug!("simulation: holding back packet of {} bytes", buf.len());
+            self.hold(buf);
+            return Ok(buf.len());
+        }
+        if self.roll(self.simulation.delay_rate) {
+            log::debug!("simulation: delaying packet of {} bytes", buf.len());
+            let (inner, buf, delay) = (self.inner.clone(), buf.to_vec(), self.simulation.delay);
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = inner.send(&buf).await;
+            });
+        } else {
+            self.inner.send(buf).await?;
+        }
+        if let Some(held) = held {
+            self.inner.send(&held).await?;
+        }
+        Ok(buf.len())
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        self.inner.send_to(buf, target).await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+
+    fn as_any(&self) -> &(dyn std::any::Any + Send + Sync) {
+        self
+    }
+}
+
+/// xorshift64, deterministic for a given seed
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must not be zero
+        Self(seed.max(1))
+    }
+
+    /// next value in the range `0.0..1.0`
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
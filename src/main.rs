@@ -53,6 +53,7 @@ fn main() {
 
 fn run() -> Result<(), LanMouseError> {
     let config = config::Config::new()?;
+    input_event::set_log_key_data(config.log_key_data());
     match config.command() {
         Some(command) => match command {
             Command::TestEmulation(args) => run_async(emulation_test::run(config, args))?,
@@ -1,29 +1,40 @@
 use crate::{
-    capture::{Capture, CaptureType, ICaptureEvent},
-    client::ClientManager,
+    capture::{Capture, CaptureClosed, CaptureType, ICaptureEvent},
+    client::{ClientLimitError, ClientManager},
     config::Config,
     connect::LanMouseConnection,
     crypto,
     dns::{DnsEvent, DnsResolver},
     emulation::{Emulation, EmulationEvent},
-    listen::{LanMouseListener, ListenerCreationError},
+    listen::{log_raw_packet, LanMouseListener, ListenerCreationError, RawHook, SkippedEvents},
+    system_log::{Severity, SystemLog},
 };
 use futures::StreamExt;
 use hickory_resolver::error::ResolveError;
 use lan_mouse_ipc::{
-    AsyncFrontendListener, ClientConfig, ClientHandle, ClientState, FrontendEvent, FrontendRequest,
-    IpcError, IpcListenerCreationError, Position, Status,
+    AsyncFrontendListener, ClientConfig, ClientHandle, ClientState, FallbackRecovery,
+    FrontendEvent, FrontendRequest, IpcError, IpcListenerCreationError, Orientation, PointerMode,
+    Position, Status, WireFormat,
 };
+use lan_mouse_proto::{ControlMessage, ProtoEvent};
 use log;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     io,
     net::{IpAddr, SocketAddr},
+    rc::Rc,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 use thiserror::Error;
 use tokio::{process::Command, signal, sync::Notify};
 
+/// first delay before input capture is recreated after it closed
+const CAPTURE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// maximum delay before input capture is recreated after repeated failures
+const MAX_CAPTURE_RETRY_DELAY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Error)]
 pub enum ServiceError {
     #[error(transparent)]
@@ -63,11 +74,23 @@ pub struct Service {
     capture_status: Status,
     /// status of input emulation (enabled / disabled)
     emulation_status: Status,
+    /// input emulation is suspended
+    emulation_paused: bool,
     /// keep track of registered connections to avoid duplicate barriers
     incoming_conns: HashSet<SocketAddr>,
     /// map from capture handle to connection info
     incoming_conn_info: HashMap<ClientHandle, Incoming>,
     next_trigger_handle: u64,
+    /// relay events from connected devices to configured clients
+    relay: bool,
+    /// structured log of significant events, if enabled
+    system_log: Option<SystemLog>,
+    /// configuration the service was started with
+    config: Config,
+    /// delay before input capture is recreated the next time it closes
+    capture_retry_delay: Duration,
+    /// shut down after handling the current event
+    exit_requested: bool,
 }
 
 #[derive(Debug)]
@@ -79,21 +102,57 @@ struct Incoming {
 
 impl Service {
     pub async fn new(config: Config) -> Result<Self, ServiceError> {
-        let client_manager = ClientManager::default();
+        let client_manager = ClientManager::new(config.max_clients());
         for client in config.clients() {
+            let uuid = match client_manager.get_client_by_uuid(&client.uuid) {
+                None => client.uuid,
+                Some(other) => {
+                    let uuid = lan_mouse_ipc::generate_uuid();
+                    log::warn!(
+                        "client {other} has the same uuid {} as {:?}, using {uuid}",
+                        client.uuid,
+                        client.hostname.as_deref().unwrap_or_default()
+                    );
+                    uuid
+                }
+            };
             let config = ClientConfig {
+                uuid,
                 hostname: client.hostname,
                 fix_ips: client.ips.into_iter().collect(),
                 port: client.port,
                 pos: client.pos,
                 cmd: client.enter_hook,
+                always_forward: client.always_forward,
+                wire_format: client.wire_format,
+                click_lock: client.click_lock,
+                scroll_acceleration: client.scroll_acceleration,
+                button_map: client.button_map,
+                relay_target: client.relay_target,
+                preferred_family: client.preferred_family,
+                ttl: client.ttl,
+                dscp: client.dscp,
+                fingerprint: client.fingerprint,
+                orientation: client.orientation,
+                blocked_keys: client.blocked_keys,
+                fallback: client.fallback,
+                fallback_recovery: client.fallback_recovery,
+                motion_prediction: client.motion_prediction,
+                pointer_mode: client.pointer_mode,
+                transforms: client.transforms,
             };
             let state = ClientState {
                 active: client.active,
                 ips: HashSet::from_iter(config.fix_ips.iter().cloned()),
                 ..Default::default()
             };
-            let handle = client_manager.add_client();
+            let handle = match client_manager.add_client() {
+                Ok(handle) => handle,
+                Err(e) => {
+                    log::error!("ignoring remaining clients in config: {e}");
+                    break;
+                }
+            };
             client_manager.set_config(handle, config);
             client_manager.set_state(handle, state);
         }
@@ -107,20 +166,68 @@ impl Service {
 
         let authorized_keys = Arc::new(RwLock::new(config.authorized_fingerprints()));
         // listener + connection
-        let listener =
-            LanMouseListener::new(config.port(), cert.clone(), authorized_keys.clone()).await?;
-        let conn = LanMouseConnection::new(cert.clone(), client_manager.clone());
+        let raw_hook: Option<RawHook> = match config.debug_raw_packets() {
+            true => Some(Rc::new(log_raw_packet)),
+            false => None,
+        };
+        let listener = LanMouseListener::new(
+            config.port(),
+            cert.clone(),
+            authorized_keys.clone(),
+            raw_hook,
+            SkippedEvents {
+                unknown: config.ignore_unknown_events(),
+                invalid: config.ignore_invalid_events(),
+            },
+            config.dedup_window(),
+            config.max_session_duration(),
+        )
+        .await?;
+        let conn = LanMouseConnection::new(
+            cert.clone(),
+            client_manager.clone(),
+            config.loss_simulation(),
+            config.log_connection_lifecycle(),
+            config.flap_damping(),
+            config.retransmit_critical(),
+            config.clock_sync_interval(),
+        );
 
         // input capture + emulation
         let capture_backend = config.capture_backend().map(|b| b.into());
-        let capture = Capture::new(capture_backend, conn, config.release_bind());
+        let capture = Capture::new(
+            capture_backend,
+            conn,
+            config.release_bind(),
+            config.capture_options(),
+            config.sticky_edge(),
+            config.tap_to_switch(),
+            config.reassert_modifiers(),
+        );
+        capture.set_action_binds(config.action_binds());
         let emulation_backend = config.emulation_backend().map(|b| b.into());
-        let emulation = Emulation::new(emulation_backend, listener);
+        let emulation = Emulation::new(
+            emulation_backend,
+            config.emulation_options(),
+            listener,
+            config.deadman(),
+            config.entry_snap(),
+            config.keyboard_merge(),
+            config.action_handlers(),
+        );
 
         // create dns resolver
         let resolver = DnsResolver::new()?;
 
+        let system_log = match config.system_log() {
+            true => SystemLog::connect()
+                .inspect_err(|e| log::warn!("could not connect to the system log: {e}"))
+                .ok(),
+            false => None,
+        };
+
         let port = config.port();
+        let relay = config.relay();
         let service = Self {
             capture,
             emulation,
@@ -134,9 +241,15 @@ impl Service {
             pending_frontend_events: Default::default(),
             capture_status: Default::default(),
             emulation_status: Default::default(),
+            emulation_paused: false,
             incoming_conn_info: Default::default(),
             incoming_conns: Default::default(),
             next_trigger_handle: 0,
+            relay,
+            system_log,
+            config,
+            capture_retry_delay: CAPTURE_RETRY_DELAY,
+            exit_requested: false,
         };
         Ok(service)
     }
@@ -163,6 +276,9 @@ impl Service {
                 event = self.resolver.event() => self.handle_resolver_event(event),
                 r = signal::ctrl_c() => break r.expect("failed to wait for CTRL+C"),
             }
+            if self.exit_requested {
+                break;
+            }
         }
 
         log::info!("terminating service ...");
@@ -189,6 +305,7 @@ impl Service {
             FrontendRequest::Delete(handle) => self.remove_client(handle),
             FrontendRequest::EnableCapture => self.capture.reenable(),
             FrontendRequest::EnableEmulation => self.emulation.reenable(),
+            FrontendRequest::PauseEmulation(paused) => self.emulation.pause(paused),
             FrontendRequest::Enumerate() => self.enumerate(),
             FrontendRequest::UpdateFixIps(handle, fix_ips) => self.update_fix_ips(handle, fix_ips),
             FrontendRequest::UpdateHostname(handle, host) => self.update_hostname(handle, host),
@@ -200,6 +317,39 @@ impl Service {
             FrontendRequest::UpdateEnterHook(handle, enter_hook) => {
                 self.update_enter_hook(handle, enter_hook)
             }
+            FrontendRequest::UpdateAlwaysForward(handle, always_forward) => {
+                self.update_always_forward(handle, always_forward)
+            }
+            FrontendRequest::UpdateWireFormat(handle, wire_format) => {
+                self.update_wire_format(handle, wire_format)
+            }
+            FrontendRequest::UpdatePointerMode(handle, pointer_mode) => {
+                self.update_pointer_mode(handle, pointer_mode)
+            }
+            FrontendRequest::SaveConfig => self.save_config(),
+            FrontendRequest::TypeText(handle, text) => self.capture.type_text(handle, text),
+        }
+    }
+
+    /// write the current configuration to the config file
+    fn save_config(&mut self) {
+        let clients = self
+            .client_manager
+            .get_client_states()
+            .into_iter()
+            .map(|(_, c, s)| (c, s))
+            .collect();
+        let authorized_keys = self.authorized_keys.read().expect("lock").clone();
+        match self.config.save(self.port, clients, authorized_keys) {
+            Ok(()) => {
+                let path = self.config.config_path().to_owned();
+                log::info!("saved config to {path:?}");
+                self.notify_frontend(FrontendEvent::ConfigSaved(path));
+            }
+            Err(e) => {
+                log::warn!("could not save config: {e}");
+                self.notify_frontend(FrontendEvent::Error(format!("could not save config: {e}")));
+            }
         }
     }
 
@@ -252,10 +402,61 @@ impl Service {
                 self.emulation_status = Status::Enabled;
                 self.notify_frontend(FrontendEvent::EmulationStatus(self.emulation_status));
             }
+            EmulationEvent::EmulationPaused(paused) => {
+                self.emulation_paused = paused;
+                self.notify_frontend(FrontendEvent::EmulationPaused(paused));
+            }
+            EmulationEvent::WarmupIncomplete(missing) => {
+                self.notify_frontend(FrontendEvent::Error(format!(
+                    "input emulation: no {} device after warmup",
+                    missing.join(", ")
+                )));
+            }
+            EmulationEvent::MissingDevices(missing) => {
+                match missing.is_empty() {
+                    true => log::info!("input emulation devices ready"),
+                    false => log::warn!("input emulation: no {} device", missing.join(", ")),
+                }
+                let missing = missing.into_iter().map(String::from).collect();
+                self.notify_frontend(FrontendEvent::EmulationMissingDevices(missing));
+            }
+            EmulationEvent::GrantedDevices(granted) => {
+                let granted = granted.into_iter().map(String::from).collect();
+                self.notify_frontend(FrontendEvent::EmulationGrantedDevices(granted));
+            }
+            EmulationEvent::BackendFailed(e) => {
+                log::warn!("input emulation failed: {e}");
+                self.notify_frontend(FrontendEvent::EmulationFailed(e));
+                if self.config.restart_emulation_on_failure() {
+                    log::info!("restarting input emulation ...");
+                    self.emulation.reenable();
+                }
+            }
             EmulationEvent::ReleaseNotify => self.capture.release(),
             EmulationEvent::Connected { addr, fingerprint } => {
+                self.reassociate(addr, &fingerprint);
                 self.notify_frontend(FrontendEvent::DeviceConnected { addr, fingerprint });
             }
+            EmulationEvent::Relay {
+                addr,
+                destination,
+                event,
+            } => self.relay_event(addr, destination, event),
+            EmulationEvent::Control { addr, message } => {
+                self.handle_control_message(&addr.to_string(), message)
+            }
+            EmulationEvent::SessionStarted { addr } => {
+                self.notify_frontend(FrontendEvent::SessionStarted(addr))
+            }
+            EmulationEvent::SessionEnded { addr } => {
+                self.notify_frontend(FrontendEvent::SessionEnded(addr))
+            }
+            EmulationEvent::ReauthRequired { addr } => {
+                self.notify_frontend(FrontendEvent::ReauthRequired(addr))
+            }
+            EmulationEvent::DuplicatesDropped { fingerprint, count } => {
+                self.notify_frontend(FrontendEvent::DuplicatesDropped { fingerprint, count })
+            }
         }
     }
 
@@ -268,21 +469,126 @@ impl Service {
                     self.emulation.send_leave_event(incoming.addr);
                 }
             }
+            ICaptureEvent::Closed(reason) => {
+                self.notify_frontend(FrontendEvent::CaptureClosed(reason));
+                match self.config.capture_closed() {
+                    CaptureClosed::Disable => {
+                        log::info!("input capture disabled until reenabled")
+                    }
+                    CaptureClosed::Recreate => {
+                        let delay = self.capture_retry_delay;
+                        log::info!("recreating input capture in {delay:?} ...");
+                        self.capture.reenable_after(delay);
+                        self.capture_retry_delay = (delay * 2).min(MAX_CAPTURE_RETRY_DELAY);
+                    }
+                    CaptureClosed::Exit => {
+                        log::info!("exiting: input capture closed");
+                        self.exit_requested = true;
+                    }
+                }
+            }
             ICaptureEvent::CaptureDisabled => {
                 self.capture_status = Status::Disabled;
                 self.notify_frontend(FrontendEvent::CaptureStatus(self.capture_status));
             }
             ICaptureEvent::CaptureEnabled => {
+                // capture works again
+                self.capture_retry_delay = CAPTURE_RETRY_DELAY;
                 self.capture_status = Status::Enabled;
                 self.notify_frontend(FrontendEvent::CaptureStatus(self.capture_status));
             }
             ICaptureEvent::ClientEntered(handle) => {
                 log::info!("entering client {handle} ...");
                 self.spawn_hook_command(handle);
+                self.notify_frontend(FrontendEvent::ClientEntered(handle));
             }
+            ICaptureEvent::RelayReply(addr, event) => self.emulation.reply(addr, event),
+            ICaptureEvent::AddressChanged(handle, addr) => {
+                log::info!("client {handle} is now reached at {addr}");
+                self.broadcast_client(handle);
+            }
+            ICaptureEvent::Control(handle, message) => {
+                self.handle_control_message(&format!("client {handle}"), message)
+            }
+            ICaptureEvent::Failover(handle, fallback) => {
+                self.notify_frontend(FrontendEvent::Failover(handle, fallback))
+            }
+            ICaptureEvent::Recovered(handle) => {
+                self.notify_frontend(FrontendEvent::Recovered(handle))
+            }
+            ICaptureEvent::SwitchPending(handle, countdown) => {
+                log::info!("entering client {handle} in {countdown:?} ...");
+                let countdown = countdown.as_millis() as u64;
+                self.notify_frontend(FrontendEvent::SwitchPending(handle, countdown))
+            }
+            ICaptureEvent::SwitchCancelled(handle) => {
+                log::info!("switch to client {handle} cancelled");
+                self.notify_frontend(FrontendEvent::SwitchCancelled(handle))
+            }
+            ICaptureEvent::LocalInput(events) => self.emulation.emulate_local(events),
+            ICaptureEvent::Incompatible(handle, mismatch) => {
+                self.broadcast_client(handle);
+                if let Some(mismatch) = mismatch {
+                    self.notify_frontend(FrontendEvent::IncompatibleVersion(handle, mismatch));
+                }
+            }
+            ICaptureEvent::TextTyped(handle, Ok(skipped)) => {
+                self.notify_frontend(FrontendEvent::TextTyped(handle, skipped))
+            }
+            ICaptureEvent::TextTyped(handle, Err(e)) => self.notify_frontend(FrontendEvent::Error(
+                format!("typing text on client {handle}: {e}"),
+            )),
         }
     }
 
+    /// recognize a configured client connecting from a new address
+    fn reassociate(&mut self, addr: SocketAddr, fingerprint: &str) {
+        let Some(handle) = self.client_manager.get_client_by_fingerprint(fingerprint) else {
+            return;
+        };
+        if self.client_manager.reassociate(handle, addr.ip()) {
+            log::info!("client {handle} reappeared at {}", addr.ip());
+            self.broadcast_client(handle);
+            self.notify_frontend(FrontendEvent::Reassociated(handle, addr));
+        }
+    }
+
+    /// handle a control message received from a connected device or client
+    fn handle_control_message(&mut self, source: &str, message: ControlMessage) {
+        match message {
+            ControlMessage::Heartbeat => log::trace!("heartbeat from {source}"),
+            // only sent to receiving devices or handled by the connection
+            ControlMessage::SessionStart(_)
+            | ControlMessage::SessionEnd(_)
+            | ControlMessage::ClockRequest(_)
+            | ControlMessage::ClockResponse { .. }
+            | ControlMessage::Action { .. } => {
+                log::debug!("ignoring unexpected {message} from {source}")
+            }
+        }
+    }
+
+    fn relay_event(&mut self, addr: SocketAddr, destination: ClientHandle, event: ProtoEvent) {
+        if !self.relay {
+            log::warn!("{addr}: ignoring event for client {destination} - relaying is disabled");
+            return;
+        }
+        if self.client_manager.get_state(destination).is_none() {
+            log::warn!("{addr}: can not relay to client {destination} - no such client");
+            return;
+        }
+        // answer pings on behalf of the destination
+        if let ProtoEvent::Ping = event {
+            let alive = self.client_manager.active_addr(destination).is_some()
+                && self.client_manager.alive(destination);
+            self.emulation.reply(
+                addr,
+                ProtoEvent::Pong(alive, lan_mouse_proto::WireFormat::NEWEST.version()),
+            );
+        }
+        self.capture.relay(destination, event, addr);
+    }
+
     fn handle_resolver_event(&mut self, event: DnsEvent) {
         let handle = match event {
             DnsEvent::Resolving(handle) => {
@@ -311,6 +617,7 @@ impl Service {
     fn sync_frontend(&mut self) {
         self.enumerate();
         self.notify_frontend(FrontendEvent::EmulationStatus(self.emulation_status));
+        self.notify_frontend(FrontendEvent::EmulationPaused(self.emulation_paused));
         self.notify_frontend(FrontendEvent::CaptureStatus(self.capture_status));
         self.notify_frontend(FrontendEvent::PortChanged(self.port, None));
         self.notify_frontend(FrontendEvent::PublicKeyFingerprint(
@@ -379,10 +686,135 @@ impl Service {
     }
 
     fn notify_frontend(&mut self, event: FrontendEvent) {
+        self.log_to_system(&event);
         self.pending_frontend_events.push_back(event);
         self.frontend_event_pending.notify_one();
     }
 
+    /// record connections, switches and errors in the system log
+    fn log_to_system(&self, event: &FrontendEvent) {
+        let Some(system_log) = self.system_log.as_ref() else {
+            return;
+        };
+        let client = |handle: ClientHandle| {
+            let hostname = self.client_manager.get_hostname(handle);
+            vec![
+                ("client", handle.to_string()),
+                ("hostname", hostname.unwrap_or_default()),
+            ]
+        };
+        let (severity, name, message, fields) = match event {
+            FrontendEvent::DeviceConnected { addr, fingerprint } => (
+                Severity::Info,
+                "device-connected",
+                format!("device {addr} connected"),
+                vec![
+                    ("addr", addr.to_string()),
+                    ("fingerprint", fingerprint.clone()),
+                ],
+            ),
+            FrontendEvent::ConnectionAttempt { fingerprint } => (
+                Severity::Notice,
+                "connection-rejected",
+                "rejected connection of an unauthorized device".to_string(),
+                vec![("fingerprint", fingerprint.clone())],
+            ),
+            FrontendEvent::DeviceEntered {
+                fingerprint,
+                addr,
+                pos,
+            } => (
+                Severity::Info,
+                "device-entered",
+                format!("device {addr} entered this device ({pos})"),
+                vec![
+                    ("addr", addr.to_string()),
+                    ("fingerprint", fingerprint.clone()),
+                    ("position", pos.to_string()),
+                ],
+            ),
+            FrontendEvent::IncomingDisconnected(addr) => (
+                Severity::Info,
+                "device-disconnected",
+                format!("device {addr} disconnected"),
+                vec![("addr", addr.to_string())],
+            ),
+            FrontendEvent::ClientEntered(handle) => (
+                Severity::Info,
+                "client-entered",
+                format!("entered client {handle}"),
+                client(*handle),
+            ),
+            FrontendEvent::Failover(handle, fallback) => {
+                let mut fields = client(*handle);
+                let message = match fallback {
+                    Some(fallback) => {
+                        fields.push(("fallback", fallback.to_string()));
+                        format!("client {handle} unreachable, falling back to {fallback}")
+                    }
+                    None => format!("client {handle} reachable again"),
+                };
+                (Severity::Warning, "client-failover", message, fields)
+            }
+            FrontendEvent::Recovered(handle) => (
+                Severity::Notice,
+                "client-recovered",
+                format!("client {handle} recovered"),
+                client(*handle),
+            ),
+            FrontendEvent::IncompatibleVersion(handle, mismatch) => {
+                let mut fields = client(*handle);
+                fields.push(("local_version", mismatch.local.to_string()));
+                fields.push(("remote_version", mismatch.remote.to_string()));
+                (
+                    Severity::Error,
+                    "incompatible-version",
+                    format!("client {handle}: {mismatch}"),
+                    fields,
+                )
+            }
+            FrontendEvent::PortChanged(port, Some(e)) => (
+                Severity::Error,
+                "port-change-failed",
+                format!("could not change port: {e}"),
+                vec![("port", port.to_string())],
+            ),
+            FrontendEvent::EmulationFailed(e) => (
+                Severity::Error,
+                "emulation-failed",
+                format!("input emulation failed: {e}"),
+                vec![],
+            ),
+            FrontendEvent::CaptureClosed(e) => (
+                Severity::Error,
+                "capture-closed",
+                format!("input capture closed: {e}"),
+                vec![],
+            ),
+            FrontendEvent::CaptureStatus(Status::Disabled) => (
+                Severity::Warning,
+                "capture-disabled",
+                "input capture disabled".to_string(),
+                vec![],
+            ),
+            FrontendEvent::EmulationStatus(Status::Disabled) => (
+                Severity::Warning,
+                "emulation-disabled",
+                "input emulation disabled".to_string(),
+                vec![],
+            ),
+            FrontendEvent::ReauthRequired(addr) => (
+                Severity::Warning,
+                "reauth-required",
+                format!("session of {addr} expired, re-authentication required"),
+                vec![("addr", addr.to_string())],
+            ),
+            FrontendEvent::Error(e) => (Severity::Error, "error", e.clone(), vec![]),
+            _ => return,
+        };
+        system_log.log(severity, name, &message, &fields);
+    }
+
     fn add_authorized_key(&mut self, desc: String, fp: String) {
         self.authorized_keys.write().expect("lock").insert(fp, desc);
         let keys = self.authorized_keys.read().expect("lock").clone();
@@ -401,7 +833,14 @@ impl Service {
     }
 
     fn add_client(&mut self) {
-        let handle = self.client_manager.add_client();
+        let handle = match self.client_manager.add_client() {
+            Ok(handle) => handle,
+            Err(ClientLimitError(max)) => {
+                log::warn!("can not add client: maximum number of clients ({max}) reached");
+                self.notify_frontend(FrontendEvent::ClientLimitReached(max));
+                return;
+            }
+        };
         log::info!("added client {handle}");
         let (c, s) = self.client_manager.get_state(handle).unwrap();
         self.notify_frontend(FrontendEvent::Created(handle, c, s));
@@ -445,11 +884,66 @@ impl Service {
         if self.client_manager.activate_client(handle) {
             /* notify capture and frontends */
             self.capture.create(handle, pos, CaptureType::Default);
+            if self.client_manager.always_forward(handle) {
+                self.capture.set_always_forward(handle, true);
+            }
+            let pointer_mode = self.client_manager.pointer_mode(handle);
+            if pointer_mode != PointerMode::default() {
+                self.capture.set_pointer_mode(handle, pointer_mode);
+            }
+            let click_lock = self.client_manager.get_click_lock(handle);
+            if !click_lock.is_empty() {
+                self.capture.set_click_lock(handle, click_lock);
+            }
+            let blocked_keys = self.client_manager.get_blocked_keys(handle);
+            if !blocked_keys.is_empty() {
+                self.capture.set_blocked_keys(handle, blocked_keys);
+            }
+            let button_map = self.client_manager.get_button_map(handle);
+            if !button_map.is_empty() {
+                self.capture.set_button_map(handle, button_map);
+            }
+            let scroll_acceleration = self.client_manager.get_scroll_acceleration(handle);
+            if scroll_acceleration > 0 {
+                self.capture
+                    .set_scroll_acceleration(handle, scroll_acceleration);
+            }
+            if let Some(fallback) = self.fallback(handle) {
+                self.capture.set_fallback(handle, Some(fallback));
+            }
+            let motion_prediction = self.client_manager.get_motion_prediction(handle);
+            if motion_prediction > 0 {
+                self.capture
+                    .set_motion_prediction(handle, motion_prediction);
+            }
+            let orientation = self.client_manager.get_orientation(handle);
+            if orientation != Orientation::Normal {
+                self.capture.set_orientation(handle, orientation);
+            }
+            let transforms = self.client_manager.get_transforms(handle);
+            if !transforms.is_empty() {
+                self.capture.set_transforms(handle, transforms);
+            }
             self.broadcast_client(handle);
             log::info!("activated client {handle} ({pos})");
         }
     }
 
+    /// the configured fallback of a client, resolving its address
+    fn fallback(&self, handle: ClientHandle) -> Option<(ClientHandle, FallbackRecovery)> {
+        let (hostname, recovery) = self.client_manager.get_fallback(handle)?;
+        match self.client_manager.find_hostname(&hostname) {
+            Some(fallback) if fallback != handle => {
+                self.resolve(fallback);
+                Some((fallback, recovery))
+            }
+            _ => {
+                log::warn!("client {handle}: no other client `{hostname}` to fall back to");
+                None
+            }
+        }
+    }
+
     fn change_port(&mut self, port: u16) {
         if self.port != port {
             self.emulation.request_port_change(port);
@@ -502,6 +996,31 @@ impl Service {
         self.broadcast_client(handle);
     }
 
+    fn update_pointer_mode(&mut self, handle: ClientHandle, pointer_mode: PointerMode) {
+        log::info!("client {handle}: {pointer_mode} pointer mode");
+        self.client_manager.set_pointer_mode(handle, pointer_mode);
+        if self.client_manager.active_clients().contains(&handle) {
+            self.capture.set_pointer_mode(handle, pointer_mode);
+        }
+        self.broadcast_client(handle);
+    }
+
+    fn update_always_forward(&mut self, handle: ClientHandle, always_forward: bool) {
+        log::info!("client {handle}: always forward {always_forward}");
+        self.client_manager
+            .set_always_forward(handle, always_forward);
+        if self.client_manager.active_clients().contains(&handle) {
+            self.capture.set_always_forward(handle, always_forward);
+        }
+        self.broadcast_client(handle);
+    }
+
+    fn update_wire_format(&mut self, handle: ClientHandle, wire_format: WireFormat) {
+        log::info!("client {handle}: using wire format {wire_format}");
+        self.client_manager.set_wire_format(handle, wire_format);
+        self.broadcast_client(handle);
+    }
+
     fn broadcast_client(&mut self, handle: ClientHandle) {
         let event = self
             .client_manager
@@ -0,0 +1,136 @@
+use std::io;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+/// native protocol socket of journald
+#[cfg(unix)]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// syslog socket, used if journald is not available
+#[cfg(unix)]
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+const IDENTIFIER: &str = "lan-mouse";
+
+/// syslog facility of system daemons
+const FACILITY_DAEMON: u8 = 3;
+
+/// enterprise number used for the structured data id (reserved for examples)
+const SD_ID: &str = "lan-mouse@32473";
+
+/// severity of a system log entry (RFC 5424)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Severity {
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+}
+
+/// Logs significant events (connections, switches, errors) with structured
+/// fields to journald or, if unavailable, syslog. Independent of the `log`
+/// output, which is meant for interactive use.
+pub(crate) struct SystemLog {
+    #[cfg(unix)]
+    socket: UnixDatagram,
+    #[cfg(unix)]
+    journald: bool,
+}
+
+impl SystemLog {
+    /// connect to journald or the syslog socket
+    #[cfg(unix)]
+    pub(crate) fn connect() -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        if socket.connect(JOURNALD_SOCKET).is_ok() {
+            log::info!("logging events to journald");
+            return Ok(Self {
+                socket,
+                journald: true,
+            });
+        }
+        socket.connect(SYSLOG_SOCKET)?;
+        log::info!("logging events to syslog");
+        Ok(Self {
+            socket,
+            journald: false,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn connect() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no system log on this platform",
+        ))
+    }
+
+    /// Log `message` about `event` (e.g. `client-entered`).
+    /// Field names are lowercase words separated by `_`.
+    pub(crate) fn log(
+        &self,
+        severity: Severity,
+        event: &str,
+        message: &str,
+        fields: &[(&str, String)],
+    ) {
+        #[cfg(unix)]
+        {
+            let entry = match self.journald {
+                true => journal_entry(severity, event, message, fields),
+                false => syslog_entry(severity, event, message, fields),
+            };
+            if let Err(e) = self.socket.send(entry.as_bytes()) {
+                log::debug!("could not write to the system log: {e}");
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (severity, event, message, fields);
+    }
+}
+
+/// entry in the native journald protocol: one `FIELD=value` per line
+#[cfg_attr(not(unix), allow(dead_code))]
+fn journal_entry(
+    severity: Severity,
+    event: &str,
+    message: &str,
+    fields: &[(&str, String)],
+) -> String {
+    // values with newlines require the binary format, keep it simple
+    let line = |name: &str, value: &str| format!("{name}={}\n", value.replace('\n', " "));
+    let mut entry = line("MESSAGE", message);
+    entry += &line("PRIORITY", &(severity as u8).to_string());
+    entry += &line("SYSLOG_IDENTIFIER", IDENTIFIER);
+    entry += &line("LAN_MOUSE_EVENT", event);
+    for (name, value) in fields {
+        entry += &line(&format!("LAN_MOUSE_{}", name.to_uppercase()), value);
+    }
+    entry
+}
+
+/// entry in the RFC 5424 syslog format with the fields as structured data
+#[cfg_attr(not(unix), allow(dead_code))]
+fn syslog_entry(
+    severity: Severity,
+    event: &str,
+    message: &str,
+    fields: &[(&str, String)],
+) -> String {
+    let priority = FACILITY_DAEMON * 8 + severity as u8;
+    let pid = std::process::id();
+    let escape = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace(']', "\\]")
+    };
+    let mut data = format!("[{SD_ID} event=\"{}\"", escape(event));
+    for (name, value) in fields {
+        data += &format!(" {name}=\"{}\"", escape(value));
+    }
+    data.push(']');
+    // timestamp and hostname are filled in by the syslog daemon
+    format!("<{priority}>1 - - {IDENTIFIER} {pid} {event} {data} {message}")
+}